@@ -7,6 +7,7 @@ use crate::{Program, State};
 #[derive(Debug, Clone)]
 pub struct Initialized {
         pub code: Vec<u8>,
+        pub std: Standard,
 }
 impl State for Initialized {}
 
@@ -21,6 +22,16 @@ pub enum Operation {
         Compile,
 }
 
+// the language the source is written in, chosen with -std. almost every C23 feature starts with a keyword or a token
+// that doesn't exist in C17, so the lexer is what tells them apart. the exception is an empty initializer, 6.7.10p1,
+// which the type checker has to look out for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Standard {
+        #[default]
+        C17,
+        C23,
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
         #[error("No file input")]
@@ -29,6 +40,8 @@ pub enum Error {
         NoOperationInput,
         #[error("Malformed operation input")]
         MalformedOperationInput,
+        #[error("Unsupported language standard {0}, expected c17 or c23")]
+        UnsupportedStandard(String),
         #[error("IO Error {0}")]
         IoError(io::Error),
 }
@@ -38,14 +51,34 @@ impl From<io::Error> for Error {
         }
 }
 
-fn get_request() -> Result<(Operation, PathBuf, bool), Error> {
-        let mut args = std::env::args();
-        args.next();
+// -std=<standard> can go anywhere on the command line, and c18 is the same language as c17
+fn get_standard(args: &mut Vec<String>) -> Result<Standard, Error> {
+        let mut standard = Standard::default();
+        let mut error = None;
+        args.retain(|f| {
+                let Some(name) = f.strip_prefix("-std=") else {
+                        return true;
+                };
+                match name {
+                        "c17" | "c18" => standard = Standard::C17,
+                        "c23" | "c2x" => standard = Standard::C23,
+                        _ => error = Some(Error::UnsupportedStandard(name.to_string())),
+                }
+                false
+        });
+
+        error.map_or(Ok(standard), Err)
+}
+
+fn get_request() -> Result<(Operation, PathBuf, bool, Standard), Error> {
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        let standard = get_standard(&mut args)?;
+        let mut args = args.into_iter();
 
         let first_two = (args.next(), args.next());
 
         if let (Some(string), None) = first_two {
-                return Ok((Operation::Compile, PathBuf::from(string), false));
+                return Ok((Operation::Compile, PathBuf::from(string), false, standard));
         }
 
         let Some(op) = first_two.0 else {
@@ -55,28 +88,36 @@ fn get_request() -> Result<(Operation, PathBuf, bool), Error> {
         let file = first_two.1.unwrap();
         let file = PathBuf::from(file);
 
-        match op.as_str() {
-                "--lex" => Ok((Operation::Lex, file, false)),
-                "--parse" => Ok((Operation::ParseToCTree, file, false)),
-                "--tacky" | "--tactile" => Ok((Operation::ParseToTACTILETree, file, false)),
-                "--validate" => Ok((Operation::Validate, file, false)),
-                "--codegen" => Ok((Operation::ParseToASMTree, file, false)),
-                "-S" => Ok((Operation::GenerateASM, file, false)),
-                "-C" => Ok((Operation::Compile, file, false)),
-                "-c" => Ok((Operation::Compile, file, true)),
-                _ => Err(Error::MalformedOperationInput),
-        }
+        let (operation, obj) = match op.as_str() {
+                "--lex" => (Operation::Lex, false),
+                "--parse" => (Operation::ParseToCTree, false),
+                "--tacky" | "--tactile" => (Operation::ParseToTACTILETree, false),
+                "--validate" => (Operation::Validate, false),
+                "--codegen" => (Operation::ParseToASMTree, false),
+                "-S" => (Operation::GenerateASM, false),
+                "-C" => (Operation::Compile, false),
+                "-c" => (Operation::Compile, true),
+                _ => return Err(Error::MalformedOperationInput),
+        };
+
+        Ok((operation, file, obj, standard))
 }
 
 pub fn initialize() -> Result<Program<Initialized>, Error> {
-        let (operation, path, obj) = get_request()?;
+        let (operation, path, obj, std) = get_request()?;
+        // the headers look at __STDC_VERSION__, so the preprocessor has to agree on the standard. the GNU dialects
+        // keep the extensions the system headers expect, and gnu2x is the name older compilers know C23 by
+        let std_flag = match std {
+                Standard::C17 => "-std=gnu17",
+                Standard::C23 => "-std=gnu2x",
+        };
         let mut binding = Command::new("cc");
-        let preprocessor = binding.args(["-E", "-P"]).arg(path).args(["-o", "-"]);
+        let preprocessor = binding.args(["-E", "-P", std_flag]).arg(path).args(["-o", "-"]);
         let code = preprocessor.output()?.stdout;
 
         Ok(Program {
                 operation,
-                state: Initialized { code },
+                state: Initialized { code, std },
                 obj,
         })
 }
@@ -4,9 +4,12 @@ use std::{
 };
 
 use thiserror::Error;
-use tokentype::{Token, TokenType};
+use tokentype::{AtomicOp, AttributeName, Token, TokenType};
 
-use crate::{initialize::Initialized, Program, State};
+use crate::{
+        initialize::{Initialized, Standard},
+        Program, State,
+};
 
 pub mod tokentype;
 
@@ -14,6 +17,7 @@ pub mod tokentype;
 pub struct Lexed {
         pub code: Vec<u8>,
         pub tokens: Vec<Token>,
+        pub std: Standard,
 }
 impl State for Lexed {}
 
@@ -24,6 +28,11 @@ pub enum Error {
 }
 
 pub static INT: &[u8] = b"int";
+pub static CHAR: &[u8] = b"char";
+pub static SHORT: &[u8] = b"short";
+pub static LONG: &[u8] = b"long";
+pub static SIGNED: &[u8] = b"signed";
+pub static UNSIGNED: &[u8] = b"unsigned";
 pub static VOID: &[u8] = b"void";
 pub static RETURN: &[u8] = b"return";
 pub static IF: &[u8] = b"if";
@@ -36,6 +45,79 @@ pub static CONTINUE: &[u8] = b"continue";
 pub static SWITCH: &[u8] = b"switch";
 pub static CASE: &[u8] = b"case";
 pub static DEFAULT: &[u8] = b"default";
+pub static SIZEOF: &[u8] = b"sizeof";
+pub static ALIGNOF: &[u8] = b"_Alignof";
+pub static STATIC: &[u8] = b"static";
+pub static EXTERN: &[u8] = b"extern";
+pub static GOTO: &[u8] = b"goto";
+pub static TYPEDEF: &[u8] = b"typedef";
+pub static CONST: &[u8] = b"const";
+pub static VOLATILE: &[u8] = b"volatile";
+pub static RESTRICT: &[u8] = b"restrict";
+pub static BUILTIN_VA_LIST: &[u8] = b"__builtin_va_list";
+pub static BUILTIN_VA_START: &[u8] = b"__builtin_va_start";
+pub static BUILTIN_VA_ARG: &[u8] = b"__builtin_va_arg";
+pub static BUILTIN_VA_END: &[u8] = b"__builtin_va_end";
+pub static BUILTIN_VA_COPY: &[u8] = b"__builtin_va_copy";
+pub static STATIC_ASSERT: &[u8] = b"_Static_assert";
+pub static BOOL: &[u8] = b"_Bool";
+pub static GENERIC: &[u8] = b"_Generic";
+pub static ATOMIC: &[u8] = b"_Atomic";
+pub static THREAD_LOCAL: &[u8] = b"_Thread_local";
+
+// GNU extensions, which system headers use whatever the standard
+pub static ATTRIBUTE: &[u8] = b"__attribute__";
+pub static ATTRIBUTE_SHORT: &[u8] = b"__attribute";
+pub static BUILTIN_EXPECT: &[u8] = b"__builtin_expect";
+pub static BUILTIN_UNREACHABLE: &[u8] = b"__builtin_unreachable";
+pub static BUILTIN_POPCOUNT: &[u8] = b"__builtin_popcount";
+pub static BUILTIN_CLZ: &[u8] = b"__builtin_clz";
+pub static BUILTIN_OFFSETOF: &[u8] = b"__builtin_offsetof";
+// asm isn't reserved by the standard, but it's the common extension in J.5.10, so it's a keyword either way
+pub static ASM: &[u8] = b"asm";
+pub static ASM_UNDERSCORES: &[u8] = b"__asm__";
+pub static ASM_SHORT: &[u8] = b"__asm";
+pub static VOLATILE_UNDERSCORES: &[u8] = b"__volatile__";
+// the atomic builtins GCC and Clang's <stdatomic.h> are written with
+pub static ATOMIC_BUILTINS: [(&[u8], AtomicOp); 8] = [
+        (b"__atomic_load_n", AtomicOp::Load),
+        (b"__atomic_store_n", AtomicOp::Store),
+        (b"__atomic_exchange_n", AtomicOp::Exchange),
+        (b"__atomic_compare_exchange_n", AtomicOp::CompareExchange),
+        (b"__atomic_fetch_add", AtomicOp::FetchAdd),
+        (b"__atomic_fetch_sub", AtomicOp::FetchSub),
+        (b"__atomic_thread_fence", AtomicOp::ThreadFence),
+        (b"__atomic_signal_fence", AtomicOp::SignalFence),
+];
+pub static C11_ATOMIC_BUILTINS: [(&[u8], AtomicOp); 9] = [
+        (b"__c11_atomic_load", AtomicOp::Load),
+        (b"__c11_atomic_store", AtomicOp::Store),
+        (b"__c11_atomic_exchange", AtomicOp::Exchange),
+        (b"__c11_atomic_compare_exchange_strong", AtomicOp::CompareExchange),
+        (b"__c11_atomic_compare_exchange_weak", AtomicOp::CompareExchangeWeak),
+        (b"__c11_atomic_fetch_add", AtomicOp::FetchAdd),
+        (b"__c11_atomic_fetch_sub", AtomicOp::FetchSub),
+        (b"__c11_atomic_thread_fence", AtomicOp::ThreadFence),
+        (b"__c11_atomic_signal_fence", AtomicOp::SignalFence),
+];
+// GNU spellings of typeof, which work in every mode
+pub static TYPEOF_UNDERSCORES: &[u8] = b"__typeof__";
+pub static TYPEOF_SHORT: &[u8] = b"__typeof";
+
+// C23 keywords, which are ordinary identifiers before that. static_assert, bool and thread_local used to be macros from
+// assert.h, stdbool.h and threads.h, and true, false and nullptr are constants
+pub static STATIC_ASSERT_C23: &[u8] = b"static_assert";
+pub static BOOL_C23: &[u8] = b"bool";
+pub static TRUE: &[u8] = b"true";
+pub static FALSE: &[u8] = b"false";
+pub static NULLPTR: &[u8] = b"nullptr";
+pub static TYPEOF: &[u8] = b"typeof";
+pub static TYPEOF_UNQUAL: &[u8] = b"typeof_unqual";
+pub static CONSTEXPR: &[u8] = b"constexpr";
+pub static THREAD_LOCAL_C23: &[u8] = b"thread_local";
+
+// nothing longer than the longest keyword needs looking up
+const MAX_KEYWORD_LEN: usize = 36;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct KeywordHash(pub u32);
@@ -67,8 +149,13 @@ type KeywordHasher = BuildHasherDefault<KeywordHash>;
 
 pub fn lex(program: Program<Initialized>) -> Result<Program<Lexed>, Error> {
         let mut keyword_map: HashMap<&[u8], TokenType, KeywordHasher> =
-                HashMap::with_capacity_and_hasher(3, BuildHasherDefault::default());
+                HashMap::with_capacity_and_hasher(77, BuildHasherDefault::default());
         keyword_map.entry(INT).or_insert(TokenType::Int);
+        keyword_map.entry(CHAR).or_insert(TokenType::Char);
+        keyword_map.entry(SHORT).or_insert(TokenType::Short);
+        keyword_map.entry(LONG).or_insert(TokenType::Long);
+        keyword_map.entry(SIGNED).or_insert(TokenType::Signed);
+        keyword_map.entry(UNSIGNED).or_insert(TokenType::Unsigned);
         keyword_map.entry(VOID).or_insert(TokenType::Void);
         keyword_map.entry(RETURN).or_insert(TokenType::Return);
         keyword_map.entry(IF).or_insert(TokenType::If);
@@ -81,6 +168,63 @@ pub fn lex(program: Program<Initialized>) -> Result<Program<Lexed>, Error> {
         keyword_map.entry(SWITCH).or_insert(TokenType::Switch);
         keyword_map.entry(CASE).or_insert(TokenType::Case);
         keyword_map.entry(DEFAULT).or_insert(TokenType::Default);
+        keyword_map.entry(SIZEOF).or_insert(TokenType::Sizeof);
+        keyword_map.entry(ALIGNOF).or_insert(TokenType::Alignof);
+        keyword_map.entry(STATIC).or_insert(TokenType::Static);
+        keyword_map.entry(EXTERN).or_insert(TokenType::Extern);
+        keyword_map.entry(GOTO).or_insert(TokenType::Goto);
+        keyword_map.entry(TYPEDEF).or_insert(TokenType::Typedef);
+        keyword_map.entry(CONST).or_insert(TokenType::Const);
+        keyword_map.entry(VOLATILE).or_insert(TokenType::Volatile);
+        keyword_map.entry(RESTRICT).or_insert(TokenType::Restrict);
+        keyword_map.entry(BUILTIN_VA_LIST).or_insert(TokenType::BuiltinVaList);
+        keyword_map.entry(BUILTIN_VA_START).or_insert(TokenType::BuiltinVaStart);
+        keyword_map.entry(BUILTIN_VA_ARG).or_insert(TokenType::BuiltinVaArg);
+        keyword_map.entry(BUILTIN_VA_END).or_insert(TokenType::BuiltinVaEnd);
+        keyword_map.entry(BUILTIN_VA_COPY).or_insert(TokenType::BuiltinVaCopy);
+        keyword_map.entry(STATIC_ASSERT).or_insert(TokenType::StaticAssert);
+        keyword_map.entry(BOOL).or_insert(TokenType::Bool);
+        keyword_map.entry(GENERIC).or_insert(TokenType::Generic);
+        keyword_map.entry(ATOMIC).or_insert(TokenType::Atomic);
+        keyword_map.entry(THREAD_LOCAL).or_insert(TokenType::ThreadLocal);
+        keyword_map.entry(ATTRIBUTE).or_insert(TokenType::GnuAttribute);
+        keyword_map.entry(ATTRIBUTE_SHORT).or_insert(TokenType::GnuAttribute);
+        keyword_map.entry(BUILTIN_EXPECT).or_insert(TokenType::BuiltinExpect);
+        keyword_map
+                .entry(BUILTIN_UNREACHABLE)
+                .or_insert(TokenType::BuiltinUnreachable);
+        keyword_map
+                .entry(BUILTIN_POPCOUNT)
+                .or_insert(TokenType::BuiltinPopcount);
+        keyword_map.entry(BUILTIN_CLZ).or_insert(TokenType::BuiltinClz);
+        keyword_map
+                .entry(BUILTIN_OFFSETOF)
+                .or_insert(TokenType::BuiltinOffsetof);
+        keyword_map.entry(ASM).or_insert(TokenType::Asm);
+        keyword_map.entry(ASM_UNDERSCORES).or_insert(TokenType::Asm);
+        keyword_map.entry(ASM_SHORT).or_insert(TokenType::Asm);
+        keyword_map.entry(VOLATILE_UNDERSCORES).or_insert(TokenType::Volatile);
+        for (name, op) in ATOMIC_BUILTINS {
+                keyword_map.entry(name).or_insert(TokenType::BuiltinAtomic(op));
+        }
+        for (name, op) in C11_ATOMIC_BUILTINS {
+                keyword_map.entry(name).or_insert(TokenType::BuiltinC11Atomic(op));
+        }
+        keyword_map.entry(TYPEOF_UNDERSCORES).or_insert(TokenType::Typeof);
+        keyword_map.entry(TYPEOF_SHORT).or_insert(TokenType::Typeof);
+
+        let std = program.state.std;
+        if std == Standard::C23 {
+                keyword_map.entry(STATIC_ASSERT_C23).or_insert(TokenType::StaticAssert);
+                keyword_map.entry(BOOL_C23).or_insert(TokenType::Bool);
+                keyword_map.entry(TRUE).or_insert(TokenType::Constant);
+                keyword_map.entry(FALSE).or_insert(TokenType::Constant);
+                keyword_map.entry(NULLPTR).or_insert(TokenType::Constant);
+                keyword_map.entry(TYPEOF).or_insert(TokenType::Typeof);
+                keyword_map.entry(TYPEOF_UNQUAL).or_insert(TokenType::TypeofUnqual);
+                keyword_map.entry(CONSTEXPR).or_insert(TokenType::Constexpr);
+                keyword_map.entry(THREAD_LOCAL_C23).or_insert(TokenType::ThreadLocal);
+        }
 
         let mut left = 0;
         let tot_len = program.state.code.len();
@@ -93,7 +237,7 @@ pub fn lex(program: Program<Initialized>) -> Result<Program<Lexed>, Error> {
                         continue;
                 }
 
-                let Some(token) = get_largest_match(&code, left, &keyword_map) else {
+                let Some(token) = get_largest_match(&code, left, &keyword_map, std) else {
                         return Err(Error::OutOfTokens(left, tokens));
                 };
 
@@ -101,8 +245,10 @@ pub fn lex(program: Program<Initialized>) -> Result<Program<Lexed>, Error> {
                 tokens.push(token);
         }
 
+        tag_attributes(&mut tokens, &code);
+
         Ok(Program {
-                state: Lexed { code, tokens },
+                state: Lexed { code, tokens, std },
                 ..program
         })
 }
@@ -111,44 +257,46 @@ pub fn get_largest_match<S: BuildHasher>(
         code: &[u8],
         start: usize,
         keyword_map: &HashMap<&[u8], TokenType, S>,
+        std: Standard,
 ) -> Option<Token> {
-        if let Some(value) = match_symbol(code, start) {
+        if let Some(value) = match_symbol(code, start, std) {
                 return Some(value);
         }
 
-        let is_constant = code[start].is_ascii_digit();
-        let is_identifier = code[start].is_ascii_alphabetic() | (code[start] == b'_');
-
-        if !(is_identifier | is_constant) {
-                return None;
+        if code[start].is_ascii_digit() {
+                return constant_len(&code[start..], std).map(|len| Token {
+                        token_type: TokenType::Constant,
+                        len,
+                        start,
+                });
         }
 
-        let mut len = 0;
-
-        for &i in &code[start..] {
-                let is_digit = i.is_ascii_digit();
-                let is_alpha = i.is_ascii_alphabetic();
-                if is_constant && !is_digit {
-                        if is_alpha {
-                                return None;
-                        }
-                        break;
-                }
-                if !((i == b'_') | is_alpha | is_digit) {
-                        break;
-                }
-                len += 1;
+        if code[start] == b'\'' {
+                return character_constant_len(&code[start..]).map(|len| Token {
+                        token_type: TokenType::Constant,
+                        len,
+                        start,
+                });
         }
 
-        if is_constant {
-                return Some(Token {
-                        token_type: TokenType::Constant,
+        if code[start] == b'"' {
+                return string_literal_len(&code[start..]).map(|len| Token {
+                        token_type: TokenType::StringLiteral,
                         len,
                         start,
                 });
         }
 
-        if (len <= 1) | (len > 8) {
+        if !(code[start].is_ascii_alphabetic() | (code[start] == b'_')) {
+                return None;
+        }
+
+        let len = code[start..]
+                .iter()
+                .take_while(|&&i| (i == b'_') | i.is_ascii_alphanumeric())
+                .count();
+
+        if (len <= 1) | (len > MAX_KEYWORD_LEN) {
                 return Some(Token {
                         token_type: TokenType::Identifier,
                         len,
@@ -169,7 +317,109 @@ pub fn get_largest_match<S: BuildHasher>(
         })
 }
 
-fn match_symbol(code: &[u8], start: usize) -> Option<Token> {
+// <int> ::= ( <decimal> | "0" <octal> | "0x" <hex> | "0b" <binary> ) [ <suffix> ], where <suffix> is some order of "u"
+// and "l" or "ll". binary constants and the ' that can separate digits are C23, 6.4.4.1
+fn constant_len(code: &[u8], std: Standard) -> Option<usize> {
+        let c23 = std == Standard::C23;
+        let has_prefix = |prefix: u8| (code.len() > 2) && (code[0] == b'0') && code[1].eq_ignore_ascii_case(&prefix);
+        let (prefix_len, is_digit): (usize, fn(&u8) -> bool) = if has_prefix(b'x') {
+                (2, u8::is_ascii_hexdigit)
+        } else if c23 && has_prefix(b'b') {
+                (2, |f| matches!(f, b'0' | b'1'))
+        } else {
+                (0, u8::is_ascii_digit)
+        };
+        let digits = digits_len(&code[prefix_len..], is_digit, c23);
+        if prefix_len == 2 && digits == 0 {
+                return None;
+        }
+        let mut len = prefix_len + digits;
+
+        let suffix_len = code[len..]
+                .iter()
+                .take_while(|f| matches!(f, b'u' | b'U' | b'l' | b'L'))
+                .count();
+        let suffix = &code[len..len + suffix_len];
+        let valid_suffix = match suffix.len() {
+                0 | 1 => true,
+                2 => suffix.eq_ignore_ascii_case(b"ul") | suffix.eq_ignore_ascii_case(b"lu") | is_long_long(suffix),
+                3 => {
+                        (matches!(suffix[0], b'u' | b'U') && is_long_long(&suffix[1..]))
+                                | (matches!(suffix[2], b'u' | b'U') && is_long_long(&suffix[..2]))
+                }
+                _ => false,
+        };
+        len += suffix_len;
+
+        if !valid_suffix || code.get(len).is_some_and(|f| f.is_ascii_alphanumeric() | (*f == b'_')) {
+                return None;
+        }
+
+        Some(len)
+}
+
+// a ' only separates digits when there's one on either side of it, so "1'" is still a 1 followed by a character constant
+fn digits_len(code: &[u8], is_digit: fn(&u8) -> bool, separators: bool) -> usize {
+        let mut len = code.iter().take_while(|f| is_digit(f)).count();
+        while separators && len > 0 && code.get(len) == Some(&b'\'') && code.get(len + 1).is_some_and(is_digit) {
+                len += 1 + code[len + 1..].iter().take_while(|f| is_digit(f)).count();
+        }
+        len
+}
+
+// <char> ::= "'" { <character> | "\" <escape> } "'", which is evaluated by the parser
+fn character_constant_len(code: &[u8]) -> Option<usize> {
+        let mut len = 1;
+        loop {
+                match *code.get(len)? {
+                        b'\'' if len > 1 => return Some(len + 1),
+                        b'\'' | b'\n' => return None,
+                        b'\\' => len += 2,
+                        _ => len += 1,
+                }
+        }
+}
+
+// <string> ::= """ { <character> | "\" <escape> } """, which is only ever used as the message of a static assertion or an attribute
+fn string_literal_len(code: &[u8]) -> Option<usize> {
+        let mut len = 1;
+        loop {
+                match *code.get(len)? {
+                        b'"' => return Some(len + 1),
+                        b'\n' => return None,
+                        b'\\' => len += 2,
+                        _ => len += 1,
+                }
+        }
+}
+
+fn is_long_long(suffix: &[u8]) -> bool {
+        (suffix == b"ll") | (suffix == b"LL")
+}
+
+fn match_symbol(code: &[u8], start: usize, std: Standard) -> Option<Token> {
+        if code[start..].starts_with(b"...") {
+                return Some(Token {
+                        token_type: TokenType::Ellipsis,
+                        len: 3,
+                        start,
+                });
+        }
+
+        if std == Standard::C23 {
+                if let Some(token_type) = match &code[start..] {
+                        [b'[', b'[', ..] => Some(TokenType::DoubleOpenBracket),
+                        [b':', b':', ..] => Some(TokenType::DoubleColon),
+                        _ => None,
+                } {
+                        return Some(Token {
+                                token_type,
+                                len: 2,
+                                start,
+                        });
+                }
+        }
+
         if let Some(token_type) = match code[start] {
                 b'(' => Some(TokenType::OpenParen),
                 b')' => Some(TokenType::CloseParen),
@@ -192,6 +442,9 @@ fn match_symbol(code: &[u8], start: usize) -> Option<Token> {
                 b'?' => Some(TokenType::Ternary),
                 b':' => Some(TokenType::Colon),
                 b',' => Some(TokenType::Comma),
+                b'.' => Some(TokenType::Dot),
+                b'[' => Some(TokenType::OpenBracket),
+                b']' => Some(TokenType::CloseBracket),
                 _ => None,
         } {
                 let Some(curr) = code.get(start + 1) else {
@@ -257,3 +510,65 @@ fn match_symbol(code: &[u8], start: usize) -> Option<Token> {
         }
         None
 }
+
+// attribute names aren't reserved, so they're only told apart inside "[[" "]]" or "__attribute__((" "))", where every
+// name outside the parentheses around arguments is an attribute or the vendor prefix of one
+fn tag_attributes(tokens: &mut [Token], code: &[u8]) {
+        let mut idx = 0;
+        while idx < tokens.len() {
+                // the names of a GNU attribute sit inside two parentheses rather than none
+                let (gnu, base) = match tokens[idx].token_type {
+                        TokenType::DoubleOpenBracket => (false, 0),
+                        TokenType::GnuAttribute => (true, 2),
+                        _ => {
+                                idx += 1;
+                                continue;
+                        }
+                };
+                idx += 1;
+
+                let mut depth = 0usize;
+                while let Some(&Token { token_type, start, len }) = tokens.get(idx) {
+                        match token_type {
+                                TokenType::OpenParen => depth += 1,
+                                TokenType::CloseParen if gnu && depth <= 1 => break,
+                                _ if gnu && depth == 0 => break,
+                                TokenType::CloseParen => depth = depth.saturating_sub(1),
+                                TokenType::CloseBracket if !gnu && depth == 0 => break,
+                                _ if depth == base && (code[start].is_ascii_alphabetic() | (code[start] == b'_')) => {
+                                        let prefixed = tokens[idx - 1].token_type == TokenType::DoubleColon
+                                                || tokens
+                                                        .get(idx + 1)
+                                                        .is_some_and(|f| f.token_type == TokenType::DoubleColon);
+                                        let name = if prefixed {
+                                                AttributeName::Other
+                                        } else {
+                                                attribute_name(&code[start..start + len], gnu)
+                                        };
+                                        tokens[idx].token_type = TokenType::Attribute(name);
+                                }
+                                _ => {}
+                        }
+                        idx += 1;
+                }
+        }
+}
+
+// GNU spells a few of the standard attributes differently, and has aligned, which the standard does with _Alignas
+fn attribute_name(text: &[u8], gnu: bool) -> AttributeName {
+        let text = text
+                .strip_prefix(b"__")
+                .and_then(|f| f.strip_suffix(b"__"))
+                .unwrap_or(text);
+        match text {
+                b"nodiscard" => AttributeName::Nodiscard,
+                b"maybe_unused" => AttributeName::MaybeUnused,
+                b"deprecated" => AttributeName::Deprecated,
+                b"fallthrough" => AttributeName::Fallthrough,
+                b"noreturn" | b"_Noreturn" => AttributeName::Noreturn,
+                b"unused" if gnu => AttributeName::MaybeUnused,
+                b"warn_unused_result" if gnu => AttributeName::Nodiscard,
+                b"aligned" if gnu => AttributeName::Aligned,
+                _ => AttributeName::Other,
+        }
+}
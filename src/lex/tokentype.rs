@@ -1,8 +1,15 @@
 use std::fmt::Display;
 
+use crate::semantic_analysis::type_checker::{Qualifiers, Type};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
         Int,
+        Char,
+        Short,
+        Long,
+        Signed,
+        Unsigned,
         Void,
         Return,
         Identifier,
@@ -61,6 +68,86 @@ pub enum TokenType {
         Case,
         Default,
         Comma,
+        Sizeof,
+        Alignof,
+        Static,
+        Extern,
+        Goto,
+        Dot,
+        OpenBracket,
+        CloseBracket,
+        Ellipsis,
+        Typedef,
+        Const,
+        Volatile,
+        Restrict,
+        // an identifier declared by a file scope typedef, retagged by the parser
+        TypedefName(Type, Qualifiers),
+        BuiltinVaList,
+        BuiltinVaStart,
+        BuiltinVaArg,
+        BuiltinVaEnd,
+        BuiltinVaCopy,
+        // _Static_assert or static_assert
+        StaticAssert,
+        StringLiteral,
+        // _Bool or bool
+        Bool,
+        Generic,
+        Typeof,
+        TypeofUnqual,
+        Constexpr,
+        // _Thread_local or thread_local
+        ThreadLocal,
+        // [[, which can only start an attribute specifier in C23, 6.7.12.1p2
+        DoubleOpenBracket,
+        // ::
+        DoubleColon,
+        // a name inside an attribute specifier, retagged by the lexer
+        Attribute(AttributeName),
+        // __attribute__ or __attribute
+        GnuAttribute,
+        BuiltinExpect,
+        BuiltinUnreachable,
+        BuiltinPopcount,
+        BuiltinClz,
+        BuiltinOffsetof,
+        // asm, __asm__ or __asm
+        Asm,
+        Atomic,
+        // __atomic_*, the GNU builtins
+        BuiltinAtomic(AtomicOp),
+        // __c11_atomic_*, which only take _Atomic objects
+        BuiltinC11Atomic(AtomicOp),
+}
+
+// what an atomic builtin does. only compare exchange differs between the two families, as the GNU one takes whether
+// it's weak as an argument rather than in its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicOp {
+        Load,
+        Store,
+        Exchange,
+        CompareExchange,
+        CompareExchangeWeak,
+        FetchAdd,
+        FetchSub,
+        ThreadFence,
+        SignalFence,
+}
+
+// the attributes that mean something, 6.7.12, also recognized as __name__. the rest are ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeName {
+        Nodiscard,
+        MaybeUnused,
+        Deprecated,
+        Fallthrough,
+        Noreturn,
+        // GNU only, __attribute__((aligned)) or __attribute__((aligned(N)))
+        Aligned,
+        // anything else, including every attribute with a vendor prefix and the prefixes themselves
+        Other,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1,7 +1,4 @@
 #![feature(type_changing_struct_update)]
-#![feature(box_vec_non_null)]
-#![feature(let_chains)]
-#![feature(unchecked_shifts)]
 
 use initialize::Operation;
 
@@ -31,10 +31,13 @@ fn main() {
                 return;
         }
         let code = res.state.code;
-        let (variable_map, semanal, max_label) = analyze(res.state.program, &code).unwrap_or_else(|f| {
+        let (_, semanal, max_label) = analyze(res.state.program, &code, res.state.std).unwrap_or_else(|f| {
                 eprintln!("{f}");
                 exit(1);
         });
+        for warning in &semanal.warnings {
+                eprintln!("warning: {warning}");
+        }
         if res.operation == Operation::Validate {
                 return;
         }
@@ -45,7 +48,7 @@ fn main() {
                 obj: res.obj,
         };
 
-        let res = tactile(res, max_label, variable_map, &code);
+        let res = tactile(res, max_label);
         if res.operation == Operation::ParseToTACTILETree {
                 return;
         }
@@ -1,15 +1,20 @@
+use std::{cell::RefCell, collections::HashMap};
+
 use nodes::{
-        ABlock, AConstant, AExpression, AFactor, AIdentifier, AProgram, AStatement, Binop, BlockItem, BreakType,
-        Conditional, Declaration, For, ForInit, FunctionDeclaration, IfStatement, LoopSwitchOrNone, ParseLabel, Switch,
-        Unop, VariableDeclaration,
+        ABlock, AConstant, AExpression, AFactor, AIdentifier, AProgram, AStatement, AStringLiteral, AsmOperand,
+        AtomicBuiltin, Attributes, Binop, BlockItem, BreakType, Builtin, Conditional, Constraint, Declaration,
+        Designator, ExprId, For, ForInit, FunctionDeclaration, GenericSelection, IfStatement, Initializer, InlineAsm,
+        ParseLabel, StaticAssert, StorageClass, Switch, TypeofOperand, Unop, VariableDeclaration,
 };
 use thiserror::Error;
 
 use crate::{
+        initialize::Standard,
         lex::{
-                tokentype::{Token, TokenType},
+                tokentype::{AtomicOp, AttributeName, Token, TokenType},
                 Lexed,
         },
+        semantic_analysis::type_checker::{FuncType, Qualifiers, Type},
         Program, State,
 };
 
@@ -19,6 +24,7 @@ pub mod nodes;
 pub struct Parsed {
         pub code: Vec<u8>,
         pub program: AProgram,
+        pub std: Standard,
 }
 impl State for Parsed {}
 
@@ -34,131 +40,1054 @@ pub enum Error {
         InvalidFactorAt(Token),
         #[error("Trailing comma in function declaration")]
         TrailingCommaInParamList,
-        #[error("Break outside loop")]
-        BreakOutsideLoop,
+        #[error("Invalid combination of type specifiers starting at {0}")]
+        InvalidTypeSpecifiers(Token),
+        #[error("More than one storage class in the declaration starting at {0}")]
+        MultipleStorageClasses(Token),
+        #[error("Parameter of type void at {0}")]
+        VoidParameter(Token),
+        #[error("restrict can only qualify a pointer to an object, but the declaration starting at {0} has none")]
+        RestrictWithoutPointer(Token),
+        #[error("Only pointers to functions are supported, not pointers to {0:?}")]
+        ObjectPointer(Type),
+        #[error("_Atomic can't be applied to a qualified type, as it is at {0}")]
+        QualifiedAtomic(Token),
+        #[error("Function definition with an unnamed parameter, with the body starting at {0}")]
+        UnnamedParameter(Token),
+        #[error("Invalid declaration in for loop header starting at {0}")]
+        InvalidForInit(Token),
+        #[error("Typedef at {0} redefines a name as a different type")]
+        ConflictingTypedef(Token),
+        #[error("{0} is declared as both a typedef name and something else in the same scope")]
+        TypedefRedeclared(Token),
+        #[error("Attribute {0} doesn't take arguments")]
+        AttributeArguments(Token),
+        #[error("fallthrough at {0} can only be on an empty statement")]
+        MisplacedFallthrough(Token),
+        #[error("constexpr can only declare an object without extern, but the declaration starting at {0} doesn't")]
+        InvalidConstexpr(Token),
+        #[error(
+                "_Thread_local can't declare a function or a constexpr object, as the declaration starting at {0} does"
+        )]
+        InvalidThreadLocal(Token),
 }
 
-// <program> ::= { <function-declaration> }
-pub fn parse_program(mut program: Program<Lexed>) -> Result<Program<Parsed>, Error> {
-        let mut ptr = 0;
+// what the names declared in a scope are, where None is something other than a typedef
+type Scope<'a> = HashMap<&'a [u8], Option<(Type, Qualifiers)>>;
+
+// the tokens, along with the typedef names in sight. an identifier only names a type where a typedef of it is in scope
+// and no declaration in an inner scope hides it, 6.2.1p4, so identifiers are retagged as the parser looks at them
+struct Tokens<'a> {
+        tokens: Vec<Token>,
+        code: &'a [u8],
+        // the innermost scope is last
+        scopes: RefCell<Vec<Scope<'a>>>,
+        // what ends up as AProgram::exprs
+        exprs: RefCell<Vec<AExpression>>,
+}
+
+impl<'a> Tokens<'a> {
+        fn new(tokens: Vec<Token>, code: &'a [u8]) -> Tokens<'a> {
+                Tokens {
+                        tokens,
+                        code,
+                        scopes: RefCell::new(vec![HashMap::new()]),
+                        exprs: RefCell::new(vec![]),
+                }
+        }
+
+        fn keep(&self, expr: AExpression) -> ExprId {
+                let mut exprs = self.exprs.borrow_mut();
+                exprs.push(expr);
+                ExprId(exprs.len() - 1)
+        }
+
+        fn len(&self) -> usize {
+                self.tokens.len()
+        }
+
+        fn get(&self, idx: usize) -> Option<Token> {
+                let mut token = *self.tokens.get(idx)?;
+                if token.token_type == TokenType::Identifier {
+                        let name = &self.code[token.start..token.start + token.len];
+                        if let Some(Some((aliased, qualifiers))) =
+                                self.scopes.borrow().iter().rev().find_map(|f| f.get(name).copied())
+                        {
+                                token.token_type = TokenType::TypedefName(aliased, qualifiers);
+                        }
+                }
+                Some(token)
+        }
 
-        let mut functions = vec![];
+        fn at(&self, idx: usize) -> Token {
+                self.get(idx).expect("parser looked past the last token")
+        }
+
+        // whatever the innermost scope already declares the name as
+        fn declared_here(&self, start: usize, len: usize) -> Option<Option<(Type, Qualifiers)>> {
+                let scopes = self.scopes.borrow();
+                scopes.last()?.get(&self.code[start..start + len]).copied()
+        }
 
-        while let Some(function) = parse_function_declaration(&mut program.state.tokens, &mut ptr)? {
-                functions.push(function);
+        fn declare(&self, start: usize, len: usize, typedef: Option<(Type, Qualifiers)>) {
+                if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+                        scope.insert(&self.code[start..start + len], typedef);
+                }
         }
 
-        if ptr < program.state.tokens.len() {
-                return Err(Error::TooManyTokens);
+        // runs f in a new scope, which is gone again afterwards whether or not f succeeded
+        fn in_scope<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+                self.scopes.borrow_mut().push(HashMap::new());
+                let result = f();
+                self.scopes.borrow_mut().pop();
+                result
+        }
+}
+
+// <program> ::= { <declaration> | <typedef> }
+pub fn parse_program(program: Program<Lexed>) -> Result<Program<Parsed>, Error> {
+        let mut ptr = 0;
+        let tokens = Tokens::new(program.state.tokens, &program.state.code);
+
+        let mut declarations = vec![];
+
+        while ptr < tokens.len() {
+                if tokens.at(ptr).token_type == TokenType::Typedef {
+                        declarations.extend(parse_typedef(&tokens, &mut ptr)?);
+                        continue;
+                }
+                declarations.push(parse_declaration(&tokens, &mut ptr)?);
         }
 
+        let exprs = tokens.exprs.into_inner();
         Ok(Program {
                 state: Parsed {
                         code: program.state.code,
-                        program: AProgram { functions },
+                        program: AProgram { declarations, exprs },
+                        std: program.state.std,
                 },
                 operation: program.operation,
                 obj: program.obj,
         })
 }
 
-// <block> ::= "{" { <block-item> } "}"
-fn parse_block(tokens: &[Token], ptr: &mut usize, curr_state: LoopSwitchOrNone) -> Result<ABlock, Error> {
-        is_token(tokens, TokenType::OpenBrace, ptr)?;
-        let mut block = vec![];
-        while tokens[*ptr].token_type != TokenType::CloseBrace {
-                block.push(parse_block_item(tokens, ptr, curr_state)?);
-        }
+// <typedef> ::= "typedef" <specifier-list> <declarator> { <gnu-attribute> } ";"
+// the name is a typedef name from the end of its declarator to the end of the scope it's in, unless something in an
+// inner scope hides it
+fn parse_typedef(tokens: &Tokens, ptr: &mut usize) -> Result<Option<Declaration>, Error> {
+        let first = tokens.at(*ptr);
         *ptr += 1;
-        Ok(ABlock(block))
+
+        let (base, qualifiers) = parse_type_specifiers(tokens, ptr)?;
+        let declared = apply_declarator(parse_declarator(tokens, ptr)?, base, qualifiers)?;
+        // aligned would only matter for objects of the new type, which is rare enough to leave
+        parse_attributes(tokens, ptr)?;
+        let Some(name) = declared.name else {
+                return Err(Error::InvalidTokenAt(tokens.at(*ptr), TokenType::Identifier));
+        };
+        let aliased = (declared.declared_type, declared.qualifiers);
+
+        // a typedef can be repeated in the same scope as long as it names the same type, 6.7
+        match tokens.declared_here(name.start, name.len) {
+                Some(Some(previous)) if previous != aliased => return Err(Error::ConflictingTypedef(first)),
+                Some(None) => return Err(Error::TypedefRedeclared(name)),
+                _ => {}
+        }
+        is_token(tokens, TokenType::SemiColon, ptr)?;
+        tokens.declare(name.start, name.len, Some(aliased));
+
+        Ok(has_expression(declared.declared_type).then_some(Declaration::T(declared.declared_type)))
+}
+
+// whether there's a typeof of an expression anywhere in the type
+fn has_expression(t: Type) -> bool {
+        match t {
+                Type::Typeof(_) => true,
+                Type::Pointer(&pointee) => has_expression(pointee),
+                Type::Func(func_type, _) => {
+                        has_expression(func_type.ret) || func_type.params.iter().any(|&param| has_expression(param))
+                }
+                _ => false,
+        }
+}
+
+// <block> ::= "{" { <block-item> | <typedef> } "}"
+fn parse_block(tokens: &Tokens, ptr: &mut usize) -> Result<ABlock, Error> {
+        is_token(tokens, TokenType::OpenBrace, ptr)?;
+        tokens.in_scope(|| {
+                let mut block = vec![];
+                while tokens.at(*ptr).token_type != TokenType::CloseBrace {
+                        if tokens.at(*ptr).token_type == TokenType::Typedef {
+                                block.extend(parse_typedef(tokens, ptr)?.map(BlockItem::D));
+                                continue;
+                        }
+                        block.push(parse_block_item(tokens, ptr)?);
+                }
+                *ptr += 1;
+                Ok(ABlock(block))
+        })
 }
 
 // <block-item> ::= <statement> | <declaration>
-fn parse_block_item(tokens: &[Token], ptr: &mut usize, curr_state: LoopSwitchOrNone) -> Result<BlockItem, Error> {
-        match tokens[*ptr].token_type {
-                TokenType::Int => Ok(BlockItem::D(parse_declaration(tokens, ptr)?)),
-                _ => Ok(BlockItem::S(parse_statement(tokens, ptr, curr_state)?)),
+// both can start with attributes, so it's the token after them that tells which one it is
+fn parse_block_item(tokens: &Tokens, ptr: &mut usize) -> Result<BlockItem, Error> {
+        let mut after_attributes = *ptr;
+        parse_attributes(tokens, &mut after_attributes)?;
+        let Some(next) = tokens.get(after_attributes) else {
+                return Err(Error::NotEnoughTokens);
+        };
+        if is_specifier(next.token_type) | (next.token_type == TokenType::StaticAssert) {
+                Ok(BlockItem::D(parse_declaration(tokens, ptr)?))
+        } else {
+                Ok(BlockItem::S(parse_statement(tokens, ptr)?))
         }
 }
 
-// <declaration> ::= <variable-declaration> | <function-declaration>
-fn parse_declaration(tokens: &[Token], ptr: &mut usize) -> Result<Declaration, Error> {
-        if [TokenType::Equal, TokenType::SemiColon].contains(&tokens[*ptr + 2].token_type) {
-                Ok(Declaration::V(parse_variable_declaration(tokens, ptr)?))
+// <declaration> ::= { <attribute-specifier> } ( <variable-declaration> | <function-declaration> ) | <static-assert>
+// where a <gnu-attribute> can also be among the specifiers or right after the declarator
+fn parse_declaration(tokens: &Tokens, ptr: &mut usize) -> Result<Declaration, Error> {
+        if is_token(tokens, TokenType::StaticAssert, ptr).is_ok() {
+                return Ok(Declaration::A(parse_static_assert(tokens, ptr)?));
+        }
+
+        let (attributes, fallthrough) = parse_attributes(tokens, ptr)?;
+        if let Some(token) = fallthrough {
+                return Err(Error::MisplacedFallthrough(token));
+        }
+
+        let Some(first) = tokens.get(*ptr) else {
+                return Err(Error::NotEnoughTokens);
+        };
+        let Specifiers {
+                specified_type,
+                qualifiers,
+                storage_class,
+                constexpr,
+                thread_local,
+                attributes: among_specifiers,
+        } = parse_specifiers(tokens, ptr)?;
+        let declared = apply_declarator(parse_declarator(tokens, ptr)?, specified_type, qualifiers)?;
+        // GNU attributes can also follow the declarator
+        let (after_declarator, fallthrough) = parse_attributes(tokens, ptr)?;
+        if let Some(token) = fallthrough {
+                return Err(Error::MisplacedFallthrough(token));
+        }
+        let attributes = after_declarator.merge(Some(among_specifiers.merge(Some(attributes))));
+        let name = match declared.name {
+                Some(name) => declare_ordinary(tokens, name)?,
+                None => match tokens.get(*ptr) {
+                        Some(token) => return Err(Error::InvalidTokenAt(token, TokenType::Identifier)),
+                        None => return Err(Error::NotEnoughTokens),
+                },
+        };
+
+        if constexpr
+                && (storage_class == Some(StorageClass::Extern) || matches!(declared.declared_type, Type::Func(..)))
+        {
+                return Err(Error::InvalidConstexpr(first));
+        }
+        if thread_local && (constexpr || matches!(declared.declared_type, Type::Func(..))) {
+                return Err(Error::InvalidThreadLocal(first));
+        }
+
+        if let Type::Func(func_type, _) = declared.declared_type {
+                Ok(Declaration::F(FunctionDeclaration {
+                        attributes,
+                        ..parse_function_declaration(tokens, ptr, name, func_type, declared.params, storage_class)?
+                }))
         } else {
-                Ok(Declaration::F(parse_function_declaration(tokens, ptr)?.unwrap()))
+                Ok(Declaration::V(VariableDeclaration {
+                        attributes,
+                        constexpr,
+                        thread_local,
+                        ..parse_variable_declaration(
+                                tokens,
+                                ptr,
+                                name,
+                                declared.declared_type,
+                                declared.qualifiers,
+                                storage_class,
+                        )?
+                }))
+        }
+}
+
+// <attribute-specifier> ::= "[[" [ <attribute> ] { "," [ <attribute> ] } "]" "]"
+// <gnu-attribute> ::= ( "__attribute__" | "__attribute" ) "(" "(" [ <attribute> ] { "," [ <attribute> ] } ")" ")"
+// <attribute> ::= [ <attribute-name> "::" ] <attribute-name> [ "(" <balanced-tokens> ")" ]
+// nodiscard and deprecated can only be given a string, aligned an expression, fallthrough, maybe_unused and noreturn
+// nothing, and anything else is ignored, 6.7.12. the fallthrough attribute is returned separately, as it's only
+// allowed on an empty statement
+fn parse_attributes(tokens: &Tokens, ptr: &mut usize) -> Result<(Attributes, Option<Token>), Error> {
+        let mut attributes = Attributes::default();
+        let mut fallthrough = None;
+
+        loop {
+                let gnu = if is_token(tokens, TokenType::DoubleOpenBracket, ptr).is_ok() {
+                        false
+                } else if is_token(tokens, TokenType::GnuAttribute, ptr).is_ok() {
+                        is_token(tokens, TokenType::OpenParen, ptr)?;
+                        is_token(tokens, TokenType::OpenParen, ptr)?;
+                        true
+                } else {
+                        break;
+                };
+
+                loop {
+                        if let Some(token) = tokens
+                                .get(*ptr)
+                                .filter(|f| matches!(f.token_type, TokenType::Attribute(_)))
+                        {
+                                *ptr += 1;
+                                parse_attribute(tokens, ptr, token, &mut attributes, &mut fallthrough)?;
+                        }
+
+                        if is_token(tokens, TokenType::Comma, ptr).is_err() {
+                                break;
+                        }
+                }
+                if gnu {
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+                } else {
+                        is_token(tokens, TokenType::CloseBracket, ptr)?;
+                        is_token(tokens, TokenType::CloseBracket, ptr)?;
+                }
         }
+
+        Ok((attributes, fallthrough))
 }
 
-// <function-declaration> ::= "int" <identifier> "(" <param-list> ")" ( <block> | ";")
-fn parse_function_declaration(tokens: &[Token], ptr: &mut usize) -> Result<Option<FunctionDeclaration>, Error> {
-        if tokens.len() == *ptr {
+// called once the name of the attribute has been consumed
+fn parse_attribute(
+        tokens: &Tokens,
+        ptr: &mut usize,
+        token: Token,
+        attributes: &mut Attributes,
+        fallthrough: &mut Option<Token>,
+) -> Result<(), Error> {
+        let mut attribute = token.token_type;
+        // a prefixed attribute belongs to some other implementation
+        if is_token(tokens, TokenType::DoubleColon, ptr).is_ok() {
+                is_token(tokens, TokenType::Attribute(AttributeName::Other), ptr)?;
+                attribute = TokenType::Attribute(AttributeName::Other);
+        }
+
+        let has_arguments = tokens.get(*ptr).is_some_and(|f| f.token_type == TokenType::OpenParen);
+        match attribute {
+                TokenType::Attribute(AttributeName::Nodiscard) => {
+                        attributes.nodiscard = Some(parse_attribute_reason(tokens, ptr)?);
+                }
+                TokenType::Attribute(AttributeName::Deprecated) => {
+                        attributes.deprecated = Some(parse_attribute_reason(tokens, ptr)?);
+                }
+                TokenType::Attribute(AttributeName::Aligned) => {
+                        let mut alignment = None;
+                        if is_token(tokens, TokenType::OpenParen, ptr).is_ok() {
+                                let expr = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Ternary))?;
+                                alignment = Some(tokens.keep(expr));
+                                is_token(tokens, TokenType::CloseParen, ptr)?;
+                        }
+                        attributes.aligned = Some(alignment);
+                }
+                _ if has_arguments && attribute != TokenType::Attribute(AttributeName::Other) => {
+                        return Err(Error::AttributeArguments(token));
+                }
+                TokenType::Attribute(AttributeName::MaybeUnused) => attributes.maybe_unused = true,
+                TokenType::Attribute(AttributeName::Noreturn) => attributes.noreturn = true,
+                TokenType::Attribute(AttributeName::Fallthrough) => *fallthrough = Some(token),
+                _ => skip_balanced(tokens, ptr)?,
+        }
+        Ok(())
+}
+
+// [ "(" <string> { <string> } ")" ]
+fn parse_attribute_reason(tokens: &Tokens, ptr: &mut usize) -> Result<Option<AStringLiteral>, Error> {
+        if is_token(tokens, TokenType::OpenParen, ptr).is_err() {
                 return Ok(None);
         }
+        let reason = parse_string_literal(tokens, ptr)?;
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+        Ok(Some(reason))
+}
 
-        is_token(tokens, TokenType::Int, ptr)?;
+// the arguments of an attribute rcc doesn't know can be any tokens, as long as the brackets in them are balanced
+fn skip_balanced(tokens: &Tokens, ptr: &mut usize) -> Result<(), Error> {
+        let mut depth = 0;
+        loop {
+                let Some(token) = tokens.get(*ptr) else {
+                        return Err(Error::NotEnoughTokens);
+                };
+                match token.token_type {
+                        TokenType::OpenParen | TokenType::OpenBracket | TokenType::OpenBrace => depth += 1,
+                        TokenType::CloseParen | TokenType::CloseBracket | TokenType::CloseBrace if depth > 0 => {
+                                depth -= 1
+                        }
+                        _ if depth == 0 => return Ok(()),
+                        _ => {}
+                }
+                *ptr += 1;
+                if depth == 0 {
+                        return Ok(());
+                }
+        }
+}
 
-        let name = parse_identifier(tokens, ptr)?;
+// <static-assert> ::= "_Static_assert" "(" <exp> [ "," <string> { <string> } ] ")" ";"
+// the message can only be left out since C23, but there's no reason to insist on it
+fn parse_static_assert(tokens: &Tokens, ptr: &mut usize) -> Result<StaticAssert, Error> {
+        is_token(tokens, TokenType::OpenParen, ptr)?;
+        let condition = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Ternary))?;
 
+        let mut message = None;
+        if is_token(tokens, TokenType::Comma, ptr).is_ok() {
+                message = Some(parse_string_literal(tokens, ptr)?);
+        }
+
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+        is_token(tokens, TokenType::SemiColon, ptr)?;
+
+        Ok(StaticAssert { condition, message })
+}
+
+// <string> { <string> }, where the adjacent literals are kept as the one span of source they cover
+fn parse_string_literal(tokens: &Tokens, ptr: &mut usize) -> Result<AStringLiteral, Error> {
+        let (start, len) = is_token(tokens, TokenType::StringLiteral, ptr)?;
+        let mut end = start + len;
+        while let Ok((next, len)) = is_token(tokens, TokenType::StringLiteral, ptr) {
+                end = next + len;
+        }
+        Ok(AStringLiteral {
+                start,
+                len: end - start,
+        })
+}
+
+// a declarator before it's applied to the type from the specifiers
+enum Declarator {
+        // None in an abstract declarator
+        Name(Option<Token>),
+        Pointer(Box<Declarator>, Qualifiers),
+        Function {
+                inner: Box<Declarator>,
+                params: Vec<(Type, Qualifiers, Option<AIdentifier>)>,
+                variadic: bool,
+                prototyped: bool,
+        },
+}
+
+// what a declarator declares. the parameters are only kept when the function declarator is applied directly to the
+// name, as that's the only place a body can follow
+struct Declared {
+        name: Option<Token>,
+        declared_type: Type,
+        qualifiers: Qualifiers,
+        params: Option<Vec<(Option<AIdentifier>, Qualifiers)>>,
+}
+
+// <declarator> ::= "*" { <type-qualifier> } <declarator> | <direct-declarator>
+// <direct-declarator> ::= [ <identifier> | "(" <declarator> ")" ] [ "(" <param-list> ")" ], where only an abstract
+// declarator can leave out the identifier
+fn parse_declarator(tokens: &Tokens, ptr: &mut usize) -> Result<Declarator, Error> {
+        if let Some(star) = tokens.get(*ptr).filter(|f| f.token_type == TokenType::Asterisk) {
+                *ptr += 1;
+                let mut qualifiers = vec![];
+                while let Some(token) = tokens.get(*ptr).filter(|f| is_type_qualifier(f.token_type)) {
+                        qualifiers.push(token.token_type);
+                        *ptr += 1;
+                }
+                let (qualifiers, _) = split_qualifiers(&qualifiers, star)?;
+
+                return Ok(Declarator::Pointer(
+                        Box::new(parse_declarator(tokens, ptr)?),
+                        qualifiers,
+                ));
+        }
+
+        let mut declarator = match tokens.get(*ptr).map(|f| f.token_type) {
+                Some(TokenType::Identifier | TokenType::TypedefName(..)) => {
+                        *ptr += 1;
+                        Declarator::Name(Some(tokens.at(*ptr - 1)))
+                }
+                // a parameter list starts with a specifier, so anything else after "(" is a nested declarator
+                Some(TokenType::OpenParen)
+                        if tokens.get(*ptr + 1).is_some_and(|f| {
+                                matches!(
+                                        f.token_type,
+                                        TokenType::Asterisk | TokenType::OpenParen | TokenType::Identifier
+                                )
+                        }) =>
+                {
+                        *ptr += 1;
+                        let inner = parse_declarator(tokens, ptr)?;
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+                        inner
+                }
+                _ => Declarator::Name(None),
+        };
+
+        if is_token(tokens, TokenType::OpenParen, ptr).is_ok() {
+                // the parameters are only in scope until the end of the declarator, 6.2.1p4
+                let (params, variadic, prototyped) = tokens.in_scope(|| parse_param_list(tokens, ptr))?;
+                is_token(tokens, TokenType::CloseParen, ptr)?;
+                declarator = Declarator::Function {
+                        inner: Box::new(declarator),
+                        params,
+                        variadic,
+                        prototyped,
+                };
+        }
+
+        Ok(declarator)
+}
+
+// the declarator says what to derive from the base type from the outside in, so "int (*f)(void)" is a pointer to a
+// function returning int. qualifiers on the return type don't mean anything, as the value a call returns isn't an
+// lvalue, so only the ones on the outermost pointer or the specifiers are kept
+fn apply_declarator(declarator: Declarator, base: Type, qualifiers: Qualifiers) -> Result<Declared, Error> {
+        match declarator {
+                Declarator::Name(name) => Ok(Declared {
+                        name,
+                        declared_type: base,
+                        qualifiers,
+                        params: None,
+                }),
+                Declarator::Pointer(inner, pointer_qualifiers) => {
+                        // what typeof(expr) names is only known once the expression is type checked
+                        let (Type::Func(..) | Type::Typeof(_)) = base else {
+                                return Err(Error::ObjectPointer(base));
+                        };
+                        apply_declarator(*inner, base.pointer_to(), pointer_qualifiers)
+                }
+                Declarator::Function {
+                        inner,
+                        params,
+                        variadic,
+                        prototyped,
+                } => {
+                        let direct = matches!(*inner, Declarator::Name(_));
+                        let (param_types, params): (Vec<_>, Vec<_>) = params
+                                .into_iter()
+                                .map(|(f, qualifiers, name)| (f, (name, qualifiers)))
+                                .unzip();
+                        let func_type = FuncType {
+                                ret: base,
+                                params: param_types,
+                                variadic,
+                                prototyped,
+                        }
+                        .intern();
+
+                        let mut declared =
+                                apply_declarator(*inner, Type::Func(func_type, false), Qualifiers::default())?;
+                        if direct {
+                                declared.params = Some(params);
+                        }
+                        Ok(declared)
+                }
+        }
+}
+
+// the name a declarator declares as an object, function or parameter, which hides any typedef name it had outside
+fn declare_ordinary(tokens: &Tokens, name: Token) -> Result<AIdentifier, Error> {
+        if let Some(Some(_)) = tokens.declared_here(name.start, name.len) {
+                return Err(Error::TypedefRedeclared(name));
+        }
+        tokens.declare(name.start, name.len, None);
+
+        Ok(AIdentifier {
+                start: name.start,
+                len: name.len,
+                symbol: None,
+        })
+}
+
+fn is_specifier(token_type: TokenType) -> bool {
+        is_type_specifier(token_type)
+                | is_type_qualifier(token_type)
+                | matches!(
+                        token_type,
+                        TokenType::Static | TokenType::Extern | TokenType::Constexpr | TokenType::ThreadLocal
+                )
+}
+
+// a typedef name after another type specifier is the name being declared, as in a repeated typedef, 6.7.2p2
+fn is_declared_name(token_type: TokenType, specifiers: &[TokenType]) -> bool {
+        matches!(token_type, TokenType::TypedefName(..)) && specifiers.iter().any(|&f| is_type_specifier(f))
+}
+
+fn is_type_qualifier(token_type: TokenType) -> bool {
+        matches!(
+                token_type,
+                TokenType::Const | TokenType::Volatile | TokenType::Restrict | TokenType::Atomic
+        )
+}
+
+fn is_type_specifier(token_type: TokenType) -> bool {
+        matches!(
+                token_type,
+                TokenType::Int
+                        | TokenType::Char
+                        | TokenType::Short
+                        | TokenType::Long
+                        | TokenType::Signed
+                        | TokenType::Unsigned
+                        | TokenType::Void
+                        | TokenType::Bool
+                        | TokenType::BuiltinVaList
+                        | TokenType::TypedefName(..)
+                        | TokenType::Typeof
+                        | TokenType::TypeofUnqual
+        )
+}
+
+// what the specifiers of a declaration say, along with any GNU attributes among them
+struct Specifiers {
+        specified_type: Type,
+        qualifiers: Qualifiers,
+        storage_class: Option<StorageClass>,
+        constexpr: bool,
+        thread_local: bool,
+        attributes: Attributes,
+}
+
+// <storage-class> ::= "static" | "extern" | "constexpr" | "_Thread_local" | "thread_local"
+// <specifier> ::= <type-specifier> | <type-qualifier> | <storage-class> | <gnu-attribute>
+// <specifiers> ::= <specifier> { <specifier> }, with at most one <storage-class> other than constexpr, which also
+// makes the object const, and _Thread_local, which can go with static or extern but not appear twice, 6.7.1
+fn parse_specifiers(tokens: &Tokens, ptr: &mut usize) -> Result<Specifiers, Error> {
+        let Some(first) = tokens.get(*ptr) else {
+                return Err(Error::NotEnoughTokens);
+        };
+
+        let mut specifiers = vec![];
+        let mut storage_class = None;
+        let mut constexpr = false;
+        let mut thread_local = false;
+        let mut attributes = Attributes::default();
+        while let Some(token) = tokens.get(*ptr).filter(|f| {
+                (is_specifier(f.token_type) | (f.token_type == TokenType::GnuAttribute))
+                        && !is_declared_name(f.token_type, &specifiers)
+        }) {
+                let class = match token.token_type {
+                        TokenType::Static => Some(StorageClass::Static),
+                        TokenType::Extern => Some(StorageClass::Extern),
+                        TokenType::Constexpr => {
+                                constexpr = true;
+                                None
+                        }
+                        TokenType::ThreadLocal if thread_local => return Err(Error::MultipleStorageClasses(first)),
+                        TokenType::ThreadLocal => {
+                                thread_local = true;
+                                None
+                        }
+                        TokenType::Typeof | TokenType::TypeofUnqual => {
+                                specifiers.push(parse_typeof(tokens, ptr)?);
+                                continue;
+                        }
+                        TokenType::Atomic if is_atomic_specifier(tokens, *ptr) => {
+                                specifiers.push(parse_atomic_specifier(tokens, ptr)?);
+                                continue;
+                        }
+                        TokenType::GnuAttribute => {
+                                let (among, fallthrough) = parse_attributes(tokens, ptr)?;
+                                if let Some(token) = fallthrough {
+                                        return Err(Error::MisplacedFallthrough(token));
+                                }
+                                attributes = among.merge(Some(attributes));
+                                continue;
+                        }
+                        token_type => {
+                                specifiers.push(token_type);
+                                None
+                        }
+                };
+                if class.is_some() {
+                        if storage_class.is_some() {
+                                return Err(Error::MultipleStorageClasses(first));
+                        }
+                        storage_class = class;
+                }
+                *ptr += 1;
+        }
+
+        let (specified_type, mut qualifiers) = type_from_specifiers(&specifiers, first)?;
+        qualifiers.constant |= constexpr;
+        Ok(Specifiers {
+                specified_type,
+                qualifiers,
+                storage_class,
+                constexpr,
+                thread_local,
+                attributes,
+        })
+}
+
+// <type-specifier> ::= "int" | "char" | "short" | "long" | "signed" | "unsigned" | "void" | "_Bool" | "bool"
+// | "__builtin_va_list" | <typedef-name> | <typeof> | <atomic-specifier>
+// <type-qualifier> ::= "const" | "volatile" | "restrict" | "_Atomic"
+// <specifier-list> ::= ( <type-specifier> | <type-qualifier> ) { <type-specifier> | <type-qualifier> }, in any order,
+// with the type specifiers forming one of the combinations in 6.7.2, and any <gnu-attribute> among them ignored
+fn parse_type_specifiers(tokens: &Tokens, ptr: &mut usize) -> Result<(Type, Qualifiers), Error> {
+        let Some(first) = tokens.get(*ptr) else {
+                return Err(Error::NotEnoughTokens);
+        };
+
+        let mut specifiers = vec![];
+        while tokens.get(*ptr).is_some_and(|f| {
+                (is_type_specifier(f.token_type)
+                        | is_type_qualifier(f.token_type)
+                        | (f.token_type == TokenType::GnuAttribute))
+                        && !is_declared_name(f.token_type, &specifiers)
+        }) {
+                match tokens.at(*ptr).token_type {
+                        TokenType::Typeof | TokenType::TypeofUnqual => specifiers.push(parse_typeof(tokens, ptr)?),
+                        TokenType::Atomic if is_atomic_specifier(tokens, *ptr) => {
+                                specifiers.push(parse_atomic_specifier(tokens, ptr)?)
+                        }
+                        // nothing an attribute here could say matters
+                        TokenType::GnuAttribute => {
+                                parse_attributes(tokens, ptr)?;
+                        }
+                        token_type => {
+                                specifiers.push(token_type);
+                                *ptr += 1;
+                        }
+                }
+        }
+
+        type_from_specifiers(&specifiers, first)
+}
+
+// <typeof> ::= ( "typeof" | "typeof_unqual" ) "(" ( <type-name> | <exp> ) ")"
+// it names a type the same way a typedef name does, so that's what it's turned into. the type of an expression is
+// only known after type checking, 6.7.2.5
+fn parse_typeof(tokens: &Tokens, ptr: &mut usize) -> Result<TokenType, Error> {
+        let unqual = tokens.at(*ptr).token_type == TokenType::TypeofUnqual;
+        *ptr += 1;
         is_token(tokens, TokenType::OpenParen, ptr)?;
 
-        let params = parse_param_list(tokens, ptr)?;
+        let (of, qualifiers) = if tokens
+                .get(*ptr)
+                .is_some_and(|f| is_type_specifier(f.token_type) | is_type_qualifier(f.token_type))
+        {
+                parse_qualified_type_name(tokens, ptr)?
+        } else {
+                let expr = parse_expression(tokens, ptr, 0)?;
+                (
+                        Type::Typeof(TypeofOperand {
+                                expr: tokens.keep(expr),
+                                unqual,
+                        }),
+                        Qualifiers::default(),
+                )
+        };
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+
+        Ok(TokenType::TypedefName(
+                of,
+                if unqual { Qualifiers::default() } else { qualifiers },
+        ))
+}
 
+// "_Atomic" followed by "(" is always the specifier rather than the qualifier, 6.7.2.4p4
+fn is_atomic_specifier(tokens: &Tokens, ptr: usize) -> bool {
+        tokens.get(ptr + 1)
+                .is_some_and(|f| f.token_type == TokenType::OpenParen)
+}
+
+// <atomic-specifier> ::= "_Atomic" "(" <type-name> ")"
+// it names the same type the qualifier would, and is turned into a typedef name like typeof. the type in it can't be
+// qualified, 6.7.2.4p3
+fn parse_atomic_specifier(tokens: &Tokens, ptr: &mut usize) -> Result<TokenType, Error> {
+        let atomic = tokens.at(*ptr);
+        *ptr += 1;
+        is_token(tokens, TokenType::OpenParen, ptr)?;
+        let (of, qualifiers) = parse_qualified_type_name(tokens, ptr)?;
         is_token(tokens, TokenType::CloseParen, ptr)?;
+        if qualifiers != Qualifiers::default() {
+                return Err(Error::QualifiedAtomic(atomic));
+        }
+
+        Ok(TokenType::TypedefName(
+                of,
+                Qualifiers {
+                        atomic: true,
+                        ..Qualifiers::default()
+                },
+        ))
+}
 
+fn type_from_specifiers(specifiers: &[TokenType], first: Token) -> Result<(Type, Qualifiers), Error> {
+        let (qualifiers, type_specifiers) = split_qualifiers(specifiers, first)?;
+        Ok((unqualified_type(&type_specifiers, first)?, qualifiers))
+}
+
+// qualifiers can be repeated, and add to the ones a typedef name already has, 6.7.3
+fn split_qualifiers(specifiers: &[TokenType], first: Token) -> Result<(Qualifiers, Vec<TokenType>), Error> {
+        let mut qualifiers = Qualifiers::default();
+        let mut type_specifiers = vec![];
+        for &specifier in specifiers {
+                match specifier {
+                        TokenType::Const => qualifiers.constant = true,
+                        TokenType::Volatile => qualifiers.volatile = true,
+                        TokenType::Atomic => qualifiers.atomic = true,
+                        TokenType::Restrict => return Err(Error::RestrictWithoutPointer(first)),
+                        TokenType::TypedefName(_, named) => {
+                                qualifiers.constant |= named.constant;
+                                qualifiers.volatile |= named.volatile;
+                                qualifiers.atomic |= named.atomic;
+                                type_specifiers.push(specifier);
+                        }
+                        _ => type_specifiers.push(specifier),
+                }
+        }
+
+        Ok((qualifiers, type_specifiers))
+}
+
+fn unqualified_type(specifiers: &[TokenType], first: Token) -> Result<Type, Error> {
+        if specifiers.is_empty() {
+                return Err(Error::InvalidTokenAt(first, TokenType::Int));
+        }
+
+        // void, bool, a typedef name or __builtin_va_list can't be combined with any other type specifier
+        if let Some(named) = specifiers.iter().find_map(|f| match f {
+                TokenType::TypedefName(named, _) => Some(*named),
+                TokenType::BuiltinVaList => Some(Type::VaList),
+                TokenType::Void => Some(Type::Void),
+                TokenType::Bool => Some(Type::Bool),
+                _ => None,
+        }) {
+                if specifiers.len() > 1 {
+                        return Err(Error::InvalidTypeSpecifiers(first));
+                }
+                return Ok(named);
+        }
+
+        let count = |token_type| specifiers.iter().filter(|&&f| f == token_type).count();
+        let (int, char, short, long) = (
+                count(TokenType::Int),
+                count(TokenType::Char),
+                count(TokenType::Short),
+                count(TokenType::Long),
+        );
+        let (signed, unsigned) = (count(TokenType::Signed), count(TokenType::Unsigned));
+
+        if (int > 1)
+                | (char > 1)
+                | (short > 1)
+                | (long > 2)
+                | (signed + unsigned > 1)
+                | ((char == 1) & (int + short + long > 0))
+                | ((short == 1) & (long > 0))
+        {
+                return Err(Error::InvalidTypeSpecifiers(first));
+        }
+
+        let is_unsigned = unsigned == 1;
+        Ok(match (char, short, long) {
+                (1, _, _) if signed == 1 => Type::SChar,
+                (1, _, _) if is_unsigned => Type::UChar,
+                (1, _, _) => Type::Char,
+                (_, 1, _) if is_unsigned => Type::UShort,
+                (_, 1, _) => Type::Short,
+                (_, _, 1) if is_unsigned => Type::ULong,
+                (_, _, 1) => Type::Long,
+                (_, _, 2) if is_unsigned => Type::ULongLong,
+                (_, _, 2) => Type::LongLong,
+                _ if is_unsigned => Type::UInt,
+                _ => Type::Int,
+        })
+}
+
+// <type-name> ::= <specifier-list> <declarator>, where the declarator is abstract
+// the qualifiers are dropped, as casts, sizeof and the rest never produce an lvalue that could be modified
+fn parse_type_name(tokens: &Tokens, ptr: &mut usize) -> Result<Type, Error> {
+        Ok(parse_qualified_type_name(tokens, ptr)?.0)
+}
+
+// typeof keeps them though
+fn parse_qualified_type_name(tokens: &Tokens, ptr: &mut usize) -> Result<(Type, Qualifiers), Error> {
+        let (base, qualifiers) = parse_type_specifiers(tokens, ptr)?;
+        let declared = apply_declarator(parse_declarator(tokens, ptr)?, base, qualifiers)?;
+        if let Some(name) = declared.name {
+                return Err(Error::InvalidTokenAt(name, TokenType::CloseParen));
+        }
+        Ok((declared.declared_type, declared.qualifiers))
+}
+
+// is the next token the "(" of a parenthesised <type-name>?
+fn is_type_name_next(tokens: &Tokens, ptr: usize) -> bool {
+        tokens.get(ptr).is_some_and(|f| f.token_type == TokenType::OpenParen)
+                && tokens
+                        .get(ptr + 1)
+                        .is_some_and(|f| is_type_specifier(f.token_type) | is_type_qualifier(f.token_type))
+}
+
+// <function-declaration> ::= <specifiers> <declarator> ( <block> | ";"), where the declarator declares a function
+// called once the declarator has been consumed, with its parameters if it has a parameter list rather than a typedef
+// name for a function type
+fn parse_function_declaration(
+        tokens: &Tokens,
+        ptr: &mut usize,
+        name: AIdentifier,
+        func_type: &'static FuncType,
+        params: Option<Vec<(Option<AIdentifier>, Qualifiers)>>,
+        storage_class: Option<StorageClass>,
+) -> Result<FunctionDeclaration, Error> {
         let mut body = None;
-        if tokens[*ptr].token_type == TokenType::OpenBrace {
-                body = Some(parse_block(tokens, ptr, LoopSwitchOrNone::Neither)?);
+        if let Some(open) = tokens.get(*ptr).filter(|f| f.token_type == TokenType::OpenBrace) {
+                if params.as_ref().is_none_or(|f| f.iter().any(|f| f.0.is_none())) {
+                        return Err(Error::UnnamedParameter(open));
+                }
+                // unless it's followed by a body, which they're in scope in too
+                body = Some(tokens.in_scope(|| {
+                        for (name, _) in params.iter().flatten() {
+                                if let Some(name) = name {
+                                        tokens.declare(name.start, name.len, None);
+                                }
+                        }
+                        parse_block(tokens, ptr)
+                })?);
         } else {
                 is_token(tokens, TokenType::SemiColon, ptr)?;
         }
 
-        Ok(Some(FunctionDeclaration { name, params, body }))
+        Ok(FunctionDeclaration {
+                name,
+                func_type,
+                params: params.unwrap_or_else(|| vec![(None, Qualifiers::default()); func_type.params.len()]),
+                body,
+                storage_class,
+                attributes: Attributes::default(),
+        })
 }
 
-// <param-list> ::= "void" | "int" <identifier> { "," "int" <identifier> }
-fn parse_param_list(tokens: &[Token], ptr: &mut usize) -> Result<Option<Vec<AIdentifier>>, Error> {
-        if is_token(tokens, TokenType::Void, ptr).is_ok() {
-                return Ok(None);
+// <param-list> ::= "void" | <param> { "," <param> } [ "," "..." ] | nothing, which leaves the function unprototyped
+// <param> ::= { <attribute-specifier> } <specifier-list> <declarator> { <gnu-attribute> }, where the declarator can be
+// abstract
+// also returns whether the list ends in "..." and whether it is a prototype
+#[allow(clippy::type_complexity)]
+fn parse_param_list(
+        tokens: &Tokens,
+        ptr: &mut usize,
+) -> Result<(Vec<(Type, Qualifiers, Option<AIdentifier>)>, bool, bool), Error> {
+        if are_tokens(tokens, &[TokenType::Void, TokenType::CloseParen], ptr).is_ok() {
+                *ptr -= 1;
+                return Ok((vec![], false, true));
+        }
+        if tokens.get(*ptr).is_some_and(|f| f.token_type == TokenType::CloseParen) {
+                return Ok((vec![], false, false));
         }
 
         let mut parameters = vec![];
-
         loop {
-                if is_token(tokens, TokenType::Int, ptr).is_ok() {
-                        parameters.push(parse_identifier(tokens, ptr)?);
-                } else if is_token(tokens, TokenType::Comma, ptr).is_ok() {
-                        continue;
-                } else {
-                        break;
+                if !parameters.is_empty() && is_token(tokens, TokenType::Ellipsis, ptr).is_ok() {
+                        return Ok((parameters, true, true));
+                }
+                if tokens.get(*ptr).is_some_and(|f| f.token_type == TokenType::CloseParen) {
+                        return Err(Error::TrailingCommaInParamList);
                 }
-        }
 
-        if tokens[*ptr - 1].token_type == TokenType::Comma {
-                return Err(Error::TrailingCommaInParamList);
-        }
+                // there's nothing an attribute on a parameter could say that matters
+                parse_attributes(tokens, ptr)?;
+                let first = tokens.at(*ptr);
+                let (base, qualifiers) = parse_type_specifiers(tokens, ptr)?;
+                let declared = apply_declarator(parse_declarator(tokens, ptr)?, base, qualifiers)?;
+                parse_attributes(tokens, ptr)?;
+                let name = declared.name.map(|f| declare_ordinary(tokens, f)).transpose()?;
+
+                // a parameter declared as a function is adjusted to a pointer to it, 6.7.6.3p8
+                let param_type = match declared.declared_type {
+                        Type::Void => return Err(Error::VoidParameter(first)),
+                        Type::Func(..) => declared.declared_type.pointer_to(),
+                        param_type => param_type,
+                };
+                parameters.push((param_type, declared.qualifiers, name));
 
-        Ok(Some(parameters))
+                if is_token(tokens, TokenType::Comma, ptr).is_err() {
+                        return Ok((parameters, false, true));
+                }
+        }
 }
 
-// <variable-declaration> ::= "int" <identifier> [ "=" <exp> ] ";"
-fn parse_variable_declaration(tokens: &[Token], ptr: &mut usize) -> Result<VariableDeclaration, Error> {
-        is_token(tokens, TokenType::Int, ptr)?;
-
-        let (start, len) = is_token(tokens, TokenType::Identifier, ptr)?;
-        let id = AIdentifier { start, len };
+// <variable-declaration> ::= <specifiers> <identifier> [ "=" <exp> ] ";"
+// called once the identifier has been consumed
+fn parse_variable_declaration(
+        tokens: &Tokens,
+        ptr: &mut usize,
+        id: AIdentifier,
+        var_type: Type,
+        qualifiers: Qualifiers,
+        storage_class: Option<StorageClass>,
+) -> Result<VariableDeclaration, Error> {
         let mut init = None;
 
-        if tokens[*ptr].token_type == TokenType::Equal {
+        if tokens.at(*ptr).token_type == TokenType::Equal {
                 *ptr += 1;
-                init = Some(parse_expression(tokens, ptr, 0)?);
+                init = Some(parse_initializer(tokens, ptr)?);
         }
 
         is_token(tokens, TokenType::SemiColon, ptr)?;
-        Ok(VariableDeclaration { id, init })
+        Ok(VariableDeclaration {
+                id,
+                init,
+                var_type,
+                qualifiers,
+                storage_class,
+                attributes: Attributes::default(),
+                constexpr: false,
+                thread_local: false,
+                alignment: None,
+        })
+}
+
+// <initializer> ::= <assignment-exp> | "{" [ <initializer-list> [ "," ] ] "}"
+// <initializer-list> ::= [ <designation> ] <initializer> { "," [ <designation> ] <initializer> }
+fn parse_initializer(tokens: &Tokens, ptr: &mut usize) -> Result<Initializer, Error> {
+        if is_token(tokens, TokenType::OpenBrace, ptr).is_err() {
+                let expr = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Equal))?;
+                return Ok(Initializer::Single(expr));
+        }
+
+        let mut list = vec![];
+        while is_token(tokens, TokenType::CloseBrace, ptr).is_err() {
+                let designators = parse_designation(tokens, ptr)?;
+                list.push((designators, parse_initializer(tokens, ptr)?));
+
+                if is_token(tokens, TokenType::Comma, ptr).is_err() {
+                        is_token(tokens, TokenType::CloseBrace, ptr)?;
+                        break;
+                }
+        }
+
+        Ok(Initializer::Compound(list))
+}
+
+// <designation> ::= { "[" <constant-exp> "]" | "." <identifier> } "="
+fn parse_designation(tokens: &Tokens, ptr: &mut usize) -> Result<Vec<Designator>, Error> {
+        let mut designators = vec![];
+        loop {
+                if is_token(tokens, TokenType::OpenBracket, ptr).is_ok() {
+                        let index = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Ternary))?;
+                        is_token(tokens, TokenType::CloseBracket, ptr)?;
+                        designators.push(Designator::Index(index));
+                } else if is_token(tokens, TokenType::Dot, ptr).is_ok() {
+                        designators.push(Designator::Member(parse_identifier(tokens, ptr)?));
+                } else {
+                        break;
+                }
+        }
+
+        if !designators.is_empty() {
+                is_token(tokens, TokenType::Equal, ptr)?;
+        }
+
+        Ok(designators)
 }
 
-/* <statement> ::= "return" <exp> ";"
+/* <statement> ::= "return" [ <exp> ] ";"
 | <exp> ";"
 | "if" "(" <exp> ")" <statement> [ "else" <statement> ]
 | <block>
@@ -168,13 +1097,40 @@ fn parse_variable_declaration(tokens: &[Token], ptr: &mut usize) -> Result<Varia
 | "do" <statement> "while" "(" <exp> ")" ";"
 | "for" "(" <for-init> [ <exp> ] ";" [ <exp> ] ")" <statement>
 | ";"
-| "switch" (aexpression) "{" [ { "case" ":" <statement> } ] [ "default" ":" <statement> ] "}"
+| "switch" "(" <exp> ")" <statement>
+| "case" <exp> ":" <statement>
+| "default" ":" <statement>
+| "goto" <identifier> ";"
+| <identifier> ":" <statement>
+| <attribute-specifier> { <attribute-specifier> } <statement>, where fallthrough can only be on ";"
+| <asm>
 */
-fn parse_statement(tokens: &[Token], ptr: &mut usize, curr_state: LoopSwitchOrNone) -> Result<AStatement, Error> {
-        // println!("curr_state: {:?}, curr_token: {:?}", curr_state, tokens[*ptr]);
+fn parse_statement(tokens: &Tokens, ptr: &mut usize) -> Result<AStatement, Error> {
+        if let (_, Some(fallthrough)) = parse_attributes(tokens, ptr)? {
+                if is_token(tokens, TokenType::SemiColon, ptr).is_err() {
+                        return Err(Error::MisplacedFallthrough(fallthrough));
+                }
+                return Ok(AStatement::Fallthrough);
+        }
+
+        if tokens.get(*ptr + 1).is_some_and(|f| f.token_type == TokenType::Colon) {
+                if let Ok(name) = parse_identifier(tokens, ptr) {
+                        *ptr += 1;
+                        let statement = parse_statement(tokens, ptr)?;
+                        return Ok(AStatement::Labeled(name, Box::new(statement), ParseLabel(0)));
+                }
+        }
+
+        if is_token(tokens, TokenType::Asm, ptr).is_ok() {
+                return Ok(AStatement::Asm(Box::new(parse_asm(tokens, ptr)?)));
+        }
+
         if is_token(tokens, TokenType::Return, ptr).is_ok() {
-                let expr = parse_expression(tokens, ptr, 0)?;
-                is_token(tokens, TokenType::SemiColon, ptr)?;
+                let mut expr = None;
+                if is_token(tokens, TokenType::SemiColon, ptr).is_err() {
+                        expr = Some(parse_expression(tokens, ptr, 0)?);
+                        is_token(tokens, TokenType::SemiColon, ptr)?;
+                }
                 Ok(AStatement::Return(expr))
         } else if let Ok(expr) = parse_expression(tokens, ptr, 0) {
                 is_token(tokens, TokenType::SemiColon, ptr)?;
@@ -183,109 +1139,108 @@ fn parse_statement(tokens: &[Token], ptr: &mut usize, curr_state: LoopSwitchOrNo
                 let condition = parse_expression(tokens, ptr, 0)?;
                 is_token(tokens, TokenType::CloseParen, ptr)?;
 
-                let then = Box::new(parse_statement(tokens, ptr, curr_state)?);
+                let then = Box::new(parse_statement(tokens, ptr)?);
 
-                let mut Else = None;
+                let mut else_statement = None;
                 if is_token(tokens, TokenType::Else, ptr).is_ok() {
-                        Else = Some(Box::new(parse_statement(tokens, ptr, curr_state)?));
+                        else_statement = Some(Box::new(parse_statement(tokens, ptr)?));
                 }
 
-                Ok(AStatement::I(IfStatement { condition, then, Else }))
+                Ok(AStatement::I(IfStatement {
+                        condition,
+                        then,
+                        Else: else_statement,
+                }))
         } else if is_token(tokens, TokenType::Switch, ptr).is_ok() {
                 is_token(tokens, TokenType::OpenParen, ptr)?;
                 let aexpression = parse_expression(tokens, ptr, 0)?;
-                are_tokens(tokens, &[TokenType::CloseParen, TokenType::OpenBrace], ptr)?;
-
-                let mut cases = vec![];
-                let mut default = None;
-
-                loop {
-                        if is_token(tokens, TokenType::Case, ptr).is_ok() {
-                                let constant = parse_constant(tokens, ptr)?;
-                                is_token(tokens, TokenType::Colon, ptr)?;
-                                let mut statements = vec![];
-                                while let Ok(statement) = parse_statement(tokens, ptr, LoopSwitchOrNone::Switch) {
-                                        statements.push(statement);
-                                }
-                                cases.push((constant, statements));
-                                continue;
-                        }
-
-                        let cond = are_tokens(tokens, &[TokenType::Default, TokenType::Colon], ptr);
-
-                        if cond.is_ok() && default.is_none() {
-                                default = Some(Box::new(parse_statement(tokens, ptr, LoopSwitchOrNone::Switch)?));
-                        } else if cond.is_ok() && default.is_some() {
-                                return Err(Error::InvalidTokenAt(tokens[*ptr], TokenType::Default));
-                        } else {
-                                break;
-                        }
-                }
-
-                is_token(tokens, TokenType::CloseBrace, ptr)?;
+                is_token(tokens, TokenType::CloseParen, ptr)?;
+                let body = parse_statement(tokens, ptr)?;
 
                 Ok(AStatement::S(Switch {
                         value: aexpression,
-                        cases,
-                        default,
+                        body: Box::new(body),
+                        cases: vec![],
+                        default: None,
                         label: ParseLabel(0),
                 }))
-        } else if let Ok(block) = parse_block(tokens, ptr, curr_state) {
+        } else if is_token(tokens, TokenType::Case, ptr).is_ok() {
+                let aexpression = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Ternary))?;
+                is_token(tokens, TokenType::Colon, ptr)?;
+                let statement = parse_statement(tokens, ptr)?;
+
+                Ok(AStatement::Case(aexpression, Box::new(statement), ParseLabel(0)))
+        } else if are_tokens(tokens, &[TokenType::Default, TokenType::Colon], ptr).is_ok() {
+                let statement = parse_statement(tokens, ptr)?;
+
+                Ok(AStatement::Default(Box::new(statement), ParseLabel(0)))
+        } else if let Ok(block) = parse_block(tokens, ptr) {
                 Ok(AStatement::Compound(block))
         } else if are_tokens(tokens, &[TokenType::Break, TokenType::SemiColon], ptr).is_ok() {
-                match curr_state {
-                        LoopSwitchOrNone::Loop => Ok(AStatement::Break(ParseLabel(0), BreakType::Loop)),
-                        LoopSwitchOrNone::Switch => Ok(AStatement::Break(ParseLabel(0), BreakType::Switch)),
-                        LoopSwitchOrNone::Neither => Err(Error::BreakOutsideLoop),
-                }
+                Ok(AStatement::Break(ParseLabel(0), BreakType::Loop))
+        } else if is_token(tokens, TokenType::Goto, ptr).is_ok() {
+                let name = parse_identifier(tokens, ptr)?;
+                is_token(tokens, TokenType::SemiColon, ptr)?;
+                Ok(AStatement::Goto(name, ParseLabel(0)))
         } else if are_tokens(tokens, &[TokenType::Continue, TokenType::SemiColon], ptr).is_ok() {
                 Ok(AStatement::Continue(ParseLabel(0)))
         } else if are_tokens(tokens, &[TokenType::While, TokenType::OpenParen], ptr).is_ok() {
                 let expr = parse_expression(tokens, ptr, 0)?;
                 is_token(tokens, TokenType::CloseParen, ptr)?;
-                let statement = parse_statement(tokens, ptr, LoopSwitchOrNone::Loop)?;
+                let statement = parse_statement(tokens, ptr)?;
 
                 Ok(AStatement::While(expr, Box::new(statement), ParseLabel(0)))
         } else if is_token(tokens, TokenType::Do, ptr).is_ok() {
-                let statement = parse_statement(tokens, ptr, LoopSwitchOrNone::Loop)?;
+                let statement = parse_statement(tokens, ptr)?;
                 are_tokens(tokens, &[TokenType::While, TokenType::OpenParen], ptr)?;
                 let expr = parse_expression(tokens, ptr, 0)?;
                 are_tokens(tokens, &[TokenType::CloseParen, TokenType::SemiColon], ptr)?;
 
                 Ok(AStatement::DoWhile(Box::new(statement), expr, ParseLabel(0)))
         } else if are_tokens(tokens, &[TokenType::For, TokenType::OpenParen], ptr).is_ok() {
-                let init = parse_for_init(tokens, ptr)?;
+                // a declaration in the header is in scope until the end of the loop, 6.8.5p5
+                tokens.in_scope(|| {
+                        let init = parse_for_init(tokens, ptr)?;
 
-                let (mut post, mut condition) = (None, None);
-                if let Ok(expr) = parse_expression(tokens, ptr, 0) {
-                        condition = Some(expr);
-                }
-                is_token(tokens, TokenType::SemiColon, ptr)?;
-                if let Ok(expr) = parse_expression(tokens, ptr, 0) {
-                        post = Some(expr);
-                }
-                is_token(tokens, TokenType::CloseParen, ptr)?;
-                let body = parse_statement(tokens, ptr, LoopSwitchOrNone::Loop)?;
-
-                Ok(AStatement::F(
-                        Box::new(For {
-                                init,
-                                condition,
-                                post,
-                                body,
-                        }),
-                        ParseLabel(0),
-                ))
+                        let (mut post, mut condition) = (None, None);
+                        if let Ok(expr) = parse_expression(tokens, ptr, 0) {
+                                condition = Some(expr);
+                        }
+                        is_token(tokens, TokenType::SemiColon, ptr)?;
+                        if let Ok(expr) = parse_expression(tokens, ptr, 0) {
+                                post = Some(expr);
+                        }
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+                        let body = parse_statement(tokens, ptr)?;
+
+                        Ok(AStatement::F(
+                                Box::new(For {
+                                        init,
+                                        condition,
+                                        post,
+                                        body,
+                                }),
+                                ParseLabel(0),
+                        ))
+                })
         } else {
                 is_token(tokens, TokenType::SemiColon, ptr)?;
                 Ok(AStatement::Nul)
         }
 }
 
-// <for-init> ::= <variable_declaration> | [ <exp> ] ";"
-fn parse_for_init(tokens: &[Token], ptr: &mut usize) -> Result<ForInit, Error> {
-        if let Ok(variable) = parse_variable_declaration(tokens, ptr) {
-                return Ok(ForInit::D(variable));
+// <for-init> ::= <variable_declaration> | [ <exp> ] ";", where the declaration has no storage class
+fn parse_for_init(tokens: &Tokens, ptr: &mut usize) -> Result<ForInit, Error> {
+        if is_specifier(tokens.at(*ptr).token_type) {
+                let first = tokens.at(*ptr);
+                return match parse_declaration(tokens, ptr)? {
+                        Declaration::V(declaration)
+                                if declaration.storage_class.is_none() && !declaration.thread_local =>
+                        {
+                                Ok(ForInit::D(declaration))
+                        }
+                        _ => Err(Error::InvalidForInit(first)),
+                };
         }
 
         let mut expression = None;
@@ -312,8 +1267,9 @@ pub static ASSIGNBINOP: [Binop; 12] = [
         Binop::BitwiseXOrAssign,
 ];
 
-// <exp> ::= <factor> | <exp> <binop> <exp> | <exp> "?" <exp> ":" <exp>
-fn parse_expression(tokens: &[Token], ptr: &mut usize, min_precedence: usize) -> Result<AExpression, Error> {
+// <exp> ::= <factor> | <exp> <binop> <exp> | <exp> "?" <exp> ":" <exp>, where a min_precedence of 0 is a full
+// expression, including the comma operator
+fn parse_expression(tokens: &Tokens, ptr: &mut usize, min_precedence: usize) -> Result<AExpression, Error> {
         let mut left = AExpression::F(parse_factor(tokens, ptr)?);
 
         while let Some(operator) = parse_binary_operator(tokens, ptr) {
@@ -350,11 +1306,11 @@ fn parse_expression(tokens: &[Token], ptr: &mut usize, min_precedence: usize) ->
         Ok(left)
 }
 
-// <argument-list> ::= <exp> { "," <exp> }
-fn parse_call_list(tokens: &[Token], ptr: &mut usize) -> Result<Option<Vec<AExpression>>, Error> {
+// <argument-list> ::= <assignment-exp> { "," <assignment-exp> }, so a comma here separates arguments
+fn parse_call_list(tokens: &Tokens, ptr: &mut usize) -> Result<Option<Vec<AExpression>>, Error> {
         let mut params = vec![];
 
-        while let Ok(expr) = parse_expression(tokens, ptr, 0) {
+        while let Ok(expr) = parse_expression(tokens, ptr, binary_operator_precedence(Binop::Equal)) {
                 params.push(expr);
 
                 if is_token(tokens, TokenType::Comma, ptr).is_err() {
@@ -362,7 +1318,7 @@ fn parse_call_list(tokens: &[Token], ptr: &mut usize) -> Result<Option<Vec<AExpr
                 }
         }
 
-        if tokens[*ptr - 1].token_type == TokenType::Comma {
+        if tokens.at(*ptr - 1).token_type == TokenType::Comma {
                 return Err(Error::TrailingCommaInParamList);
         }
 
@@ -373,83 +1329,401 @@ fn parse_call_list(tokens: &[Token], ptr: &mut usize) -> Result<Option<Vec<AExpr
         }
 }
 
-// <factor> ::= <identifier> | <int> | "(" <exp> ")" | <unop> <factor> | <factor> <unop> (postfix in/decrement)
-// <identifier> "(" [ <argument-list> ] ")"
-fn parse_factor(tokens: &[Token], ptr: &mut usize) -> Result<AFactor, Error> {
-        let postfix_unop = |f: Token| match f.token_type {
-                TokenType::DoubleMinus => Some(Unop::DecrementPost),
-                TokenType::DoublePlus => Some(Unop::IncrementPost),
-                _ => None,
-        };
-
-        if let Ok(identifier) = parse_identifier(tokens, ptr) {
-                let mut temp = AFactor::Id(identifier);
+// <factor> ::= <unop> <factor> | "(" <type-name> ")" <factor> | "sizeof" <factor> | "sizeof" "(" <type-name> ")"
+// | "_Alignof" "(" <type-name> ")" | <postfix-exp>
+fn parse_factor(tokens: &Tokens, ptr: &mut usize) -> Result<AFactor, Error> {
+        if is_type_name_next(tokens, *ptr) {
+                *ptr += 1;
+                let to = parse_type_name(tokens, ptr)?;
+                is_token(tokens, TokenType::CloseParen, ptr)?;
 
-                if is_token(tokens, TokenType::OpenParen, ptr).is_ok() {
-                        let list = parse_call_list(tokens, ptr)?;
-                        temp = AFactor::Expr(Box::new(AExpression::FunctionCall(identifier, list)));
-                        is_token(tokens, TokenType::CloseParen, ptr)?;
+                if tokens.get(*ptr).is_some_and(|f| f.token_type == TokenType::OpenBrace) {
+                        let init = parse_initializer(tokens, ptr)?;
+                        let literal = AFactor::Expr(Box::new(AExpression::CompoundLiteral(to, Box::new(init))));
+                        return parse_postfix_operators(tokens, ptr, literal);
                 }
 
-                while let Some(incdec) = postfix_unop(tokens[*ptr]) {
+                let factor = parse_factor(tokens, ptr)?;
+
+                return Ok(AFactor::Expr(Box::new(AExpression::Cast(
+                        to,
+                        Box::new(AExpression::F(factor)),
+                ))));
+        }
+
+        if is_token(tokens, TokenType::Sizeof, ptr).is_ok() {
+                if is_type_name_next(tokens, *ptr) {
                         *ptr += 1;
-                        temp = AFactor::Unop(incdec, Box::new(temp));
+                        let of = parse_type_name(tokens, ptr)?;
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+
+                        return Ok(AFactor::Expr(Box::new(AExpression::SizeOfType(of))));
                 }
+                let factor = parse_factor(tokens, ptr)?;
 
-                return Ok(temp);
+                return Ok(AFactor::Expr(Box::new(AExpression::SizeOf(Box::new(AExpression::F(
+                        factor,
+                ))))));
         }
 
-        if let Ok(constant) = parse_constant(tokens, ptr) {
-                let mut temp = AFactor::Constant(constant);
+        if are_tokens(tokens, &[TokenType::Alignof, TokenType::OpenParen], ptr).is_ok() {
+                let of = parse_type_name(tokens, ptr)?;
+                is_token(tokens, TokenType::CloseParen, ptr)?;
 
-                while let Some(incdec) = postfix_unop(tokens[*ptr]) {
-                        *ptr += 1;
-                        temp = AFactor::Unop(incdec, Box::new(temp));
+                return Ok(AFactor::Expr(Box::new(AExpression::AlignOf(of))));
+        }
+
+        if let Some(unop) = parse_unary_operator(tokens, ptr) {
+                if let Ok(factor) = parse_factor(tokens, ptr) {
+                        return Ok(AFactor::Unop(unop, Box::new(factor)));
                 }
+                *ptr -= 1;
+        }
+
+        parse_postfix_expression(tokens, ptr)
+}
+
+// <postfix-exp> ::= <primary-exp> { <postfix-op> } | "(" <type-name> ")" "{" <initializer-list> "}" { <postfix-op> }
+// <postfix-op> ::= "++" | "--" | "(" [ <argument-list> ] ")"
+fn parse_postfix_expression(tokens: &Tokens, ptr: &mut usize) -> Result<AFactor, Error> {
+        let factor = parse_primary_expression(tokens, ptr)?;
+
+        parse_postfix_operators(tokens, ptr, factor)
+}
+
+fn parse_postfix_operators(tokens: &Tokens, ptr: &mut usize, mut factor: AFactor) -> Result<AFactor, Error> {
+        loop {
+                let unop = match tokens.get(*ptr).map(|f| f.token_type) {
+                        Some(TokenType::DoublePlus) => Unop::IncrementPost,
+                        Some(TokenType::DoubleMinus) => Unop::DecrementPost,
+                        Some(TokenType::OpenParen) => {
+                                *ptr += 1;
+                                let list = parse_call_list(tokens, ptr)?;
+                                is_token(tokens, TokenType::CloseParen, ptr)?;
+
+                                // the return type is filled in by the type checker
+                                factor = AFactor::Expr(Box::new(AExpression::FunctionCall(
+                                        Box::new(AExpression::F(factor)),
+                                        list,
+                                        Type::Int,
+                                )));
+                                continue;
+                        }
+                        _ => break,
+                };
+                *ptr += 1;
+                factor = AFactor::Unop(unop, Box::new(factor));
+        }
+
+        Ok(factor)
+}
 
-                return Ok(temp);
+// <primary-exp> ::= <int> | <identifier> | "(" <exp> ")" | <builtin> | "(" <block> ")" | <generic-selection>
+fn parse_primary_expression(tokens: &Tokens, ptr: &mut usize) -> Result<AFactor, Error> {
+        if let Some(builtin) = parse_builtin(tokens, ptr)? {
+                return Ok(AFactor::Expr(Box::new(AExpression::Builtin(builtin))));
+        }
+
+        if is_token(tokens, TokenType::Generic, ptr).is_ok() {
+                let selection = parse_generic_selection(tokens, ptr)?;
+                return Ok(AFactor::Expr(Box::new(AExpression::Generic(Box::new(selection)))));
+        }
+
+        if let Ok(identifier) = parse_identifier(tokens, ptr) {
+                return Ok(AFactor::Id(identifier));
+        }
+
+        if let Ok(constant) = parse_constant(tokens, ptr) {
+                return Ok(AFactor::Constant(constant));
         }
 
         if is_token(tokens, TokenType::OpenParen, ptr).is_ok() {
+                let start = *ptr - 1;
+                if tokens.get(*ptr).is_some_and(|f| f.token_type == TokenType::OpenBrace) {
+                        let ABlock(mut block) = parse_block(tokens, ptr)?;
+                        is_token(tokens, TokenType::CloseParen, ptr)?;
+
+                        let value = match block.pop() {
+                                Some(BlockItem::S(AStatement::Expr(expr))) => Some(Box::new(expr)),
+                                last => {
+                                        block.extend(last);
+                                        None
+                                }
+                        };
+                        return Ok(AFactor::Expr(Box::new(AExpression::StatementExpr(
+                                ABlock(block),
+                                value,
+                        ))));
+                }
                 if let Ok(expr) = parse_expression(tokens, ptr, 0) {
                         if is_token(tokens, TokenType::CloseParen, ptr).is_ok() {
-                                let mut temp = AFactor::Expr(Box::new(expr));
-                                while let Some(incdec) = postfix_unop(tokens[*ptr]) {
-                                        *ptr += 1;
-                                        temp = AFactor::Unop(incdec, Box::new(temp));
-                                }
-
-                                return Ok(temp);
+                                return Ok(AFactor::Expr(Box::new(expr)));
                         }
-                        *ptr -= 1;
                 }
-                *ptr -= 1;
+                *ptr = start;
         }
 
-        if let Some(unop) = parse_unary_operator(tokens, ptr) {
-                if let Ok(factor) = parse_factor(tokens, ptr) {
-                        let mut temp = AFactor::Unop(unop, Box::new(factor));
+        Err(Error::InvalidFactorAt(tokens.at(*ptr)))
+}
 
-                        while let Some(incdec) = postfix_unop(tokens[*ptr]) {
-                                *ptr += 1;
-                                temp = AFactor::Unop(incdec, Box::new(temp));
+// <generic-selection> ::= "_Generic" "(" <assignment-exp> "," <generic-association> { "," <generic-association> } ")"
+// <generic-association> ::= ( <type-name> | "default" ) ":" <assignment-exp>
+// called once "_Generic" has been consumed
+fn parse_generic_selection(tokens: &Tokens, ptr: &mut usize) -> Result<GenericSelection, Error> {
+        let assignment = binary_operator_precedence(Binop::Equal);
+        is_token(tokens, TokenType::OpenParen, ptr)?;
+        let controlling = parse_expression(tokens, ptr, assignment)?;
+        let mut associations = vec![];
+        loop {
+                is_token(tokens, TokenType::Comma, ptr)?;
+                let association_type = if is_token(tokens, TokenType::Default, ptr).is_ok() {
+                        None
+                } else {
+                        Some(parse_qualified_type_name(tokens, ptr)?)
+                };
+                is_token(tokens, TokenType::Colon, ptr)?;
+                associations.push((association_type, parse_expression(tokens, ptr, assignment)?));
+                if is_token(tokens, TokenType::CloseParen, ptr).is_ok() {
+                        break;
+                }
+        }
+
+        Ok(GenericSelection {
+                controlling,
+                associations,
+        })
+}
+
+/*
+<builtin> ::= "__builtin_va_start" "(" <identifier> "," <assignment-exp> ")"
+| "__builtin_va_arg" "(" <identifier> "," <type-name> ")"
+| "__builtin_va_end" "(" <identifier> ")"
+| "__builtin_va_copy" "(" <identifier> "," <identifier> ")"
+| "__builtin_expect" "(" <assignment-exp> "," <assignment-exp> ")"
+| "__builtin_unreachable" "(" ")"
+| "__builtin_popcount" "(" <assignment-exp> ")"
+| "__builtin_clz" "(" <assignment-exp> ")"
+| "__builtin_offsetof" "(" <type-name> "," <identifier> ")"
+| <atomic-builtin>
+*/
+fn parse_builtin(tokens: &Tokens, ptr: &mut usize) -> Result<Option<Builtin>, Error> {
+        let Some(Token { token_type, .. }) = tokens.get(*ptr) else {
+                return Ok(None);
+        };
+        if let TokenType::BuiltinAtomic(op) | TokenType::BuiltinC11Atomic(op) = token_type {
+                *ptr += 1;
+                is_token(tokens, TokenType::OpenParen, ptr)?;
+                let c11 = matches!(token_type, TokenType::BuiltinC11Atomic(_));
+                return Ok(Some(Builtin::Atomic(Box::new(parse_atomic_builtin(
+                        tokens, ptr, op, c11,
+                )?))));
+        }
+        if !matches!(
+                token_type,
+                TokenType::BuiltinVaStart
+                        | TokenType::BuiltinVaArg
+                        | TokenType::BuiltinVaEnd
+                        | TokenType::BuiltinVaCopy
+                        | TokenType::BuiltinExpect
+                        | TokenType::BuiltinUnreachable
+                        | TokenType::BuiltinPopcount
+                        | TokenType::BuiltinClz
+                        | TokenType::BuiltinOffsetof
+        ) {
+                return Ok(None);
+        }
+        *ptr += 1;
+        is_token(tokens, TokenType::OpenParen, ptr)?;
+        let argument = |ptr: &mut usize| -> Result<Box<AExpression>, Error> {
+                Ok(Box::new(parse_expression(
+                        tokens,
+                        ptr,
+                        binary_operator_precedence(Binop::Equal),
+                )?))
+        };
+
+        let builtin = match token_type {
+                TokenType::BuiltinVaStart => {
+                        let ap = parse_identifier(tokens, ptr)?;
+                        // the last named parameter, which the backend already knows, so it is checked for syntax only
+                        is_token(tokens, TokenType::Comma, ptr)?;
+                        argument(ptr)?;
+                        Builtin::VaStart(ap)
+                }
+                TokenType::BuiltinVaArg => {
+                        let ap = parse_identifier(tokens, ptr)?;
+                        is_token(tokens, TokenType::Comma, ptr)?;
+                        Builtin::VaArg(ap, parse_type_name(tokens, ptr)?)
+                }
+                TokenType::BuiltinVaEnd => Builtin::VaEnd(parse_identifier(tokens, ptr)?),
+                TokenType::BuiltinVaCopy => {
+                        let dst = parse_identifier(tokens, ptr)?;
+                        is_token(tokens, TokenType::Comma, ptr)?;
+                        Builtin::VaCopy(dst, parse_identifier(tokens, ptr)?)
+                }
+                TokenType::BuiltinExpect => {
+                        let value = argument(ptr)?;
+                        is_token(tokens, TokenType::Comma, ptr)?;
+                        Builtin::Expect(value, argument(ptr)?)
+                }
+                TokenType::BuiltinUnreachable => Builtin::Unreachable,
+                TokenType::BuiltinPopcount => Builtin::Popcount(argument(ptr)?),
+                TokenType::BuiltinClz => Builtin::Clz(argument(ptr)?),
+                _ => {
+                        let of = parse_type_name(tokens, ptr)?;
+                        is_token(tokens, TokenType::Comma, ptr)?;
+                        Builtin::Offsetof(of, parse_identifier(tokens, ptr)?)
+                }
+        };
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+
+        Ok(Some(builtin))
+}
+
+/*
+<atomic-builtin> ::= "__atomic_load_n" "(" <object> "," <order> ")"
+| ( "__atomic_store_n" | "__atomic_exchange_n" | "__atomic_fetch_add" | "__atomic_fetch_sub" )
+"(" <object> "," <assignment-exp> "," <order> ")"
+| "__atomic_compare_exchange_n" "(" <object> "," <object> "," <assignment-exp> "," <assignment-exp> "," <order> ","
+<order> ")"
+| ( "__atomic_thread_fence" | "__atomic_signal_fence" ) "(" <order> ")"
+and the same for the __c11_atomic_* ones, where compare exchange is "_strong" or "_weak" and has no weak argument
+<object> ::= "&" <identifier>
+<order> ::= <assignment-exp>
+called once the name and "(" have been consumed. compare exchange is always strong, so the weak argument is checked
+for syntax only
+*/
+fn parse_atomic_builtin(tokens: &Tokens, ptr: &mut usize, op: AtomicOp, c11: bool) -> Result<AtomicBuiltin, Error> {
+        let argument = |ptr: &mut usize| -> Result<Box<AExpression>, Error> {
+                Ok(Box::new(parse_expression(
+                        tokens,
+                        ptr,
+                        binary_operator_precedence(Binop::Equal),
+                )?))
+        };
+        let named_object = |ptr: &mut usize| -> Result<AIdentifier, Error> {
+                is_token(tokens, TokenType::BitwiseAnd, ptr)?;
+                parse_identifier(tokens, ptr)
+        };
+        let comma = |ptr: &mut usize| is_token(tokens, TokenType::Comma, ptr);
+
+        let (object, expected, value) = match op {
+                AtomicOp::ThreadFence | AtomicOp::SignalFence => (None, None, None),
+                AtomicOp::Load => {
+                        let object = named_object(ptr)?;
+                        comma(ptr)?;
+                        (Some(object), None, None)
+                }
+                AtomicOp::Store | AtomicOp::Exchange | AtomicOp::FetchAdd | AtomicOp::FetchSub => {
+                        let object = named_object(ptr)?;
+                        comma(ptr)?;
+                        let value = argument(ptr)?;
+                        comma(ptr)?;
+                        (Some(object), None, Some(value))
+                }
+                AtomicOp::CompareExchange | AtomicOp::CompareExchangeWeak => {
+                        let object = named_object(ptr)?;
+                        comma(ptr)?;
+                        let expected = named_object(ptr)?;
+                        comma(ptr)?;
+                        let desired = argument(ptr)?;
+                        comma(ptr)?;
+                        if !c11 {
+                                argument(ptr)?;
+                                comma(ptr)?;
+                        }
+                        (Some(object), Some(expected), Some(desired))
+                }
+        };
+        let order = argument(ptr)?;
+        let failure_order = if expected.is_some() {
+                comma(ptr)?;
+                Some(argument(ptr)?)
+        } else {
+                None
+        };
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+
+        Ok(AtomicBuiltin {
+                op,
+                c11,
+                object,
+                expected,
+                value,
+                order,
+                failure_order,
+                seq_cst: false,
+        })
+}
+
+// <asm> ::= "asm" { "volatile" } "(" <string> [ ":" [ <asm-operands> ] [ ":" [ <asm-operands> ] [ ":" [ <clobbers> ] ] ] ] ")" ";"
+// <asm-operands> ::= <string> "(" <exp> ")" { "," <string> "(" <exp> ")" }
+// <clobbers> ::= <string> { "," <string> }
+// called once "asm" has been consumed. volatile is accepted but changes nothing, as no asm is ever optimized away
+fn parse_asm(tokens: &Tokens, ptr: &mut usize) -> Result<InlineAsm, Error> {
+        while is_token(tokens, TokenType::Volatile, ptr).is_ok() {}
+        is_token(tokens, TokenType::OpenParen, ptr)?;
+        let template = parse_string_literal(tokens, ptr)?;
+
+        let basic = is_token(tokens, TokenType::Colon, ptr).is_err();
+        let mut outputs = vec![];
+        let mut inputs = vec![];
+        let mut clobbers = vec![];
+        if !basic {
+                outputs = parse_asm_operands(tokens, ptr)?;
+                if is_token(tokens, TokenType::Colon, ptr).is_ok() {
+                        inputs = parse_asm_operands(tokens, ptr)?;
+                        if is_token(tokens, TokenType::Colon, ptr).is_ok() {
+                                while let Ok(clobber) = parse_string_literal(tokens, ptr) {
+                                        clobbers.push(clobber);
+                                        if is_token(tokens, TokenType::Comma, ptr).is_err() {
+                                                break;
+                                        }
+                                }
                         }
-                        return Ok(temp);
                 }
-                *ptr -= 1;
         }
+        is_token(tokens, TokenType::CloseParen, ptr)?;
+        is_token(tokens, TokenType::SemiColon, ptr)?;
 
-        Err(Error::InvalidFactorAt(tokens[*ptr]))
+        Ok(InlineAsm {
+                template,
+                outputs,
+                inputs,
+                clobbers,
+                basic,
+        })
 }
 
-// <unop> ::= "-" | "~" | "!" | ++ (post) | -- (post)
-fn parse_unary_operator(tokens: &[Token], ptr: &mut usize) -> Option<Unop> {
-        if let Some(unop) = match tokens[*ptr].token_type {
+fn parse_asm_operands(tokens: &Tokens, ptr: &mut usize) -> Result<Vec<AsmOperand>, Error> {
+        let mut operands = vec![];
+        while let Ok(text) = parse_string_literal(tokens, ptr) {
+                is_token(tokens, TokenType::OpenParen, ptr)?;
+                let expr = parse_expression(tokens, ptr, 0)?;
+                is_token(tokens, TokenType::CloseParen, ptr)?;
+                operands.push(AsmOperand {
+                        text,
+                        constraint: Constraint::default(),
+                        expr,
+                });
+                if is_token(tokens, TokenType::Comma, ptr).is_err() {
+                        break;
+                }
+        }
+        Ok(operands)
+}
+
+// <unop> ::= "-" | "+" | "~" | "!" | "++" | "--" | "*" | "&"
+fn parse_unary_operator(tokens: &Tokens, ptr: &mut usize) -> Option<Unop> {
+        if let Some(unop) = match tokens.at(*ptr).token_type {
                 TokenType::Minus => Some(Unop::Negate),
+                TokenType::Plus => Some(Unop::Plus),
                 TokenType::Tilde => Some(Unop::Complement),
                 TokenType::Not => Some(Unop::Not),
-                TokenType::DoubleMinus => Some(Unop::DecrementPost),
-                TokenType::DoublePlus => Some(Unop::IncrementPost),
+                TokenType::DoubleMinus => Some(Unop::DecrementPre),
+                TokenType::DoublePlus => Some(Unop::IncrementPre),
+                TokenType::Asterisk => Some(Unop::Deref),
+                TokenType::BitwiseAnd => Some(Unop::AddressOf),
                 _ => None,
         } {
                 *ptr += 1;
@@ -462,10 +1736,10 @@ fn parse_unary_operator(tokens: &[Token], ptr: &mut usize) -> Option<Unop> {
 /*
 <binop> ::= "-" | "+" | "*" | "/" | "%" | "<<" | ">>" | "&" | "|" | | "^"
 | "&&" | "||" | "==" | "!=" | "<" | "<=" | ">" | ">="
-| += | -= | *= | /= | %= | &= | ^= | <<= | >>= | ?
+| += | -= | *= | /= | %= | &= | ^= | <<= | >>= | ? | ,
 */
-fn parse_binary_operator(tokens: &[Token], ptr: &mut usize) -> Option<Binop> {
-        if let Some(binop) = match tokens[*ptr].token_type {
+fn parse_binary_operator(tokens: &Tokens, ptr: &mut usize) -> Option<Binop> {
+        if let Some(binop) = match tokens.at(*ptr).token_type {
                 TokenType::Minus => Some(Binop::Subtract),
                 TokenType::Plus => Some(Binop::Add),
                 TokenType::Asterisk => Some(Binop::Multiply),
@@ -498,6 +1772,7 @@ fn parse_binary_operator(tokens: &[Token], ptr: &mut usize) -> Option<Binop> {
                 TokenType::LogicalOrAssign => Some(Binop::LogicalOrAssign),
                 TokenType::BitwiseXOrAssign => Some(Binop::BitwiseXOrAssign),
                 TokenType::Ternary => Some(Binop::Ternary),
+                TokenType::Comma => Some(Binop::Comma),
                 _ => None,
         } {
                 *ptr += 1;
@@ -508,21 +1783,25 @@ fn parse_binary_operator(tokens: &[Token], ptr: &mut usize) -> Option<Binop> {
 }
 
 // <identifier> ::= ? An identifier token ?
-fn parse_identifier(tokens: &[Token], ptr: &mut usize) -> Result<AIdentifier, Error> {
+fn parse_identifier(tokens: &Tokens, ptr: &mut usize) -> Result<AIdentifier, Error> {
         let (start, len) = is_token(tokens, TokenType::Identifier, ptr)?;
 
-        Ok(AIdentifier { start, len })
+        Ok(AIdentifier {
+                start,
+                len,
+                symbol: None,
+        })
 }
 
 // <int> ::= ? A constant token ?
-fn parse_constant(tokens: &[Token], ptr: &mut usize) -> Result<AConstant, Error> {
+fn parse_constant(tokens: &Tokens, ptr: &mut usize) -> Result<AConstant, Error> {
         let (start, len) = is_token(tokens, TokenType::Constant, ptr)?;
 
         Ok(AConstant { start, len })
 }
 
-fn is_token(tokens: &[Token], wanted_token_type: TokenType, ptr: &mut usize) -> Result<(usize, usize), Error> {
-        let Some(&Token { token_type, len, start }) = tokens.get(*ptr) else {
+fn is_token(tokens: &Tokens, wanted_token_type: TokenType, ptr: &mut usize) -> Result<(usize, usize), Error> {
+        let Some(Token { token_type, len, start }) = tokens.get(*ptr) else {
                 return Err(Error::NotEnoughTokens);
         };
 
@@ -531,13 +1810,13 @@ fn is_token(tokens: &[Token], wanted_token_type: TokenType, ptr: &mut usize) ->
                 return Ok((start, len));
         }
 
-        Err(Error::InvalidTokenAt(tokens[*ptr], wanted_token_type))
+        Err(Error::InvalidTokenAt(tokens.at(*ptr), wanted_token_type))
 }
 
-fn are_tokens(tokens: &[Token], wanted_token_type: &[TokenType], ptr: &mut usize) -> Result<(), Error> {
+fn are_tokens(tokens: &Tokens, wanted_token_type: &[TokenType], ptr: &mut usize) -> Result<(), Error> {
         for (idx, &i) in wanted_token_type.iter().enumerate() {
-                if tokens[*ptr + idx].token_type != i {
-                        return Err(Error::InvalidTokenAt(tokens[*ptr + idx], i));
+                if tokens.at(*ptr + idx).token_type != i {
+                        return Err(Error::InvalidTokenAt(tokens.at(*ptr + idx), i));
                 }
         }
         assert!(!wanted_token_type.is_empty());
@@ -558,6 +1837,7 @@ fn binary_operator_precedence(operator: Binop) -> usize {
                 Binop::LogicalAnd => 10,
                 Binop::LogicalOr => 5,
                 Binop::Ternary => 3,
+                Binop::Comma => 0,
                 Binop::Equal
                 | Binop::AddAssign
                 | Binop::SubtractAssign
@@ -1,10 +1,27 @@
 use std::fmt::Display;
 
+use thiserror::Error;
+
+use crate::{
+        lex::tokentype::AtomicOp,
+        semantic_analysis::{
+                symbol_table::SymbolId,
+                type_checker::{FuncType, Qualifiers, Type, SIZE_T},
+        },
+        tactile::tree::Constant,
+};
+
 #[derive(Debug, Clone)]
 pub struct AProgram {
-        pub functions: Vec<FunctionDeclaration>,
+        pub declarations: Vec<Declaration>,
+        // the expressions that types and attributes refer to by ExprId, as those are Copy and can't hold them
+        pub exprs: Vec<AExpression>,
 }
 
+// an expression kept in AProgram::exprs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(pub usize);
+
 #[derive(Debug, Clone)]
 pub struct ABlock(pub Vec<BlockItem>);
 
@@ -12,6 +29,15 @@ pub struct ABlock(pub Vec<BlockItem>);
 pub struct AIdentifier {
         pub start: usize,
         pub len: usize,
+        // which declaration it refers to, filled in by identifier resolution for the names of variables and functions
+        pub symbol: Option<SymbolId>,
+}
+
+impl AIdentifier {
+        pub fn resolved(self) -> SymbolId {
+                self.symbol
+                        .expect("identifier resolution resolves every variable and function name")
+        }
 }
 #[derive(Debug, Clone)]
 pub enum BlockItem {
@@ -22,52 +48,227 @@ pub enum BlockItem {
 pub enum Declaration {
         V(VariableDeclaration),
         F(FunctionDeclaration),
+        A(StaticAssert),
+        // a typedef, only kept when its type has an expression in it. the expression is worked out where the typedef
+        // is, rather than where the name is used, 6.7.8p3
+        T(Type),
+}
+
+// _Static_assert, which declares nothing and is checked entirely by the type checker, 6.7.10
+#[derive(Debug, Clone)]
+pub struct StaticAssert {
+        pub condition: AExpression,
+        pub message: Option<AStringLiteral>,
+}
+
+// spans every adjacent string literal, quotes included, as they're concatenated
+#[derive(Debug, Clone, Copy)]
+pub struct AStringLiteral {
+        pub start: usize,
+        pub len: usize,
+}
+
+impl AStringLiteral {
+        // the bytes the literals stand for once concatenated, 6.4.5, or None if an escape sequence is invalid
+        pub fn value(&self, code: &[u8]) -> Option<Vec<u8>> {
+                let text = &code[self.start..self.start + self.len];
+                let mut value = vec![];
+                let mut idx = 0;
+                let mut inside = false;
+                while idx < text.len() {
+                        match text[idx] {
+                                b'"' => {
+                                        inside = !inside;
+                                        idx += 1;
+                                }
+                                _ if inside => {
+                                        let (byte, len) = unescape(&text[idx..])?;
+                                        value.push(byte);
+                                        idx += len;
+                                }
+                                _ => idx += 1,
+                        }
+                }
+                Some(value)
+        }
+}
+
+// what the attributes on a declaration say, 6.7.12. nodiscard and deprecated can give a reason, which is kept for the
+// warning. GNU's __attribute__ adds to them, 6.7.12 leaves no room for packed or format, so they're only accepted
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Attributes {
+        pub nodiscard: Option<Option<AStringLiteral>>,
+        pub deprecated: Option<Option<AStringLiteral>>,
+        // there are no warnings about unused declarations for it to silence, so it's only accepted
+        pub maybe_unused: bool,
+        // the function never returns to its caller, 6.7.12.6, and the same as _Noreturn
+        pub noreturn: bool,
+        // GNU aligned, with the alignment it asks for, or the largest one when it doesn't say
+        pub aligned: Option<Option<ExprId>>,
+}
+
+impl Attributes {
+        // an attribute on any declaration of something holds for every later one too
+        pub fn merge(self, earlier: Option<Attributes>) -> Attributes {
+                let Some(earlier) = earlier else {
+                        return self;
+                };
+                Attributes {
+                        nodiscard: self.nodiscard.or(earlier.nodiscard),
+                        deprecated: self.deprecated.or(earlier.deprecated),
+                        maybe_unused: self.maybe_unused | earlier.maybe_unused,
+                        noreturn: self.noreturn | earlier.noreturn,
+                        aligned: self.aligned.or(earlier.aligned),
+                }
+        }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+        Static,
+        Extern,
 }
 
 #[derive(Debug, Clone)]
 pub struct FunctionDeclaration {
         pub name: AIdentifier,
-        pub params: Option<Vec<AIdentifier>>,
+        pub func_type: &'static FuncType,
+        // parameters can only be unnamed in declarations without a body, and their qualifiers aren't part of the
+        // function type
+        pub params: Vec<(Option<AIdentifier>, Qualifiers)>,
         pub body: Option<ABlock>,
+        pub storage_class: Option<StorageClass>,
+        pub attributes: Attributes,
 }
 
 #[derive(Debug, Clone)]
 pub struct VariableDeclaration {
         pub id: AIdentifier,
-        pub init: Option<AExpression>,
+        pub init: Option<Initializer>,
+        pub var_type: Type,
+        pub qualifiers: Qualifiers,
+        pub storage_class: Option<StorageClass>,
+        pub attributes: Attributes,
+        // a named constant, 6.7.1p5, which is also const
+        pub constexpr: bool,
+        // one object per thread, 6.2.4p4, alongside static or extern in a block
+        pub thread_local: bool,
+        // what aligned works out to, filled in by the type checker when it's stricter than the type's own
+        pub alignment: Option<usize>,
+}
+#[derive(Debug, Clone)]
+pub enum Initializer {
+        Single(AExpression),
+        // a brace enclosed list, where every element can be designated. the type checker reduces a list for a scalar
+        // to Single, except for an empty one, which zero initializes
+        Compound(Vec<(Vec<Designator>, Initializer)>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Designator {
+        // .member
+        Member(AIdentifier),
+        // [index]
+        Index(AExpression),
+}
+
+impl Initializer {
+        // the value a scalar with static storage duration starts with
+        pub fn evaluate_constant(&self, code: &[u8], c_type: Type) -> Result<Constant, ConstantError> {
+                match self {
+                        Initializer::Single(expr) => expr.evaluate_constant(code),
+                        Initializer::Compound(list) if list.is_empty() => Ok(Constant::new(0, c_type)),
+                        Initializer::Compound(_) => unreachable!("type checker reduces scalar initializers to Single"),
+                }
+        }
+
+        pub fn function_address(&self) -> Option<AIdentifier> {
+                match self {
+                        Initializer::Single(expr) => expr.function_address(),
+                        Initializer::Compound(_) => None,
+                }
+        }
 }
+
 #[derive(Debug, Clone)]
 pub enum AStatement {
-        Return(AExpression),
+        Return(Option<AExpression>),
         Expr(AExpression),
         I(IfStatement),
         Compound(ABlock),
         Nul,
+        // the label of the loop or switch it leaves, and which of the two that is, filled in by loop labeling
         Break(ParseLabel, BreakType),
+        // the label of the loop it goes on with, filled in by loop labeling
         Continue(ParseLabel),
         While(AExpression, Box<AStatement>, ParseLabel),
         DoWhile(Box<AStatement>, AExpression, ParseLabel),
         F(Box<For>, ParseLabel),
         S(Switch),
+        Goto(AIdentifier, ParseLabel),
+        Labeled(AIdentifier, Box<AStatement>, ParseLabel),
+        Case(AExpression, Box<AStatement>, ParseLabel),
+        Default(Box<AStatement>, ParseLabel),
+        // "[[fallthrough]];", which does nothing but has to come right before a case or default label, 6.7.12.5
+        Fallthrough,
+        Asm(Box<InlineAsm>),
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum BreakType {
-        Loop,
-        Switch,
+// a GNU asm statement, which is basic asm when there are no colons, so its template is used as written. what the
+// strings say is worked out by the type checker
+#[derive(Debug, Clone)]
+pub struct InlineAsm {
+        pub template: AStringLiteral,
+        pub outputs: Vec<AsmOperand>,
+        pub inputs: Vec<AsmOperand>,
+        pub clobbers: Vec<AStringLiteral>,
+        pub basic: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsmOperand {
+        pub text: AStringLiteral,
+        pub constraint: Constraint,
+        pub expr: AExpression,
+}
+
+// where an operand can be, out of "r", "m" and "i", and whether the asm reads it, writes it ("=") or both ("+")
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Constraint {
+        pub kind: OperandKind,
+        pub read: bool,
+        pub written: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OperandKind {
+        #[default]
+        Register,
+        Memory,
+        Immediate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplatePiece {
+        Text(Vec<u8>),
+        // %N, numbering the outputs and then the inputs, with the size modifier from %bN, %wN, %kN or %qN
+        Operand(usize, Option<u8>),
 }
+
 #[derive(Debug, Clone, Copy)]
-pub enum LoopSwitchOrNone {
+pub enum BreakType {
         Loop,
         Switch,
-        Neither,
 }
 
 #[derive(Debug, Clone)]
 pub struct Switch {
         pub value: AExpression,
-        pub cases: Vec<(AConstant, Vec<AStatement>)>,
-        pub default: Option<Box<AStatement>>,
+        pub body: Box<AStatement>,
+        // case and default labels can be anywhere in the body, so the type checker collects them here, with the case
+        // values converted to the promoted type of value
+        pub cases: Vec<(Constant, ParseLabel)>,
+        pub default: Option<ParseLabel>,
         pub label: ParseLabel,
 }
 
@@ -87,6 +288,8 @@ pub enum ForInit {
         E(Option<AExpression>),
 }
 
+// named after the keywords they stand in for
+#[allow(non_snake_case)]
 #[derive(Debug, Clone)]
 pub struct IfStatement {
         pub condition: AExpression,
@@ -99,6 +302,8 @@ pub enum AFactor {
         Unop(Unop, Box<AFactor>),
         Expr(Box<AExpression>),
         Id(AIdentifier),
+        // an Id naming a function, which the type checker marks as it always decays to a pointer to the function
+        Function(AIdentifier),
 }
 
 #[derive(Debug, Clone)]
@@ -108,14 +313,87 @@ pub enum AExpression {
         Assignment(Box<AExpression>, Box<AExpression>),
         OpAssignment(Binop, Box<AExpression>, Box<AExpression>),
         C(Conditional),
-        FunctionCall(AIdentifier, Option<Vec<AExpression>>),
+        // the callee is an Id naming a function for direct calls, and a function pointer otherwise. the type the call
+        // returns is filled in by the type checker
+        FunctionCall(Box<AExpression>, Option<Vec<AExpression>>, Type),
+        Cast(Type, Box<AExpression>),
+        // replaced by SizeOfType during type checking, as the operand is never evaluated
+        SizeOf(Box<AExpression>),
+        SizeOfType(Type),
+        AlignOf(Type),
+        // an unnamed object with automatic storage duration, so unlike a cast it is an lvalue
+        CompoundLiteral(Type, Box<Initializer>),
+        Builtin(Builtin),
+        // a GNU statement expression, "({ ... })". the parser takes the last expression statement out of the block,
+        // as it's the value of the whole thing. without one the value is void
+        StatementExpr(ABlock, Option<Box<AExpression>>),
+        // replaced by the expression it selects during type checking, as the controlling expression is never evaluated
+        Generic(Box<GenericSelection>),
+}
+
+// the operand of typeof or typeof_unqual when it's an expression, 6.7.2.5. only the type checker knows its type, so
+// it's worked out wherever the type is first used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeofOperand {
+        pub expr: ExprId,
+        pub unqual: bool,
 }
+
+// the stdarg.h macros expand to the first four, and every one takes its va_list by name. the rest are GNU builtins
+// that system headers lean on
+#[derive(Debug, Clone)]
+pub enum Builtin {
+        VaStart(AIdentifier),
+        VaArg(AIdentifier, Type),
+        VaEnd(AIdentifier),
+        // dst, src
+        VaCopy(AIdentifier, AIdentifier),
+        // the value and what it's expected to be, which is only a hint
+        Expect(Box<AExpression>, Box<AExpression>),
+        Unreachable,
+        Popcount(Box<AExpression>),
+        // count leading zeros, undefined for 0
+        Clz(Box<AExpression>),
+        // a member's offset in a struct, which there are none of yet
+        Offsetof(Type, AIdentifier),
+        Atomic(Box<AtomicBuiltin>),
+}
+
+// the __atomic_* and __c11_atomic_* builtins, 7.17. the object is named by "&" <identifier>, as there are no pointers
+// to objects to pass it through
+#[derive(Debug, Clone)]
+pub struct AtomicBuiltin {
+        pub op: AtomicOp,
+        // __c11_atomic_* only take _Atomic objects
+        pub c11: bool,
+        // None for the fences
+        pub object: Option<AIdentifier>,
+        // the variable compare exchange keeps the expected value in, and stores the actual one in when they differ
+        pub expected: Option<AIdentifier>,
+        // what's stored, exchanged, added or subtracted, or what compare exchange stores if it succeeds
+        pub value: Option<Box<AExpression>>,
+        // the memory order, and for compare exchange the one for when it fails
+        pub order: Box<AExpression>,
+        pub failure_order: Option<Box<AExpression>>,
+        // filled in by the type checker. a seq_cst store or fence is the only thing x86-64 needs a barrier for
+        pub seq_cst: bool,
+}
+#[allow(non_snake_case)]
 #[derive(Debug, Clone)]
 pub struct Conditional {
         pub condition: Box<AExpression>,
         pub True: Box<AExpression>,
         pub False: Box<AExpression>,
 }
+
+// "_Generic" "(" <controlling> "," <associations> ")", 6.5.1.1
+#[derive(Debug, Clone)]
+pub struct GenericSelection {
+        pub controlling: AExpression,
+        // the type is None for the default association. a qualified type is allowed but never selected, as the type of
+        // the controlling expression is unqualified
+        pub associations: Vec<(Option<(Type, Qualifiers)>, AExpression)>,
+}
 impl Display for AExpression {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -128,13 +406,226 @@ impl Display for AExpression {
                         AExpression::OpAssignment(binop, left, right) => {
                                 write!(f, "operator: {binop:?}, left: {left}, right: {right}")
                         }
-                        AExpression::FunctionCall(aidentifier, _) => write!(f, "identifier: {aidentifier:?}"),
+                        AExpression::FunctionCall(callee, ..) => write!(f, "call to {callee}"),
+                        AExpression::Cast(to, expr) => write!(f, "cast to {to:?}: {expr}"),
+                        AExpression::SizeOf(expr) => write!(f, "sizeof {expr}"),
+                        AExpression::SizeOfType(of) => write!(f, "sizeof {of:?}"),
+                        AExpression::AlignOf(of) => write!(f, "alignof {of:?}"),
+                        AExpression::CompoundLiteral(of, init) => write!(f, "compound literal {of:?}: {init:?}"),
+                        AExpression::Builtin(builtin) => write!(f, "{builtin:?}"),
+                        AExpression::StatementExpr(..) => write!(f, "statement expression"),
+                        AExpression::Generic(selection) => write!(f, "generic selection on {}", selection.controlling),
+                }
+        }
+}
+
+impl AExpression {
+        // value of an integer constant expression, 6.6. only meaningful after type checking has made every conversion
+        // explicit. signed arithmetic has to stay in range, 6.6p4, but shifts wrap like they do at runtime, as
+        // "1 << 31" is far too common to reject
+        pub fn evaluate_constant(&self, code: &[u8]) -> Result<Constant, ConstantError> {
+                match self {
+                        AExpression::F(afactor) => afactor.evaluate_constant(code),
+                        AExpression::Cast(Type::Void, _) => Err(ConstantError::NotConstant("a cast to void")),
+                        AExpression::Cast(to, expr) => Ok(Constant::new(expr.evaluate_constant(code)?.as_u64(), *to)),
+                        AExpression::SizeOfType(of) => Ok(Constant::new(of.size() as u64, SIZE_T)),
+                        AExpression::AlignOf(of) => Ok(Constant::new(of.alignment() as u64, SIZE_T)),
+                        // the right side isn't evaluated unless it's needed, so "0 && 1 / 0" is fine
+                        AExpression::BinOp(Binop::LogicalAnd, left, right) => {
+                                let value = left.evaluate_constant(code)?.as_u64() != 0
+                                        && right.evaluate_constant(code)?.as_u64() != 0;
+                                Ok(Constant::Int(value.into()))
+                        }
+                        AExpression::BinOp(Binop::LogicalOr, left, right) => {
+                                let value = left.evaluate_constant(code)?.as_u64() != 0
+                                        || right.evaluate_constant(code)?.as_u64() != 0;
+                                Ok(Constant::Int(value.into()))
+                        }
+                        AExpression::BinOp(Binop::Comma, ..) => Err(ConstantError::NotConstant("a comma operator")),
+                        AExpression::BinOp(binop, left, right) => {
+                                evaluate_binop(*binop, left.evaluate_constant(code)?, right.evaluate_constant(code)?)
+                        }
+                        AExpression::C(Conditional { condition, True, False }) => {
+                                if condition.evaluate_constant(code)?.as_u64() != 0 {
+                                        True.evaluate_constant(code)
+                                } else {
+                                        False.evaluate_constant(code)
+                                }
+                        }
+                        AExpression::Assignment(..) | AExpression::OpAssignment(..) => {
+                                Err(ConstantError::NotConstant("an assignment"))
+                        }
+                        AExpression::FunctionCall(..) | AExpression::Builtin(_) => {
+                                Err(ConstantError::NotConstant("a function call"))
+                        }
+                        AExpression::CompoundLiteral(..) => Err(ConstantError::NotConstant("a compound literal")),
+                        AExpression::StatementExpr(..) => Err(ConstantError::NotConstant("a statement expression")),
+                        AExpression::SizeOf(_) => unreachable!("type checker replaces sizeof expr with sizeof type"),
+                        AExpression::Generic(_) => unreachable!("type checker replaces generic selections"),
+                }
+        }
+
+        // the function the expression designates, once the type checker has marked function designators. anything
+        // else is a function pointer when it's called
+        pub fn designated_function(&self) -> Option<AIdentifier> {
+                match self {
+                        AExpression::F(afactor) => afactor.designated_function(),
+                        _ => None,
+                }
+        }
+
+        // the function the expression is the address of, when it's an address constant, 6.6p9. a function designator
+        // converts to one, and neither & nor * nor a cast to another pointer type changes the address
+        pub fn function_address(&self) -> Option<AIdentifier> {
+                match self {
+                        AExpression::F(afactor) => afactor.function_address(),
+                        AExpression::Cast(Type::Pointer(_), expr) => expr.function_address(),
+                        _ => None,
+                }
+        }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum ConstantError {
+        #[error("A constant expression can't contain {0}")]
+        NotConstant(&'static str),
+        #[error("Division by zero in a constant expression")]
+        DivisionByZero,
+        #[error("Arithmetic on {0:?} overflows in a constant expression")]
+        Overflow(Type),
+        #[error("Shift count {0} is negative or at least the width of {1:?}")]
+        ShiftOutOfRange(i64, Type),
+}
+
+// both operands already have the type the operation is done in
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+fn evaluate_binop(binop: Binop, left: Constant, right: Constant) -> Result<Constant, ConstantError> {
+        let c_type = left.c_type();
+        let bits = c_type.size() as u64 * 8;
+        let (l, r) = (left.as_u64(), right.as_u64());
+        // as_u64 sign extends, so comparing the 64 bit values gives the right order for both signednesses
+        let ordering = if c_type.is_signed() {
+                (l as i64).cmp(&(r as i64))
+        } else {
+                l.cmp(&r)
+        };
+
+        let value = match binop {
+                Binop::Divide | Binop::Remainder if r == 0 => return Err(ConstantError::DivisionByZero),
+                Binop::LeftShift | Binop::RightShift if r >= bits => {
+                        return Err(ConstantError::ShiftOutOfRange(r as i64, c_type))
+                }
+                // shifting a negative value left, or a one into or past the sign bit, is undefined, 6.5.7p4
+                Binop::LeftShift if c_type.is_signed() => {
+                        let exact = i128::from(l as i64) << r;
+                        if exact < 0 || !c_type.holds(exact) {
+                                return Err(ConstantError::Overflow(c_type));
+                        }
+                        exact as u64
+                }
+                Binop::LeftShift => l << r,
+                Binop::RightShift if c_type.is_signed() => ((l as i64) >> r) as u64,
+                Binop::RightShift => l >> r,
+                // INT_MIN % -1 overflows too, as the quotient it goes with does, 6.5.5p6
+                Binop::Add | Binop::Subtract | Binop::Multiply | Binop::Divide | Binop::Remainder
+                        if c_type.is_signed() =>
+                {
+                        let (l, r) = (i128::from(l as i64), i128::from(r as i64));
+                        let exact = match binop {
+                                Binop::Add => l + r,
+                                Binop::Subtract => l - r,
+                                Binop::Multiply => l * r,
+                                _ => l / r,
+                        };
+                        if !c_type.holds(exact) {
+                                return Err(ConstantError::Overflow(c_type));
+                        }
+                        if binop == Binop::Remainder {
+                                (l % r) as u64
+                        } else {
+                                exact as u64
+                        }
+                }
+                // unsigned arithmetic wraps, and truncating to c_type below takes care of the modulus
+                Binop::Add => l.wrapping_add(r),
+                Binop::Subtract => l.wrapping_sub(r),
+                Binop::Multiply => l.wrapping_mul(r),
+                Binop::Divide => l / r,
+                Binop::Remainder => l % r,
+                Binop::BitwiseAnd => l & r,
+                Binop::BitwiseOr => l | r,
+                Binop::BitwiseXOr => l ^ r,
+                Binop::EqualTo => return Ok(Constant::Int(ordering.is_eq().into())),
+                Binop::NotEqualTo => return Ok(Constant::Int(ordering.is_ne().into())),
+                Binop::LessThan => return Ok(Constant::Int(ordering.is_lt().into())),
+                Binop::LessThanOrEqual => return Ok(Constant::Int(ordering.is_le().into())),
+                Binop::MoreThan => return Ok(Constant::Int(ordering.is_gt().into())),
+                Binop::MoreThanOrEqual => return Ok(Constant::Int(ordering.is_ge().into())),
+                _ => return Err(ConstantError::NotConstant("an assignment")),
+        };
+
+        Ok(Constant::new(value, c_type))
+}
+
+impl AFactor {
+        // *f is the same function as f, 6.5.3.2p4
+        pub fn designated_function(&self) -> Option<AIdentifier> {
+                match self {
+                        AFactor::Function(id) => Some(*id),
+                        AFactor::Expr(expr) => expr.designated_function(),
+                        AFactor::Unop(Unop::Deref, afactor) => afactor.designated_function(),
+                        _ => None,
+                }
+        }
+
+        pub fn function_address(&self) -> Option<AIdentifier> {
+                match self {
+                        AFactor::Function(id) => Some(*id),
+                        AFactor::Expr(expr) => expr.function_address(),
+                        AFactor::Unop(Unop::Deref | Unop::AddressOf, afactor) => afactor.function_address(),
+                        _ => None,
+                }
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        pub fn evaluate_constant(&self, code: &[u8]) -> Result<Constant, ConstantError> {
+                match self {
+                        AFactor::Constant(aconstant) => {
+                                let (value, constant_type) = aconstant
+                                        .evaluate(code)
+                                        .expect("type checker rejects invalid constants");
+                                Ok(Constant::new(value, constant_type))
+                        }
+                        AFactor::Expr(expr) => expr.evaluate_constant(code),
+                        AFactor::Unop(Unop::Plus, afactor) => afactor.evaluate_constant(code),
+                        AFactor::Unop(Unop::Negate, afactor) => {
+                                let constant = afactor.evaluate_constant(code)?;
+                                let c_type = constant.c_type();
+                                if c_type.is_signed() && !c_type.holds(-i128::from(constant.as_u64() as i64)) {
+                                        return Err(ConstantError::Overflow(c_type));
+                                }
+                                Ok(Constant::new(constant.as_u64().wrapping_neg(), c_type))
+                        }
+                        AFactor::Unop(Unop::Complement, afactor) => {
+                                let constant = afactor.evaluate_constant(code)?;
+                                Ok(Constant::new(!constant.as_u64(), constant.c_type()))
+                        }
+                        AFactor::Unop(Unop::Not, afactor) => {
+                                Ok(Constant::Int((afactor.evaluate_constant(code)?.as_u64() == 0).into()))
+                        }
+                        AFactor::Unop(Unop::Deref | Unop::AddressOf, _) | AFactor::Function(_) => {
+                                Err(ConstantError::NotConstant("a function designator"))
+                        }
+                        AFactor::Unop(..) => Err(ConstantError::NotConstant("an increment or decrement")),
+                        AFactor::Id(_) => Err(ConstantError::NotConstant("a variable")),
                 }
         }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Unop {
         Negate,
+        Plus,
         Complement,
         // !
         Not,
@@ -142,12 +633,129 @@ pub enum Unop {
         IncrementPost,
         DecrementPre,
         DecrementPost,
+        // *, which only applies to function pointers so far
+        Deref,
+        // &, which only applies to functions so far
+        AddressOf,
 }
 #[derive(Debug, Clone, Copy)]
 pub struct AConstant {
         pub start: usize,
         pub len: usize,
 }
+
+impl AConstant {
+        // value and type of an integer constant, 6.4.4.1, or of one of the C23 predefined constants, 6.4.4.6. None if
+        // no type it is allowed to have can hold it
+        pub fn evaluate(&self, code: &[u8]) -> Option<(u64, Type)> {
+                let text = &code[self.start..self.start + self.len];
+                match text {
+                        b"true" => return Some((1, Type::Bool)),
+                        b"false" => return Some((0, Type::Bool)),
+                        b"nullptr" => return Some((0, Type::NullPtr)),
+                        [b'\'', ..] => return evaluate_character(&text[1..text.len() - 1]),
+                        _ => {}
+                }
+
+                // digit separators don't change the value
+                let text: Vec<u8> = text.iter().copied().filter(|&f| f != b'\'').collect();
+
+                let suffix_start = text
+                        .iter()
+                        .rposition(|f| !matches!(f, b'u' | b'U' | b'l' | b'L'))
+                        .map_or(0, |f| f + 1);
+                let (digits, suffix) = text.split_at(suffix_start);
+
+                let (radix, digits) = if digits.len() > 2 && matches!(digits[1], b'x' | b'X') {
+                        (16, &digits[2..])
+                } else if digits.len() > 2 && matches!(digits[1], b'b' | b'B') {
+                        (2, &digits[2..])
+                } else if digits.len() > 1 && digits[0] == b'0' {
+                        (8, &digits[1..])
+                } else {
+                        (10, digits)
+                };
+                let value = u64::from_str_radix(std::str::from_utf8(digits).ok()?, radix).ok()?;
+
+                let unsigned = suffix.iter().any(|f| matches!(f, b'u' | b'U'));
+                let longs = suffix.iter().filter(|f| matches!(f, b'l' | b'L')).count();
+                let candidates: &[Type] = match (unsigned, longs, radix == 10) {
+                        (false, 0, true) => &[Type::Int, Type::Long, Type::LongLong],
+                        (false, 0, false) => &[
+                                Type::Int,
+                                Type::UInt,
+                                Type::Long,
+                                Type::ULong,
+                                Type::LongLong,
+                                Type::ULongLong,
+                        ],
+                        (true, 0, _) => &[Type::UInt, Type::ULong, Type::ULongLong],
+                        (false, 1, true) => &[Type::Long, Type::LongLong],
+                        (false, 1, false) => &[Type::Long, Type::ULong, Type::LongLong, Type::ULongLong],
+                        (true, 1, _) => &[Type::ULong, Type::ULongLong],
+                        (false, _, true) => &[Type::LongLong],
+                        (false, _, false) => &[Type::LongLong, Type::ULongLong],
+                        (true, _, _) => &[Type::ULongLong],
+                };
+
+                candidates.iter().find(|f| value <= f.max_value()).map(|&f| (value, f))
+        }
+}
+// a character constant has type int, and its value is that of the char it holds, 6.4.4.4. multi-character constants
+// aren't supported
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+fn evaluate_character(text: &[u8]) -> Option<(u64, Type)> {
+        let (value, len) = unescape(text)?;
+        if len != text.len() {
+                return None;
+        }
+
+        Some((value as i8 as u64, Type::Int))
+}
+
+// the byte the character or escape sequence at the start of text stands for and its length in text
+pub fn unescape(text: &[u8]) -> Option<(u8, usize)> {
+        if *text.first()? != b'\\' {
+                return Some((text[0], 1));
+        }
+
+        let simple = match *text.get(1)? {
+                b'\'' => Some(b'\''),
+                b'"' => Some(b'"'),
+                b'?' => Some(b'?'),
+                b'\\' => Some(b'\\'),
+                b'a' => Some(0x07),
+                b'b' => Some(0x08),
+                b'f' => Some(0x0c),
+                b'n' => Some(b'\n'),
+                b'r' => Some(b'\r'),
+                b't' => Some(b'\t'),
+                b'v' => Some(0x0b),
+                _ => None,
+        };
+        if let Some(value) = simple {
+                return Some((value, 2));
+        }
+
+        let (radix, digits, max_digits) = if text[1] == b'x' {
+                (16, &text[2..], usize::MAX)
+        } else {
+                (8, &text[1..], 3)
+        };
+        let len = digits
+                .iter()
+                .take(max_digits)
+                .take_while(|f| (**f as char).is_digit(radix))
+                .count();
+        if len == 0 {
+                return None;
+        }
+        let value = u32::from_str_radix(std::str::from_utf8(&digits[..len]).ok()?, radix).ok()?;
+
+        Some((u8::try_from(value).ok()?, text.len() - digits.len() + len))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Binop {
         Add,
@@ -182,4 +790,5 @@ pub enum Binop {
         MoreThanOrEqual,
         Equal,
         Ternary,
+        Comma,
 }
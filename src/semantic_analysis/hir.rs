@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use crate::{
+        lex::tokentype::AtomicOp,
+        parse::nodes::{
+                AIdentifier, Binop, BreakType, Constraint, Initializer, ParseLabel, StorageClass, TemplatePiece, Unop,
+                VariableDeclaration,
+        },
+        tactile::tree::{Constant, StaticInit, StaticStorage, Symbol},
+};
+
+use super::{
+        symbol_table::SymbolId,
+        type_checker::{Qualifiers, Type},
+};
+
+// the program once semantic analysis is done with it. every name is the symbol it resolved to, every expression knows
+// its type and whether it designates an object, and every conversion is a node of its own, so nothing after this has
+// to know about scopes or the C type rules
+#[derive(Debug, Clone)]
+pub struct HProgram {
+        pub functions: Vec<HFunction>,
+        // every variable, with where it lives when it has static storage duration
+        pub variables: HashMap<SymbolId, Symbol>,
+        // symbols are numbered from 0, so anything numbered alongside them starts here
+        pub symbol_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct HFunction {
+        pub name: AIdentifier,
+        pub global: bool,
+        // the variables the parameters are copied into
+        pub params: Vec<SymbolId>,
+        pub variadic: bool,
+        pub ret: Type,
+        pub body: Vec<HStatement>,
+}
+
+// declarations are gone, as the variables they declare are all in the program, apart from the initializers of
+// automatic variables, which run every time the declaration is reached, 6.8p3
+#[derive(Debug, Clone)]
+pub enum HStatement {
+        Expr(HExpression),
+        Return(Option<HExpression>),
+        If(HExpression, Box<HStatement>, Option<Box<HStatement>>),
+        Block(Vec<HStatement>),
+        Initialize(SymbolId, HExpression),
+        While(HExpression, Box<HStatement>, ParseLabel),
+        DoWhile(Box<HStatement>, HExpression, ParseLabel),
+        For(Box<HFor>, ParseLabel),
+        Switch(HSwitch),
+        Break(ParseLabel, BreakType),
+        Continue(ParseLabel),
+        Goto(ParseLabel),
+        // goto labels, and case and default ones, whose values the switch already has
+        Labeled(ParseLabel, Box<HStatement>),
+        Asm(HInlineAsm),
+        Nul,
+}
+
+#[derive(Debug, Clone)]
+pub struct HFor {
+        pub init: Option<HStatement>,
+        pub condition: Option<HExpression>,
+        pub post: Option<HExpression>,
+        pub body: HStatement,
+}
+
+#[derive(Debug, Clone)]
+pub struct HSwitch {
+        pub value: HExpression,
+        pub body: Box<HStatement>,
+        pub cases: Vec<(Constant, ParseLabel)>,
+        pub default: Option<ParseLabel>,
+        pub label: ParseLabel,
+}
+
+// the outputs come first among the operands, then the inputs, which is how the template numbers them
+#[derive(Debug, Clone)]
+pub struct HInlineAsm {
+        pub pieces: Vec<TemplatePiece>,
+        pub operands: Vec<(Constraint, HExpression)>,
+        pub clobbered: Vec<Vec<u8>>,
+}
+
+// whether an expression designates an object, 6.3.2.1p1. function designators always decay, so there are none left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+        Lvalue,
+        Rvalue,
+}
+
+#[derive(Debug, Clone)]
+pub struct HExpression {
+        pub kind: HExpressionKind,
+        pub c_type: Type,
+        pub category: Category,
+}
+
+#[derive(Debug, Clone)]
+pub enum HExpressionKind {
+        Constant(Constant),
+        Var(SymbolId),
+        // a function designator decayed to a pointer to the function, 6.3.2.1p4
+        FunctionAddress(AIdentifier),
+        // to c_type, whether the source asked for it or the conversion is implicit, 6.3
+        Convert(Box<HExpression>),
+        // -, ~, ! and +, with the operand already promoted
+        Unary(Unop, Box<HExpression>),
+        // ++ and --, before or after
+        Step(Unop, Box<HExpression>),
+        // the operands are already of the type the operation is done in
+        Binary(Binop, Box<HExpression>, Box<HExpression>),
+        Assign(Box<HExpression>, Box<HExpression>),
+        // the right side is of the type the operation is done in, which the left side is converted to and back from
+        // when it differs
+        CompoundAssign(Binop, Box<HExpression>, Box<HExpression>),
+        Conditional(Box<HExpression>, Box<HExpression>, Box<HExpression>),
+        // the arguments are already converted to the parameter types
+        Call(HCallee, Vec<HExpression>),
+        // an unnamed object with automatic storage duration, and its initializer
+        CompoundLiteral(Box<HExpression>),
+        StatementExpr(Vec<HStatement>, Option<Box<HExpression>>),
+        Builtin(HBuiltin),
+}
+
+#[derive(Debug, Clone)]
+pub enum HCallee {
+        Direct(AIdentifier),
+        // a function pointer
+        Indirect(Box<HExpression>),
+}
+
+// offsetof never gets past the type checker
+#[derive(Debug, Clone)]
+pub enum HBuiltin {
+        VaStart(SymbolId),
+        // the type of the argument is the type of the expression
+        VaArg(SymbolId),
+        VaEnd(SymbolId),
+        // dst, src
+        VaCopy(SymbolId, SymbolId),
+        Expect(Box<HExpression>, Box<HExpression>),
+        Unreachable,
+        Popcount(Box<HExpression>),
+        Clz(Box<HExpression>),
+        Atomic(Box<HAtomic>),
+}
+
+// the memory orders are constant expressions, so all that's left of them is whether a barrier is needed
+#[derive(Debug, Clone)]
+pub struct HAtomic {
+        pub op: AtomicOp,
+        pub object: Option<SymbolId>,
+        pub expected: Option<SymbolId>,
+        pub value: Option<HExpression>,
+        pub seq_cst: bool,
+}
+
+impl HExpression {
+        pub(super) fn rvalue(kind: HExpressionKind, c_type: Type) -> HExpression {
+                HExpression {
+                        kind,
+                        c_type,
+                        category: Category::Rvalue,
+                }
+        }
+}
+
+// the type checker builds the tree as it goes, with the type it works out for each expression. what's left here is
+// what the declarations it has checked add to the variables of the program
+
+// file scope variables only decide what goes in .data and .bss, so they leave nothing in the tree
+pub(super) fn file_scope_variable(decl: &VariableDeclaration, code: &[u8], variables: &mut HashMap<SymbolId, Symbol>) {
+        let &VariableDeclaration {
+                id,
+                ref init,
+                var_type,
+                qualifiers,
+                storage_class,
+                alignment,
+                thread_local,
+                ..
+        } = decl;
+
+        let init = match (init, storage_class) {
+                (Some(init), _) => static_init(init, code, var_type),
+                (None, Some(StorageClass::Extern)) => StaticInit::NoInitializer,
+                (None, _) => StaticInit::Tentative,
+        };
+        let global = storage_class != Some(StorageClass::Static);
+        let symbol = linked_variable(id, var_type, alignment, qualifiers, variables, global);
+        let storage = symbol
+                .storage
+                .as_mut()
+                .expect("variables with linkage have static storage");
+        storage.read_only |= qualifiers.constant;
+        storage.thread_local |= thread_local;
+
+        // an initializer beats a tentative definition, which beats only being declared extern
+        match (storage.init, init) {
+                (StaticInit::Initial(_) | StaticInit::FunctionAddress(_), _)
+                | (StaticInit::Tentative, StaticInit::NoInitializer) => {}
+                _ => storage.init = init,
+        }
+}
+
+// a block scope declaration only leaves a statement behind when it's of an automatic variable with an initializer,
+// which is init once it's checked
+pub(super) fn local_variable(
+        decl: &VariableDeclaration,
+        init: Option<HExpression>,
+        code: &[u8],
+        variables: &mut HashMap<SymbolId, Symbol>,
+) -> Option<HStatement> {
+        let &VariableDeclaration {
+                id,
+                var_type,
+                qualifiers,
+                storage_class,
+                alignment,
+                thread_local,
+                ..
+        } = decl;
+
+        match storage_class {
+                Some(StorageClass::Extern) => {
+                        let symbol = linked_variable(id, var_type, alignment, qualifiers, variables, true);
+                        symbol.storage
+                                .as_mut()
+                                .expect("variables with linkage have static storage")
+                                .thread_local |= thread_local;
+                        None
+                }
+                Some(StorageClass::Static) => {
+                        let init = match &decl.init {
+                                Some(init) => static_init(init, code, var_type),
+                                None => StaticInit::Tentative,
+                        };
+                        variables.insert(
+                                id.resolved(),
+                                Symbol {
+                                        c_type: var_type,
+                                        storage: Some(StaticStorage {
+                                                name: id,
+                                                local: true,
+                                                global: false,
+                                                read_only: qualifiers.constant,
+                                                thread_local,
+                                                init,
+                                        }),
+                                        aligned: alignment,
+                                        atomic: qualifiers.atomic,
+                                },
+                        );
+                        None
+                }
+                None => {
+                        let symbol = automatic_variable(id, var_type, alignment, qualifiers, variables);
+                        init.map(|init| HStatement::Initialize(symbol, init))
+                }
+        }
+}
+
+fn static_init(init: &Initializer, code: &[u8], var_type: Type) -> StaticInit {
+        if let Some(function) = init.function_address() {
+                return StaticInit::FunctionAddress(function);
+        }
+        StaticInit::Initial(
+                init.evaluate_constant(code, var_type)
+                        .expect("type checker only allows constant static initializers"),
+        )
+}
+
+pub(super) fn automatic_variable(
+        id: AIdentifier,
+        var_type: Type,
+        alignment: Option<usize>,
+        qualifiers: Qualifiers,
+        variables: &mut HashMap<SymbolId, Symbol>,
+) -> SymbolId {
+        let symbol = id.resolved();
+        variables.insert(
+                symbol,
+                Symbol {
+                        c_type: var_type,
+                        storage: None,
+                        aligned: alignment,
+                        atomic: qualifiers.atomic,
+                },
+        );
+        symbol
+}
+
+// every declaration of a variable with linkage has the same symbol, and the first one to be seen makes its entry. any
+// of them can ask for it to be aligned more strictly, and the strictest one wins. every declaration of an _Atomic
+// variable has to say so, as the type checker makes sure they all agree on the type
+fn linked_variable(
+        id: AIdentifier,
+        var_type: Type,
+        alignment: Option<usize>,
+        qualifiers: Qualifiers,
+        variables: &mut HashMap<SymbolId, Symbol>,
+        global: bool,
+) -> &mut Symbol {
+        let symbol = variables.entry(id.resolved()).or_insert(Symbol {
+                c_type: var_type,
+                storage: Some(StaticStorage {
+                        name: id,
+                        local: false,
+                        global,
+                        read_only: false,
+                        thread_local: false,
+                        init: StaticInit::NoInitializer,
+                }),
+                aligned: None,
+                atomic: false,
+        });
+        symbol.aligned = symbol.aligned.max(alignment);
+        symbol.atomic |= qualifiers.atomic;
+        symbol
+}
@@ -1,398 +1,507 @@
-use std::{collections::HashMap, hash::BuildHasher};
-
 use crate::{
         parse::nodes::{
-                ABlock, AExpression, AFactor, AIdentifier, AProgram, AStatement, BlockItem, Conditional, Declaration,
-                ForInit, FunctionDeclaration, IfStatement, Switch, Unop, VariableDeclaration,
+                ABlock, AExpression, AFactor, AIdentifier, AProgram, AStatement, BlockItem, Builtin, Conditional,
+                Declaration, Designator, ExprId, ForInit, FunctionDeclaration, IfStatement, Initializer, StorageClass,
+                Switch, Unop, VariableDeclaration,
         },
-        tactile::Identifier,
+        semantic_analysis::type_checker::Type,
 };
 
-use super::{Error, IdentifierMap};
+use super::{
+        symbol_table::{ScopeId, SymbolTable},
+        Error,
+};
 
-pub fn resolve_identifiers<'b, 'a: 'b>(code: &'a [u8], program: &AProgram) -> Result<IdentifierMap<'b>, Error> {
-        let mut global_max_identifier = 0;
-        let mut identifier_map = HashMap::new();
+pub fn resolve_identifiers<'a>(code: &'a [u8], program: &mut AProgram) -> Result<SymbolTable<'a>, Error> {
+        let mut table = SymbolTable::default();
+        let mut kept = Kept {
+                resolved: vec![false; program.exprs.len()],
+                exprs: std::mem::take(&mut program.exprs).into_iter().map(Some).collect(),
+        };
 
-        for i in &program.functions {
-                () = resolve_function_declaration(code, i, &mut identifier_map, &mut global_max_identifier, 0)?;
+        for i in &mut program.declarations {
+                () = resolve_declaration(code, i, &mut table, &mut kept, ScopeId::FILE)?;
         }
 
-        Ok(identifier_map)
+        program.exprs = kept
+                .exprs
+                .into_iter()
+                .map(|f| f.expect("kept expression is put back"))
+                .collect();
+        Ok(table)
 }
 
-fn resolve_block_item<'b, 'a: 'b, S: BuildHasher>(
-        block_item: &BlockItem,
+fn resolve_block_item<'a>(
+        block_item: &mut BlockItem,
         code: &'a [u8],
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        global_max_identifier: &mut usize,
-        scope: usize,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
         match block_item {
-                BlockItem::D(declaration) => {
-                        resolve_declaration(code, declaration, identifier_map, global_max_identifier, scope)?
-                }
-                BlockItem::S(astatement) => {
-                        resolve_statement(code, astatement, identifier_map, global_max_identifier, scope)?
-                }
+                BlockItem::D(declaration) => resolve_declaration(code, declaration, table, kept, scope)?,
+                BlockItem::S(astatement) => resolve_statement(code, astatement, table, kept, scope)?,
         };
         Ok(())
 }
 
-fn resolve_declaration<'b, 'a: 'b, S: BuildHasher>(
+fn resolve_declaration<'a>(
         code: &'a [u8],
-        declaration: &Declaration,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        global_max_identifier: &mut usize,
-        scope: usize,
+        declaration: &mut Declaration,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
         match declaration {
-                Declaration::V(variable_declaration) => resolve_variable_declaration(
-                        code,
-                        variable_declaration,
-                        identifier_map,
-                        global_max_identifier,
-                        scope,
-                ),
-                Declaration::F(function_declaration) => resolve_function_declaration(
-                        code,
-                        function_declaration,
-                        identifier_map,
-                        global_max_identifier,
-                        scope,
-                ),
+                Declaration::V(variable_declaration) => {
+                        resolve_variable_declaration(code, variable_declaration, table, kept, scope)
+                }
+                Declaration::F(function_declaration) => {
+                        resolve_function_declaration(code, function_declaration, table, kept, scope)
+                }
+                Declaration::A(static_assert) => resolve_exp(code, &mut static_assert.condition, table, kept, scope),
+                Declaration::T(aliased) => resolve_type(code, *aliased, table, kept, scope),
         }
 }
 
-fn resolve_function_declaration<'b, 'a: 'b, S: BuildHasher>(
+fn resolve_function_declaration<'a>(
         code: &'a [u8],
-        declaration: &FunctionDeclaration,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        global_max_identifier: &mut usize,
-        scope: usize,
+        declaration: &mut FunctionDeclaration,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
-        let AIdentifier { start, len } = declaration.name;
+        let AIdentifier { start, len, .. } = declaration.name;
         let name = &code[start..start + len];
-        if identifier_exists(code, &declaration.name, identifier_map, scope).is_ok() {
-                if let Some(curr_scope_reference) = identifier_map.get(&(name, scope)) {
-                        if !curr_scope_reference.1 {
-                                return Err(Error::DeclaredTwice(
-                                        String::from_utf8(name.to_vec()).unwrap(),
-                                        start + len,
-                                ));
-                        }
+        if scope != ScopeId::FILE && declaration.storage_class == Some(StorageClass::Static) {
+                return Err(Error::StaticBlockScopeFunction(
+                        String::from_utf8(name.to_vec()).unwrap(),
+                        start,
+                ));
+        }
+        if let Some(previous) = table.declared_in(scope, name) {
+                if !table.symbol(previous).linkage {
+                        return Err(Error::DeclaredTwice(
+                                String::from_utf8(name.to_vec()).unwrap(),
+                                start + len,
+                        ));
                 }
-        };
+        }
 
-        identifier_map
-                .entry((name, scope))
-                .insert_entry((new_id(global_max_identifier), true));
+        // typeof in the parameter types is about what's in sight before the parameters are
+        resolve_type(code, Type::Func(declaration.func_type, false), table, kept, scope)?;
 
-        if let Some(thing) = &declaration.params {
-                for i in thing {
-                        let AIdentifier { start, len } = *i;
-                        let name = &code[start..start + len];
-                        if identifier_map.get(&(name, scope + 1)).is_some() {
-                                return Err(Error::DeclaredTwice(String::from_utf8(name.to_vec()).unwrap(), start));
-                        }
-                        identifier_map
-                                .entry((name, scope + 1))
-                                .insert_entry((new_id(global_max_identifier), false));
+        // every function has linkage, 6.2.2p5
+        declaration.name.symbol = Some(table.declare(scope, name, declaration.name, true));
+
+        // parameters live in the same scope as the outermost block of the body
+        let body_scope = table.enter(scope);
+        for i in declaration.params.iter_mut().filter_map(|f| f.0.as_mut()) {
+                let AIdentifier { start, len, .. } = *i;
+                let name = &code[start..start + len];
+                if table.declared_in(body_scope, name).is_some() {
+                        return Err(Error::DeclaredTwice(String::from_utf8(name.to_vec()).unwrap(), start));
                 }
+                i.symbol = Some(table.declare(body_scope, name, *i, false));
         }
 
-        if let Some(body) = &declaration.body {
-                if scope != 0 {
+        if let Some(body) = &mut declaration.body {
+                if scope != ScopeId::FILE {
                         return Err(Error::NestedFunctionDeclaration(
                                 String::from_utf8(name.to_vec()).unwrap(),
                                 declaration.name.start,
                         ));
                 }
-                for i in &body.0 {
-                        resolve_block_item(i, code, identifier_map, global_max_identifier, scope + 1)?
+                for i in &mut body.0 {
+                        resolve_block_item(i, code, table, kept, body_scope)?
                 }
         }
 
-        identifier_map.retain(|&(_, f), _| f < (scope + 1));
-
         Ok(())
 }
 
-fn resolve_variable_declaration<'b, 'a: 'b, S: BuildHasher>(
+fn resolve_variable_declaration<'a>(
         code: &'a [u8],
-        declaration: &VariableDeclaration,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        global_max_identifier: &mut usize,
-        scope: usize,
+        declaration: &mut VariableDeclaration,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
-        let AIdentifier { start, len } = declaration.id;
+        let AIdentifier { start, len, .. } = declaration.id;
         let name = &code[start..start + len];
-        if identifier_map.get(&(name, scope)).is_some() {
-                return Err(Error::DeclaredTwice(String::from_utf8(name.to_vec()).unwrap(), start));
+
+        // the variable's own scope only starts after its declarator, 6.2.1p7, so anything in its type can't see it
+        resolve_type(code, declaration.var_type, table, kept, scope)?;
+        if let Some(Some(alignment)) = declaration.attributes.aligned {
+                resolve_kept(code, alignment, table, kept, scope)?;
         }
-        identifier_map
-                .entry((name, scope))
-                .insert_entry((new_id(global_max_identifier), false));
 
-        if let Some(extract) = &declaration.init {
-                () = resolve_exp(code, extract, identifier_map, scope)?;
+        // file scope variables and block scope externs have linkage, so every declaration of them is the same variable
+        let has_linkage = (scope == ScopeId::FILE) | (declaration.storage_class == Some(StorageClass::Extern));
+        let symbol = match table.declared_in(scope, name) {
+                Some(previous) if !(table.symbol(previous).linkage & has_linkage) => {
+                        return Err(Error::DeclaredTwice(String::from_utf8(name.to_vec()).unwrap(), start));
+                }
+                Some(previous) => previous,
+                None => table.declare(scope, name, declaration.id, has_linkage),
+        };
+        declaration.id.symbol = Some(symbol);
+
+        if let Some(init) = &mut declaration.init {
+                () = resolve_initializer(code, init, table, kept, scope)?;
         }
 
         Ok(())
 }
 
-fn resolve_statement<'b, 'a: 'b, S: BuildHasher>(
+// a type is copied to everything declared with it, so the expressions in it are only resolved where they're first
+// reached. that's where they're written, as a typedef with one is kept as a declaration of its own
+struct Kept {
+        // each one is taken out while it's being resolved
+        exprs: Vec<Option<AExpression>>,
+        resolved: Vec<bool>,
+}
+
+fn resolve_type<'a>(
+        code: &'a [u8],
+        c_type: Type,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
+) -> Result<(), Error> {
+        match c_type {
+                Type::Typeof(operand) => resolve_kept(code, operand.expr, table, kept, scope),
+                Type::Pointer(&pointee) => resolve_type(code, pointee, table, kept, scope),
+                Type::Func(func_type, _) => {
+                        resolve_type(code, func_type.ret, table, kept, scope)?;
+                        for &param in &func_type.params {
+                                resolve_type(code, param, table, kept, scope)?;
+                        }
+                        Ok(())
+                }
+                _ => Ok(()),
+        }
+}
+
+fn resolve_kept<'a>(
         code: &'a [u8],
-        statement: &AStatement,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        max_identifier: &mut usize,
-        scope: usize,
+        id: ExprId,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
+) -> Result<(), Error> {
+        if kept.resolved[id.0] {
+                return Ok(());
+        }
+        kept.resolved[id.0] = true;
+        let mut expr = kept.exprs[id.0].take().expect("an expression can't contain itself");
+        let resolved = resolve_exp(code, &mut expr, table, kept, scope);
+        kept.exprs[id.0] = Some(expr);
+        resolved
+}
+
+fn resolve_initializer<'a>(
+        code: &'a [u8],
+        init: &mut Initializer,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
+) -> Result<(), Error> {
+        match init {
+                Initializer::Single(aexpression) => resolve_exp(code, aexpression, table, kept, scope),
+                Initializer::Compound(list) => {
+                        for (designators, init) in list {
+                                for i in designators {
+                                        if let Designator::Index(index) = i {
+                                                resolve_exp(code, index, table, kept, scope)?;
+                                        }
+                                }
+                                resolve_initializer(code, init, table, kept, scope)?;
+                        }
+
+                        Ok(())
+                }
+        }
+}
+
+fn resolve_statement<'a>(
+        code: &'a [u8],
+        statement: &mut AStatement,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
         match statement {
-                AStatement::Return(expr) | AStatement::Expr(expr) => resolve_exp(code, expr, identifier_map, scope),
+                AStatement::Return(Some(expr)) | AStatement::Expr(expr) => resolve_exp(code, expr, table, kept, scope),
                 AStatement::I(if_statement) => {
                         let IfStatement { condition, then, Else } = if_statement;
-                        resolve_exp(code, condition, identifier_map, scope)?;
-                        resolve_statement(code, then, identifier_map, max_identifier, scope + 1)?;
+                        resolve_exp(code, condition, table, kept, scope)?;
+                        resolve_statement(code, then, table, kept, scope)?;
                         if let Some(else_statement) = Else {
-                                resolve_statement(code, else_statement, identifier_map, max_identifier, scope + 1)?;
+                                resolve_statement(code, else_statement, table, kept, scope)?;
                         }
 
-                        identifier_map.retain(|(_, f), _| (*f < (scope + 1)));
-
                         Ok(())
                 }
-                AStatement::Nul | AStatement::Break(..) | AStatement::Continue(_) => Ok(()),
+                // outputs are written, so they have to be lvalues
+                AStatement::Asm(inline_asm) => {
+                        for output in &mut inline_asm.outputs {
+                                check_lvalue(&output.expr)?;
+                                resolve_exp(code, &mut output.expr, table, kept, scope)?;
+                        }
+                        for input in &mut inline_asm.inputs {
+                                resolve_exp(code, &mut input.expr, table, kept, scope)?;
+                        }
+                        Ok(())
+                }
+                AStatement::Return(None)
+                | AStatement::Nul
+                | AStatement::Fallthrough
+                | AStatement::Break(..)
+                | AStatement::Continue(_)
+                | AStatement::Goto(..) => Ok(()),
+                AStatement::Labeled(_, astatement, _) => resolve_statement(code, astatement, table, kept, scope),
                 AStatement::Compound(ABlock(block)) => {
-                        let inner_scope = scope + 1;
-
+                        let inner_scope = table.enter(scope);
                         for i in block {
-                                resolve_block_item(i, code, identifier_map, max_identifier, inner_scope)?
+                                resolve_block_item(i, code, table, kept, inner_scope)?
                         }
 
-                        identifier_map.retain(|(_, f), _| *f < inner_scope);
-
                         Ok(())
                 }
                 AStatement::While(aexpression, astatement, _) | AStatement::DoWhile(astatement, aexpression, _) => {
-                        () = resolve_exp(code, aexpression, identifier_map, scope + 1)?;
-                        () = resolve_statement(code, astatement, identifier_map, max_identifier, scope + 2)?;
-                        identifier_map.retain(|&(_, f), _| f < (scope + 1));
-
-                        Ok(())
+                        () = resolve_exp(code, aexpression, table, kept, scope)?;
+                        resolve_statement(code, astatement, table, kept, scope)
                 }
+                // the body is a block inside the scope of the header, 6.8.5p5
                 AStatement::F(boxed_for, _) => {
-                        let header_scope = scope + 1;
-
-                        match &boxed_for.init {
-                                ForInit::D(declaration) => resolve_variable_declaration(
-                                        code,
-                                        declaration,
-                                        identifier_map,
-                                        max_identifier,
-                                        header_scope,
-                                )?,
+                        let header_scope = table.enter(scope);
+
+                        match &mut boxed_for.init {
+                                ForInit::D(declaration) => {
+                                        resolve_variable_declaration(code, declaration, table, kept, header_scope)?
+                                }
                                 ForInit::E(Some(aexpression)) => {
-                                        resolve_exp(code, aexpression, identifier_map, header_scope)?
+                                        resolve_exp(code, aexpression, table, kept, header_scope)?
                                 }
                                 ForInit::E(None) => {}
                         }
 
-                        if let Some(cond) = &boxed_for.condition {
-                                let () = resolve_exp(code, cond, identifier_map, header_scope)?;
+                        if let Some(cond) = &mut boxed_for.condition {
+                                let () = resolve_exp(code, cond, table, kept, header_scope)?;
                         }
-                        if let Some(post) = &boxed_for.post {
-                                let () = resolve_exp(code, post, identifier_map, header_scope)?;
+                        if let Some(post) = &mut boxed_for.post {
+                                let () = resolve_exp(code, post, table, kept, header_scope)?;
                         }
 
-                        let body_scope = header_scope + 1;
-                        () = resolve_statement(code, &boxed_for.body, identifier_map, max_identifier, body_scope)?;
-
-                        identifier_map.retain(|(_, scope), _| *scope < (*scope + 1));
-
-                        Ok(())
+                        resolve_statement(code, &mut boxed_for.body, table, kept, header_scope)
                 }
                 AStatement::S(switch) => {
-                        let Switch {
-                                value, cases, default, ..
-                        } = switch;
-                        resolve_exp(code, value, identifier_map, scope)?;
-                        for (_, statements) in cases {
-                                for j in statements {
-                                        resolve_statement(code, j, identifier_map, max_identifier, scope)?;
-                                }
-                        }
-                        if let Some(default) = default {
-                                resolve_statement(code, default, identifier_map, max_identifier, scope)?;
-                        }
-                        Ok(())
+                        let Switch { value, body, .. } = switch;
+                        resolve_exp(code, value, table, kept, scope)?;
+                        resolve_statement(code, body, table, kept, scope)
                 }
+                AStatement::Case(aexpression, astatement, _) => {
+                        resolve_exp(code, aexpression, table, kept, scope)?;
+                        resolve_statement(code, astatement, table, kept, scope)
+                }
+                AStatement::Default(astatement, _) => resolve_statement(code, astatement, table, kept, scope),
         }
 }
 
-fn resolve_exp<S: BuildHasher>(
-        code: &[u8],
-        expr: &AExpression,
-        identifier_map: &mut HashMap<(&[u8], usize), (Identifier, bool), S>,
-        scope: usize,
+fn resolve_exp<'a>(
+        code: &'a [u8],
+        expr: &mut AExpression,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
         match expr {
-                AExpression::F(afactor) => match afactor {
-                        AFactor::Expr(aexpression) => resolve_exp(code, aexpression, identifier_map, scope),
-                        AFactor::Id(aidentifier) => {
-                                identifier_exists(code, aidentifier, identifier_map, scope).map(|_| ())
-                        }
-                        AFactor::Unop(unop, afactor) => {
-                                is_valid_lvalue_unop(code, *unop, *afactor.clone(), identifier_map, scope)
-                        }
-                        AFactor::Constant(_) => Ok(()),
-                },
-                AExpression::Assignment(left, right) => {
-                        is_valid_lvalue_assignment(code, left, identifier_map, scope)?;
-                        resolve_exp(code, right, identifier_map, scope)
+                AExpression::F(afactor) => resolve_factor(code, afactor, table, kept, scope),
+                AExpression::Assignment(left, right) | AExpression::OpAssignment(_, left, right) => {
+                        check_lvalue(left)?;
+                        resolve_exp(code, left, table, kept, scope)?;
+                        resolve_exp(code, right, table, kept, scope)
                 }
                 AExpression::BinOp(_, left, right) => {
-                        resolve_exp(code, left, identifier_map, scope)?;
-                        resolve_exp(code, right, identifier_map, scope)
+                        resolve_exp(code, left, table, kept, scope)?;
+                        resolve_exp(code, right, table, kept, scope)
                 }
                 AExpression::C(Conditional { condition, True, False }) => {
-                        resolve_exp(code, condition, identifier_map, scope)?;
-                        resolve_exp(code, True, identifier_map, scope)?;
-                        resolve_exp(code, False, identifier_map, scope)
+                        resolve_exp(code, condition, table, kept, scope)?;
+                        resolve_exp(code, True, table, kept, scope)?;
+                        resolve_exp(code, False, table, kept, scope)
                 }
-                AExpression::OpAssignment(_binop, left, right) => {
-                        is_valid_lvalue_assignment(code, left, identifier_map, scope)?;
-                        resolve_exp(code, right, identifier_map, scope)
-                }
-                AExpression::FunctionCall(aidentifier, vec) => {
-                        if identifier_exists(code, aidentifier, identifier_map, scope).is_err() {
-                                let &AIdentifier { start, len } = aidentifier;
-                                let name = &code[start..start + len];
-                                return Err(Error::UndeclaredIdentifier(
-                                        String::from_utf8(name.to_vec()).unwrap(),
-                                        start + len,
-                                ));
-                        }
+                AExpression::FunctionCall(callee, vec, _) => {
+                        resolve_exp(code, callee, table, kept, scope)?;
 
                         if let Some(params) = vec {
                                 for expr in params {
-                                        resolve_exp(code, expr, identifier_map, scope)?;
+                                        resolve_exp(code, expr, table, kept, scope)?;
                                 }
                         }
 
                         Ok(())
                 }
+                AExpression::Cast(to, aexpression) => {
+                        resolve_type(code, *to, table, kept, scope)?;
+                        resolve_exp(code, aexpression, table, kept, scope)
+                }
+                AExpression::SizeOf(aexpression) => resolve_exp(code, aexpression, table, kept, scope),
+                AExpression::SizeOfType(of) | AExpression::AlignOf(of) => resolve_type(code, *of, table, kept, scope),
+                // the block is a scope of its own, and the value is the last thing in it
+                AExpression::StatementExpr(ABlock(block), value) => {
+                        let inner_scope = table.enter(scope);
+                        for i in block {
+                                resolve_block_item(i, code, table, kept, inner_scope)?;
+                        }
+                        if let Some(value) = value {
+                                resolve_exp(code, value, table, kept, inner_scope)?;
+                        }
+
+                        Ok(())
+                }
+                AExpression::CompoundLiteral(of, init) => {
+                        resolve_type(code, *of, table, kept, scope)?;
+                        resolve_initializer(code, init, table, kept, scope)
+                }
+                AExpression::Generic(selection) => {
+                        resolve_exp(code, &mut selection.controlling, table, kept, scope)?;
+                        for (association, expr) in &mut selection.associations {
+                                if let Some((of, _)) = association {
+                                        resolve_type(code, *of, table, kept, scope)?;
+                                }
+                                resolve_exp(code, expr, table, kept, scope)?;
+                        }
+                        Ok(())
+                }
+                AExpression::Builtin(builtin) => {
+                        let (ap, other) = match builtin {
+                                Builtin::VaStart(ap) | Builtin::VaEnd(ap) => (ap, None),
+                                Builtin::VaArg(ap, arg_type) => {
+                                        resolve_type(code, *arg_type, table, kept, scope)?;
+                                        (ap, None)
+                                }
+                                Builtin::VaCopy(dst, src) => (dst, Some(src)),
+                                Builtin::Expect(value, expected) => {
+                                        resolve_exp(code, value, table, kept, scope)?;
+                                        return resolve_exp(code, expected, table, kept, scope);
+                                }
+                                Builtin::Popcount(value) | Builtin::Clz(value) => {
+                                        return resolve_exp(code, value, table, kept, scope)
+                                }
+                                // the member is looked up in the struct, not the scope
+                                Builtin::Offsetof(of, _) => return resolve_type(code, *of, table, kept, scope),
+                                Builtin::Unreachable => return Ok(()),
+                                Builtin::Atomic(atomic) => {
+                                        for id in atomic.object.iter_mut().chain(&mut atomic.expected) {
+                                                resolve_identifier(code, id, table, scope)?;
+                                        }
+                                        for expr in atomic
+                                                .value
+                                                .iter_mut()
+                                                .chain([&mut atomic.order])
+                                                .chain(&mut atomic.failure_order)
+                                        {
+                                                resolve_exp(code, expr, table, kept, scope)?;
+                                        }
+                                        return Ok(());
+                                }
+                        };
+                        resolve_identifier(code, ap, table, scope)?;
+                        if let Some(src) = other {
+                                resolve_identifier(code, src, table, scope)?;
+                        }
+                        Ok(())
+                }
         }
 }
 
-fn is_valid_lvalue_assignment<S: BuildHasher>(
-        code: &[u8],
-        left: &AExpression,
-        identifier_map: &mut HashMap<(&[u8], usize), (Identifier, bool), S>,
-        scope: usize,
+fn resolve_factor<'a>(
+        code: &'a [u8],
+        afactor: &mut AFactor,
+        table: &mut SymbolTable<'a>,
+        kept: &mut Kept,
+        scope: ScopeId,
 ) -> Result<(), Error> {
+        match afactor {
+                AFactor::Expr(aexpression) => resolve_exp(code, aexpression, table, kept, scope),
+                AFactor::Id(aidentifier) | AFactor::Function(aidentifier) => {
+                        resolve_identifier(code, aidentifier, table, scope)
+                }
+                AFactor::Unop(unop, operand) => {
+                        check_unop_operand(*unop, operand)?;
+                        resolve_factor(code, operand, table, kept, scope)
+                }
+                AFactor::Constant(_) => Ok(()),
+        }
+}
+
+fn check_lvalue(left: &AExpression) -> Result<(), Error> {
         match left {
                 AExpression::F(afactor) => match afactor {
-                        AFactor::Expr(expr) => is_valid_lvalue_assignment(code, expr, identifier_map, scope),
-                        AFactor::Id(aidentifier) => {
-                                identifier_exists(code, aidentifier, identifier_map, scope).map(|_| ())
+                        AFactor::Expr(expr) => check_lvalue(expr),
+                        AFactor::Id(_) => Ok(()),
+                        AFactor::Constant(..) | AFactor::Unop(..) | AFactor::Function(_) => {
+                                Err(Error::InvalidLValueExpr(left.clone()))
                         }
-                        AFactor::Constant(..) | AFactor::Unop(..) => Err(Error::InvalidLValueExpr(left.clone())),
                 },
-                AExpression::Assignment(left, right) => {
-                        resolve_exp(code, left, identifier_map, scope)?;
-                        resolve_exp(code, right, identifier_map, scope)
-                }
+                AExpression::Assignment(..) | AExpression::CompoundLiteral(..) => Ok(()),
                 AExpression::C(_)
                 | AExpression::BinOp(..)
                 | AExpression::OpAssignment(..)
-                | AExpression::FunctionCall(..) => Err(Error::InvalidLValueExpr(left.clone())),
+                | AExpression::FunctionCall(..)
+                | AExpression::Cast(..)
+                | AExpression::SizeOf(_)
+                | AExpression::SizeOfType(_)
+                | AExpression::AlignOf(_)
+                | AExpression::Builtin(_)
+                | AExpression::StatementExpr(..)
+                // the selection is only made by the type checker, so it can't be told to be an lvalue yet
+                | AExpression::Generic(_) => Err(Error::InvalidLValueExpr(left.clone())),
         }
 }
 
-fn is_valid_lvalue_unop<S: BuildHasher>(
-        code: &[u8],
-        unop: Unop,
-        factor: AFactor,
-        identifier_map: &mut HashMap<(&[u8], usize), (Identifier, bool), S>,
-        scope: usize,
-) -> Result<(), Error> {
-        match factor.clone() {
-                AFactor::Constant(_) => match unop {
-                        Unop::Negate | Unop::Complement | Unop::Not => Ok(()),
-                        Unop::IncrementPre | Unop::IncrementPost | Unop::DecrementPre | Unop::DecrementPost => {
-                                Err(Error::InvalidLValueFactor(factor))
-                        }
-                },
-                AFactor::Unop(innerunop, afactor) => {
-                        match unop {
-                                Unop::Negate | Unop::Complement | Unop::Not => {}
-                                Unop::IncrementPre | Unop::IncrementPost | Unop::DecrementPre | Unop::DecrementPost => {
-                                        return Err(Error::InvalidLValueFactor(factor))
-                                }
-                        }
-                        is_valid_lvalue_unop(code, innerunop, *afactor, identifier_map, scope)?;
-
-                        Ok(())
+// increments and decrements need an lvalue, which nothing but a variable is
+fn check_unop_operand(unop: Unop, factor: &AFactor) -> Result<(), Error> {
+        let needs_lvalue = matches!(
+                unop,
+                Unop::IncrementPre | Unop::IncrementPost | Unop::DecrementPre | Unop::DecrementPost
+        );
+        match factor {
+                AFactor::Constant(_) | AFactor::Unop(..) if needs_lvalue => {
+                        Err(Error::InvalidLValueFactor(factor.clone()))
                 }
-                AFactor::Expr(aexpression) => {
-                        resolve_exp(code, &aexpression, identifier_map, scope)?;
-                        match *aexpression {
-                                AExpression::F(afactor) => {
-                                        is_valid_lvalue_unop(code, unop, afactor, identifier_map, scope)
-                                }
-                                AExpression::BinOp(_binop, left, right) => {
-                                        match unop {
-                                                Unop::Negate | Unop::Complement | Unop::Not => {}
-                                                Unop::IncrementPre
-                                                | Unop::IncrementPost
-                                                | Unop::DecrementPre
-                                                | Unop::DecrementPost => {
-                                                        return Err(Error::InvalidLValueFactor(factor))
-                                                }
-                                        }
-                                        resolve_exp(code, &left, identifier_map, scope)?;
-                                        resolve_exp(code, &right, identifier_map, scope)
-                                }
-                                AExpression::Assignment(..) | AExpression::C(_) | AExpression::OpAssignment(..) => {
-                                        Err(Error::InvalidLValueExpr(*aexpression))
-                                }
-                                AExpression::FunctionCall(..) => match unop {
-                                        Unop::Negate | Unop::Complement | Unop::Not => Ok(()),
-                                        Unop::IncrementPre
-                                        | Unop::IncrementPost
-                                        | Unop::DecrementPre
-                                        | Unop::DecrementPost => Err(Error::InvalidLValueFactor(factor)),
-                                },
+                AFactor::Constant(_) | AFactor::Unop(..) | AFactor::Id(_) | AFactor::Function(_) => Ok(()),
+                AFactor::Expr(aexpression) => match &**aexpression {
+                        AExpression::F(afactor) => check_unop_operand(unop, afactor),
+                        AExpression::Assignment(..) | AExpression::C(_) | AExpression::OpAssignment(..) => {
+                                Err(Error::InvalidLValueExpr(*aexpression.clone()))
                         }
-                }
-                AFactor::Id(aidentifier) => identifier_exists(code, &aidentifier, identifier_map, scope).map(|_| ()),
+                        AExpression::CompoundLiteral(..) => Ok(()),
+                        _ if needs_lvalue => Err(Error::InvalidLValueFactor(factor.clone())),
+                        _ => Ok(()),
+                },
         }
 }
 
-fn identifier_exists<S: BuildHasher>(
-        code: &[u8],
-        aidentifier: &AIdentifier,
-        identifier_map: &mut HashMap<(&[u8], usize), (Identifier, bool), S>,
-        scope: usize,
-) -> Result<(Identifier, bool), Error> {
-        let &AIdentifier { start, len } = aidentifier;
+fn resolve_identifier<'a>(
+        code: &'a [u8],
+        aidentifier: &mut AIdentifier,
+        table: &SymbolTable<'a>,
+        scope: ScopeId,
+) -> Result<(), Error> {
+        let AIdentifier { start, len, .. } = *aidentifier;
         let name = &code[start..start + len];
 
-        for i in (0..=scope).rev() {
-                if let Some(&thing) = identifier_map.get(&(name, i)) {
-                        return Ok(thing);
+        match table.lookup(scope, name) {
+                Some(symbol) => {
+                        aidentifier.symbol = Some(symbol);
+                        Ok(())
                 }
+                None => Err(Error::UndeclaredIdentifier(
+                        String::from_utf8(name.to_vec()).unwrap(),
+                        start,
+                )),
         }
-
-        Err(Error::UndeclaredIdentifier(
-                String::from_utf8(name.to_vec()).unwrap(),
-                start,
-        ))
-}
-
-fn new_id(max_identifier: &mut usize) -> Identifier {
-        let temp = Identifier(*max_identifier);
-        *max_identifier += 1;
-        temp
 }
@@ -1,93 +1,300 @@
-use crate::parse::nodes::{ABlock, AProgram, AStatement, BlockItem, ParseLabel, Switch};
+use std::collections::HashMap;
+
+use crate::parse::nodes::{
+        ABlock, AExpression, AFactor, AIdentifier, AProgram, AStatement, AtomicBuiltin, BlockItem, BreakType, Builtin,
+        Conditional, Declaration, ForInit, FunctionDeclaration, Initializer, ParseLabel, Switch,
+};
 
 use super::Error;
 
-pub(super) fn label_loops(program: &mut AProgram) -> Result<usize, Error> {
+// goto labels have function scope, and a goto can come before its label, so we remember whether we saw the label itself
+type GotoLabels<'a> = HashMap<&'a [u8], (ParseLabel, AIdentifier, bool)>;
+
+// the loops and switches around a statement, innermost last
+#[derive(Debug, Clone, Copy)]
+enum Enclosing {
+        Loop(ParseLabel),
+        Switch(ParseLabel),
+}
+
+pub(super) fn label_loops(program: &mut AProgram, code: &[u8]) -> Result<usize, Error> {
         let mut max_label = ParseLabel(0);
-        for i in &mut program.functions {
-                if let Some(body) = &mut i.body {
-                        for j in &mut body.0 {
-                                match j {
-                                        BlockItem::D(_) => {}
-                                        BlockItem::S(astatement) => label_statement(astatement, None, &mut max_label)?,
-                                }
-                        }
+        for i in &mut program.declarations {
+                let Declaration::F(FunctionDeclaration { body: Some(body), .. }) = i else {
+                        continue;
+                };
+
+                let mut goto_labels = HashMap::new();
+                for j in &mut body.0 {
+                        label_block_item(j, &mut vec![], &mut max_label, code, &mut goto_labels)?;
+                }
+
+                if let Some((_, id, _)) = goto_labels.values().find(|f| !f.2) {
+                        let AIdentifier { start, len, .. } = *id;
+                        return Err(Error::UndefinedLabel(
+                                String::from_utf8(code[start..start + len].to_vec()).unwrap(),
+                                start,
+                        ));
                 }
         }
 
         Ok(max_label.0)
 }
 
-fn label_statement(
+// break leaves the innermost loop or switch, 6.8.6.3p2, and continue goes on with the innermost loop, even from inside
+// a switch in it, 6.8.6.2p2
+fn label_statement<'a>(
         statement: &mut AStatement,
-        curr_label: Option<ParseLabel>,
+        enclosing: &mut Vec<Enclosing>,
         max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
 ) -> Result<(), Error> {
         match statement {
-                AStatement::Break(..) | AStatement::Continue(_) => {
-                        let Some(label) = curr_label else {
-                                return Err(Error::BreakOutsideLoop(statement.clone()));
+                AStatement::Goto(id, label) => {
+                        let AIdentifier { start, len, .. } = *id;
+                        let entry = goto_labels
+                                .entry(&code[start..start + len])
+                                .or_insert_with(|| (new_label(max_label), *id, false));
+                        *label = entry.0;
+                }
+                AStatement::Labeled(id, astatement, label) => {
+                        let AIdentifier { start, len, .. } = *id;
+                        let entry = goto_labels
+                                .entry(&code[start..start + len])
+                                .or_insert_with(|| (new_label(max_label), *id, false));
+                        if entry.2 {
+                                return Err(Error::DuplicateLabel(
+                                        String::from_utf8(code[start..start + len].to_vec()).unwrap(),
+                                        start,
+                                ));
+                        }
+                        entry.2 = true;
+                        *label = entry.0;
+
+                        label_statement(astatement, enclosing, max_label, code, goto_labels)?;
+                }
+                AStatement::Break(label, break_type) => {
+                        (*label, *break_type) = match enclosing.last() {
+                                Some(&Enclosing::Loop(target)) => (target, BreakType::Loop),
+                                Some(&Enclosing::Switch(target)) => (target, BreakType::Switch),
+                                None => return Err(Error::BreakOutsideLoop),
                         };
-                        annotate(statement, label);
                 }
-                AStatement::While(_, astatement, loop_label) | AStatement::DoWhile(astatement, _, loop_label) => {
+                AStatement::Continue(label) => {
+                        let Some(target) = enclosing.iter().rev().find_map(|f| match f {
+                                Enclosing::Loop(target) => Some(*target),
+                                Enclosing::Switch(_) => None,
+                        }) else {
+                                return Err(if enclosing.is_empty() {
+                                        Error::ContinueOutsideLoop
+                                } else {
+                                        Error::ContinueInSwitch
+                                });
+                        };
+                        *label = target;
+                }
+                AStatement::While(condition, astatement, loop_label)
+                | AStatement::DoWhile(astatement, condition, loop_label) => {
+                        label_expression(condition, enclosing, max_label, code, goto_labels)?;
                         let new_label = new_label(max_label);
                         *loop_label = new_label;
-                        label_statement(astatement, Some(new_label), max_label)?;
+                        enclosing.push(Enclosing::Loop(new_label));
+                        label_statement(astatement, enclosing, max_label, code, goto_labels)?;
+                        enclosing.pop();
                 }
                 AStatement::F(boxed_for, loop_label) => {
+                        match &mut boxed_for.init {
+                                ForInit::D(declaration) => {
+                                        if let Some(init) = &mut declaration.init {
+                                                label_initializer(init, enclosing, max_label, code, goto_labels)?;
+                                        }
+                                }
+                                ForInit::E(expr) => {
+                                        label_optional_expression(expr, enclosing, max_label, code, goto_labels)?
+                                }
+                        }
+                        label_optional_expression(&mut boxed_for.condition, enclosing, max_label, code, goto_labels)?;
+                        label_optional_expression(&mut boxed_for.post, enclosing, max_label, code, goto_labels)?;
                         let new_label = new_label(max_label);
                         *loop_label = new_label;
-                        label_statement(&mut boxed_for.body, Some(new_label), max_label)?;
+                        enclosing.push(Enclosing::Loop(new_label));
+                        label_statement(&mut boxed_for.body, enclosing, max_label, code, goto_labels)?;
+                        enclosing.pop();
                 }
                 AStatement::I(if_statement) => {
-                        label_statement(&mut if_statement.then, curr_label, max_label)?;
-                        if if_statement.Else.is_some() {
-                                label_statement(if_statement.Else.as_mut().unwrap(), curr_label, max_label)?;
+                        label_expression(&mut if_statement.condition, enclosing, max_label, code, goto_labels)?;
+                        label_statement(&mut if_statement.then, enclosing, max_label, code, goto_labels)?;
+                        if let Some(else_statement) = &mut if_statement.Else {
+                                label_statement(else_statement, enclosing, max_label, code, goto_labels)?;
                         }
                 }
                 AStatement::Compound(ABlock(vec)) => {
                         for i in vec {
-                                match i {
-                                        BlockItem::D(_) => {}
-                                        BlockItem::S(astatement) => label_statement(astatement, curr_label, max_label)?,
-                                }
+                                label_block_item(i, enclosing, max_label, code, goto_labels)?;
                         }
                 }
                 AStatement::S(switch) => {
                         let switch_label = new_label(max_label);
-                        let Switch {
-                                cases, default, label, ..
-                        } = switch;
+                        let Switch { value, body, label, .. } = switch;
                         *label = switch_label;
+                        label_expression(value, enclosing, max_label, code, goto_labels)?;
 
-                        for i in cases {
-                                for j in &mut i.1 {
-                                        annotate(j, switch_label);
-                                }
+                        enclosing.push(Enclosing::Switch(switch_label));
+                        label_statement(body, enclosing, max_label, code, goto_labels)?;
+                        enclosing.pop();
+                }
+                AStatement::Case(_, astatement, label) | AStatement::Default(astatement, label) => {
+                        *label = new_label(max_label);
+                        label_statement(astatement, enclosing, max_label, code, goto_labels)?;
+                }
+                AStatement::Return(expr) => label_optional_expression(expr, enclosing, max_label, code, goto_labels)?,
+                AStatement::Expr(expr) => label_expression(expr, enclosing, max_label, code, goto_labels)?,
+                AStatement::Asm(inline_asm) => {
+                        for operand in inline_asm.outputs.iter_mut().chain(&mut inline_asm.inputs) {
+                                label_expression(&mut operand.expr, enclosing, max_label, code, goto_labels)?;
                         }
+                }
+                AStatement::Nul | AStatement::Fallthrough => {}
+        }
 
-                        if let Some(default) = default {
-                                annotate(default, switch_label);
+        Ok(())
+}
+
+fn label_block_item<'a>(
+        block_item: &mut BlockItem,
+        enclosing: &mut Vec<Enclosing>,
+        max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
+) -> Result<(), Error> {
+        match block_item {
+                BlockItem::D(Declaration::V(declaration)) => match &mut declaration.init {
+                        Some(init) => label_initializer(init, enclosing, max_label, code, goto_labels),
+                        None => Ok(()),
+                },
+                BlockItem::D(Declaration::F(_) | Declaration::A(_) | Declaration::T(_)) => Ok(()),
+                BlockItem::S(astatement) => label_statement(astatement, enclosing, max_label, code, goto_labels),
+        }
+}
+
+// statement expressions can hold loops and labels of their own, and break and continue in one go to the loops and
+// switches around the expression. case and default can't, which the type checker sees to
+fn label_expression<'a>(
+        expr: &mut AExpression,
+        enclosing: &mut Vec<Enclosing>,
+        max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
+) -> Result<(), Error> {
+        match expr {
+                AExpression::F(afactor) => label_factor(afactor, enclosing, max_label, code, goto_labels),
+                AExpression::BinOp(_, left, right)
+                | AExpression::Assignment(left, right)
+                | AExpression::OpAssignment(_, left, right)
+                | AExpression::Builtin(Builtin::Expect(left, right)) => {
+                        label_expression(left, enclosing, max_label, code, goto_labels)?;
+                        label_expression(right, enclosing, max_label, code, goto_labels)
+                }
+                AExpression::C(Conditional { condition, True, False }) => {
+                        label_expression(condition, enclosing, max_label, code, goto_labels)?;
+                        label_expression(True, enclosing, max_label, code, goto_labels)?;
+                        label_expression(False, enclosing, max_label, code, goto_labels)
+                }
+                AExpression::FunctionCall(callee, args, _) => {
+                        label_expression(callee, enclosing, max_label, code, goto_labels)?;
+                        for arg in args.iter_mut().flatten() {
+                                label_expression(arg, enclosing, max_label, code, goto_labels)?;
+                        }
+                        Ok(())
+                }
+                AExpression::Cast(_, expr)
+                | AExpression::SizeOf(expr)
+                | AExpression::Builtin(Builtin::Popcount(expr) | Builtin::Clz(expr)) => {
+                        label_expression(expr, enclosing, max_label, code, goto_labels)
+                }
+                AExpression::CompoundLiteral(_, init) => {
+                        label_initializer(init, enclosing, max_label, code, goto_labels)
+                }
+                AExpression::StatementExpr(ABlock(block), value) => {
+                        for i in block {
+                                label_block_item(i, enclosing, max_label, code, goto_labels)?;
+                        }
+                        match value {
+                                Some(value) => label_expression(value, enclosing, max_label, code, goto_labels),
+                                None => Ok(()),
+                        }
+                }
+                AExpression::Builtin(Builtin::Atomic(atomic)) => {
+                        let AtomicBuiltin {
+                                value,
+                                order,
+                                failure_order,
+                                ..
+                        } = &mut **atomic;
+                        for expr in value.iter_mut().chain([order]).chain(failure_order) {
+                                label_expression(expr, enclosing, max_label, code, goto_labels)?;
                         }
+                        Ok(())
                 }
-                AStatement::Return(_) | AStatement::Expr(_) | AStatement::Nul => {}
+                AExpression::Generic(selection) => {
+                        label_expression(&mut selection.controlling, enclosing, max_label, code, goto_labels)?;
+                        for (_, expr) in &mut selection.associations {
+                                label_expression(expr, enclosing, max_label, code, goto_labels)?;
+                        }
+                        Ok(())
+                }
+                AExpression::SizeOfType(_) | AExpression::AlignOf(_) | AExpression::Builtin(_) => Ok(()),
         }
+}
 
-        Ok(())
+fn label_factor<'a>(
+        afactor: &mut AFactor,
+        enclosing: &mut Vec<Enclosing>,
+        max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
+) -> Result<(), Error> {
+        match afactor {
+                AFactor::Unop(_, afactor) => label_factor(afactor, enclosing, max_label, code, goto_labels),
+                AFactor::Expr(expr) => label_expression(expr, enclosing, max_label, code, goto_labels),
+                AFactor::Constant(_) | AFactor::Id(_) | AFactor::Function(_) => Ok(()),
+        }
 }
 
-fn new_label(max_label: &mut ParseLabel) -> ParseLabel {
-        let temp = ParseLabel(max_label.0);
-        max_label.0 += 1;
-        temp
+fn label_optional_expression<'a>(
+        expr: &mut Option<AExpression>,
+        enclosing: &mut Vec<Enclosing>,
+        max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
+) -> Result<(), Error> {
+        match expr {
+                Some(expr) => label_expression(expr, enclosing, max_label, code, goto_labels),
+                None => Ok(()),
+        }
 }
 
-fn annotate(statement: &mut AStatement, curr_label: ParseLabel) {
-        match statement {
-                AStatement::Break(label, _) | AStatement::Continue(label) => {
-                        *label = curr_label;
+fn label_initializer<'a>(
+        init: &mut Initializer,
+        enclosing: &mut Vec<Enclosing>,
+        max_label: &mut ParseLabel,
+        code: &'a [u8],
+        goto_labels: &mut GotoLabels<'a>,
+) -> Result<(), Error> {
+        match init {
+                Initializer::Single(expr) => label_expression(expr, enclosing, max_label, code, goto_labels),
+                Initializer::Compound(list) => {
+                        for (_, init) in list {
+                                label_initializer(init, enclosing, max_label, code, goto_labels)?;
+                        }
+                        Ok(())
                 }
-                _ => {}
         }
 }
+
+fn new_label(max_label: &mut ParseLabel) -> ParseLabel {
+        let temp = ParseLabel(max_label.0);
+        max_label.0 += 1;
+        temp
+}
@@ -1,23 +1,28 @@
-use std::collections::HashMap;
-
 use crate::{
-        parse::nodes::{AExpression, AFactor, AIdentifier, AProgram, AStatement},
-        tactile::Identifier,
+        initialize::Standard,
+        lex::tokentype::AtomicOp,
+        parse::nodes::{AExpression, AFactor, AIdentifier, AProgram, Binop, ConstantError, Unop},
+        tactile::tree::Constant,
         State,
 };
 
+pub mod hir;
 pub mod identifier_resolution;
 pub mod loop_labeling;
+pub mod symbol_table;
 pub mod type_checker;
 
 #[derive(Debug, Clone)]
 pub struct SemanticallyAnalyzed {
-        pub program: AProgram,
+        pub program: HProgram,
+        pub warnings: Vec<Warning>,
 }
 impl State for SemanticallyAnalyzed {}
 
+use hir::HProgram;
 use identifier_resolution::resolve_identifiers;
 use loop_labeling::label_loops;
+use symbol_table::SymbolTable;
 use thiserror::Error;
 use type_checker::{type_check, FuncType, Type};
 
@@ -31,28 +36,194 @@ pub enum Error {
         UndeclaredIdentifier(String, usize),
         #[error("Invalid left side of assignment factor: \n{0:?}")]
         InvalidLValueFactor(AFactor),
-        #[error("Break found outside loop: {0:?}")]
-        BreakOutsideLoop(AStatement),
+        #[error("break statement not within a loop or switch")]
+        BreakOutsideLoop,
+        #[error("continue statement not within a loop")]
+        ContinueOutsideLoop,
+        #[error("continue statement in a switch that isn't within a loop, and only break can leave a switch")]
+        ContinueInSwitch,
         #[error("Incompatible function definitions, one with of {0:?} and other with {1:?}")]
-        IncompatibleFunctionDeclarations(FuncType, FuncType),
+        IncompatibleFunctionDeclarations(&'static FuncType, &'static FuncType),
         #[error("Function is defined more than once")]
         FunctionDefinedMoreThanOnce(AIdentifier),
         #[error("Identifier is being used incorrectly. The identifier {0} is a {1:?} but was called as a {2:?}")]
         WrongType(String, Type, Type),
         #[error("Nested function declaration of {0} starting at {1}")]
         NestedFunctionDeclaration(String, usize),
+        #[error("Constant {0} at {1} is too large to be represented by any type it could have")]
+        InvalidConstant(String, usize),
+        #[error("Cannot cast a {0:?} to a {1:?}")]
+        InvalidCast(Type, Type),
+        #[error("Cannot take the size or alignment of a {0:?}")]
+        InvalidSizeOf(Type),
+        #[error("Function {0} is declared static at block scope, at {1}")]
+        StaticBlockScopeFunction(String, usize),
+        #[error("Identifier {0} is declared with both internal and external linkage")]
+        ConflictingLinkage(String),
+        #[error("Identifier {0} is declared as both a {1:?} and a {2:?}")]
+        ConflictingTypes(String, Type, Type),
+        #[error("Variable {0} is defined more than once")]
+        VariableDefinedMoreThanOnce(String),
+        #[error("Variable {0} has static storage duration, so it has to be initialized with a constant")]
+        NonConstantInitializer(String),
+        #[error("Block scope extern declaration of {0} can't have an initializer")]
+        ExternWithInitializer(String),
+        #[error("{0} is declared _Thread_local in a block, so it has to be static or extern too")]
+        AutomaticThreadLocal(String),
+        #[error("Variable {0} is declared both with and without _Thread_local")]
+        ConflictingThreadLocal(String),
+        #[error("Label {0} is defined more than once, second at {1}")]
+        DuplicateLabel(String, usize),
+        #[error("Label {0} is used at {1} but never defined")]
+        UndefinedLabel(String, usize),
+        #[error("case or default label outside of a switch statement")]
+        CaseOutsideSwitch,
+        #[error("case value is not an integer constant expression: {0}")]
+        NonConstantCase(AExpression),
+        #[error("Duplicate case value {0:?} in switch")]
+        DuplicateCase(Constant),
+        #[error("Switch has more than one default label")]
+        DuplicateDefault,
+        #[error("Too many elements in the initializer for a {0:?}")]
+        ExcessInitializers(Type),
+        #[error("Designator in the initializer for a {0:?}, which has no members or elements")]
+        DesignatorForScalar(Type),
+        #[error("Empty braces can only initialize a {0:?} in C23")]
+        EmptyInitializer(Type),
+        #[error("Too many braces around the initializer for a {0:?}")]
+        NestedScalarBraces(Type),
+        #[error("A {0:?} can't be initialized")]
+        InvalidInitializer(Type),
+        #[error("va_list {0} has to have automatic storage duration")]
+        StaticVaList(String),
+        #[error("Cannot pass a {0:?} as an argument")]
+        InvalidArgument(Type),
+        #[error("{0} is a {1:?}, not a function or a pointer to one")]
+        NotAFunction(String, Type),
+        #[error("Function {0} takes {1} arguments but was called with {2}")]
+        WrongArgumentCount(String, usize, usize),
+        #[error("A void expression was used as a value")]
+        VoidValue,
+        #[error("Variable {0} is declared void")]
+        VoidVariable(String),
+        #[error("{0} is declared with a function type through typeof, which only declares variables")]
+        FunctionVariable(String),
+        #[error("A function can't take a {0:?} parameter")]
+        InvalidParameter(Type),
+        #[error("A function can't return a {0:?}")]
+        InvalidReturnType(Type),
+        #[error("Variable {0} is declared with different qualifiers")]
+        ConflictingQualifiers(String),
+        #[error("Cannot modify {0}, which is const")]
+        AssignToConst(String),
+        #[error("Function {0} returns void but returns a value")]
+        ReturnValueFromVoid(String),
+        #[error("Function {0} returns {1:?} but returns without a value")]
+        ReturnWithoutValue(String, Type),
+        #[error("Cannot apply {0:?} to a {1:?}")]
+        InvalidOperand(Unop, Type),
+        #[error("Cannot apply {0:?} to a {1:?} and a {2:?}")]
+        InvalidOperands(Binop, Type, Type),
+        #[error("Cannot convert a {0:?} to a {1:?} without a cast")]
+        IncompatibleAssignment(Type, Type),
+        #[error("The branches of a conditional have incompatible types {0:?} and {1:?}")]
+        IncompatibleBranches(Type, Type),
+        #[error("Cannot take the address of a {0:?}, only of functions so far")]
+        AddressOfObject(Type),
+        #[error("Cannot assign to function {0}")]
+        AssignToFunction(String),
+        #[error("Cannot switch on a {0:?}, only on integers")]
+        NonIntegerSwitch(Type),
+        #[error(transparent)]
+        InvalidConstantExpression(#[from] ConstantError),
+        #[error("The condition of a static assertion is a {0:?}, not an integer")]
+        NonIntegerStaticAssertion(Type),
+        #[error("Static assertion failed{}", .0.as_ref().map_or(String::new(), |f| format!(": {f}")))]
+        StaticAssertionFailed(Option<String>),
+        #[error("Cannot point to a {0:?}, only to functions so far")]
+        ObjectPointer(Type),
+        #[error("Variable {0} is declared nodiscard, which only applies to functions")]
+        NodiscardObject(String),
+        #[error("Variable {0} is declared noreturn, which only applies to functions")]
+        NoreturnObject(String),
+        #[error("The alignment {0} is not a power of two")]
+        InvalidAlignment(u64),
+        #[error("The alignment of a variable has to be an integer, not a {0:?}")]
+        NonIntegerAlignment(Type),
+        #[error("Automatic variable {0} can't be aligned to {1} bytes, as the stack can only be realigned to 2^31")]
+        UnsupportedAlignment(String, usize),
+        #[error("constexpr variable {0} has to be initialized")]
+        ConstexprWithoutInitializer(String),
+        #[error("constexpr variable {0} can't be volatile or _Atomic")]
+        VolatileConstexpr(String),
+        #[error("The constexpr initializer {0} can't be represented exactly as a {1:?}")]
+        NotRepresentable(i128, Type),
+        #[error("__builtin_offsetof needs a struct, and structs aren't supported, so not a {0:?}")]
+        OffsetofNonStruct(Type),
+        #[error("Invalid escape sequence in {0}")]
+        InvalidEscape(String),
+        #[error("Invalid asm constraint {0}")]
+        InvalidConstraint(String),
+        #[error("Cannot pass a {0:?} to asm")]
+        InvalidAsmOperand(Type),
+        #[error("The asm operand for {0} has to be a variable")]
+        AsmOperandNotLvalue(String),
+        #[error("The asm operand for {0} has to be an integer constant")]
+        NonConstantAsmImmediate(String),
+        #[error("Unknown register {0} in asm clobbers")]
+        UnknownClobber(String),
+        #[error("asm needs {0} registers for its operands, but only {1} aren't clobbered")]
+        TooManyAsmRegisters(usize, usize),
+        #[error("asm template {0} has an invalid operand reference")]
+        InvalidAsmTemplate(String),
+        #[error("Statement expressions are only allowed inside functions")]
+        StatementExprOutsideFunction,
+        #[error("Generic selection has more than one default association")]
+        DuplicateGenericDefault,
+        #[error("Generic selection has more than one association compatible with {0:?}")]
+        DuplicateGenericAssociation(Type),
+        #[error("A generic association can't be for a {0:?}, only for complete object types")]
+        InvalidGenericAssociation(Type),
+        #[error("Generic selection has no association for a {0:?} and no default")]
+        NoGenericMatch(Type),
+        #[error("Cannot operate atomically on a {0:?}")]
+        InvalidAtomicObject(Type),
+        #[error("The __c11_atomic builtins only take _Atomic objects, which {0} isn't")]
+        NonAtomicObject(String),
+        #[error("The memory order has to be an integer, not a {0:?}")]
+        NonIntegerMemoryOrder(Type),
+        #[error("{0} isn't a memory order")]
+        InvalidMemoryOrder(u64),
+        #[error("Memory order {0} can't be used for an atomic {1:?}")]
+        WrongMemoryOrder(u64, AtomicOp),
+        #[error("fallthrough outside of a switch statement")]
+        FallthroughOutsideSwitch,
+        #[error("fallthrough has to be followed by a case or default label")]
+        MisplacedFallthrough,
 }
 
-// boolean to indicate if externally linked or not
-type IdentifierMap<'b> = HashMap<(&'b [u8], usize), (Identifier, bool)>;
+#[derive(Debug, Clone, Error)]
+pub enum Warning {
+        #[error("control can reach the end of non-void function {0}")]
+        MissingReturn(String),
+        #[error("ignoring the result of {0}, which is declared nodiscard{}", .1.as_ref().map_or(String::new(), |f| format!(": {f}")))]
+        DiscardedResult(String, Option<String>),
+        #[error("{0} is deprecated{}", .1.as_ref().map_or(String::new(), |f| format!(": {f}")))]
+        Deprecated(String, Option<String>),
+        #[error("function {0} is declared noreturn but can return")]
+        NoreturnReturns(String),
+}
 
-pub fn analyze<'b, 'a: 'b>(
+// the symbol table is built once, and everything after identifier resolution finds declarations through the
+// symbols it recorded on the program. what comes out is the typed tree, which is all TACTILE needs
+pub fn analyze(
         mut program: AProgram,
-        code: &'a [u8],
-) -> Result<(IdentifierMap<'b>, SemanticallyAnalyzed, usize), Error> {
-        let identifier_map = resolve_identifiers(code, &program)?;
-        let max_label = label_loops(&mut program)?;
-        let () = type_check(&mut program, code)?;
+        code: &[u8],
+        std: Standard,
+) -> Result<(SymbolTable<'_>, SemanticallyAnalyzed, usize), Error> {
+        let symbol_table = resolve_identifiers(code, &mut program)?;
+        let max_label = label_loops(&mut program, code)?;
+        let analyzed = type_check(&mut program, code, std, symbol_table.count())?;
 
-        Ok((identifier_map, SemanticallyAnalyzed { program }, max_label))
+        Ok((symbol_table, analyzed, max_label))
 }
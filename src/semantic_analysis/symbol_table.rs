@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::parse::nodes::AIdentifier;
+
+// every declaration gets its own symbol, except that every declaration of something with linkage is of the same
+// function or variable, 6.2.2p2, so they all share one. identifier resolution records it on the AIdentifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(usize);
+
+impl ScopeId {
+        // the root of the tree, 6.2.1p4
+        pub const FILE: ScopeId = ScopeId(0);
+}
+
+#[derive(Debug, Clone)]
+struct Scope<'a> {
+        parent: Option<ScopeId>,
+        names: HashMap<&'a [u8], SymbolId>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+        // the first declaration of it
+        pub declared: AIdentifier,
+        pub scope: ScopeId,
+        pub linkage: bool,
+}
+
+// the scopes only ever grow, so a sibling block is a scope of its own rather than one that was emptied out
+#[derive(Debug, Clone)]
+pub struct SymbolTable<'a> {
+        scopes: Vec<Scope<'a>>,
+        symbols: Vec<Symbol>,
+        // file scope and block scope declarations with linkage, which are looked up by name wherever they are
+        linked: HashMap<&'a [u8], SymbolId>,
+}
+
+impl Default for SymbolTable<'_> {
+        fn default() -> Self {
+                SymbolTable {
+                        scopes: vec![Scope {
+                                parent: None,
+                                names: HashMap::new(),
+                        }],
+                        symbols: vec![],
+                        linked: HashMap::new(),
+                }
+        }
+}
+
+impl<'a> SymbolTable<'a> {
+        pub fn enter(&mut self, parent: ScopeId) -> ScopeId {
+                self.scopes.push(Scope {
+                        parent: Some(parent),
+                        names: HashMap::new(),
+                });
+                ScopeId(self.scopes.len() - 1)
+        }
+
+        // only what's declared in the scope itself, not in the ones around it
+        pub fn declared_in(&self, scope: ScopeId, name: &[u8]) -> Option<SymbolId> {
+                self.scopes[scope.0].names.get(name).copied()
+        }
+
+        // the innermost declaration in sight, 6.2.1p4
+        pub fn lookup(&self, scope: ScopeId, name: &[u8]) -> Option<SymbolId> {
+                let mut scope = Some(scope);
+                while let Some(ScopeId(idx)) = scope {
+                        if let Some(&symbol) = self.scopes[idx].names.get(name) {
+                                return Some(symbol);
+                        }
+                        scope = self.scopes[idx].parent;
+                }
+                None
+        }
+
+        pub fn declare(&mut self, scope: ScopeId, name: &'a [u8], declared: AIdentifier, linkage: bool) -> SymbolId {
+                let new = SymbolId(self.symbols.len());
+                let symbol = if linkage {
+                        *self.linked.entry(name).or_insert(new)
+                } else {
+                        new
+                };
+                if symbol == new {
+                        self.symbols.push(Symbol {
+                                declared,
+                                scope,
+                                linkage,
+                        });
+                }
+                self.scopes[scope.0].names.insert(name, symbol);
+                symbol
+        }
+
+        pub fn symbol(&self, symbol: SymbolId) -> Symbol {
+                self.symbols[symbol.0]
+        }
+
+        // symbols are numbered from 0, so anything numbered alongside them starts here
+        pub fn count(&self) -> usize {
+                self.symbols.len()
+        }
+}
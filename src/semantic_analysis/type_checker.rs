@@ -1,160 +1,1771 @@
-use std::collections::HashMap;
+use std::{
+        collections::{HashMap, HashSet},
+        hash::Hash,
+        sync::{LazyLock, Mutex},
+};
 
-use crate::parse::nodes::{
-        ABlock, AExpression, AFactor, AIdentifier, AProgram, AStatement, BlockItem, Conditional, Declaration, For,
-        ForInit, FunctionDeclaration, IfStatement, Switch, VariableDeclaration,
+use crate::{
+        initialize::Standard,
+        lex::tokentype::AtomicOp,
+        parse::nodes::{
+                ABlock, AConstant, AExpression, AFactor, AIdentifier, AProgram, AStatement, AStringLiteral, AsmOperand,
+                AtomicBuiltin, Attributes, Binop, BlockItem, Builtin, Conditional, ConstantError, Constraint,
+                Declaration, ExprId, For, ForInit, FunctionDeclaration, IfStatement, Initializer, InlineAsm,
+                OperandKind, ParseLabel, StaticAssert, StorageClass, Switch, TemplatePiece, TypeofOperand, Unop,
+                VariableDeclaration,
+        },
+        tactile::tree::{Constant, Symbol},
+        toasm::{nodes::Register, ASM_REGISTERS},
 };
 
-use super::Error;
+use super::{
+        hir::{
+                self, Category, HAtomic, HBuiltin, HCallee, HExpression, HExpressionKind, HFor, HFunction, HInlineAsm,
+                HProgram, HStatement, HSwitch,
+        },
+        symbol_table::SymbolId,
+        Error, SemanticallyAnalyzed, Warning,
+};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct FuncType {
-        param_count: usize,
+        pub ret: Type,
+        pub params: Vec<Type>,
+        // declared with a trailing "...", so it takes at least params.len() arguments
+        pub variadic: bool,
+        // false for "()", which says nothing about the parameters
+        pub prototyped: bool,
+}
+
+impl FuncType {
+        pub fn intern(self) -> &'static FuncType {
+                static FUNC_TYPES: LazyLock<Mutex<HashSet<&'static FuncType>>> = LazyLock::new(Default::default);
+                intern(&FUNC_TYPES, self)
+        }
+}
+
+// each distinct value is only ever allocated once, however many times it's asked for
+fn intern<T: Eq + Hash>(interned: &Mutex<HashSet<&'static T>>, value: T) -> &'static T {
+        let mut interned = interned.lock().unwrap();
+        if let Some(&value) = interned.get(&value) {
+                return value;
+        }
+        let value = Box::leak(Box::new(value));
+        interned.insert(value);
+        value
+}
+
+// restrict is only allowed on pointers to objects, which don't exist yet, so it's never one of these
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Qualifiers {
+        pub constant: bool,
+        pub volatile: bool,
+        pub atomic: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Type {
+        Char,
+        SChar,
+        UChar,
+        Short,
+        UShort,
         Int,
+        UInt,
+        Long,
+        ULong,
+        LongLong,
+        ULongLong,
+        // _Bool, an unsigned integer that only ever holds 0 or 1
+        Bool,
+        // the type of nullptr, which is a scalar but not an integer, 6.2.5
+        NullPtr,
+        Void,
+        // __builtin_va_list, the SysV x86-64 va_list: gp_offset, fp_offset, overflow_arg_area and reg_save_area
+        VaList,
         // have we type checked the definition of the function yet?
-        Func(FuncType, bool),
+        Func(&'static FuncType, bool),
+        // only to functions so far
+        Pointer(&'static Type),
+        // typeof(expr), which the type checker replaces with the type of expr before anything else sees it
+        Typeof(TypeofOperand),
+}
+
+// size_t on x86-64
+pub const SIZE_T: Type = Type::ULong;
+
+// the strictest alignment of any type on x86-64, which is also what the stack is aligned to at every call
+pub const MAX_ALIGNMENT: usize = 16;
+
+// the most a function's frame can be realigned to, with andq $-alignment, %rsp
+pub const MAX_REALIGNMENT: usize = 1 << 31;
+
+impl Type {
+        pub fn size(&self) -> usize {
+                match self {
+                        Type::Bool | Type::Char | Type::SChar | Type::UChar => 1,
+                        Type::Short | Type::UShort => 2,
+                        Type::Int | Type::UInt => 4,
+                        Type::Long
+                        | Type::ULong
+                        | Type::LongLong
+                        | Type::ULongLong
+                        | Type::Pointer(_)
+                        | Type::NullPtr => 8,
+                        Type::VaList => 24,
+                        Type::Void => unreachable!("void doesn't have a size"),
+                        Type::Func(..) => unreachable!("functions don't have a size"),
+                        Type::Typeof(_) => unreachable!("type checker resolves typeof"),
+                }
+        }
+
+        // every scalar type is aligned to its own size on x86-64
+        pub fn alignment(&self) -> usize {
+                match self {
+                        Type::VaList => 8,
+                        _ => self.size(),
+                }
+        }
+
+        // plain char is signed on x86-64
+        pub fn is_signed(&self) -> bool {
+                matches!(
+                        self,
+                        Type::Char | Type::SChar | Type::Short | Type::Int | Type::Long | Type::LongLong
+                )
+        }
+
+        pub fn is_integer(&self) -> bool {
+                !matches!(
+                        self,
+                        Type::Func(..) | Type::VaList | Type::Void | Type::Pointer(_) | Type::NullPtr | Type::Typeof(_)
+                )
+        }
+
+        pub fn is_scalar(&self) -> bool {
+                self.is_integer() || matches!(self, Type::Pointer(_) | Type::NullPtr)
+        }
+
+        pub fn pointer_to(self) -> Type {
+                static POINTED_TO: LazyLock<Mutex<HashSet<&'static Type>>> = LazyLock::new(Default::default);
+                Type::Pointer(intern(&POINTED_TO, self))
+        }
+
+        pub fn max_value(&self) -> u64 {
+                let bits = self.size() * 8;
+                if *self == Type::Bool {
+                        1
+                } else if self.is_signed() {
+                        (1 << (bits - 1)) - 1
+                } else {
+                        u64::MAX >> (64 - bits)
+                }
+        }
+
+        // whether value is in the range of the type
+        pub fn holds(&self, value: i128) -> bool {
+                let min = if self.is_signed() {
+                        -i128::from(self.max_value()) - 1
+                } else {
+                        0
+                };
+                (min..=i128::from(self.max_value())).contains(&value)
+        }
+
+        fn rank(&self) -> usize {
+                match self {
+                        Type::Bool => 1,
+                        Type::Char | Type::SChar | Type::UChar => 2,
+                        Type::Short | Type::UShort => 3,
+                        Type::Int | Type::UInt => 4,
+                        Type::Long | Type::ULong => 5,
+                        Type::LongLong | Type::ULongLong => 6,
+                        Type::Void
+                        | Type::VaList
+                        | Type::Func(..)
+                        | Type::Pointer(_)
+                        | Type::NullPtr
+                        | Type::Typeof(_) => 0,
+                }
+        }
+
+        fn to_unsigned(self) -> Type {
+                match self {
+                        Type::Char | Type::SChar => Type::UChar,
+                        Type::Short => Type::UShort,
+                        Type::Int => Type::UInt,
+                        Type::Long => Type::ULong,
+                        Type::LongLong => Type::ULongLong,
+                        _ => self,
+                }
+        }
+
+        // integer promotions, 6.3.1.1: everything smaller than int fits in an int
+        pub fn promote(self) -> Type {
+                if self.is_integer() && self.rank() < Type::Int.rank() {
+                        Type::Int
+                } else {
+                        self
+                }
+        }
+
+        // usual arithmetic conversions, 6.3.1.8
+        pub fn common(self, other: Type) -> Type {
+                let (left, right) = (self.promote(), other.promote());
+                if left == right {
+                        return left;
+                }
+                if left.is_signed() == right.is_signed() {
+                        return if left.rank() >= right.rank() { left } else { right };
+                }
+                let (signed, unsigned) = if left.is_signed() { (left, right) } else { (right, left) };
+                if unsigned.rank() >= signed.rank() {
+                        unsigned
+                } else if signed.size() > unsigned.size() {
+                        signed
+                } else {
+                        signed.to_unsigned()
+                }
+        }
+}
+
+// what we know about an identifier with linkage on top of its type
+#[derive(Debug, Clone, Copy)]
+struct Linkage {
+        global: bool,
+        // has a declaration with an initializer (or a body) been seen yet?
+        defined: bool,
+        // every declaration of a thread-local variable has to say so, 6.7.1p3
+        thread_local: bool,
+}
+
+type LinkageMap = HashMap<SymbolId, Linkage>;
+
+// what the type checker knows about an identifier in scope. the qualifiers of a variable only matter when it's used as
+// an lvalue, and a constexpr variable keeps its converted initializer, which every use of it is replaced with
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+        c_type: Type,
+        qualifiers: Qualifiers,
+        attributes: Attributes,
+        constexpr: Option<ExprId>,
+}
+
+impl Entry {
+        fn new(c_type: Type, qualifiers: Qualifiers, attributes: Attributes) -> Entry {
+                Entry {
+                        c_type,
+                        qualifiers,
+                        attributes,
+                        constexpr: None,
+                }
+        }
+}
+
+// the entries of the identifiers checked so far, and the variables of the typed tree, which the declarations add to.
+// the standard is here too, as it's the one thing about the program rather than its identifiers that a check needs.
+// the program's kept expressions are checked the first time they're reached, and what they work out to is kept with
+// them. the values of constexpr variables are kept there too
+struct Symbols {
+        entries: HashMap<SymbolId, Entry>,
+        variables: HashMap<SymbolId, Symbol>,
+        std: Standard,
+        exprs: Vec<Option<AExpression>>,
+        typeofs: HashMap<TypeofOperand, (Type, Qualifiers)>,
+        alignments: HashMap<ExprId, usize>,
+}
+
+// the program is checked in order, and the typed tree is built as it goes, so every node has the type the rules here
+// give it. the casts that make conversions explicit go in the parsed program too, which is what constant expressions
+// are evaluated on
+pub fn type_check(
+        program: &mut AProgram,
+        code: &[u8],
+        std: Standard,
+        symbol_count: usize,
+) -> Result<SemanticallyAnalyzed, Error> {
+        let mut symbols = Symbols {
+                entries: HashMap::new(),
+                variables: HashMap::new(),
+                std,
+                exprs: std::mem::take(&mut program.exprs).into_iter().map(Some).collect(),
+                typeofs: HashMap::new(),
+                alignments: HashMap::new(),
+        };
+        let mut linkage = HashMap::new();
+        let mut warnings = vec![];
+        let mut functions = vec![];
+
+        for i in &mut program.declarations {
+                match i {
+                        Declaration::V(variable_declaration) => check_file_scope_variable_declaration(
+                                variable_declaration,
+                                &mut symbols,
+                                &mut warnings,
+                                &mut linkage,
+                                code,
+                        )?,
+                        Declaration::A(static_assert) => check_static_assert(
+                                static_assert,
+                                &mut symbols,
+                                &mut warnings,
+                                &mut linkage,
+                                code,
+                                None,
+                        )?,
+                        Declaration::T(aliased) => {
+                                resolve_type(*aliased, &mut symbols, &mut warnings, &mut linkage, code, None)?;
+                        }
+                        Declaration::F(function_declaration) => {
+                                functions.extend(check_function_declaration(
+                                        function_declaration,
+                                        &mut symbols,
+                                        &mut warnings,
+                                        &mut linkage,
+                                        code,
+                                )?);
+
+                                // main returns 0 when it reaches its closing brace, 5.1.2.2.3. a noreturn function
+                                // mustn't reach it at all, 6.7.12.6p2
+                                let func_name = name(code, function_declaration.name);
+                                if let Some(body) = &function_declaration.body {
+                                        let noreturn = symbols.entries[&function_declaration.name.resolved()]
+                                                .attributes
+                                                .noreturn;
+                                        if block_falls_through(body, &symbols, code) {
+                                                let func_name = String::from_utf8(func_name.to_vec()).unwrap();
+                                                if noreturn {
+                                                        warnings.push(Warning::NoreturnReturns(func_name));
+                                                } else if function_declaration.func_type.ret != Type::Void
+                                                        && func_name != "main"
+                                                {
+                                                        warnings.push(Warning::MissingReturn(func_name));
+                                                }
+                                        }
+                                }
+                        }
+                }
+        }
+
+        Ok(SemanticallyAnalyzed {
+                program: HProgram {
+                        functions,
+                        variables: symbols.variables,
+                        symbol_count,
+                },
+                warnings,
+        })
 }
 
-pub fn type_check(program: &mut AProgram, code: &[u8]) -> Result<(), Error> {
-        let mut symbols = HashMap::new();
+fn check_file_scope_variable_declaration(
+        decl: &mut VariableDeclaration,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+) -> Result<(), Error> {
+        let var_name = name(code, decl.id);
+        let symbol = decl.id.resolved();
+        resolve_declared_type(decl, symbols, warnings, linkage, code, None)?;
+        check_object_type(code, decl.id, decl.var_type, decl.qualifiers)?;
+        check_automatic_storage(code, decl.id, decl.var_type)?;
+        check_object_attributes(code, decl)?;
+
+        let mut global = decl.storage_class != Some(StorageClass::Static);
+        let mut defined = decl.init.is_some();
+        if let Some(previous) = linkage.get(&symbol) {
+                check_redeclared_type(code, decl.id, symbols, decl.var_type, decl.qualifiers)?;
+
+                // extern keeps whatever linkage the earlier declaration gave it
+                if decl.storage_class == Some(StorageClass::Extern) {
+                        global = previous.global;
+                } else if previous.global != global {
+                        return Err(Error::ConflictingLinkage(String::from_utf8(var_name.to_vec()).unwrap()));
+                }
 
-        for i in &program.functions {
-                check_function_declaration(i, &mut symbols, code, 0)?;
+                if previous.defined && defined {
+                        return Err(Error::VariableDefinedMoreThanOnce(
+                                String::from_utf8(var_name.to_vec()).unwrap(),
+                        ));
+                }
+                if previous.thread_local != decl.thread_local {
+                        return Err(Error::ConflictingThreadLocal(
+                                String::from_utf8(var_name.to_vec()).unwrap(),
+                        ));
+                }
+                defined |= previous.defined;
         }
 
+        linkage.insert(
+                symbol,
+                Linkage {
+                        global,
+                        defined,
+                        thread_local: decl.thread_local,
+                },
+        );
+        let attributes = decl
+                .attributes
+                .merge(symbols.entries.get(&symbol).map(|f| f.attributes));
+        check_alignment(decl, attributes, symbols, warnings, linkage, code, None)?;
+        symbols.entries
+                .insert(symbol, Entry::new(decl.var_type, decl.qualifiers, attributes));
+
+        check_static_initializer(decl, symbols, warnings, linkage, code, None)?;
+        hir::file_scope_variable(decl, code, &mut symbols.variables);
         Ok(())
 }
 
-fn check_variable_declaration<'b, 'a: 'b>(
-        decl: &VariableDeclaration,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
+fn check_variable_declaration(
+        decl: &mut VariableDeclaration,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<Option<HStatement>, Error> {
+        let var_name = name(code, decl.id);
+        let symbol = decl.id.resolved();
+        resolve_declared_type(decl, symbols, warnings, linkage, code, function)?;
+        check_object_type(code, decl.id, decl.var_type, decl.qualifiers)?;
+        check_object_attributes(code, decl)?;
+
+        if decl.storage_class.is_some() {
+                check_automatic_storage(code, decl.id, decl.var_type)?;
+        } else if decl.thread_local {
+                return Err(Error::AutomaticThreadLocal(
+                        String::from_utf8(var_name.to_vec()).unwrap(),
+                ));
+        }
+
+        let init = match decl.storage_class {
+                Some(StorageClass::Extern) => {
+                        if decl.init.is_some() {
+                                return Err(Error::ExternWithInitializer(
+                                        String::from_utf8(var_name.to_vec()).unwrap(),
+                                ));
+                        }
+
+                        if let Some(previous) = linkage.get(&symbol) {
+                                if previous.thread_local != decl.thread_local {
+                                        return Err(Error::ConflictingThreadLocal(
+                                                String::from_utf8(var_name.to_vec()).unwrap(),
+                                        ));
+                                }
+                                check_redeclared_type(code, decl.id, symbols, decl.var_type, decl.qualifiers)?;
+                        } else {
+                                linkage.insert(
+                                        symbol,
+                                        Linkage {
+                                                global: true,
+                                                defined: false,
+                                                thread_local: decl.thread_local,
+                                        },
+                                );
+                        }
+                        let entry = symbols.entries.entry(symbol).or_insert(Entry::new(
+                                decl.var_type,
+                                decl.qualifiers,
+                                Attributes::default(),
+                        ));
+                        entry.attributes = decl.attributes.merge(Some(entry.attributes));
+                        let entry = *entry;
+                        check_alignment(decl, entry.attributes, symbols, warnings, linkage, code, function)?;
+                        None
+                }
+                Some(StorageClass::Static) => {
+                        check_alignment(decl, decl.attributes, symbols, warnings, linkage, code, function)?;
+                        symbols.entries
+                                .insert(symbol, Entry::new(decl.var_type, decl.qualifiers, decl.attributes));
+                        check_static_initializer(decl, symbols, warnings, linkage, code, function)?;
+                        None
+                }
+                None => {
+                        check_alignment(decl, decl.attributes, symbols, warnings, linkage, code, function)?;
+                        // we already checked for uniqueness of variable names, so don't bother doing anything again obvi
+                        symbols.entries
+                                .insert(symbol, Entry::new(decl.var_type, decl.qualifiers, decl.attributes));
+
+                        match &mut decl.init {
+                                Some(init) => {
+                                        let init = check_initializer(
+                                                init,
+                                                decl.var_type,
+                                                decl.constexpr,
+                                                symbols,
+                                                warnings,
+                                                linkage,
+                                                code,
+                                                function,
+                                        )?;
+                                        record_constexpr(decl, symbols, code)?;
+                                        Some(init)
+                                }
+                                None => None,
+                        }
+                }
+        };
+
+        Ok(hir::local_variable(decl, init, code, &mut symbols.variables))
+}
+
+// variables with static storage duration are initialized before the program starts, so only constants will do, or
+// the address of a function, which the linker fills in
+fn check_static_initializer(
+        decl: &mut VariableDeclaration,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
 ) -> Result<(), Error> {
-        // we already checked for uniqueness of variable names, so don't bother doing anything again obvi
-        symbols.entry((name(code, decl.id), scope)).insert_entry(Type::Int);
+        if let Some(init) = &mut decl.init {
+                check_initializer(
+                        init,
+                        decl.var_type,
+                        decl.constexpr,
+                        symbols,
+                        warnings,
+                        linkage,
+                        code,
+                        function,
+                )?;
+
+                match init.evaluate_constant(code, decl.var_type) {
+                        Ok(_) => {}
+                        Err(ConstantError::NotConstant(_)) if init.function_address().is_some() => {}
+                        Err(ConstantError::NotConstant(_)) => {
+                                return Err(Error::NonConstantInitializer(
+                                        String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                                ))
+                        }
+                        Err(error) => return Err(error.into()),
+                }
+                record_constexpr(decl, symbols, code)?;
+        }
+
+        Ok(())
+}
 
-        if let Some(expr) = &decl.init {
-                check_expr(expr, symbols, code, scope)?;
+// every use of a constexpr variable is replaced with its value, so it's kept with the variable once it's checked.
+// the variable is const, but it can't be volatile as well, 6.7.1p5
+fn record_constexpr(decl: &VariableDeclaration, symbols: &mut Symbols, code: &[u8]) -> Result<(), Error> {
+        let (true, Some(Initializer::Single(value))) = (decl.constexpr, &decl.init) else {
+                return Ok(());
+        };
+        if decl.qualifiers.volatile || decl.qualifiers.atomic {
+                return Err(Error::VolatileConstexpr(
+                        String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                ));
         }
 
+        if let Some(entry) = symbols.entries.get_mut(&decl.id.resolved()) {
+                symbols.exprs.push(Some(value.clone()));
+                entry.constexpr = Some(ExprId(symbols.exprs.len() - 1));
+        }
         Ok(())
 }
 
-fn check_expr<'b, 'a: 'b>(
-        expr: &AExpression,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
+// nodiscard and noreturn are about calling a function, so they can't be said of an object, 6.7.12.2p1
+fn check_object_attributes(code: &[u8], decl: &VariableDeclaration) -> Result<(), Error> {
+        if decl.attributes.nodiscard.is_some() {
+                return Err(Error::NodiscardObject(
+                        String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                ));
+        }
+        if decl.attributes.noreturn {
+                return Err(Error::NoreturnObject(
+                        String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                ));
+        }
+        if decl.constexpr && decl.init.is_none() {
+                return Err(Error::ConstexprWithoutInitializer(
+                        String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                ));
+        }
+        Ok(())
+}
+
+// GNU aligned can only make an object more strictly aligned than its type, with no alignment given being the
+// strictest there is. the stack is only 16 byte aligned, so the backend realigns the frame of a function with an
+// automatic object that asks for more, which it can do for anything andq's sign extended immediate can mask
+#[allow(clippy::too_many_arguments)]
+fn check_alignment(
+        decl: &mut VariableDeclaration,
+        attributes: Attributes,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
 ) -> Result<(), Error> {
+        let Some(aligned) = attributes.aligned else {
+                return Ok(());
+        };
+        let alignment = match aligned {
+                Some(id) if symbols.alignments.contains_key(&id) => symbols.alignments[&id],
+                Some(id) => {
+                        let mut expr = symbols.exprs[id.0].take().expect("an expression can't contain itself");
+                        let expr_type = check_value(&mut expr, symbols, warnings, linkage, code, function)?.c_type;
+                        if !expr_type.is_integer() {
+                                return Err(Error::NonIntegerAlignment(expr_type));
+                        }
+                        let alignment = expr.evaluate_constant(code)?.as_u64();
+                        symbols.exprs[id.0] = Some(expr);
+                        if !alignment.is_power_of_two() {
+                                return Err(Error::InvalidAlignment(alignment));
+                        }
+                        let alignment = usize::try_from(alignment).map_err(|_| Error::InvalidAlignment(alignment))?;
+                        symbols.alignments.insert(id, alignment);
+                        alignment
+                }
+                None => MAX_ALIGNMENT,
+        };
+
+        if alignment > decl.var_type.alignment() {
+                if function.is_some() && decl.storage_class.is_none() && alignment > MAX_REALIGNMENT {
+                        return Err(Error::UnsupportedAlignment(
+                                String::from_utf8(name(code, decl.id).to_vec()).unwrap(),
+                                alignment,
+                        ));
+                }
+                decl.alignment = Some(alignment);
+        }
+        Ok(())
+}
+
+// typeof in the declared type is worked out before anything looks at it. typeof of a variable takes on the
+// variable's qualifiers too, which add to the ones the declaration has itself
+fn resolve_declared_type(
+        decl: &mut VariableDeclaration,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<(), Error> {
+        let (var_type, qualifiers) = resolve_type(decl.var_type, symbols, warnings, linkage, code, function)?;
+        decl.var_type = var_type;
+        decl.qualifiers.constant |= qualifiers.constant;
+        decl.qualifiers.volatile |= qualifiers.volatile;
+        decl.qualifiers.atomic |= qualifiers.atomic;
+        Ok(())
+}
+
+// the type with every typeof in it replaced by the type of its operand, 6.7.2.5. like with sizeof, a function
+// designator doesn't decay, and only the qualifiers of a plain variable carry over to typeof
+fn resolve_type(
+        t: Type,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<(Type, Qualifiers), Error> {
+        match t {
+                Type::Typeof(operand) if symbols.typeofs.contains_key(&operand) => Ok(symbols.typeofs[&operand]),
+                Type::Typeof(operand) => {
+                        let mut expr = symbols.exprs[operand.expr.0]
+                                .take()
+                                .expect("an expression can't contain itself");
+                        let variable = match expr {
+                                AExpression::F(AFactor::Id(id)) if !operand.unqual => Some(id),
+                                _ => None,
+                        };
+                        let of = match (
+                                check_expr(&mut expr, symbols, warnings, linkage, code, function)?.c_type,
+                                &expr,
+                        ) {
+                                (Type::Pointer(&pointee), AExpression::F(afactor)) if designates_function(afactor) => {
+                                        pointee
+                                }
+                                (of, _) => of,
+                        };
+                        symbols.exprs[operand.expr.0] = Some(expr);
+                        let qualifiers = match variable {
+                                Some(id) => lookup(code, id, symbols)?.qualifiers,
+                                None => Qualifiers::default(),
+                        };
+                        symbols.typeofs.insert(operand, (of, qualifiers));
+                        Ok((of, qualifiers))
+                }
+                Type::Pointer(&pointee) => match resolve_type(pointee, symbols, warnings, linkage, code, function)?.0 {
+                        resolved if resolved == pointee => Ok((t, Qualifiers::default())),
+                        resolved @ Type::Func(..) => Ok((resolved.pointer_to(), Qualifiers::default())),
+                        resolved => Err(Error::ObjectPointer(resolved)),
+                },
+                Type::Func(func_type, defined) => {
+                        let ret = resolve_type(func_type.ret, symbols, warnings, linkage, code, function)?.0;
+                        let mut params = Vec::with_capacity(func_type.params.len());
+                        for &param in &func_type.params {
+                                // a parameter declared as a function is adjusted to a pointer to one, 6.7.6.3p8
+                                match resolve_type(param, symbols, warnings, linkage, code, function)?.0 {
+                                        resolved @ Type::Func(..) => params.push(resolved.pointer_to()),
+                                        resolved => params.push(resolved),
+                                }
+                        }
+                        if ret == func_type.ret && params == func_type.params {
+                                return Ok((t, Qualifiers::default()));
+                        }
+
+                        let resolved = FuncType {
+                                ret,
+                                params,
+                                ..func_type.clone()
+                        };
+                        Ok((Type::Func(resolved.intern(), defined), Qualifiers::default()))
+                }
+                _ => Ok((t, Qualifiers::default())),
+        }
+}
+
+// the condition has to be an integer constant expression, and the program is rejected if it's 0, 6.7.10
+fn check_static_assert(
+        static_assert: &mut StaticAssert,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<(), Error> {
+        let condition_type =
+                check_value(&mut static_assert.condition, symbols, warnings, linkage, code, function)?.c_type;
+        if !condition_type.is_integer() {
+                return Err(Error::NonIntegerStaticAssertion(condition_type));
+        }
+
+        if static_assert.condition.evaluate_constant(code)?.as_u64() == 0 {
+                let message = static_assert.message.map(|f| string_literal(code, f));
+                return Err(Error::StaticAssertionFailed(message));
+        }
+
+        Ok(())
+}
+
+// void is an incomplete type, so nothing can be declared with it, 6.7p7. a function type can only end up here through
+// typeof, which the parser couldn't see through to declare a function. a va_list can't be _Atomic, as nothing but a
+// scalar fits in one instruction
+fn check_object_type(code: &[u8], id: AIdentifier, var_type: Type, qualifiers: Qualifiers) -> Result<(), Error> {
+        match var_type {
+                Type::Void => Err(Error::VoidVariable(String::from_utf8(name(code, id).to_vec()).unwrap())),
+                Type::Func(..) => Err(Error::FunctionVariable(
+                        String::from_utf8(name(code, id).to_vec()).unwrap(),
+                )),
+                _ if qualifiers.atomic && !var_type.is_scalar() => Err(Error::InvalidAtomicObject(var_type)),
+                _ => Ok(()),
+        }
+}
+
+// a va_list is only ever set up by va_start or va_copy, so it can't live in .data or .bss
+fn check_automatic_storage(code: &[u8], id: AIdentifier, var_type: Type) -> Result<(), Error> {
+        if var_type == Type::VaList {
+                return Err(Error::StaticVaList(String::from_utf8(name(code, id).to_vec()).unwrap()));
+        }
+        Ok(())
+}
+
+// every type but va_list is a scalar so far, and a scalar is initialized by a single expression, optionally in braces, 6.7.9.
+// exact is for constexpr objects, whose initializer has to be a constant the target can hold unchanged, 6.7.1p5. an
+// empty list zero initializes, which only C23 allows
+#[allow(clippy::too_many_arguments)]
+fn check_initializer(
+        init: &mut Initializer,
+        target: Type,
+        exact: bool,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HExpression, Error> {
+        if !target.is_scalar() {
+                return Err(Error::InvalidInitializer(target));
+        }
+
+        match init {
+                Initializer::Single(expr) => {
+                        let value = check_value(expr, symbols, warnings, linkage, code, function)?;
+                        if exact {
+                                check_representable(expr, value.c_type, target, code)?;
+                        }
+                        convert_by_assignment(expr, value, target, code)
+                }
+                Initializer::Compound(list) => {
+                        if list.len() > 1 {
+                                return Err(Error::ExcessInitializers(target));
+                        }
+                        let Some((designators, inner)) = list.pop() else {
+                                if symbols.std != Standard::C23 {
+                                        return Err(Error::EmptyInitializer(target));
+                                }
+                                return Ok(HExpression::rvalue(
+                                        HExpressionKind::Constant(Constant::new(0, target)),
+                                        target,
+                                ));
+                        };
+                        if !designators.is_empty() {
+                                return Err(Error::DesignatorForScalar(target));
+                        }
+                        if matches!(inner, Initializer::Compound(_)) {
+                                return Err(Error::NestedScalarBraces(target));
+                        }
+
+                        *init = inner;
+                        check_initializer(init, target, exact, symbols, warnings, linkage, code, function)
+                }
+        }
+}
+
+fn check_representable(expr: &AExpression, from: Type, to: Type, code: &[u8]) -> Result<(), Error> {
+        let value = expr.evaluate_constant(code)?.as_u64();
+        if from.is_integer() && to.is_integer() {
+                let value = if from.is_signed() {
+                        i128::from(value as i64)
+                } else {
+                        i128::from(value)
+                };
+                if !to.holds(value) {
+                        return Err(Error::NotRepresentable(value, to));
+                }
+        }
+        Ok(())
+}
+
+// every declaration of something with linkage has to agree on its type
+fn check_redeclared_type(
+        code: &[u8],
+        aidentifier: AIdentifier,
+        symbols: &Symbols,
+        declared_type: Type,
+        qualifiers: Qualifiers,
+) -> Result<(), Error> {
+        let Some(&Entry {
+                c_type: previous_type,
+                qualifiers: previous_qualifiers,
+                ..
+        }) = symbols.entries.get(&aidentifier.resolved())
+        else {
+                return Ok(());
+        };
+        if previous_qualifiers != qualifiers {
+                return Err(Error::ConflictingQualifiers(
+                        String::from_utf8(name(code, aidentifier).to_vec()).unwrap(),
+                ));
+        }
+
+        match (previous_type, declared_type) {
+                (Type::Func(previous, _), Type::Func(declared, _)) if compatible(previous, declared) => Ok(()),
+                _ if previous_type == declared_type => Ok(()),
+                _ => Err(Error::ConflictingTypes(
+                        String::from_utf8(name(code, aidentifier).to_vec()).unwrap(),
+                        previous_type,
+                        declared_type,
+                )),
+        }
+}
+
+// a declaration with "()" agrees with a prototype as long as calling through either one passes the same arguments,
+// which means no "..." and no parameters that the default argument promotions would change, 6.7.6.3p15
+fn compatible(left: &FuncType, right: &FuncType) -> bool {
+        if left.ret != right.ret {
+                return false;
+        }
+        match (left.prototyped, right.prototyped) {
+                (true, true) => left == right,
+                (false, false) => true,
+                _ => {
+                        let prototype = if left.prototyped { left } else { right };
+                        !prototype.variadic && prototype.params.iter().all(|f| f.promote() == *f)
+                }
+        }
+}
+
+// wraps expr in a cast if value isn't already of type to, so that every conversion is explicit in both trees
+fn convert_to(expr: &mut AExpression, value: HExpression, to: Type) -> HExpression {
+        if value.c_type == to {
+                return value;
+        }
+        let inner = std::mem::replace(expr, AExpression::F(AFactor::Constant(AConstant { start: 0, len: 0 })));
+        *expr = AExpression::Cast(to, Box::new(inner));
+        HExpression::rvalue(HExpressionKind::Convert(Box::new(value)), to)
+}
+
+// assignment, initialization, passing arguments and returning all convert like this, 6.5.16.1: integers convert to
+// each other, but a pointer only takes a compatible pointer or a null pointer constant, and so does nullptr_t
+fn convert_by_assignment(
+        expr: &mut AExpression,
+        value: HExpression,
+        to: Type,
+        code: &[u8],
+) -> Result<HExpression, Error> {
+        let from = value.c_type;
+        let allowed = match (from, to) {
+                _ if from.is_integer() && to.is_integer() => true,
+                // any scalar converts to bool by comparing it against 0, 6.3.1.2
+                (_, Type::Bool) => from.is_scalar(),
+                (Type::Pointer(_), Type::Pointer(_)) => pointers_compatible(from, to),
+                (_, Type::Pointer(_) | Type::NullPtr) => is_null_pointer_constant(expr, from, code),
+                _ => false,
+        };
+        if !allowed {
+                return Err(Error::IncompatibleAssignment(from, to));
+        }
+
+        Ok(convert_to(expr, value, to))
+}
+
+// the type comparisons and conditionals with a pointer operand are done in. the other operand has to be a compatible
+// pointer or a null pointer constant, 6.5.9p2 and 6.5.15p3
+fn common_pointer_type(
+        left: &AExpression,
+        left_type: Type,
+        right: &AExpression,
+        right_type: Type,
+        code: &[u8],
+) -> Option<Type> {
+        match (left_type, right_type) {
+                (Type::Pointer(_), Type::Pointer(_)) => pointers_compatible(left_type, right_type).then_some(left_type),
+                // nullptr is a null pointer constant of its own type, which two of agree on, 6.5.9p2
+                (Type::NullPtr, Type::NullPtr) => Some(Type::NullPtr),
+                (Type::Pointer(_) | Type::NullPtr, _) => {
+                        is_null_pointer_constant(right, right_type, code).then_some(left_type)
+                }
+                (_, Type::Pointer(_) | Type::NullPtr) => {
+                        is_null_pointer_constant(left, left_type, code).then_some(right_type)
+                }
+                _ => None,
+        }
+}
+
+fn pointers_compatible(left: Type, right: Type) -> bool {
+        match (left, right) {
+                (Type::Pointer(Type::Func(left, _)), Type::Pointer(Type::Func(right, _))) => compatible(left, right),
+                _ => left == right,
+        }
+}
+
+// an integer constant expression with the value 0, or anything of type nullptr_t, 6.3.2.3p3
+fn is_null_pointer_constant(expr: &AExpression, expr_type: Type, code: &[u8]) -> bool {
+        expr_type == Type::NullPtr
+                || (expr_type.is_integer() && expr.evaluate_constant(code).is_ok_and(|f| f.as_u64() == 0))
+}
+
+// whether the operand of & or sizeof is a function rather than a pointer to one. dereferencing only applies to
+// function pointers so far, so it always gives a function
+fn designates_function(afactor: &AFactor) -> bool {
+        match afactor {
+                AFactor::Function(_) | AFactor::Unop(Unop::Deref, _) => true,
+                AFactor::Expr(expr) => matches!(&**expr, AExpression::F(afactor) if designates_function(afactor)),
+                _ => false,
+        }
+}
+
+// the function an expression statement calls directly, if that's all it does
+fn called_function(expr: &AExpression) -> Option<AIdentifier> {
         match expr {
-                AExpression::F(afactor) => check_factor(afactor, symbols, code, scope),
-                AExpression::BinOp(_, left, right)
-                | AExpression::Assignment(left, right)
-                | AExpression::OpAssignment(_, left, right) => {
-                        check_expr(left, symbols, code, scope)?;
-                        check_expr(right, symbols, code, scope)?;
-                        Ok(())
+                AExpression::FunctionCall(callee, ..) => callee.designated_function(),
+                AExpression::F(AFactor::Expr(inner)) => called_function(inner),
+                _ => None,
+        }
+}
+
+// how the function a call goes to is named in errors, as it can be any expression giving a function pointer
+fn callee_name(callee: &AExpression, code: &[u8]) -> String {
+        match callee.designated_function() {
+                Some(id) => String::from_utf8(name(code, id).to_vec()).unwrap(),
+                None => match callee {
+                        AExpression::F(AFactor::Id(id)) => String::from_utf8(name(code, *id).to_vec()).unwrap(),
+                        _ => String::from("called expression"),
+                },
+        }
+}
+
+fn convert_factor_to(factor: &mut AFactor, value: HExpression, to: Type) -> HExpression {
+        if value.c_type == to {
+                return value;
+        }
+        let inner = std::mem::replace(factor, AFactor::Constant(AConstant { start: 0, len: 0 }));
+        *factor = AFactor::Expr(Box::new(AExpression::Cast(to, Box::new(AExpression::F(inner)))));
+        HExpression::rvalue(HExpressionKind::Convert(Box::new(value)), to)
+}
+
+// like check_expr, for the places where the value of the expression is used, which a void expression doesn't have
+fn check_value(
+        expr: &mut AExpression,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HExpression, Error> {
+        let value = check_expr(expr, symbols, warnings, linkage, code, function)?;
+        if value.c_type == Type::Void {
+                return Err(Error::VoidValue);
+        }
+        Ok(value)
+}
+
+fn check_expr(
+        expr: &mut AExpression,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HExpression, Error> {
+        match expr {
+                AExpression::F(afactor) => check_factor(afactor, symbols, warnings, linkage, code, function),
+                AExpression::BinOp(Binop::Comma, left, right) => {
+                        let left = check_expr(left, symbols, warnings, linkage, code, function)?;
+                        let right = check_expr(right, symbols, warnings, linkage, code, function)?;
+                        let c_type = right.c_type;
+                        Ok(binary(Binop::Comma, left, right, c_type))
+                }
+                AExpression::BinOp(binop, left, right) => {
+                        let binop = *binop;
+                        let left_value = check_value(left, symbols, warnings, linkage, code, function)?;
+                        let right_value = check_value(right, symbols, warnings, linkage, code, function)?;
+                        let (left_type, right_type) = (left_value.c_type, right_value.c_type);
+
+                        match binop {
+                                Binop::LogicalAnd | Binop::LogicalOr => {
+                                        Ok(binary(binop, left_value, right_value, Type::Int))
+                                }
+                                Binop::EqualTo | Binop::NotEqualTo
+                                        if matches!(left_type, Type::Pointer(_) | Type::NullPtr)
+                                                || matches!(right_type, Type::Pointer(_) | Type::NullPtr) =>
+                                {
+                                        let Some(common) =
+                                                common_pointer_type(left, left_type, right, right_type, code)
+                                        else {
+                                                return Err(Error::InvalidOperands(binop, left_type, right_type));
+                                        };
+                                        let left_value = convert_to(left, left_value, common);
+                                        let right_value = convert_to(right, right_value, common);
+                                        Ok(binary(binop, left_value, right_value, Type::Int))
+                                }
+                                // there's no arithmetic on function pointers, and they have no order
+                                _ if !left_type.is_integer() || !right_type.is_integer() => {
+                                        Err(Error::InvalidOperands(binop, left_type, right_type))
+                                }
+                                // both operands are of the type the operation is done in, even for shifts
+                                Binop::LeftShift | Binop::RightShift => {
+                                        let promoted = left_type.promote();
+                                        let left_value = convert_to(left, left_value, promoted);
+                                        let right_value = convert_to(right, right_value, promoted);
+                                        Ok(binary(binop, left_value, right_value, promoted))
+                                }
+                                Binop::EqualTo
+                                | Binop::NotEqualTo
+                                | Binop::LessThan
+                                | Binop::LessThanOrEqual
+                                | Binop::MoreThan
+                                | Binop::MoreThanOrEqual => {
+                                        let common = left_type.common(right_type);
+                                        let left_value = convert_to(left, left_value, common);
+                                        let right_value = convert_to(right, right_value, common);
+                                        Ok(binary(binop, left_value, right_value, Type::Int))
+                                }
+                                _ => {
+                                        let common = left_type.common(right_type);
+                                        let left_value = convert_to(left, left_value, common);
+                                        let right_value = convert_to(right, right_value, common);
+                                        Ok(binary(binop, left_value, right_value, common))
+                                }
+                        }
+                }
+                // an assignment has the value of the left side after it, but isn't an lvalue, 6.5.16p3
+                AExpression::Assignment(left, right) => {
+                        if let AExpression::F(afactor) = &**left {
+                                check_modifiable(afactor, symbols, code)?;
+                        }
+                        let left_value = check_value(left, symbols, warnings, linkage, code, function)?;
+                        let right_value = check_value(right, symbols, warnings, linkage, code, function)?;
+                        let c_type = left_value.c_type;
+                        let right_value = convert_by_assignment(right, right_value, c_type, code)?;
+                        Ok(HExpression::rvalue(
+                                HExpressionKind::Assign(Box::new(left_value), Box::new(right_value)),
+                                c_type,
+                        ))
+                }
+                AExpression::OpAssignment(binop, left, right) => {
+                        if let AExpression::F(afactor) = &**left {
+                                check_modifiable(afactor, symbols, code)?;
+                        }
+                        let left_value = check_value(left, symbols, warnings, linkage, code, function)?;
+                        let right_value = check_value(right, symbols, warnings, linkage, code, function)?;
+                        let (left_type, right_type) = (left_value.c_type, right_value.c_type);
+                        if !left_type.is_integer() || !right_type.is_integer() {
+                                return Err(Error::InvalidOperands(*binop, left_type, right_type));
+                        }
+
+                        // the right side is converted to the type the operation is done in, and tactile converts the
+                        // left side to and from it if it differs
+                        let operation_type = match binop {
+                                Binop::LeftShiftAssign | Binop::RightShiftAssign => left_type.promote(),
+                                _ => left_type.common(right_type),
+                        };
+                        let right_value = convert_to(right, right_value, operation_type);
+                        Ok(HExpression::rvalue(
+                                HExpressionKind::CompoundAssign(*binop, Box::new(left_value), Box::new(right_value)),
+                                left_type,
+                        ))
                 }
                 AExpression::C(Conditional { condition, True, False }) => {
-                        check_expr(condition, symbols, code, scope)?;
-                        check_expr(True, symbols, code, scope)?;
-                        check_expr(False, symbols, code, scope)?;
-                        Ok(())
+                        let condition = check_value(condition, symbols, warnings, linkage, code, function)?;
+                        let true_value = check_expr(True, symbols, warnings, linkage, code, function)?;
+                        let false_value = check_expr(False, symbols, warnings, linkage, code, function)?;
+                        let (true_type, false_type) = (true_value.c_type, false_value.c_type);
+
+                        // both branches can be void, but not just one of them, 6.5.15p3
+                        let common = match (true_type, false_type) {
+                                (Type::Void, Type::Void) => Type::Void,
+                                (Type::Void, _) | (_, Type::Void) => return Err(Error::VoidValue),
+                                (Type::Pointer(_) | Type::NullPtr, _) | (_, Type::Pointer(_) | Type::NullPtr) => {
+                                        common_pointer_type(True, true_type, False, false_type, code)
+                                                .ok_or(Error::IncompatibleBranches(true_type, false_type))?
+                                }
+                                _ => true_type.common(false_type),
+                        };
+                        let true_value = convert_to(True, true_value, common);
+                        let false_value = convert_to(False, false_value, common);
+                        Ok(HExpression::rvalue(
+                                HExpressionKind::Conditional(
+                                        Box::new(condition),
+                                        Box::new(true_value),
+                                        Box::new(false_value),
+                                ),
+                                common,
+                        ))
                 }
-                AExpression::FunctionCall(aidentifier, vec) => {
-                        let name = name(code, *aidentifier);
-                        let param_count = if let Some(params) = vec { params.len() } else { 0 };
+                AExpression::FunctionCall(callee, vec, ret) => {
+                        // a function designator decays like anywhere else, so every call goes through a function
+                        // pointer as far as the types are concerned, 6.5.2.2p1
+                        let callee_value = check_value(callee, symbols, warnings, linkage, code, function)?;
+                        let Type::Pointer(&Type::Func(func_type, _)) = callee_value.c_type else {
+                                return Err(Error::NotAFunction(callee_name(callee, code), callee_value.c_type));
+                        };
 
-                        let Ok(func_type) = symbol_exists(code, *aidentifier, symbols, scope) else {
-                                return Err(Error::UndeclaredIdentifier(
-                                        String::from_utf8(name.to_vec()).unwrap(),
-                                        aidentifier.start,
+                        // nothing is known about the parameters of a function declared with "()"
+                        let arg_count = vec.as_ref().map_or(0, Vec::len);
+                        let param_count = func_type.params.len();
+                        if func_type.prototyped
+                                && (arg_count < param_count || (arg_count > param_count && !func_type.variadic))
+                        {
+                                return Err(Error::WrongArgumentCount(
+                                        callee_name(callee, code),
+                                        param_count,
+                                        arg_count,
                                 ));
-                        };
+                        }
 
-                        match func_type {
-                                Type::Int => Err(Error::WrongType(
-                                        String::from_utf8(name.to_vec()).unwrap(),
-                                        Type::Int,
-                                        Type::Func(FuncType { param_count }, true),
-                                )),
-                                Type::Func(func_type, defined) => {
-                                        if func_type.param_count != param_count {
-                                                return Err(Error::WrongType(
-                                                        String::from_utf8(name.to_vec()).unwrap(),
-                                                        Type::Func(func_type, defined),
-                                                        Type::Func(FuncType { param_count }, true),
-                                                ));
+                        let mut args = Vec::with_capacity(arg_count);
+                        for (idx, arg) in vec.iter_mut().flatten().enumerate() {
+                                let arg_value = check_expr(arg, symbols, warnings, linkage, code, function)?;
+                                if !arg_value.c_type.is_scalar() {
+                                        return Err(Error::InvalidArgument(arg_value.c_type));
+                                }
+                                // arguments without a parameter type, like the ones matching the ..., only get the
+                                // default argument promotions, 6.5.2.2
+                                args.push(match func_type.params.get(idx) {
+                                        Some(&param_type) => convert_by_assignment(arg, arg_value, param_type, code)?,
+                                        None => {
+                                                let promoted = arg_value.c_type.promote();
+                                                convert_to(arg, arg_value, promoted)
                                         }
+                                });
+                        }
 
-                                        if let Some(params) = vec {
-                                                for i in params {
-                                                        check_expr(i, symbols, code, scope)?;
+                        let callee = match callee.designated_function() {
+                                Some(id) => HCallee::Direct(id),
+                                None => HCallee::Indirect(Box::new(callee_value)),
+                        };
+                        *ret = func_type.ret;
+                        Ok(HExpression::rvalue(HExpressionKind::Call(callee, args), func_type.ret))
+                }
+                // a cast is a conversion even when it's to the type the value already has
+                AExpression::Cast(to, aexpression) => {
+                        *to = resolve_type(*to, symbols, warnings, linkage, code, function)?.0;
+                        let value = check_expr(aexpression, symbols, warnings, linkage, code, function)?;
+                        let from = value.c_type;
+                        // anything can be cast to void to throw its value away, 6.5.4p2
+                        if *to != Type::Void {
+                                if !from.is_scalar() || !to.is_scalar() {
+                                        return Err(Error::InvalidCast(from, *to));
+                                }
+                                // nullptr_t only comes from itself, and only goes to bool and pointers, 6.5.4p4
+                                if (*to == Type::NullPtr) != (from == Type::NullPtr)
+                                        && !matches!(to, Type::Bool | Type::Pointer(_))
+                                {
+                                        return Err(Error::InvalidCast(from, *to));
+                                }
+                        }
+                        Ok(HExpression::rvalue(HExpressionKind::Convert(Box::new(value)), *to))
+                }
+                AExpression::SizeOf(aexpression) => {
+                        let of = check_expr(aexpression, symbols, warnings, linkage, code, function)?.c_type;
+                        // the function itself rather than the pointer it decayed to
+                        if let (AExpression::F(afactor), Type::Pointer(&pointee)) = (&**aexpression, of) {
+                                if designates_function(afactor) {
+                                        return Err(Error::InvalidSizeOf(pointee));
+                                }
+                        }
+                        *expr = AExpression::SizeOfType(of);
+                        check_expr(expr, symbols, warnings, linkage, code, function)
+                }
+                AExpression::CompoundLiteral(of, init) => {
+                        *of = resolve_type(*of, symbols, warnings, linkage, code, function)?.0;
+                        let init = check_initializer(init, *of, false, symbols, warnings, linkage, code, function)?;
+                        Ok(HExpression {
+                                kind: HExpressionKind::CompoundLiteral(Box::new(init)),
+                                c_type: *of,
+                                category: Category::Lvalue,
+                        })
+                }
+                // the associations other than the selected one are never evaluated, so they're dropped unchecked.
+                // lvalue conversion only drops qualifiers, which the type of an expression doesn't carry, as function
+                // designators have decayed already, 6.5.1.1p2
+                AExpression::Generic(selection) => {
+                        let controlling_type =
+                                check_expr(&mut selection.controlling, symbols, warnings, linkage, code, function)?
+                                        .c_type;
+                        let mut association_types: Vec<Option<(Type, Qualifiers)>> =
+                                Vec::with_capacity(selection.associations.len());
+                        for (association_type, _) in &selection.associations {
+                                let resolved = match association_type {
+                                        Some((association_type, qualifiers)) => {
+                                                let (association_type, inner) = resolve_type(
+                                                        *association_type,
+                                                        symbols,
+                                                        warnings,
+                                                        linkage,
+                                                        code,
+                                                        function,
+                                                )?;
+                                                if matches!(association_type, Type::Func(..) | Type::Void) {
+                                                        return Err(Error::InvalidGenericAssociation(association_type));
+                                                }
+                                                let qualifiers = Qualifiers {
+                                                        constant: qualifiers.constant | inner.constant,
+                                                        volatile: qualifiers.volatile | inner.volatile,
+                                                        atomic: qualifiers.atomic | inner.atomic,
+                                                };
+                                                if association_types.iter().flatten().any(|&(f, q)| {
+                                                        q == qualifiers && pointers_compatible(f, association_type)
+                                                }) {
+                                                        return Err(Error::DuplicateGenericAssociation(
+                                                                association_type,
+                                                        ));
                                                 }
+                                                Some((association_type, qualifiers))
                                         }
+                                        None if association_types.contains(&None) => {
+                                                return Err(Error::DuplicateGenericDefault)
+                                        }
+                                        None => None,
+                                };
+                                association_types.push(resolved);
+                        }
+
+                        let selected = association_types
+                                .iter()
+                                .position(|f| {
+                                        f.is_some_and(|(f, q)| {
+                                                q == Qualifiers::default() && pointers_compatible(f, controlling_type)
+                                        })
+                                })
+                                .or_else(|| association_types.iter().position(Option::is_none))
+                                .ok_or(Error::NoGenericMatch(controlling_type))?;
+                        *expr = selection.associations.swap_remove(selected).1;
+                        check_expr(expr, symbols, warnings, linkage, code, function)
+                }
+                // the value is at the end of the block. case labels inside it can't belong to a switch outside it, so
+                // they're checked as if there were none
+                AExpression::StatementExpr(block, value) => {
+                        let Some(function) = function else {
+                                return Err(Error::StatementExprOutsideFunction);
+                        };
+                        let mut statements = vec![];
+                        for i in &mut block.0 {
+                                statements
+                                        .extend(check_block_item(i, symbols, warnings, linkage, code, function, None)?);
+                        }
+                        let value = match value {
+                                Some(value) => {
+                                        Some(check_expr(value, symbols, warnings, linkage, code, Some(function))?)
+                                }
+                                None => None,
+                        };
+
+                        let c_type = value.as_ref().map_or(Type::Void, |f| f.c_type);
+                        Ok(HExpression::rvalue(
+                                HExpressionKind::StatementExpr(statements, value.map(Box::new)),
+                                c_type,
+                        ))
+                }
+                AExpression::SizeOfType(of) | AExpression::AlignOf(of) => {
+                        *of = resolve_type(*of, symbols, warnings, linkage, code, function)?.0;
+                        let of = *of;
+                        if matches!(of, Type::Func(..) | Type::Void) {
+                                return Err(Error::InvalidSizeOf(of));
+                        }
+                        let value = match expr {
+                                AExpression::SizeOfType(_) => of.size(),
+                                _ => of.alignment(),
+                        };
+                        Ok(HExpression::rvalue(
+                                HExpressionKind::Constant(Constant::new(value as u64, SIZE_T)),
+                                SIZE_T,
+                        ))
+                }
+                AExpression::Builtin(builtin) => {
+                        if let Builtin::VaArg(_, arg_type) | Builtin::Offsetof(arg_type, _) = builtin {
+                                *arg_type = resolve_type(*arg_type, symbols, warnings, linkage, code, function)?.0;
+                        }
+                        // the GNU builtins are checked as if they were declared "long __builtin_expect(long, long)",
+                        // "int __builtin_popcount(unsigned)" and "int __builtin_clz(unsigned)"
+                        let (ap, other) = match builtin {
+                                Builtin::VaStart(ap) | Builtin::VaArg(ap, _) | Builtin::VaEnd(ap) => (*ap, None),
+                                Builtin::VaCopy(dst, src) => (*dst, Some(*src)),
+                                Builtin::Expect(value, expected) => {
+                                        let value = check_argument(
+                                                value,
+                                                Type::Long,
+                                                symbols,
+                                                warnings,
+                                                linkage,
+                                                code,
+                                                function,
+                                        )?;
+                                        let expected = check_argument(
+                                                expected,
+                                                Type::Long,
+                                                symbols,
+                                                warnings,
+                                                linkage,
+                                                code,
+                                                function,
+                                        )?;
+                                        return Ok(builtin_call(HBuiltin::Expect(value, expected), Type::Long));
+                                }
+                                Builtin::Popcount(value) => {
+                                        let value = check_argument(
+                                                value,
+                                                Type::UInt,
+                                                symbols,
+                                                warnings,
+                                                linkage,
+                                                code,
+                                                function,
+                                        )?;
+                                        return Ok(builtin_call(HBuiltin::Popcount(value), Type::Int));
+                                }
+                                Builtin::Clz(value) => {
+                                        let value = check_argument(
+                                                value,
+                                                Type::UInt,
+                                                symbols,
+                                                warnings,
+                                                linkage,
+                                                code,
+                                                function,
+                                        )?;
+                                        return Ok(builtin_call(HBuiltin::Clz(value), Type::Int));
+                                }
+                                Builtin::Unreachable => return Ok(builtin_call(HBuiltin::Unreachable, Type::Void)),
+                                Builtin::Offsetof(of, _) => return Err(Error::OffsetofNonStruct(*of)),
+                                Builtin::Atomic(atomic) => {
+                                        return check_atomic_builtin(atomic, symbols, warnings, linkage, code, function)
+                                }
+                        };
+                        for id in std::iter::once(ap).chain(other) {
+                                let id_type = symbol_exists(code, id, symbols)?;
+                                if id_type != Type::VaList {
+                                        return Err(Error::WrongType(
+                                                String::from_utf8(name(code, id).to_vec()).unwrap(),
+                                                id_type,
+                                                Type::VaList,
+                                        ));
+                                }
+                        }
 
-                                        Ok(())
+                        match *builtin {
+                                Builtin::VaArg(_, arg_type) if !arg_type.is_scalar() => {
+                                        Err(Error::InvalidArgument(arg_type))
                                 }
+                                Builtin::VaArg(ap, arg_type) => {
+                                        Ok(builtin_call(HBuiltin::VaArg(ap.resolved()), arg_type))
+                                }
+                                Builtin::VaStart(ap) => Ok(builtin_call(HBuiltin::VaStart(ap.resolved()), Type::Void)),
+                                Builtin::VaEnd(ap) => Ok(builtin_call(HBuiltin::VaEnd(ap.resolved()), Type::Void)),
+                                Builtin::VaCopy(dst, src) => Ok(builtin_call(
+                                        HBuiltin::VaCopy(dst.resolved(), src.resolved()),
+                                        Type::Void,
+                                )),
+                                _ => unreachable!("the other builtins are done with already"),
                         }
                 }
         }
 }
 
-fn check_factor<'b, 'a: 'b>(
-        afactor: &AFactor,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
-) -> Result<(), Error> {
+// a binary operation on operands that have been converted already
+fn binary(binop: Binop, left: HExpression, right: HExpression, c_type: Type) -> HExpression {
+        HExpression::rvalue(HExpressionKind::Binary(binop, Box::new(left), Box::new(right)), c_type)
+}
+
+fn builtin_call(builtin: HBuiltin, c_type: Type) -> HExpression {
+        HExpression::rvalue(HExpressionKind::Builtin(builtin), c_type)
+}
+
+// an argument to a builtin that's checked as if it had a prototype, converted to the type of its parameter
+fn check_argument(
+        arg: &mut AExpression,
+        param_type: Type,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<Box<HExpression>, Error> {
+        let value = check_expr(arg, symbols, warnings, linkage, code, function)?;
+        Ok(Box::new(convert_by_assignment(arg, value, param_type, code)?))
+}
+
+// memory_order, 7.17.1p4, numbered the way the __ATOMIC_* macros <stdatomic.h> uses are
+const MEMORY_ORDER_CONSUME: u64 = 1;
+const MEMORY_ORDER_ACQUIRE: u64 = 2;
+const MEMORY_ORDER_RELEASE: u64 = 3;
+const MEMORY_ORDER_ACQ_REL: u64 = 4;
+const MEMORY_ORDER_SEQ_CST: u64 = 5;
+
+// the builtins are checked as if they were declared for the type T of the object, like "T __atomic_load_n(T *, int)",
+// "void __atomic_store_n(T *, T, int)" and "bool __atomic_compare_exchange_n(T *, T *, T, bool, int, int)", 7.17.7
+fn check_atomic_builtin(
+        atomic: &mut AtomicBuiltin,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HExpression, Error> {
+        let order = check_memory_order(&mut atomic.order, symbols, warnings, linkage, code, function)?;
+        let failure_order = match &mut atomic.failure_order {
+                Some(failure_order) => Some(check_memory_order(
+                        failure_order,
+                        symbols,
+                        warnings,
+                        linkage,
+                        code,
+                        function,
+                )?),
+                None => None,
+        };
+        // a load can't release and a store can't acquire, 7.17.7.1p2 and 7.17.7.2p2, and neither can a compare exchange
+        // that fails, as it only loads, 7.17.7.4p2
+        let wrong = match (atomic.op, failure_order) {
+                (AtomicOp::Load, _) => matches!(order, MEMORY_ORDER_RELEASE | MEMORY_ORDER_ACQ_REL),
+                (AtomicOp::Store, _) => {
+                        matches!(
+                                order,
+                                MEMORY_ORDER_CONSUME | MEMORY_ORDER_ACQUIRE | MEMORY_ORDER_ACQ_REL
+                        )
+                }
+                (_, Some(failure_order)) => matches!(failure_order, MEMORY_ORDER_RELEASE | MEMORY_ORDER_ACQ_REL),
+                _ => false,
+        };
+        if wrong {
+                return Err(Error::WrongMemoryOrder(failure_order.unwrap_or(order), atomic.op));
+        }
+        atomic.seq_cst = order == MEMORY_ORDER_SEQ_CST;
+
+        let Some(object) = atomic.object else {
+                return Ok(atomic_call(atomic, None, Type::Void));
+        };
+        let object_name = |id| String::from_utf8(name(code, id).to_vec()).unwrap();
+        let Entry { c_type, qualifiers, .. } = lookup(code, object, symbols)?;
+        let fetch = matches!(atomic.op, AtomicOp::FetchAdd | AtomicOp::FetchSub);
+        if !c_type.is_scalar() || (fetch && (!c_type.is_integer() || c_type == Type::Bool)) {
+                return Err(Error::InvalidAtomicObject(c_type));
+        }
+        if atomic.c11 && !qualifiers.atomic {
+                return Err(Error::NonAtomicObject(object_name(object)));
+        }
+        if atomic.op != AtomicOp::Load && qualifiers.constant {
+                return Err(Error::AssignToConst(object_name(object)));
+        }
+
+        if let Some(expected) = atomic.expected {
+                let Entry {
+                        c_type: expected_type,
+                        qualifiers,
+                        ..
+                } = lookup(code, expected, symbols)?;
+                if expected_type != c_type {
+                        return Err(Error::WrongType(object_name(expected), expected_type, c_type));
+                }
+                if qualifiers.constant {
+                        return Err(Error::AssignToConst(object_name(expected)));
+                }
+        }
+        let value = match &mut atomic.value {
+                Some(value) => {
+                        let checked = check_value(value, symbols, warnings, linkage, code, function)?;
+                        Some(convert_by_assignment(value, checked, c_type, code)?)
+                }
+                None => None,
+        };
+
+        let result_type = match atomic.op {
+                AtomicOp::Store => Type::Void,
+                AtomicOp::CompareExchange | AtomicOp::CompareExchangeWeak => Type::Bool,
+                _ => c_type,
+        };
+        Ok(atomic_call(atomic, value, result_type))
+}
+
+fn atomic_call(atomic: &AtomicBuiltin, value: Option<HExpression>, c_type: Type) -> HExpression {
+        let atomic = HAtomic {
+                op: atomic.op,
+                object: atomic.object.map(AIdentifier::resolved),
+                expected: atomic.expected.map(AIdentifier::resolved),
+                value,
+                seq_cst: atomic.seq_cst,
+        };
+        builtin_call(HBuiltin::Atomic(Box::new(atomic)), c_type)
+}
+
+// a memory order has to be known at compile time, as it decides which instructions are used
+fn check_memory_order(
+        order: &mut AExpression,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<u64, Error> {
+        let order_type = check_value(order, symbols, warnings, linkage, code, function)?.c_type;
+        if !order_type.is_integer() {
+                return Err(Error::NonIntegerMemoryOrder(order_type));
+        }
+        let order = order.evaluate_constant(code)?.as_u64();
+        if order > MEMORY_ORDER_SEQ_CST {
+                return Err(Error::InvalidMemoryOrder(order));
+        }
+        Ok(order)
+}
+
+fn check_factor(
+        afactor: &mut AFactor,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HExpression, Error> {
         match afactor {
-                AFactor::Constant(_) => Ok(()),
-                AFactor::Unop(_, afactor) => check_factor(afactor, symbols, code, scope),
-                AFactor::Expr(aexpression) => check_expr(aexpression, symbols, code, scope),
+                AFactor::Constant(aconstant) => match aconstant.evaluate(code) {
+                        Some((value, constant_type)) => Ok(HExpression::rvalue(
+                                HExpressionKind::Constant(Constant::new(value, constant_type)),
+                                constant_type,
+                        )),
+                        None => Err(Error::InvalidConstant(
+                                String::from_utf8(code[aconstant.start..aconstant.start + aconstant.len].to_vec())
+                                        .unwrap(),
+                                aconstant.start,
+                        )),
+                },
+                AFactor::Unop(unop, afactor) => {
+                        let unop = *unop;
+                        // before the operand is checked, as that replaces a constexpr variable with its value
+                        if matches!(
+                                unop,
+                                Unop::IncrementPre | Unop::IncrementPost | Unop::DecrementPre | Unop::DecrementPost
+                        ) {
+                                check_modifiable(afactor, symbols, code)?;
+                        }
+                        let operand = check_factor(afactor, symbols, warnings, linkage, code, function)?;
+                        let inner_type = operand.c_type;
+                        if inner_type == Type::Void {
+                                return Err(Error::VoidValue);
+                        }
+                        match unop {
+                                Unop::Not => {
+                                        return Ok(HExpression::rvalue(
+                                                HExpressionKind::Unary(unop, Box::new(operand)),
+                                                Type::Int,
+                                        ))
+                                }
+                                // a function designator decays right back to the pointer it came from, so *f and &f
+                                // are both the same function pointer as f
+                                Unop::Deref => {
+                                        return match inner_type {
+                                                Type::Pointer(Type::Func(..)) => Ok(operand),
+                                                _ => Err(Error::InvalidOperand(unop, inner_type)),
+                                        }
+                                }
+                                Unop::AddressOf if designates_function(afactor) => return Ok(operand),
+                                Unop::AddressOf => return Err(Error::AddressOfObject(inner_type)),
+                                _ if !inner_type.is_integer() => return Err(Error::InvalidOperand(unop, inner_type)),
+                                _ => {}
+                        }
+                        match unop {
+                                Unop::IncrementPre | Unop::IncrementPost | Unop::DecrementPre | Unop::DecrementPost => {
+                                        Ok(HExpression::rvalue(
+                                                HExpressionKind::Step(unop, Box::new(operand)),
+                                                inner_type,
+                                        ))
+                                }
+                                _ => {
+                                        let promoted = inner_type.promote();
+                                        let operand = convert_factor_to(afactor, operand, promoted);
+                                        Ok(HExpression::rvalue(
+                                                HExpressionKind::Unary(unop, Box::new(operand)),
+                                                promoted,
+                                        ))
+                                }
+                        }
+                }
+                AFactor::Expr(aexpression) => check_expr(aexpression, symbols, warnings, linkage, code, function),
                 AFactor::Id(aidentifier) => {
-                        let Ok(id_type) = symbol_exists(code, *aidentifier, symbols, scope) else {
+                        let id = *aidentifier;
+                        let Ok(entry) = lookup(code, id, symbols) else {
                                 return Err(Error::UndeclaredIdentifier(
-                                        String::from_utf8(name(code, *aidentifier).to_vec()).unwrap(),
-                                        aidentifier.start,
+                                        String::from_utf8(name(code, id).to_vec()).unwrap(),
+                                        id.start,
                                 ));
                         };
-                        if id_type != Type::Int {
-                                return Err(Error::WrongType(
-                                        String::from_utf8(name(code, *aidentifier).to_vec()).unwrap(),
+                        if let Some(reason) = entry.attributes.deprecated {
+                                warnings.push(Warning::Deprecated(
+                                        String::from_utf8(name(code, id).to_vec()).unwrap(),
+                                        reason.map(|f| string_literal(code, f)),
+                                ));
+                        }
+
+                        let id_type = entry.c_type;
+                        match id_type {
+                                // the value of a constexpr variable is known, so it's used directly. it was checked
+                                // already, so checking it again changes nothing but gives the typed tree its copy
+                                _ if entry.constexpr.is_some() => {
+                                        let value = symbols.exprs[entry.constexpr.unwrap().0].clone();
+                                        *afactor = AFactor::Expr(Box::new(
+                                                value.expect("a constexpr value is never taken"),
+                                        ));
+                                        check_factor(afactor, symbols, warnings, linkage, code, function)
+                                }
+                                // a function designator decays to a pointer to the function, 6.3.2.1p4
+                                Type::Func(..) => {
+                                        *afactor = AFactor::Function(id);
+                                        check_factor(afactor, symbols, warnings, linkage, code, function)
+                                }
+                                _ if !id_type.is_scalar() => Err(Error::WrongType(
+                                        String::from_utf8(name(code, id).to_vec()).unwrap(),
                                         id_type,
                                         Type::Int,
+                                )),
+                                _ => Ok(HExpression {
+                                        kind: HExpressionKind::Var(id.resolved()),
+                                        c_type: id_type,
+                                        category: Category::Lvalue,
+                                }),
+                        }
+                }
+                AFactor::Function(id) => match symbol_exists(code, *id, symbols)? {
+                        Type::Func(func_type, _) => Ok(HExpression::rvalue(
+                                HExpressionKind::FunctionAddress(*id),
+                                Type::Func(func_type, false).pointer_to(),
+                        )),
+                        _ => unreachable!("only identifiers naming functions are marked as functions"),
+                },
+        }
+}
+
+// assignments and increments need a modifiable lvalue, 6.3.2.1p1. identifier resolution already made sure there is an
+// lvalue, and variables are the only ones that can be const
+fn check_modifiable(afactor: &AFactor, symbols: &mut Symbols, code: &[u8]) -> Result<(), Error> {
+        match afactor {
+                AFactor::Function(aidentifier) => Err(Error::AssignToFunction(
+                        String::from_utf8(name(code, *aidentifier).to_vec()).unwrap(),
+                )),
+                AFactor::Id(aidentifier) => {
+                        let Entry { c_type, qualifiers, .. } = lookup(code, *aidentifier, symbols)?;
+                        if let Type::Func(..) = c_type {
+                                return Err(Error::AssignToFunction(
+                                        String::from_utf8(name(code, *aidentifier).to_vec()).unwrap(),
+                                ));
+                        }
+                        if qualifiers.constant {
+                                return Err(Error::AssignToConst(
+                                        String::from_utf8(name(code, *aidentifier).to_vec()).unwrap(),
                                 ));
                         }
                         Ok(())
                 }
+                AFactor::Expr(inner) => match &**inner {
+                        AExpression::F(afactor) => check_modifiable(afactor, symbols, code),
+                        _ => Ok(()),
+                },
+                _ => Ok(()),
         }
 }
 
-fn check_function_declaration<'b, 'a: 'b>(
-        decl: &FunctionDeclaration,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
-) -> Result<(), Error> {
+fn check_function_declaration(
+        decl: &mut FunctionDeclaration,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+) -> Result<Option<HFunction>, Error> {
+        if let (Type::Func(resolved, _), _) = resolve_type(
+                Type::Func(decl.func_type, false),
+                symbols,
+                warnings,
+                linkage,
+                code,
+                None,
+        )? {
+                decl.func_type = resolved;
+        }
         let defined = decl.body.is_some();
+        let mut func_type = decl.func_type;
 
-        let func_type = FuncType {
-                param_count: match &decl.params {
-                        Some(list) => list.len(),
-                        _ => 0,
-                },
-        };
+        if !func_type.ret.is_scalar() && func_type.ret != Type::Void {
+                return Err(Error::InvalidReturnType(func_type.ret));
+        }
+        if let Some(&param_type) = func_type.params.iter().find(|f| !f.is_scalar()) {
+                return Err(Error::InvalidParameter(param_type));
+        }
+
+        let func_name = name(code, decl.name);
+        let symbol = decl.name.resolved();
+        let mut global = decl.storage_class != Some(StorageClass::Static);
+        if let Some(previous) = linkage.get(&symbol) {
+                check_redeclared_type(
+                        code,
+                        decl.name,
+                        symbols,
+                        Type::Func(func_type, defined),
+                        Qualifiers::default(),
+                )?;
+
+                // a function declared without static keeps whatever linkage the earlier declaration gave it
+                if previous.global && !global {
+                        return Err(Error::ConflictingLinkage(
+                                String::from_utf8(func_name.to_vec()).unwrap(),
+                        ));
+                }
+                global = previous.global;
+        }
 
         let mut prev_defined = false;
 
-        if let Ok(Type::Func(entry_func_type, entry_defined)) = symbol_exists(code, decl.name, symbols, scope) {
-                if func_type != entry_func_type {
+        if let Ok(Type::Func(entry_func_type, entry_defined)) = symbol_exists(code, decl.name, symbols) {
+                if !compatible(func_type, entry_func_type) {
                         return Err(Error::IncompatibleFunctionDeclarations(func_type, entry_func_type));
                 }
+                // a prototype says more than "()", so it's the one calls are checked against
+                if !func_type.prototyped {
+                        func_type = entry_func_type;
+                }
 
                 prev_defined = entry_defined;
                 if defined && entry_defined {
@@ -162,146 +1773,694 @@ fn check_function_declaration<'b, 'a: 'b>(
                 }
         }
 
-        symbols.entry((name(code, decl.name), scope))
-                .insert_entry(Type::Func(func_type, prev_defined | defined));
-        symbols.entry((name(code, decl.name), 0))
-                .insert_entry(Type::Func(func_type, prev_defined | defined));
+        linkage.insert(
+                symbol,
+                Linkage {
+                        global,
+                        defined: prev_defined | defined,
+                        thread_local: false,
+                },
+        );
+        let attributes = decl
+                .attributes
+                .merge(symbols.entries.get(&symbol).map(|f| f.attributes));
+        let entry = Entry::new(
+                Type::Func(func_type, prev_defined | defined),
+                Qualifiers::default(),
+                attributes,
+        );
+        symbols.entries.insert(symbol, entry);
+
+        let Some(body) = &mut decl.body else {
+                return Ok(None);
+        };
+        let mut params = Vec::with_capacity(decl.params.len());
+        for (&(param, qualifiers), &param_type) in decl.params.iter().zip(&decl.func_type.params) {
+                let param = param.expect("parser rejects definitions with unnamed parameters");
+                symbols.entries.insert(
+                        param.resolved(),
+                        Entry::new(param_type, qualifiers, Attributes::default()),
+                );
+                params.push(hir::automatic_variable(
+                        param,
+                        param_type,
+                        None,
+                        Qualifiers::default(),
+                        &mut symbols.variables,
+                ));
+        }
+
+        let function = EnclosingFunction {
+                name: decl.name,
+                ret: decl.func_type.ret,
+                noreturn: attributes.noreturn,
+        };
+        let body = check_block(body, symbols, warnings, linkage, code, function, None)?;
 
-        if let Some(body) = &decl.body {
-                if let Some(vec) = &decl.params {
-                        for param in vec {
-                                symbols.entry((name(code, *param), scope + 1)).insert_entry(Type::Int);
-                        }
+        Ok(Some(HFunction {
+                name: decl.name,
+                global,
+                params,
+                variadic: decl.func_type.variadic,
+                ret: decl.func_type.ret,
+                body,
+        }))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_block(
+        block: &mut ABlock,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: EnclosingFunction,
+        mut switch: Option<&mut EnclosingSwitch>,
+) -> Result<Vec<HStatement>, Error> {
+        // a fallthrough has to lead right into the next case of the switch, 6.7.12.5p2. at the end of a block it leads
+        // into whatever follows the block, which is checked where that is
+        for (idx, i) in block.0.iter().enumerate() {
+                if switch.is_some()
+                        && matches!(i, BlockItem::S(astatement) if ends_in_fallthrough(astatement))
+                        && !matches!(
+                                block.0.get(idx + 1),
+                                None | Some(BlockItem::S(AStatement::Case(..) | AStatement::Default(..)))
+                        )
+                {
+                        return Err(Error::MisplacedFallthrough);
                 }
+        }
 
-                check_block(body, symbols, code, scope + 1)?;
+        let mut statements = vec![];
+        for i in &mut block.0 {
+                statements.extend(check_block_item(
+                        i,
+                        symbols,
+                        warnings,
+                        linkage,
+                        code,
+                        function,
+                        switch.as_deref_mut(),
+                )?);
         }
 
-        symbols.retain(|(_, f), _| *f < (scope + 1));
+        Ok(statements)
+}
 
-        Ok(())
+#[allow(clippy::too_many_arguments)]
+fn check_block_item(
+        block_item: &mut BlockItem,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: EnclosingFunction,
+        switch: Option<&mut EnclosingSwitch>,
+) -> Result<Option<HStatement>, Error> {
+        match block_item {
+                BlockItem::D(declaration) => match declaration {
+                        Declaration::V(variable_declaration) => check_variable_declaration(
+                                variable_declaration,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                Some(function),
+                        ),
+                        // a block scope function declaration can't have a body, so it only matters for the type of
+                        // the function
+                        Declaration::F(function_declaration) => {
+                                check_function_declaration(function_declaration, symbols, warnings, linkage, code)?;
+                                Ok(None)
+                        }
+                        Declaration::A(static_assert) => {
+                                check_static_assert(static_assert, symbols, warnings, linkage, code, Some(function))?;
+                                Ok(None)
+                        }
+                        Declaration::T(aliased) => {
+                                resolve_type(*aliased, symbols, warnings, linkage, code, Some(function))?;
+                                Ok(None)
+                        }
+                },
+                BlockItem::S(astatement) => {
+                        check_statement(astatement, symbols, warnings, linkage, code, function, switch).map(Some)
+                }
+        }
 }
 
-fn check_block<'b, 'a: 'b>(
-        block: &ABlock,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
-) -> Result<(), Error> {
-        for i in &block.0 {
-                match i {
-                        BlockItem::D(declaration) => match declaration {
-                                Declaration::V(variable_declaration) => {
-                                        check_variable_declaration(variable_declaration, symbols, code, scope)?
-                                }
-                                Declaration::F(function_declaration) => {
-                                        check_function_declaration(function_declaration, symbols, code, scope)?
-                                }
-                        },
-                        BlockItem::S(astatement) => check_statement(astatement, symbols, code, scope)?,
+// a fallthrough can have labels of its own in front of it
+fn ends_in_fallthrough(astatement: &AStatement) -> bool {
+        match astatement {
+                AStatement::Fallthrough => true,
+                AStatement::Labeled(_, astatement, _)
+                | AStatement::Case(_, astatement, _)
+                | AStatement::Default(astatement, _) => ends_in_fallthrough(astatement),
+                // a block ending in one leads into whatever follows the block
+                AStatement::Compound(block) => {
+                        matches!(block.0.last(), Some(BlockItem::S(astatement)) if ends_in_fallthrough(astatement))
                 }
+                _ => false,
         }
+}
 
-        symbols.retain(|(_, item_scope), _| *item_scope < scope);
+// the function that return statements belong to
+#[derive(Clone, Copy)]
+struct EnclosingFunction {
+        name: AIdentifier,
+        ret: Type,
+        noreturn: bool,
+}
 
-        Ok(())
+// the switch that case and default labels belong to
+struct EnclosingSwitch<'s> {
+        value_type: Type,
+        cases: &'s mut Vec<(Constant, ParseLabel)>,
+        default: &'s mut Option<ParseLabel>,
 }
 
-fn check_statement<'b, 'a: 'b>(
-        astatement: &AStatement,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        code: &'a [u8],
-        scope: usize,
-) -> Result<(), Error> {
-        match astatement {
-                AStatement::Expr(aexpression) | AStatement::Return(aexpression) => {
-                        check_expr(aexpression, symbols, code, scope)?
+#[allow(clippy::too_many_arguments)]
+fn check_statement(
+        astatement: &mut AStatement,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: EnclosingFunction,
+        mut switch: Option<&mut EnclosingSwitch>,
+) -> Result<HStatement, Error> {
+        if function.noreturn && matches!(astatement, AStatement::Return(_)) {
+                warnings.push(Warning::NoreturnReturns(
+                        String::from_utf8(name(code, function.name).to_vec()).unwrap(),
+                ));
+        }
+
+        let statement = match astatement {
+                AStatement::Expr(aexpression) => {
+                        let value = check_expr(aexpression, symbols, warnings, linkage, code, Some(function))?;
+
+                        // casting the call to void is how its result is thrown away on purpose
+                        if let Some(id) = called_function(aexpression) {
+                                if let Some(reason) = lookup(code, id, symbols)?.attributes.nodiscard {
+                                        warnings.push(Warning::DiscardedResult(
+                                                String::from_utf8(name(code, id).to_vec()).unwrap(),
+                                                reason.map(|f| string_literal(code, f)),
+                                        ));
+                                }
+                        }
+                        HStatement::Expr(value)
                 }
-                AStatement::I(IfStatement { condition, then, Else }) => {
-                        check_expr(condition, symbols, code, scope)?;
-                        check_statement(then, symbols, code, scope + 1)?;
-                        if let Some(statement) = Else {
-                                check_statement(statement, symbols, code, scope + 1)?;
+                AStatement::Return(Some(aexpression)) => {
+                        if function.ret == Type::Void {
+                                return Err(Error::ReturnValueFromVoid(
+                                        String::from_utf8(name(code, function.name).to_vec()).unwrap(),
+                                ));
                         }
-                        symbols.retain(|(_, f), _| *f < scope + 1);
+                        let value = check_value(aexpression, symbols, warnings, linkage, code, Some(function))?;
+                        HStatement::Return(Some(convert_by_assignment(aexpression, value, function.ret, code)?))
                 }
-                AStatement::Nul | AStatement::Break(_, _) | AStatement::Continue(_) => (),
-                AStatement::Compound(ablock) => check_block(ablock, symbols, code, scope + 1)?,
-                AStatement::While(aexpression, astatement, _) | AStatement::DoWhile(astatement, aexpression, _) => {
-                        check_expr(aexpression, symbols, code, scope + 1)?;
-                        check_statement(astatement, symbols, code, scope + 2)?;
-
-                        symbols.retain(|(_, f), _| *f < scope + 1);
+                AStatement::Return(None) => {
+                        if function.ret != Type::Void {
+                                return Err(Error::ReturnWithoutValue(
+                                        String::from_utf8(name(code, function.name).to_vec()).unwrap(),
+                                        function.ret,
+                                ));
+                        }
+                        HStatement::Return(None)
+                }
+                AStatement::I(IfStatement { condition, then, Else }) => {
+                        let condition = check_value(condition, symbols, warnings, linkage, code, Some(function))?;
+                        let then = check_statement(
+                                then,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                switch.as_deref_mut(),
+                        )?;
+                        let else_statement = match Else {
+                                Some(statement) => Some(Box::new(check_statement(
+                                        statement,
+                                        symbols,
+                                        warnings,
+                                        linkage,
+                                        code,
+                                        function,
+                                        switch.as_deref_mut(),
+                                )?)),
+                                None => None,
+                        };
+                        HStatement::If(condition, Box::new(then), else_statement)
+                }
+                AStatement::Nul => HStatement::Nul,
+                AStatement::Break(label, break_type) => HStatement::Break(*label, *break_type),
+                AStatement::Continue(label) => HStatement::Continue(*label),
+                AStatement::Goto(_, label) => HStatement::Goto(*label),
+                AStatement::Asm(inline_asm) => HStatement::Asm(check_inline_asm(
+                        inline_asm,
+                        symbols,
+                        warnings,
+                        linkage,
+                        code,
+                        Some(function),
+                )?),
+                AStatement::Fallthrough if switch.is_none() => return Err(Error::FallthroughOutsideSwitch),
+                // fallthrough only tells the reader that leaving out the break was intended
+                AStatement::Fallthrough => HStatement::Nul,
+                AStatement::Labeled(_, astatement, label) => HStatement::Labeled(
+                        *label,
+                        Box::new(check_statement(
+                                astatement,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                switch.as_deref_mut(),
+                        )?),
+                ),
+                AStatement::Compound(ablock) => HStatement::Block(check_block(
+                        ablock,
+                        symbols,
+                        warnings,
+                        linkage,
+                        code,
+                        function,
+                        switch.as_deref_mut(),
+                )?),
+                AStatement::While(aexpression, astatement, label) => {
+                        let condition = check_value(aexpression, symbols, warnings, linkage, code, Some(function))?;
+                        let body = check_statement(
+                                astatement,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                switch.as_deref_mut(),
+                        )?;
+                        HStatement::While(condition, Box::new(body), *label)
                 }
-                AStatement::F(For, _) => {
+                AStatement::DoWhile(astatement, aexpression, label) => {
+                        let condition = check_value(aexpression, symbols, warnings, linkage, code, Some(function))?;
+                        let body = check_statement(
+                                astatement,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                switch.as_deref_mut(),
+                        )?;
+                        HStatement::DoWhile(Box::new(body), condition, *label)
+                }
+                AStatement::F(boxed_for, label) => {
                         let For {
                                 init,
                                 condition,
                                 post,
                                 body,
-                        } = &**For;
-
-                        match init {
-                                ForInit::D(variable_declaration) => {
-                                        check_variable_declaration(variable_declaration, symbols, code, scope + 1)?
-                                }
-                                ForInit::E(Some(expr)) => check_expr(expr, symbols, code, scope + 2)?,
-                                ForInit::E(None) => (),
-                        }
+                        } = &mut **boxed_for;
 
-                        if let Some(condition) = condition {
-                                check_expr(condition, symbols, code, scope + 1)?;
-                        }
-                        if let Some(post) = post {
-                                check_expr(post, symbols, code, scope + 1)?;
-                        }
-
-                        check_statement(body, symbols, code, scope + 2)?;
+                        let init = match init {
+                                ForInit::D(variable_declaration) => check_variable_declaration(
+                                        variable_declaration,
+                                        symbols,
+                                        warnings,
+                                        linkage,
+                                        code,
+                                        Some(function),
+                                )?,
+                                ForInit::E(Some(expr)) => Some(HStatement::Expr(check_expr(
+                                        expr,
+                                        symbols,
+                                        warnings,
+                                        linkage,
+                                        code,
+                                        Some(function),
+                                )?)),
+                                ForInit::E(None) => None,
+                        };
+                        let condition = match condition {
+                                Some(condition) => Some(check_value(
+                                        condition,
+                                        symbols,
+                                        warnings,
+                                        linkage,
+                                        code,
+                                        Some(function),
+                                )?),
+                                None => None,
+                        };
+                        let post = match post {
+                                Some(post) => Some(check_expr(post, symbols, warnings, linkage, code, Some(function))?),
+                                None => None,
+                        };
+                        let body = check_statement(
+                                body,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                switch.as_deref_mut(),
+                        )?;
 
-                        symbols.retain(|(_, f), _| *f < scope + 1);
+                        HStatement::For(
+                                Box::new(HFor {
+                                        init,
+                                        condition,
+                                        post,
+                                        body,
+                                }),
+                                *label,
+                        )
                 }
                 AStatement::S(Switch {
                         value,
+                        body,
                         cases,
                         default,
-                        label: _,
+                        label,
                 }) => {
-                        check_expr(value, symbols, code, scope)?;
-                        for (_, i) in cases {
-                                for j in i {
-                                        check_statement(j, symbols, code, scope)?;
-                                }
+                        let checked = check_value(value, symbols, warnings, linkage, code, Some(function))?;
+                        let value_type = checked.c_type;
+                        if !value_type.is_integer() {
+                                return Err(Error::NonIntegerSwitch(value_type));
                         }
-                        if let Some(statement) = default {
-                                check_statement(statement, symbols, code, scope)?;
+                        let checked = convert_to(value, checked, value_type.promote());
+                        // the end of the body leads out of the switch rather than into another case of it
+                        if ends_in_fallthrough(body) {
+                                return Err(Error::MisplacedFallthrough);
                         }
+
+                        let mut enclosing = EnclosingSwitch {
+                                value_type: value_type.promote(),
+                                cases,
+                                default,
+                        };
+                        let body = check_statement(
+                                body,
+                                symbols,
+                                warnings,
+                                linkage,
+                                code,
+                                function,
+                                Some(&mut enclosing),
+                        )?;
+                        HStatement::Switch(HSwitch {
+                                value: checked,
+                                body: Box::new(body),
+                                cases: cases.clone(),
+                                default: *default,
+                                label: *label,
+                        })
+                }
+                AStatement::Case(aexpression, astatement, label) => {
+                        let Some(switch) = switch else {
+                                return Err(Error::CaseOutsideSwitch);
+                        };
+
+                        let case_type =
+                                check_expr(aexpression, symbols, warnings, linkage, code, Some(function))?.c_type;
+                        if !case_type.is_integer() {
+                                return Err(Error::NonConstantCase(aexpression.clone()));
+                        }
+                        let value = Constant::new(aexpression.evaluate_constant(code)?.as_u64(), switch.value_type);
+                        if switch.cases.iter().any(|(f, _)| *f == value) {
+                                return Err(Error::DuplicateCase(value));
+                        }
+                        switch.cases.push((value, *label));
+
+                        // the switch already has the value, so all that's left is the label
+                        let body =
+                                check_statement(astatement, symbols, warnings, linkage, code, function, Some(switch))?;
+                        HStatement::Labeled(*label, Box::new(body))
+                }
+                AStatement::Default(astatement, label) => {
+                        let Some(switch) = switch else {
+                                return Err(Error::CaseOutsideSwitch);
+                        };
+                        if switch.default.replace(*label).is_some() {
+                                return Err(Error::DuplicateDefault);
+                        }
+
+                        let body =
+                                check_statement(astatement, symbols, warnings, linkage, code, function, Some(switch))?;
+                        HStatement::Labeled(*label, Box::new(body))
                 }
         };
-        Ok(())
+        Ok(statement)
 }
 
-fn name<'b, 'a: 'b>(code: &'a [u8], id: AIdentifier) -> &'b [u8] {
-        let AIdentifier { start, len } = id;
-        &code[start..start + len]
+// outputs have to be modifiable lvalues, and so do memory inputs, as the asm is given their address. immediates have
+// to be integer constants. every register operand needs its own register, and a clobbered one can't be used
+fn check_inline_asm(
+        inline_asm: &mut InlineAsm,
+        symbols: &mut Symbols,
+        warnings: &mut Vec<Warning>,
+        linkage: &mut LinkageMap,
+        code: &[u8],
+        function: Option<EnclosingFunction>,
+) -> Result<HInlineAsm, Error> {
+        // the outputs come first, which is how the template numbers them
+        let mut operands = vec![];
+        let outputs = inline_asm.outputs.iter_mut().map(|f| (f, true));
+        let inputs = inline_asm.inputs.iter_mut().map(|f| (f, false));
+        for (AsmOperand { text, constraint, expr }, output) in outputs.chain(inputs) {
+                *constraint = parse_constraint(&string_value(code, *text)?)
+                        .filter(|f| (f.written == output) && !(output && f.kind == OperandKind::Immediate))
+                        .ok_or_else(|| Error::InvalidConstraint(string_literal(code, *text)))?;
+
+                if let (true, AExpression::F(afactor)) = (output, &*expr) {
+                        check_modifiable(afactor, symbols, code)?;
+                }
+                let value = check_value(expr, symbols, warnings, linkage, code, function)?;
+                let operand_type = value.c_type;
+                if !operand_type.is_scalar() {
+                        return Err(Error::InvalidAsmOperand(operand_type));
+                }
+                if (output || constraint.kind == OperandKind::Memory) && designated_object(expr).is_none() {
+                        return Err(Error::AsmOperandNotLvalue(string_literal(code, *text)));
+                }
+                if constraint.kind == OperandKind::Immediate
+                        && (!operand_type.is_integer() || expr.evaluate_constant(code).is_err())
+                {
+                        return Err(Error::NonConstantAsmImmediate(string_literal(code, *text)));
+                }
+                operands.push((*constraint, value));
+        }
+
+        let mut clobbered = vec![];
+        for &clobber in &inline_asm.clobbers {
+                let value = string_value(code, clobber)?;
+                if !matches!(&value[..], b"memory" | b"cc") && Register::named(&value).is_none() {
+                        return Err(Error::UnknownClobber(string_literal(code, clobber)));
+                }
+                clobbered.push(value);
+        }
+        let available = ASM_REGISTERS
+                .iter()
+                .filter(|&&f| !clobbered.iter().any(|clobber| Register::named(clobber) == Some(f)))
+                .count();
+        // a thread-local variable from another file needs a register for its address, and this counts one that's
+        // defined further down the file too
+        let needed = inline_asm
+                .outputs
+                .iter()
+                .chain(&inline_asm.inputs)
+                .filter(|f| {
+                        f.constraint.kind == OperandKind::Register
+                                || designated_object(&f.expr)
+                                        .and_then(|f| linkage.get(&f.resolved()))
+                                        .is_some_and(|f| f.thread_local && !f.defined)
+                })
+                .count();
+        if needed > available {
+                return Err(Error::TooManyAsmRegisters(needed, available));
+        }
+
+        let template = string_value(code, inline_asm.template)?;
+        let pieces = if inline_asm.basic {
+                vec![TemplatePiece::Text(template)]
+        } else {
+                parse_template(&template, operands.len())?
+        };
+        Ok(HInlineAsm {
+                pieces,
+                operands,
+                clobbered,
+        })
 }
 
-fn symbol_exists<'b, 'a: 'b>(
-        code: &'a [u8],
-        aidentifier: AIdentifier,
-        symbols: &mut HashMap<(&'b [u8], usize), Type>,
-        scope: usize,
-) -> Result<Type, Error> {
-        let AIdentifier { start, len } = aidentifier;
-        let name = &code[start..start + len];
+// an optional "=" or "+", an optional "&", which changes nothing as operands never share registers, and then the
+// places the operand can be, out of which a register is preferred. "g" is any of them
+fn parse_constraint(text: &[u8]) -> Option<Constraint> {
+        let (read, written, rest) = match text {
+                [b'=', rest @ ..] => (false, true, rest),
+                [b'+', rest @ ..] => (true, true, rest),
+                _ => (true, false, text),
+        };
+        let rest = rest.strip_prefix(b"&").unwrap_or(rest);
+        if rest.is_empty() || !rest.iter().all(|f| matches!(f, b'r' | b'm' | b'i' | b'n' | b'g')) {
+                return None;
+        }
+
+        let kind = if rest.iter().any(|f| matches!(f, b'r' | b'g')) {
+                OperandKind::Register
+        } else if rest.contains(&b'm') {
+                OperandKind::Memory
+        } else {
+                OperandKind::Immediate
+        };
+        Some(Constraint { kind, read, written })
+}
 
-        for i in (0..=scope).rev() {
-                if let Some(&found_type) = symbols.get(&(name, i)) {
-                        return Ok(found_type);
+// "%%" is a "%", and "%N" is operand N, optionally with the size of register to use given by b, w, k or q first
+fn parse_template(template: &[u8], operands: usize) -> Result<Vec<TemplatePiece>, Error> {
+        let mut pieces = vec![];
+        let mut text = vec![];
+        let mut idx = 0;
+        while idx < template.len() {
+                if template[idx] != b'%' {
+                        text.push(template[idx]);
+                        idx += 1;
+                        continue;
                 }
+                idx += 1;
+                if template.get(idx) == Some(&b'%') {
+                        text.push(b'%');
+                        idx += 1;
+                        continue;
+                }
+
+                let modifier = template
+                        .get(idx)
+                        .copied()
+                        .filter(|f| matches!(f, b'b' | b'w' | b'k' | b'q'));
+                idx += usize::from(modifier.is_some());
+                let digits = template[idx..].iter().take_while(|f| f.is_ascii_digit()).count();
+                let operand = std::str::from_utf8(&template[idx..idx + digits])
+                        .ok()
+                        .and_then(|f| f.parse::<usize>().ok())
+                        .filter(|&f| f < operands)
+                        .ok_or_else(|| Error::InvalidAsmTemplate(String::from_utf8_lossy(template).into_owned()))?;
+                idx += digits;
+
+                pieces.push(TemplatePiece::Text(std::mem::take(&mut text)));
+                pieces.push(TemplatePiece::Operand(operand, modifier));
+        }
+        pieces.push(TemplatePiece::Text(text));
+
+        Ok(pieces)
+}
+
+// whether control can reach the end of a block, conservatively, so only code that obviously returns counts
+fn block_falls_through(block: &ABlock, symbols: &Symbols, code: &[u8]) -> bool {
+        match block.0.last() {
+                Some(BlockItem::S(statement)) => falls_through(statement, symbols, code),
+                _ => true,
         }
+}
+
+fn falls_through(statement: &AStatement, symbols: &Symbols, code: &[u8]) -> bool {
+        // a loop without a condition or with a nonzero constant one only ends through a break
+        let endless = |condition: Option<&AExpression>, body: &AStatement, label: ParseLabel| {
+                condition.is_none_or(|f| f.evaluate_constant(code).is_ok_and(|f| f.as_u64() != 0))
+                        && !breaks_out(body, label)
+        };
+
+        match statement {
+                AStatement::Return(_) | AStatement::Goto(..) => false,
+                AStatement::Expr(aexpression) => !never_returns(aexpression, symbols),
+                AStatement::Compound(block) => block_falls_through(block, symbols, code),
+                AStatement::I(IfStatement { then, Else, .. }) => {
+                        falls_through(then, symbols, code)
+                                || Else.as_ref().is_none_or(|f| falls_through(f, symbols, code))
+                }
+                AStatement::Labeled(_, astatement, _)
+                | AStatement::Case(_, astatement, _)
+                | AStatement::Default(astatement, _) => falls_through(astatement, symbols, code),
+                AStatement::While(condition, body, label) | AStatement::DoWhile(body, condition, label) => {
+                        !endless(Some(condition), body, *label)
+                }
+                AStatement::F(boxed_for, label) => !endless(boxed_for.condition.as_ref(), &boxed_for.body, *label),
+                // without a default the value can match no label at all and skip the whole body
+                AStatement::S(switch) => {
+                        switch.default.is_none()
+                                || falls_through(&switch.body, symbols, code)
+                                || breaks_out(&switch.body, switch.label)
+                }
+                _ => true,
+        }
+}
+
+// a call to a noreturn function or __builtin_unreachable ends control flow just like a return does
+fn never_returns(expr: &AExpression, symbols: &Symbols) -> bool {
+        match expr {
+                AExpression::Builtin(Builtin::Unreachable) => true,
+                AExpression::F(AFactor::Expr(inner)) => never_returns(inner, symbols),
+                _ => called_function(expr)
+                        .and_then(|f| symbols.entries.get(&f.resolved()))
+                        .is_some_and(|f| f.attributes.noreturn),
+        }
+}
+
+// whether a break in statement leaves the loop or switch with the given label
+fn breaks_out(statement: &AStatement, label: ParseLabel) -> bool {
+        match statement {
+                AStatement::Break(target, _) => target.0 == label.0,
+                AStatement::Compound(ABlock(vec)) => vec
+                        .iter()
+                        .any(|f| matches!(f, BlockItem::S(astatement) if breaks_out(astatement, label))),
+                AStatement::I(IfStatement { then, Else, .. }) => {
+                        breaks_out(then, label) || Else.as_ref().is_some_and(|f| breaks_out(f, label))
+                }
+                AStatement::Labeled(_, astatement, _)
+                | AStatement::Case(_, astatement, _)
+                | AStatement::Default(astatement, _)
+                | AStatement::While(_, astatement, _)
+                | AStatement::DoWhile(astatement, _, _) => breaks_out(astatement, label),
+                AStatement::F(boxed_for, _) => breaks_out(&boxed_for.body, label),
+                AStatement::S(switch) => breaks_out(&switch.body, label),
+                _ => false,
+        }
+}
+
+// the object an expression designates, as opposed to a value, which can only be a variable so far
+fn designated_object(expr: &AExpression) -> Option<AIdentifier> {
+        match expr {
+                AExpression::F(AFactor::Id(id)) => Some(*id),
+                AExpression::F(AFactor::Expr(inner)) => designated_object(inner),
+                _ => None,
+        }
+}
+
+fn string_value(code: &[u8], literal: AStringLiteral) -> Result<Vec<u8>, Error> {
+        literal.value(code)
+                .ok_or_else(|| Error::InvalidEscape(string_literal(code, literal)))
+}
+
+// the messages of static assertions and attributes are shown as written, quotes and all
+fn string_literal(code: &[u8], AStringLiteral { start, len }: AStringLiteral) -> String {
+        String::from_utf8_lossy(&code[start..start + len]).into_owned()
+}
+
+fn name(code: &[u8], id: AIdentifier) -> &[u8] {
+        let AIdentifier { start, len, .. } = id;
+        &code[start..start + len]
+}
+
+fn symbol_exists(code: &[u8], aidentifier: AIdentifier, symbols: &mut Symbols) -> Result<Type, Error> {
+        lookup(code, aidentifier, symbols).map(|f| f.c_type)
+}
 
-        Err(Error::UndeclaredIdentifier(
-                String::from_utf8(name.to_vec()).unwrap(),
-                start,
-        ))
+// identifier resolution found the declaration, and it's only missing here if it hasn't been checked yet
+fn lookup(code: &[u8], aidentifier: AIdentifier, symbols: &mut Symbols) -> Result<Entry, Error> {
+        symbols.entries.get(&aidentifier.resolved()).copied().ok_or_else(|| {
+                Error::UndeclaredIdentifier(
+                        String::from_utf8(name(code, aidentifier).to_vec()).unwrap(),
+                        aidentifier.start,
+                )
+        })
 }
@@ -1,25 +1,36 @@
 pub mod tree;
-use std::{collections::HashMap, hash::BuildHasher};
+use std::collections::HashMap;
+use tree::AsmStatement;
+use tree::Callee;
 use tree::Constant;
+use tree::FunctionCall;
 use tree::Label;
-use tree::SwitchLabel;
+use tree::Symbol;
 use tree::TACTILEInstruction;
-use tree::TACTILELabel;
 use tree::TACTILELoopLabel;
 use tree::Value;
 
 use crate::{
-        parse::nodes::{
-                ABlock, AExpression, AFactor, AIdentifier, AProgram, AStatement, Binop, BlockItem, BreakType,
-                Conditional, Declaration, For, ForInit, IfStatement, ParseLabel, Switch, Unop, VariableDeclaration,
+        lex::tokentype::AtomicOp,
+        parse::nodes::{AIdentifier, Binop, BreakType, ParseLabel, Unop},
+        semantic_analysis::{
+                hir::{
+                        HAtomic, HBuiltin, HCallee, HExpression, HExpressionKind, HFor, HInlineAsm, HProgram,
+                        HStatement, HSwitch,
+                },
+                symbol_table::SymbolId,
+                type_checker::Type,
+                SemanticallyAnalyzed,
         },
-        semantic_analysis::SemanticallyAnalyzed,
         Program, State,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Identifier(pub usize);
 
+// what a void expression evaluates to. the type checker makes sure nothing ever reads it
+const VOID: Value = Value::Constant(Constant::Int(0));
+
 #[derive(Debug, Clone)]
 pub struct TACTILE {
         pub program: TACTILEProgram,
@@ -41,765 +52,772 @@ fn tactilify_loop_label(label: ParseLabel, max_label: &mut usize) -> TACTILELoop
 #[derive(Debug, Clone)]
 pub struct TACTILEFunctionDefinition {
         pub identifier: AIdentifier,
-        pub params: Option<Vec<AIdentifier>>,
+        pub global: bool,
+        // the variables the parameters are copied into
+        pub params: Vec<Identifier>,
+        pub variadic: bool,
         pub instructions: Vec<TACTILEInstruction>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TACTILEProgram {
         pub functions: Vec<TACTILEFunctionDefinition>,
+        // the type of every variable and temporary, so the backend knows how wide and how signed they are, and where
+        // variables with static storage duration live
+        pub symbols: HashMap<Identifier, Symbol>,
 }
 
-fn emit_tactile_expr<'b, 'a: 'b, S: BuildHasher>(
-        code: &'a [u8],
-        value: AExpression,
+fn emit_tactile_expr(
+        value: HExpression,
         instructions: &mut Vec<TACTILEInstruction>,
         max_id: &mut usize,
         max_label: &mut usize,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        scope: usize,
+        symbols: &mut HashMap<Identifier, Symbol>,
+        labels: &mut HashMap<usize, TACTILELoopLabel>,
 ) -> Value {
-        match value {
-                AExpression::F(AFactor::Constant(n)) => Value::Constant(Constant::A(n)),
-                AExpression::F(AFactor::Unop(unop, afactor)) => match unop {
-                        Unop::Negate | Unop::Complement | Unop::Not => {
-                                let src = emit_tactile_expr(
-                                        code,
-                                        AExpression::F(*afactor),
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                let dst = Value::Var(new_id(max_id));
-                                instructions.push(TACTILEInstruction::Unary(unop, src, dst));
-                                dst
+        let HExpression { kind, c_type, .. } = value;
+        match kind {
+                HExpressionKind::Constant(constant) => Value::Constant(constant),
+                HExpressionKind::Var(symbol) => Value::Var(variable(symbol)),
+                HExpressionKind::FunctionAddress(id) => {
+                        let dst = new_var(c_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::FunctionAddress(id, dst));
+                        dst
+                }
+                HExpressionKind::Convert(expr) => {
+                        let value = emit_tactile_expr(*expr, instructions, max_id, max_label, symbols, labels);
+                        if c_type == Type::Void {
+                                return VOID;
                         }
-                        Unop::IncrementPre | Unop::DecrementPre => {
-                                let left = emit_tactile_expr(
-                                        code,
-                                        AExpression::F(*afactor),
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::Unary(unop, left, left));
-                                left
+                        convert_value(value, c_type, instructions, max_id, symbols)
+                }
+                HExpressionKind::Unary(unop, operand) => {
+                        let src = emit_tactile_expr(*operand, instructions, max_id, max_label, symbols, labels);
+                        let dst = new_var(c_type, max_id, symbols);
+                        // the type checker has already promoted the operand, which is all unary plus does
+                        instructions.push(match unop {
+                                Unop::Plus => TACTILEInstruction::Copy(src, dst),
+                                _ => TACTILEInstruction::Unary(unop, src, dst),
+                        });
+                        dst
+                }
+                HExpressionKind::Step(unop, operand) => {
+                        let object = emit_tactile_expr(*operand, instructions, max_id, max_label, symbols, labels);
+                        let post = matches!(unop, Unop::IncrementPost | Unop::DecrementPost);
+                        if is_atomic(object, symbols) {
+                                let (old, new) =
+                                        emit_atomic_step(unop, object, instructions, max_id, max_label, symbols);
+                                return if post { old } else { new };
                         }
-                        Unop::IncrementPost | Unop::DecrementPost => {
-                                let src = emit_tactile_expr(
-                                        code,
-                                        AExpression::F(*afactor),
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                let dst = Value::Var(new_id(max_id));
-                                instructions.push(TACTILEInstruction::Copy(src, dst));
-                                instructions.push(TACTILEInstruction::Unary(unop, src, src));
-                                dst
+                        if !post {
+                                emit_step(unop, object, instructions, symbols);
+                                return object;
                         }
-                },
-                AExpression::BinOp(binop, left, right) => match binop {
-                        Binop::LogicalOr => {
-                                let false_label = new_label(max_label);
-                                let end_label = new_label(max_label);
-
-                                let v1 = emit_tactile_expr(
-                                        code,
-                                        *left,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::JumpIfNotZero(v1, false_label));
-                                let v2 = emit_tactile_expr(
-                                        code,
-                                        *right,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::JumpIfNotZero(v2, false_label));
+                        let dst = new_var(c_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Copy(object, dst));
+                        emit_step(unop, object, instructions, symbols);
+                        dst
+                }
+                HExpressionKind::Binary(Binop::LogicalOr, left, right) => {
+                        let true_label = new_label(max_label);
+                        let end_label = new_label(max_label);
 
-                                let dst = Value::Var(new_id(max_id));
+                        let v1 = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::JumpIfNotZero(v1, true_label));
+                        let v2 = emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::JumpIfNotZero(v2, true_label));
 
-                                instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::S(1)), dst));
-                                instructions.push(TACTILEInstruction::Jump(end_label));
-                                instructions.push(TACTILEInstruction::L(false_label));
-                                instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::S(0)), dst));
-                                instructions.push(TACTILEInstruction::L(end_label));
+                        let dst = new_var(Type::Int, max_id, symbols);
 
-                                dst
-                        }
-                        Binop::LogicalAnd => {
-                                let false_label = new_label(max_label);
-                                let end_label = new_label(max_label);
+                        instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::Int(0)), dst));
+                        instructions.push(TACTILEInstruction::Jump(end_label));
+                        instructions.push(TACTILEInstruction::L(true_label));
+                        instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::Int(1)), dst));
+                        instructions.push(TACTILEInstruction::L(end_label));
 
-                                let v1 = emit_tactile_expr(
-                                        code,
-                                        *left,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::JumpIfZero(v1, false_label));
-                                let v2 = emit_tactile_expr(
-                                        code,
-                                        *right,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::JumpIfZero(v2, false_label));
+                        dst
+                }
+                HExpressionKind::Binary(Binop::LogicalAnd, left, right) => {
+                        let false_label = new_label(max_label);
+                        let end_label = new_label(max_label);
+
+                        let v1 = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::JumpIfZero(v1, false_label));
+                        let v2 = emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::JumpIfZero(v2, false_label));
 
-                                let dst = Value::Var(new_id(max_id));
+                        let dst = new_var(Type::Int, max_id, symbols);
 
-                                instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::S(1)), dst));
-                                instructions.push(TACTILEInstruction::Jump(end_label));
-                                instructions.push(TACTILEInstruction::L(false_label));
-                                instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::S(0)), dst));
-                                instructions.push(TACTILEInstruction::L(end_label));
+                        instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::Int(1)), dst));
+                        instructions.push(TACTILEInstruction::Jump(end_label));
+                        instructions.push(TACTILEInstruction::L(false_label));
+                        instructions.push(TACTILEInstruction::Copy(Value::Constant(Constant::Int(0)), dst));
+                        instructions.push(TACTILEInstruction::L(end_label));
 
-                                dst
+                        dst
+                }
+                HExpressionKind::Binary(Binop::Comma, left, right) => {
+                        let _ = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels)
+                }
+                HExpressionKind::Binary(binop, left, right) => {
+                        let v1 = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        let v2 = emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels);
+                        let dst = new_var(c_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Binary(binop, v1, v2, dst));
+                        dst
+                }
+                HExpressionKind::Assign(left, right) => {
+                        let left = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        let right = emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::Copy(right, left));
+                        // assigning to an _Atomic object is a seq_cst store, 6.5.16.1
+                        if is_atomic(left, symbols) {
+                                instructions.push(TACTILEInstruction::Fence);
                         }
-                        _ => {
-                                let v1 = emit_tactile_expr(
-                                        code,
-                                        *left,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                let v2 = emit_tactile_expr(
-                                        code,
-                                        *right,
+                        left
+                }
+                HExpressionKind::CompoundAssign(binop, left, right) => {
+                        let operation_type = right.c_type;
+                        let left = emit_tactile_expr(*left, instructions, max_id, max_label, symbols, labels);
+                        let right = emit_tactile_expr(*right, instructions, max_id, max_label, symbols, labels);
+
+                        if is_atomic(left, symbols) {
+                                return emit_atomic_op_assignment(
+                                        binop,
+                                        left,
+                                        right,
                                         instructions,
                                         max_id,
                                         max_label,
-                                        identifier_map,
-                                        scope,
+                                        symbols,
                                 );
-                                let dst = Value::Var(new_id(max_id));
-                                instructions.push(TACTILEInstruction::Binary(binop, v1, v2, dst));
-                                dst
                         }
-                },
-                AExpression::F(AFactor::Expr(expr)) => {
-                        emit_tactile_expr(code, *expr, instructions, max_id, max_label, identifier_map, scope)
-                }
-                AExpression::Assignment(lval, rval) => {
-                        let left =
-                                emit_tactile_expr(code, *lval, instructions, max_id, max_label, identifier_map, scope);
-                        let right =
-                                emit_tactile_expr(code, *rval, instructions, max_id, max_label, identifier_map, scope);
-                        instructions.push(TACTILEInstruction::Copy(right, left));
-                        left
-                }
-                AExpression::F(AFactor::Id(id)) => {
-                        let AIdentifier { start, len } = id;
-                        let name = &code[start..start + len];
-                        let mut potential = identifier_map.keys().filter(|(f, _)| *f == name).peekable();
 
-                        if potential.peek().is_none() {
-                                let entered = identifier_map
-                                        .entry((name, scope))
-                                        .insert_entry((new_id(max_id), false));
-                                return Value::Var(entered.get().0);
+                        if c_type == operation_type {
+                                instructions.push(TACTILEInstruction::Binary(binop, left, right, left));
+                        } else {
+                                let temp = convert_value(left, operation_type, instructions, max_id, symbols);
+                                instructions.push(TACTILEInstruction::Binary(binop, temp, right, temp));
+                                let result = convert_value(temp, c_type, instructions, max_id, symbols);
+                                instructions.push(TACTILEInstruction::Copy(result, left));
                         }
-
-                        let max = potential.max_by_key(|f| f.1).unwrap();
-
-                        Value::Var(identifier_map.get(max).unwrap().0)
-                }
-                AExpression::OpAssignment(binop, left, right) => {
-                        let left =
-                                emit_tactile_expr(code, *left, instructions, max_id, max_label, identifier_map, scope);
-                        let right =
-                                emit_tactile_expr(code, *right, instructions, max_id, max_label, identifier_map, scope);
-
-                        instructions.push(TACTILEInstruction::Binary(binop, left, right, left));
                         left
                 }
-                AExpression::C(Conditional { condition, True, False }) => {
+                HExpressionKind::Conditional(condition, true_branch, false_branch) => {
                         let end_label = new_label(max_label);
                         let else_label = new_label(max_label);
 
-                        let result = Value::Var(new_id(max_id));
-
-                        let c = emit_tactile_expr(
-                                code,
-                                *condition,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope,
-                        );
+                        let c = emit_tactile_expr(*condition, instructions, max_id, max_label, symbols, labels);
                         instructions.push(TACTILEInstruction::JumpIfZero(c, else_label));
 
-                        let val1 =
-                                emit_tactile_expr(code, *True, instructions, max_id, max_label, identifier_map, scope);
-                        instructions.extend_from_slice(&[
-                                TACTILEInstruction::Copy(val1, result),
-                                TACTILEInstruction::Jump(end_label),
-                                TACTILEInstruction::L(else_label),
-                        ]);
+                        // both branches can be void, and then there's no value to copy
+                        let result = (c_type != Type::Void).then(|| new_var(c_type, max_id, symbols));
+                        let val1 = emit_tactile_expr(*true_branch, instructions, max_id, max_label, symbols, labels);
+                        if let Some(result) = result {
+                                instructions.push(TACTILEInstruction::Copy(val1, result));
+                        }
+                        instructions.extend([TACTILEInstruction::Jump(end_label), TACTILEInstruction::L(else_label)]);
 
-                        let val2 =
-                                emit_tactile_expr(code, *False, instructions, max_id, max_label, identifier_map, scope);
-                        instructions.extend_from_slice(&[
-                                TACTILEInstruction::Copy(val2, result),
-                                TACTILEInstruction::L(end_label),
-                        ]);
+                        let val2 = emit_tactile_expr(*false_branch, instructions, max_id, max_label, symbols, labels);
+                        if let Some(result) = result {
+                                instructions.push(TACTILEInstruction::Copy(val2, result));
+                        }
+                        instructions.push(TACTILEInstruction::L(end_label));
 
-                        result
+                        result.unwrap_or(VOID)
+                }
+                HExpressionKind::Call(callee, args) => {
+                        let callee = match callee {
+                                HCallee::Direct(id) => Callee::Direct(id),
+                                HCallee::Indirect(callee) => Callee::Indirect(emit_tactile_expr(
+                                        *callee,
+                                        instructions,
+                                        max_id,
+                                        max_label,
+                                        symbols,
+                                        labels,
+                                )),
+                        };
+                        let args = args
+                                .into_iter()
+                                .map(|arg| emit_tactile_expr(arg, instructions, max_id, max_label, symbols, labels))
+                                .collect();
+                        let dst = (c_type != Type::Void).then(|| new_var(c_type, max_id, symbols));
+                        instructions.push(TACTILEInstruction::F(FunctionCall { callee, args, dst }));
+                        dst.unwrap_or(VOID)
+                }
+                HExpressionKind::CompoundLiteral(init) => {
+                        let var = new_var(c_type, max_id, symbols);
+                        let src = emit_tactile_expr(*init, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::Copy(src, var));
+                        var
+                }
+                // break and continue in the block can go to the loops around the expression
+                HExpressionKind::StatementExpr(block, value) => {
+                        for i in block {
+                                emit_tactile_statement(i, instructions, max_id, max_label, symbols, labels);
+                        }
+                        value.map_or(VOID, |value| {
+                                emit_tactile_expr(*value, instructions, max_id, max_label, symbols, labels)
+                        })
+                }
+                HExpressionKind::Builtin(builtin) => {
+                        let va_list = |ap| Value::Var(variable(ap));
+                        let instruction = match builtin {
+                                HBuiltin::VaStart(ap) => TACTILEInstruction::VaStart(va_list(ap)),
+                                HBuiltin::VaArg(ap) => {
+                                        let dst = new_var(c_type, max_id, symbols);
+                                        let stack_label = new_label(max_label);
+                                        let end_label = new_label(max_label);
+                                        instructions.push(TACTILEInstruction::VaArg(
+                                                va_list(ap),
+                                                dst,
+                                                stack_label,
+                                                end_label,
+                                        ));
+                                        return dst;
+                                }
+                                // nothing to clean up on x86-64
+                                HBuiltin::VaEnd(_) => return VOID,
+                                HBuiltin::VaCopy(dst, src) => TACTILEInstruction::VaCopy(va_list(src), va_list(dst)),
+                                // the expected value is only a hint about which way branches go, which isn't used
+                                HBuiltin::Expect(value, expected) => {
+                                        let value = emit_tactile_expr(
+                                                *value,
+                                                instructions,
+                                                max_id,
+                                                max_label,
+                                                symbols,
+                                                labels,
+                                        );
+                                        emit_tactile_expr(*expected, instructions, max_id, max_label, symbols, labels);
+                                        return value;
+                                }
+                                HBuiltin::Popcount(value) => {
+                                        let src = emit_tactile_expr(
+                                                *value,
+                                                instructions,
+                                                max_id,
+                                                max_label,
+                                                symbols,
+                                                labels,
+                                        );
+                                        let dst = new_var(c_type, max_id, symbols);
+                                        instructions.push(TACTILEInstruction::Popcount(src, dst));
+                                        return dst;
+                                }
+                                HBuiltin::Clz(value) => {
+                                        let src = emit_tactile_expr(
+                                                *value,
+                                                instructions,
+                                                max_id,
+                                                max_label,
+                                                symbols,
+                                                labels,
+                                        );
+                                        let dst = new_var(c_type, max_id, symbols);
+                                        instructions.push(TACTILEInstruction::CountLeadingZeros(src, dst));
+                                        return dst;
+                                }
+                                HBuiltin::Unreachable => TACTILEInstruction::Unreachable,
+                                HBuiltin::Atomic(atomic) => {
+                                        return emit_atomic_builtin(
+                                                *atomic,
+                                                instructions,
+                                                max_id,
+                                                max_label,
+                                                symbols,
+                                                labels,
+                                        )
+                                }
+                        };
+                        instructions.push(instruction);
+                        VOID
                 }
-                AExpression::FunctionCall(aidentifier, vec) => todo!(),
         }
 }
 
-fn tactile_program<'b, 'a: 'b, S: BuildHasher>(
-        program: AProgram,
-        code: &'a [u8],
+// x86-64 loads are already acquire and stores release, so only seq_cst stores and fences need a barrier, and every
+// read-modify-write is a single locked instruction
+fn emit_atomic_builtin(
+        atomic: HAtomic,
+        instructions: &mut Vec<TACTILEInstruction>,
+        max_id: &mut usize,
         max_label: &mut usize,
-        mut identifier_map: HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-) -> TACTILEProgram {
-        let value = program.functions;
-        let mut max_id = 1;
-        let mut loop_labels = vec![];
-        let mut functions = vec![];
-        let scope = 0;
-
-        for i in value {
-                if let Some(body) = i.body {
-                        for j in body.0 {
-                                let mut instructions = vec![];
-                                tactile_block_item(
-                                        j,
-                                        code,
-                                        &mut instructions,
-                                        &mut max_id,
-                                        max_label,
-                                        &mut identifier_map,
-                                        scope,
-                                        &mut loop_labels,
-                                );
-                                instructions.push(TACTILEInstruction::Return(Value::Constant(Constant::S(0))));
-                                functions.push(TACTILEFunctionDefinition {
-                                        identifier: i.name,
-                                        instructions,
-                                        params: todo!(),
-                                });
+        symbols: &mut HashMap<Identifier, Symbol>,
+        labels: &mut HashMap<usize, TACTILELoopLabel>,
+) -> Value {
+        let HAtomic {
+                op,
+                object,
+                expected,
+                value,
+                seq_cst,
+        } = atomic;
+        let object = object.map(|object| Value::Var(variable(object)));
+        let expected = expected.map(|expected| Value::Var(variable(expected)));
+        let value = value.map(|value| emit_tactile_expr(value, instructions, max_id, max_label, symbols, labels));
+
+        let Some(object) = object else {
+                // a signal fence only has to stop the compiler reordering memory accesses, which it never does
+                if op == AtomicOp::ThreadFence && seq_cst {
+                        instructions.push(TACTILEInstruction::Fence);
+                }
+                return VOID;
+        };
+        let object_type = value_type(object, symbols);
+        let value = || value.expect("parser requires a value for everything but loads and fences");
+        match op {
+                AtomicOp::Load => {
+                        let dst = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Copy(object, dst));
+                        dst
+                }
+                AtomicOp::Store => {
+                        instructions.push(TACTILEInstruction::Copy(value(), object));
+                        if seq_cst {
+                                instructions.push(TACTILEInstruction::Fence);
                         }
+                        VOID
+                }
+                AtomicOp::Exchange => {
+                        let dst = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::AtomicExchange(value(), object, dst));
+                        dst
+                }
+                AtomicOp::FetchAdd => {
+                        let dst = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::AtomicFetchAdd(value(), object, dst));
+                        dst
                 }
+                AtomicOp::FetchSub => {
+                        let negated = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Unary(Unop::Negate, value(), negated));
+                        let dst = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::AtomicFetchAdd(negated, object, dst));
+                        dst
+                }
+                // lock cmpxchg never fails spuriously, so the weak one is the same as the strong one
+                AtomicOp::CompareExchange | AtomicOp::CompareExchangeWeak => {
+                        let expected = expected.expect("parser requires an expected value for compare exchange");
+                        let dst = new_var(Type::Bool, max_id, symbols);
+                        instructions.push(TACTILEInstruction::AtomicCompareExchange(
+                                object,
+                                expected,
+                                value(),
+                                dst,
+                        ));
+                        dst
+                }
+                AtomicOp::ThreadFence | AtomicOp::SignalFence => unreachable!("fences don't take an object"),
         }
-
-        TACTILEProgram { functions }
 }
 
-fn tactile_block_item<'b, 'a: 'b, S: BuildHasher>(
-        block_item: BlockItem,
-        code: &'a [u8],
+// a compound assignment to an _Atomic object is a single read-modify-write, 6.5.16.2p3. adding and subtracting is a
+// locked xadd, anything else goes round a compare exchange loop until nothing else has written in between
+fn emit_atomic_op_assignment(
+        binop: Binop,
+        object: Value,
+        right: Value,
         instructions: &mut Vec<TACTILEInstruction>,
         max_id: &mut usize,
         max_label: &mut usize,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        scope: usize,
-        loop_labels: &mut Vec<TACTILELabel>,
-) {
-        match block_item {
-                BlockItem::D(declaration) => match declaration {
-                        Declaration::V(variable_declaration) => {
-                                tactilify_variable_declaration(
-                                        variable_declaration,
-                                        code,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                        }
-                        Declaration::F(function_declaration) => todo!(),
-                },
-                BlockItem::S(astatement) => emit_tactile_statement(
-                        code,
-                        astatement,
-                        instructions,
-                        max_id,
-                        max_label,
-                        identifier_map,
-                        loop_labels,
-                        scope,
-                ),
+        symbols: &mut HashMap<Identifier, Symbol>,
+) -> Value {
+        let object_type = value_type(object, symbols);
+        let operation_type = value_type(right, symbols);
+        if matches!(binop, Binop::AddAssign | Binop::SubtractAssign) && object_type != Type::Bool {
+                // wrapping to the narrower type before or after adding gives the same bits
+                let mut addend = convert_value(right, object_type, instructions, max_id, symbols);
+                if binop == Binop::SubtractAssign {
+                        let negated = new_var(object_type, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Unary(Unop::Negate, addend, negated));
+                        addend = negated;
+                }
+                let old = new_var(object_type, max_id, symbols);
+                instructions.push(TACTILEInstruction::AtomicFetchAdd(addend, object, old));
+                let new = new_var(object_type, max_id, symbols);
+                instructions.push(TACTILEInstruction::Binary(Binop::Add, old, addend, new));
+                return new;
         }
+
+        let (_, new) = emit_atomic_update(
+                object,
+                instructions,
+                max_id,
+                max_label,
+                symbols,
+                |old, instructions, max_id, symbols| {
+                        let temp = new_var(operation_type, max_id, symbols);
+                        let converted = convert_value(old, operation_type, instructions, max_id, symbols);
+                        instructions.push(TACTILEInstruction::Copy(converted, temp));
+                        instructions.push(TACTILEInstruction::Binary(binop, temp, right, temp));
+                        convert_value(temp, object_type, instructions, max_id, symbols)
+                },
+        );
+        new
 }
 
-fn tactilify_variable_declaration<'b, 'a: 'b, S: BuildHasher>(
-        variable_declaration: VariableDeclaration,
-        code: &'a [u8],
+// ++ and -- on an _Atomic object, giving back the value before and after
+fn emit_atomic_step(
+        unop: Unop,
+        object: Value,
         instructions: &mut Vec<TACTILEInstruction>,
         max_id: &mut usize,
         max_label: &mut usize,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        scope: usize,
-) {
-        if let Some(init) = variable_declaration.init {
-                let var = emit_tactile_expr(
-                        code,
-                        AExpression::F(AFactor::Id(variable_declaration.id)),
+        symbols: &mut HashMap<Identifier, Symbol>,
+) -> (Value, Value) {
+        let object_type = value_type(object, symbols);
+        if object_type == Type::Bool {
+                return emit_atomic_update(
+                        object,
                         instructions,
                         max_id,
                         max_label,
-                        identifier_map,
-                        scope,
+                        symbols,
+                        |old, instructions, max_id, symbols| {
+                                let new = new_var(Type::Bool, max_id, symbols);
+                                instructions.push(TACTILEInstruction::Copy(old, new));
+                                emit_step(unop, new, instructions, symbols);
+                                new
+                        },
                 );
-                let expr = emit_tactile_expr(code, init, instructions, max_id, max_label, identifier_map, scope);
-                instructions.push(TACTILEInstruction::Copy(expr, var));
         }
+
+        let step = match unop {
+                Unop::IncrementPre | Unop::IncrementPost => 1,
+                _ => u64::MAX,
+        };
+        let step = Value::Constant(Constant::new(step, object_type));
+        let old = new_var(object_type, max_id, symbols);
+        instructions.push(TACTILEInstruction::AtomicFetchAdd(step, object, old));
+        let new = new_var(object_type, max_id, symbols);
+        instructions.push(TACTILEInstruction::Binary(Binop::Add, old, step, new));
+        (old, new)
 }
 
-fn emit_tactile_statement<'b, 'a: 'b, S: BuildHasher>(
-        code: &'a [u8],
-        value: AStatement,
+// reads the object, works out what to replace it with, and tries again if it changed in the meantime, giving back the
+// value before and after
+fn emit_atomic_update(
+        object: Value,
         instructions: &mut Vec<TACTILEInstruction>,
         max_id: &mut usize,
         max_label: &mut usize,
-        identifier_map: &mut HashMap<(&'b [u8], usize), (Identifier, bool), S>,
-        labels: &mut Vec<TACTILELabel>,
-        scope: usize,
+        symbols: &mut HashMap<Identifier, Symbol>,
+        update: impl Fn(Value, &mut Vec<TACTILEInstruction>, &mut usize, &mut HashMap<Identifier, Symbol>) -> Value,
+) -> (Value, Value) {
+        let object_type = value_type(object, symbols);
+        let old = new_var(object_type, max_id, symbols);
+        instructions.push(TACTILEInstruction::Copy(object, old));
+        // a failed compare exchange has already put what the object holds now in old
+        let retry_label = new_label(max_label);
+        instructions.push(TACTILEInstruction::L(retry_label));
+        let new = update(old, instructions, max_id, symbols);
+        let swapped = new_var(Type::Bool, max_id, symbols);
+        instructions.push(TACTILEInstruction::AtomicCompareExchange(object, old, new, swapped));
+        instructions.push(TACTILEInstruction::JumpIfZero(swapped, retry_label));
+        (old, new)
+}
+
+// emits whatever is needed to get value as a to, folding constants at compile time
+// adding or subtracting 1 from a bool and converting back sets it to 1 or flips it, 6.5.2.4
+fn emit_step(
+        unop: Unop,
+        value: Value,
+        instructions: &mut Vec<TACTILEInstruction>,
+        symbols: &HashMap<Identifier, Symbol>,
 ) {
-        match value {
-                AStatement::Return(aexpression) => {
-                        let val = emit_tactile_expr(
-                                code,
-                                aexpression,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope,
-                        );
-                        instructions.push(TACTILEInstruction::Return(val));
-                }
-                AStatement::Expr(aexpression) => {
-                        let _ = emit_tactile_expr(
-                                code,
-                                aexpression,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope,
-                        );
-                }
-                AStatement::Nul => {}
-                AStatement::I(IfStatement {
-                        condition,
-                        then,
-                        Else: else_statement,
-                }) => {
-                        if else_statement.is_none() {
-                                let end = new_label(max_label);
+        let one = Value::Constant(Constant::UChar(1));
+        instructions.push(match (unop, value_type(value, symbols)) {
+                (Unop::IncrementPre | Unop::IncrementPost, Type::Bool) => TACTILEInstruction::Copy(one, value),
+                (_, Type::Bool) => TACTILEInstruction::Binary(Binop::BitwiseXOr, value, one, value),
+                _ => TACTILEInstruction::Unary(unop, value, value),
+        });
+}
 
-                                let c = emit_tactile_expr(
-                                        code,
-                                        condition,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope,
-                                );
-                                instructions.push(TACTILEInstruction::JumpIfZero(c, end));
+fn convert_value(
+        value: Value,
+        to: Type,
+        instructions: &mut Vec<TACTILEInstruction>,
+        max_id: &mut usize,
+        symbols: &mut HashMap<Identifier, Symbol>,
+) -> Value {
+        let from = value_type(value, symbols);
+        if from == to {
+                return value;
+        }
+        if let Value::Constant(constant) = value {
+                return Value::Constant(Constant::new(constant.as_u64(), to));
+        }
 
+        let dst = new_var(to, max_id, symbols);
+        // anything converts to a bool by comparing it against 0, 6.3.1.2
+        if to == Type::Bool {
+                let zero = Value::Constant(Constant::new(0, from));
+                instructions.push(TACTILEInstruction::Binary(Binop::NotEqualTo, value, zero, dst));
+                return dst;
+        }
+        instructions.push(if to.size() == from.size() {
+                TACTILEInstruction::Copy(value, dst)
+        } else if to.size() < from.size() {
+                TACTILEInstruction::Truncate(value, dst)
+        } else if from.is_signed() {
+                TACTILEInstruction::SignExtend(value, dst)
+        } else {
+                TACTILEInstruction::ZeroExtend(value, dst)
+        });
+        dst
+}
+
+fn tactile_program(program: HProgram, max_label: &mut usize) -> TACTILEProgram {
+        let HProgram {
+                functions,
+                variables,
+                symbol_count,
+        } = program;
+        // variables are numbered by their symbols, and temporaries come after them
+        let mut max_id = symbol_count;
+        let mut symbols: HashMap<_, _> = variables.into_iter().map(|(f, symbol)| (variable(f), symbol)).collect();
+        let mut loop_labels = HashMap::new();
+
+        let functions = functions
+                .into_iter()
+                .map(|function| {
+                        let mut instructions = vec![];
+                        for i in function.body {
                                 emit_tactile_statement(
-                                        code,
-                                        *then,
-                                        instructions,
-                                        max_id,
+                                        i,
+                                        &mut instructions,
+                                        &mut max_id,
                                         max_label,
-                                        identifier_map,
-                                        labels,
-                                        scope + 1,
+                                        &mut symbols,
+                                        &mut loop_labels,
                                 );
+                        }
+                        // reaching the end of main returns 0, and the value is undefined for any other function anyway
+                        let ret = function.ret;
+                        instructions.push(TACTILEInstruction::Return(
+                                (ret != Type::Void).then(|| Value::Constant(Constant::new(0, ret))),
+                        ));
+
+                        TACTILEFunctionDefinition {
+                                identifier: function.name,
+                                global: function.global,
+                                params: function.params.into_iter().map(variable).collect(),
+                                variadic: function.variadic,
+                                instructions,
+                        }
+                })
+                .collect();
 
-                                identifier_map.retain(|&(_, f), _| f <= scope);
+        TACTILEProgram { functions, symbols }
+}
 
-                                instructions.push(TACTILEInstruction::L(end));
+fn is_atomic(value: Value, symbols: &HashMap<Identifier, Symbol>) -> bool {
+        matches!(value, Value::Var(identifier) if symbols[&identifier].atomic)
+}
 
-                                return;
-                        }
+// loop labeling gave every break and continue the label of the loop or switch it goes to. a switch breaks to its own
+// label, and a loop's labels are kept by the label it started with
+fn emit_tactile_statement(
+        value: HStatement,
+        instructions: &mut Vec<TACTILEInstruction>,
+        max_id: &mut usize,
+        max_label: &mut usize,
+        symbols: &mut HashMap<Identifier, Symbol>,
+        labels: &mut HashMap<usize, TACTILELoopLabel>,
+) {
+        match value {
+                HStatement::Return(expr) => {
+                        let val = expr.map(|expr| {
+                                emit_tactile_expr(expr, instructions, max_id, max_label, symbols, labels)
+                        });
+                        instructions.push(TACTILEInstruction::Return(val));
+                }
+                HStatement::Expr(expr) => {
+                        let _ = emit_tactile_expr(expr, instructions, max_id, max_label, symbols, labels);
+                }
+                HStatement::Initialize(symbol, init) => {
+                        let src = emit_tactile_expr(init, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::Copy(src, Value::Var(variable(symbol))));
+                }
+                HStatement::Nul => {}
+                // outputs are always variables, so they're written straight back to
+                HStatement::Asm(HInlineAsm {
+                        pieces,
+                        operands,
+                        clobbered,
+                }) => {
+                        let operands = operands
+                                .into_iter()
+                                .map(|(constraint, expr)| {
+                                        let value = emit_tactile_expr(
+                                                expr,
+                                                instructions,
+                                                max_id,
+                                                max_label,
+                                                symbols,
+                                                labels,
+                                        );
+                                        (constraint, value)
+                                })
+                                .collect();
+                        instructions.push(TACTILEInstruction::Asm(AsmStatement {
+                                pieces,
+                                operands,
+                                clobbered,
+                        }));
+                }
+                HStatement::Goto(label) => instructions.push(TACTILEInstruction::Jump(Label(label.0))),
+                HStatement::Labeled(label, statement) => {
+                        instructions.push(TACTILEInstruction::L(Label(label.0)));
+                        emit_tactile_statement(*statement, instructions, max_id, max_label, symbols, labels);
+                }
+                HStatement::If(condition, then, None) => {
+                        let end = new_label(max_label);
+
+                        let c = emit_tactile_expr(condition, instructions, max_id, max_label, symbols, labels);
+                        instructions.push(TACTILEInstruction::JumpIfZero(c, end));
 
-                        let else_statement = else_statement.unwrap();
+                        emit_tactile_statement(*then, instructions, max_id, max_label, symbols, labels);
+
+                        instructions.push(TACTILEInstruction::L(end));
+                }
+                HStatement::If(condition, then, Some(else_statement)) => {
                         let end_label = new_label(max_label);
                         let else_label = new_label(max_label);
 
-                        let c = emit_tactile_expr(
-                                code,
-                                condition,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope,
-                        );
+                        let c = emit_tactile_expr(condition, instructions, max_id, max_label, symbols, labels);
                         instructions.push(TACTILEInstruction::JumpIfZero(c, else_label));
 
-                        emit_tactile_statement(
-                                code,
-                                *then,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                labels,
-                                scope + 1,
-                        );
+                        emit_tactile_statement(*then, instructions, max_id, max_label, symbols, labels);
                         instructions.extend_from_slice(&[
                                 TACTILEInstruction::Jump(end_label),
                                 TACTILEInstruction::L(else_label),
                         ]);
-                        identifier_map.retain(|&(_, b), _| b <= scope);
 
-                        emit_tactile_statement(
-                                code,
-                                *else_statement,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                labels,
-                                scope + 1,
-                        );
+                        emit_tactile_statement(*else_statement, instructions, max_id, max_label, symbols, labels);
                         instructions.push(TACTILEInstruction::L(end_label));
-                        identifier_map.retain(|&(_, b), _| b <= scope);
                 }
-                AStatement::Compound(ABlock(block)) => {
-                        let inner_scope = scope + 1;
+                HStatement::Block(block) => {
                         for i in block {
-                                match i {
-                                        BlockItem::D(declaration) => match declaration {
-                                                Declaration::V(variable_declaration) => tactilify_variable_declaration(
-                                                        variable_declaration,
-                                                        code,
-                                                        instructions,
-                                                        max_id,
-                                                        max_label,
-                                                        identifier_map,
-                                                        scope,
-                                                ),
-                                                Declaration::F(function_declaration) => todo!(),
-                                        },
-                                        BlockItem::S(astatement) => {
-                                                emit_tactile_statement(
-                                                        code,
-                                                        astatement,
-                                                        instructions,
-                                                        max_id,
-                                                        max_label,
-                                                        identifier_map,
-                                                        labels,
-                                                        inner_scope,
-                                                );
-                                        }
-                                }
-                        }
-                        identifier_map.retain(|&(_, f), _| f <= scope);
-                }
-                AStatement::Break(_, breaktype) => match breaktype {
-                        BreakType::Loop => {
-                                let TACTILELabel::T(last_label) = labels[labels.len() - 1] else {
-                                        panic!("logic bug")
-                                };
-                                instructions.push(TACTILEInstruction::Jump(Label(last_label.break_label)))
-                        }
-                        BreakType::Switch => {
-                                let TACTILELabel::S(last_label) = &labels[labels.len() - 1] else {
-                                        panic!("logic bug")
-                                };
-
-                                instructions.push(TACTILEInstruction::Jump(last_label.label));
+                                emit_tactile_statement(i, instructions, max_id, max_label, symbols, labels);
                         }
-                },
-                AStatement::Continue(_) => {
-                        let TACTILELabel::T(last_label) = labels[labels.len() - 1] else {
-                                panic!("logic bug")
-                        };
-                        instructions.push(TACTILEInstruction::Jump(Label(last_label.continue_label)));
                 }
-                AStatement::DoWhile(astatement, aexpression, loop_label) => {
+                HStatement::Break(label, BreakType::Switch) => {
+                        instructions.push(TACTILEInstruction::Jump(Label(label.0)))
+                }
+                HStatement::Break(label, BreakType::Loop) => {
+                        instructions.push(TACTILEInstruction::Jump(Label(labels[&label.0].break_label)));
+                }
+                HStatement::Continue(label) => {
+                        instructions.push(TACTILEInstruction::Jump(Label(labels[&label.0].continue_label)));
+                }
+                HStatement::DoWhile(statement, condition, loop_label) => {
                         let tactile_label = tactilify_loop_label(loop_label, max_label);
-                        labels.push(TACTILELabel::T(tactile_label));
+                        labels.insert(loop_label.0, tactile_label);
 
                         let begin = Label(tactile_label.begin);
                         instructions.push(TACTILEInstruction::L(begin));
 
-                        emit_tactile_statement(
-                                code,
-                                *astatement,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                labels,
-                                scope + 1,
-                        );
+                        emit_tactile_statement(*statement, instructions, max_id, max_label, symbols, labels);
 
                         instructions.push(TACTILEInstruction::L(Label(tactile_label.continue_label)));
 
-                        let result = emit_tactile_expr(
-                                code,
-                                aexpression,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope + 1,
-                        );
+                        let result = emit_tactile_expr(condition, instructions, max_id, max_label, symbols, labels);
 
                         instructions.extend([
                                 TACTILEInstruction::JumpIfNotZero(result, begin),
                                 TACTILEInstruction::L(Label(tactile_label.break_label)),
                         ]);
-                        identifier_map.retain(|&(_, f), _| f <= scope);
                 }
-                AStatement::While(aexpression, astatement, loop_label) => {
+                HStatement::While(condition, statement, loop_label) => {
                         let tactile_label = tactilify_loop_label(loop_label, max_label);
-                        labels.push(TACTILELabel::T(tactile_label));
+                        labels.insert(loop_label.0, tactile_label);
 
                         instructions.push(TACTILEInstruction::L(Label(tactile_label.continue_label)));
 
-                        let result = emit_tactile_expr(
-                                code,
-                                aexpression,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                scope + 1,
-                        );
+                        let result = emit_tactile_expr(condition, instructions, max_id, max_label, symbols, labels);
                         instructions.push(TACTILEInstruction::JumpIfZero(result, Label(tactile_label.break_label)));
 
-                        emit_tactile_statement(
-                                code,
-                                *astatement,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                labels,
-                                scope + 1,
-                        );
+                        emit_tactile_statement(*statement, instructions, max_id, max_label, symbols, labels);
 
                         instructions.extend([
                                 TACTILEInstruction::Jump(Label(tactile_label.continue_label)),
                                 TACTILEInstruction::L(Label(tactile_label.break_label)),
                         ]);
-                        identifier_map.retain(|&(_, f), _| f <= scope);
                 }
-                AStatement::F(boxed_for, loop_label) => {
-                        let For {
+                HStatement::For(boxed_for, loop_label) => {
+                        let HFor {
                                 init,
                                 condition,
                                 post,
                                 body,
                         } = *boxed_for;
                         let tactile_label = tactilify_loop_label(loop_label, max_label);
-                        labels.push(TACTILELabel::T(tactile_label));
-                        match init {
-                                ForInit::D(VariableDeclaration {
-                                        id,
-                                        init: Some(initializer),
-                                }) => {
-                                        let var = emit_tactile_expr(
-                                                code,
-                                                AExpression::F(AFactor::Id(id)),
-                                                instructions,
-                                                max_id,
-                                                max_label,
-                                                identifier_map,
-                                                scope + 1,
-                                        );
-                                        let expr = emit_tactile_expr(
-                                                code,
-                                                initializer,
-                                                instructions,
-                                                max_id,
-                                                max_label,
-                                                identifier_map,
-                                                scope + 1,
-                                        );
-                                        instructions.push(TACTILEInstruction::Copy(expr, var));
-                                }
-                                ForInit::E(Some(aexpression)) => {
-                                        let expr = AStatement::Expr(aexpression);
-                                        emit_tactile_statement(
-                                                code,
-                                                expr,
-                                                instructions,
-                                                max_id,
-                                                max_label,
-                                                identifier_map,
-                                                labels,
-                                                scope + 1,
-                                        );
-                                }
-
-                                _ => {}
+                        labels.insert(loop_label.0, tactile_label);
+                        if let Some(init) = init {
+                                emit_tactile_statement(init, instructions, max_id, max_label, symbols, labels);
                         }
 
                         instructions.push(TACTILEInstruction::L(Label(tactile_label.begin)));
-                        if let Some(aexpression) = condition {
-                                let value = emit_tactile_expr(
-                                        code,
-                                        aexpression,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope + 1,
-                                );
+                        if let Some(condition) = condition {
+                                let value =
+                                        emit_tactile_expr(condition, instructions, max_id, max_label, symbols, labels);
                                 instructions
                                         .push(TACTILEInstruction::JumpIfZero(value, Label(tactile_label.break_label)));
                         }
-                        () = emit_tactile_statement(
-                                code,
-                                body,
-                                instructions,
-                                max_id,
-                                max_label,
-                                identifier_map,
-                                labels,
-                                scope + 1,
-                        );
+                        () = emit_tactile_statement(body, instructions, max_id, max_label, symbols, labels);
 
                         instructions.push(TACTILEInstruction::L(Label(tactile_label.continue_label)));
 
-                        if let Some(aexpression) = post {
-                                let _ = emit_tactile_expr(
-                                        code,
-                                        aexpression,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        scope + 1,
-                                );
+                        if let Some(post) = post {
+                                let _ = emit_tactile_expr(post, instructions, max_id, max_label, symbols, labels);
                         }
 
                         instructions.extend([
                                 TACTILEInstruction::Jump(Label(tactile_label.begin)),
                                 TACTILEInstruction::L(Label(tactile_label.break_label)),
                         ]);
-
-                        identifier_map.retain(|&(_, f), _| f <= scope);
                 }
-                AStatement::S(switch) => {
-                        let Switch {
-                                value,
-                                cases,
-                                default,
-                                label,
-                        } = switch;
-
-                        if cases.is_empty() && default.is_none() {
-                                return;
-                        }
-
-                        let mut case_labels = Vec::with_capacity(cases.len() - 1);
-
-                        for _ in 0..cases.len() {
-                                case_labels.push(new_label(max_id));
-                        }
-
-                        let mut potential_default = None;
-                        if let Some(default) = default {
-                                potential_default = Some(default);
-                        }
-
+                HStatement::Switch(HSwitch {
+                        value,
+                        body,
+                        cases,
+                        default,
+                        label,
+                }) => {
                         let break_label = Label(label.0);
 
-                        labels.push(TACTILELabel::S(SwitchLabel { label: break_label }));
-
-                        let value =
-                                emit_tactile_expr(code, value, instructions, max_id, max_label, identifier_map, scope);
-
-                        for (idx, (constant, statements)) in cases.into_iter().enumerate() {
-                                let curr_const = Value::Constant(Constant::A(constant));
-                                let dst = Value::Var(new_id(max_id));
+                        let value = emit_tactile_expr(value, instructions, max_id, max_label, symbols, labels);
 
+                        for (constant, case_label) in cases {
+                                let dst = new_var(Type::Int, max_id, symbols);
                                 instructions.extend([
-                                        TACTILEInstruction::Binary(Binop::EqualTo, value, curr_const, dst),
-                                        TACTILEInstruction::JumpIfZero(dst, case_labels[idx]),
+                                        TACTILEInstruction::Binary(
+                                                Binop::EqualTo,
+                                                value,
+                                                Value::Constant(constant),
+                                                dst,
+                                        ),
+                                        TACTILEInstruction::JumpIfNotZero(dst, Label(case_label.0)),
                                 ]);
-
-                                for i in statements {
-                                        () = emit_tactile_statement(
-                                                code,
-                                                i,
-                                                instructions,
-                                                max_id,
-                                                max_label,
-                                                identifier_map,
-                                                labels,
-                                                scope,
-                                        );
-                                }
-
-                                if idx > 0 {
-                                        instructions.push(TACTILEInstruction::L(case_labels[idx - 1]));
-                                }
-                        }
-                        if let Some(case_label) = case_labels.last() {
-                                instructions.push(TACTILEInstruction::L(*case_label));
                         }
+                        instructions.push(TACTILEInstruction::Jump(default.map_or(break_label, |f| Label(f.0))));
 
-                        if let Some(default) = potential_default {
-                                let default = *default;
-                                () = emit_tactile_statement(
-                                        code,
-                                        default,
-                                        instructions,
-                                        max_id,
-                                        max_label,
-                                        identifier_map,
-                                        labels,
-                                        scope,
-                                );
-                        }
+                        () = emit_tactile_statement(*body, instructions, max_id, max_label, symbols, labels);
 
                         instructions.push(TACTILEInstruction::L(break_label));
                 }
         }
 }
 
-pub fn tactile<S: BuildHasher>(
-        program: Program<SemanticallyAnalyzed>,
-        mut max_label: usize,
-        identifier_map: HashMap<(&[u8], usize), (Identifier, bool), S>,
-        code: &[u8],
-) -> Program<TACTILE> {
+pub fn tactile(program: Program<SemanticallyAnalyzed>, mut max_label: usize) -> Program<TACTILE> {
         Program {
                 operation: program.operation,
                 state: TACTILE {
-                        program: tactile_program(program.state.program, code, &mut max_label, identifier_map),
+                        program: tactile_program(program.state.program, &mut max_label),
                 },
                 obj: program.obj,
         }
@@ -810,8 +828,34 @@ fn new_label(max_label: &mut usize) -> Label {
         *max_label += 1;
         temp
 }
+// a variable is numbered by its symbol
+fn variable(symbol: SymbolId) -> Identifier {
+        Identifier(symbol.0)
+}
+
 fn new_id(max_id: &mut usize) -> Identifier {
         let temp = Identifier(*max_id);
         *max_id += 1;
         temp
 }
+
+fn new_var(var_type: Type, max_id: &mut usize, symbols: &mut HashMap<Identifier, Symbol>) -> Value {
+        let id = new_id(max_id);
+        symbols.insert(
+                id,
+                Symbol {
+                        c_type: var_type,
+                        storage: None,
+                        aligned: None,
+                        atomic: false,
+                },
+        );
+        Value::Var(id)
+}
+
+fn value_type(value: Value, symbols: &HashMap<Identifier, Symbol>) -> Type {
+        match value {
+                Value::Constant(constant) => constant.c_type(),
+                Value::Var(identifier) => symbols[&identifier].c_type,
+        }
+}
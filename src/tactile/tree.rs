@@ -1,4 +1,7 @@
-use crate::parse::nodes::{AConstant, AIdentifier, Binop, Unop};
+use crate::{
+        parse::nodes::{AIdentifier, Binop, Constraint, TemplatePiece, Unop},
+        semantic_analysis::type_checker::Type,
+};
 
 use super::Identifier;
 
@@ -8,30 +11,173 @@ pub enum Value {
         Var(Identifier),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Constant {
-        A(AConstant),
-        S(i64),
+        Char(i8),
+        UChar(u8),
+        Short(i16),
+        UShort(u16),
+        Int(i32),
+        UInt(u32),
+        Long(i64),
+        ULong(u64),
+}
+
+impl Constant {
+        // keeps the low bits of value, which is how C converts between integer types, except that anything but 0
+        // converts to a bool as 1, 6.3.1.2
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_possible_wrap)]
+        pub fn new(value: u64, c_type: Type) -> Constant {
+                match c_type {
+                        Type::Char | Type::SChar => Constant::Char(value as i8),
+                        Type::Bool => Constant::UChar(u8::from(value != 0)),
+                        Type::UChar => Constant::UChar(value as u8),
+                        Type::Short => Constant::Short(value as i16),
+                        Type::UShort => Constant::UShort(value as u16),
+                        Type::Int => Constant::Int(value as i32),
+                        Type::UInt => Constant::UInt(value as u32),
+                        Type::Long | Type::LongLong => Constant::Long(value as i64),
+                        // only null pointer constants are known at compile time
+                        Type::ULong | Type::ULongLong | Type::Pointer(_) | Type::NullPtr => Constant::ULong(value),
+                        Type::Void | Type::VaList | Type::Func(..) | Type::Typeof(_) => {
+                                unreachable!("only integers can be constants")
+                        }
+                }
+        }
+
+        // the value extended to 64 bits according to its signedness
+        #[allow(clippy::cast_sign_loss)]
+        pub fn as_u64(&self) -> u64 {
+                match *self {
+                        Constant::Char(n) => n as u64,
+                        Constant::UChar(n) => n as u64,
+                        Constant::Short(n) => n as u64,
+                        Constant::UShort(n) => n as u64,
+                        Constant::Int(n) => n as u64,
+                        Constant::UInt(n) => n as u64,
+                        Constant::Long(n) => n as u64,
+                        Constant::ULong(n) => n,
+                }
+        }
+
+        pub fn c_type(&self) -> Type {
+                match self {
+                        Constant::Char(_) => Type::Char,
+                        Constant::UChar(_) => Type::UChar,
+                        Constant::Short(_) => Type::Short,
+                        Constant::UShort(_) => Type::UShort,
+                        Constant::Int(_) => Type::Int,
+                        Constant::UInt(_) => Type::UInt,
+                        Constant::Long(_) => Type::Long,
+                        Constant::ULong(_) => Type::ULong,
+                }
+        }
+}
+
+// everything the backend needs to know about a variable or temporary
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+        pub c_type: Type,
+        // variables with static storage duration live in .data, .bss or .rodata rather than on the stack
+        pub storage: Option<StaticStorage>,
+        // set by GNU aligned when it's stricter than the type's own alignment
+        pub aligned: Option<usize>,
+        // _Atomic, so assignments are seq_cst stores and compound assignments a single read-modify-write, 6.5.16.2p3
+        pub atomic: bool,
+}
+
+impl Symbol {
+        pub fn alignment(&self) -> usize {
+                self.aligned.unwrap_or_else(|| self.c_type.alignment())
+        }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StaticStorage {
+        pub name: AIdentifier,
+        // static locals can share a name, so they get their identifier appended to it
+        pub local: bool,
+        pub global: bool,
+        // const, so it goes in .rodata
+        pub read_only: bool,
+        // _Thread_local, so it goes in .tdata or .tbss and is found relative to %fs
+        pub thread_local: bool,
+        pub init: StaticInit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticInit {
+        Initial(Constant),
+        // the address of a function, which only the linker knows
+        FunctionAddress(AIdentifier),
+        // declared without an initializer or extern, so defined as zero unless an initializer shows up later
+        Tentative,
+        // only ever declared extern, so defined somewhere else
+        NoInitializer,
 }
 
 #[derive(Debug, Clone)]
 pub enum TACTILEInstruction {
-        Return(Value),
+        // None for functions returning void
+        Return(Option<Value>),
         Unary(Unop, Value, Value),
         Binary(Binop, Value, Value, Value),
         Copy(Value, Value),
+        SignExtend(Value, Value),
+        ZeroExtend(Value, Value),
+        Truncate(Value, Value),
         Jump(Label),
         JumpIfZero(Value, Label),
         JumpIfNotZero(Value, Label),
         L(Label),
         F(FunctionCall),
+        // va_list
+        VaStart(Value),
+        // va_list, dst, and the labels for where the argument is found on the stack and where both ways meet
+        VaArg(Value, Value, Label, Label),
+        // src, dst
+        VaCopy(Value, Value),
+        // function, dst
+        FunctionAddress(AIdentifier, Value),
+        // src, dst, where src is an unsigned int and dst an int
+        Popcount(Value, Value),
+        CountLeadingZeros(Value, Value),
+        // traps if it's ever reached
+        Unreachable,
+        Asm(AsmStatement),
+        // a full barrier, for seq_cst stores and fences
+        Fence,
+        // src, object, dst, where dst gets what the object held before
+        AtomicExchange(Value, Value, Value),
+        AtomicFetchAdd(Value, Value, Value),
+        // object, expected, desired, dst. dst is whether the object held what was expected, and when it didn't,
+        // expected gets what it held instead
+        AtomicCompareExchange(Value, Value, Value, Value),
+}
+
+// the outputs come first among the operands, then the inputs, which is how the template numbers them
+#[derive(Debug, Clone)]
+pub struct AsmStatement {
+        pub pieces: Vec<TemplatePiece>,
+        pub operands: Vec<(Constraint, Value)>,
+        pub clobbered: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Callee {
+        Direct(AIdentifier),
+        // a function pointer
+        Indirect(Value),
 }
 
 #[derive(Debug, Clone)]
 pub struct FunctionCall {
-        id: AIdentifier,
-        args: Option<Vec<Value>>,
-        dst: Value,
+        pub callee: Callee,
+        // already converted to the parameter types
+        pub args: Vec<Value>,
+        // None for functions returning void
+        pub dst: Option<Value>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -43,14 +189,3 @@ pub struct TACTILELoopLabel {
         pub break_label: usize,
         pub continue_label: usize,
 }
-
-#[derive(Debug, Clone)]
-pub enum TACTILELabel {
-        T(TACTILELoopLabel),
-        S(SwitchLabel),
-}
-
-#[derive(Debug, Clone)]
-pub struct SwitchLabel {
-        pub label: Label,
-}
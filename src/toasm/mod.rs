@@ -1,9 +1,18 @@
+use std::collections::HashMap;
+
 use crate::{
-        parse::nodes::{Binop, Unop},
-        tactile::{tree::Constant, tree::TACTILEInstruction, tree::Value, TACTILEFunctionDefinition, TACTILE},
+        parse::nodes::{AIdentifier, Binop, OperandKind, Unop},
+        semantic_analysis::type_checker::{Type, MAX_ALIGNMENT},
+        tactile::{
+                tree::AsmStatement, tree::Callee, tree::Constant, tree::FunctionCall, tree::StaticInit, tree::Symbol,
+                tree::TACTILEInstruction, tree::Value, Identifier, TACTILEFunctionDefinition, TACTILE,
+        },
         State,
 };
-use nodes::{ASMBinary, ASMFunction, ASMInstruction, ASMProgram, ASMUnary, CondCode, Operand, Register};
+use nodes::{
+        ASMBinary, ASMFunction, ASMInstruction, ASMProgram, ASMStaticInit, ASMStaticVariable, ASMUnary, AsmType,
+        CondCode, Operand, Register,
+};
 
 pub mod nodes;
 
@@ -16,11 +25,44 @@ impl State for Compiled {}
 pub fn asm(tactile: TACTILE) -> Compiled {
         let mut functions = vec![];
         for i in tactile.program.functions {
-                functions.push(ASMFunction::from(i));
+                functions.push(function_to_asm(i, &tactile.program.symbols));
         }
 
+        let mut static_variables: Vec<_> = tactile
+                .program
+                .symbols
+                .iter()
+                .filter_map(|(identifier, symbol)| {
+                        let storage = symbol.storage?;
+                        let init = match storage.init {
+                                StaticInit::Initial(constant) => ASMStaticInit::Constant(constant),
+                                StaticInit::FunctionAddress(function) => ASMStaticInit::FunctionAddress(function),
+                                StaticInit::Tentative => ASMStaticInit::Constant(Constant::new(0, symbol.c_type)),
+                                StaticInit::NoInitializer => return None,
+                        };
+
+                        Some((
+                                identifier.0,
+                                ASMStaticVariable {
+                                        name: storage.name,
+                                        suffix: storage.local.then_some(identifier.0),
+                                        global: storage.global,
+                                        read_only: storage.read_only,
+                                        thread_local: storage.thread_local,
+                                        alignment: symbol.alignment(),
+                                        init,
+                                },
+                        ))
+                })
+                .collect();
+        // so the output doesn't depend on hashmap order
+        static_variables.sort_by_key(|f| f.0);
+
         Compiled {
-                program: ASMProgram { functions },
+                program: ASMProgram {
+                        functions,
+                        static_variables: static_variables.into_iter().map(|f| f.1).collect(),
+                },
         }
 }
 
@@ -31,96 +73,176 @@ fn val_to_op(value: Value) -> Operand {
         }
 }
 
-impl From<TACTILEFunctionDefinition> for ASMFunction {
-        fn from(value: TACTILEFunctionDefinition) -> Self {
-                let identifier = value.identifier;
-                let mut temp_instructions = vec![];
+fn val_to_type(value: Value, symbols: &HashMap<Identifier, Symbol>) -> Type {
+        match value {
+                Value::Constant(constant) => constant.c_type(),
+                Value::Var(identifier) => symbols[&identifier].c_type,
+        }
+}
 
-                temp_instructions.push(ASMInstruction::AllocateStack(0));
+// a byte offset into a va_list
+fn va_list_member(value: Value, offset: usize) -> Operand {
+        let Value::Var(identifier) = value else {
+                unreachable!("va_lists are always variables")
+        };
+        Operand::PseudoMember(identifier.0, offset)
+}
 
-                let from_tactile = |value: &TACTILEInstruction| match value {
-                        TACTILEInstruction::Return(val) => temp_instructions.extend([
-                                ASMInstruction::Mov(val_to_op(*val), Operand::Register(Register::AX)),
-                                ASMInstruction::Ret,
-                        ]),
-                        TACTILEInstruction::Unary(unop, src, dst) => {
-                                let op = match unop {
-                                        Unop::Negate => ASMUnary::Negate,
-                                        Unop::Complement => ASMUnary::Complement,
-                                        Unop::Not => ASMUnary::Not,
-                                        Unop::IncrementPre | Unop::IncrementPost => ASMUnary::Increment,
-                                        Unop::DecrementPre | Unop::DecrementPost => ASMUnary::Decrement,
-                                };
+static ZERO: Operand = Operand::Imm(Constant::Int(0));
 
-                                temp_instructions.extend([
-                                        ASMInstruction::Mov(val_to_op(*src), val_to_op(*dst)),
-                                        ASMInstruction::Unary(op, val_to_op(*dst)),
-                                ]);
-                        }
+// the registers inline asm operands can go in, in the order they're handed out. r10 and r11 are left out, as
+// last_pass uses them as scratch registers
+pub static ASM_REGISTERS: [Register; 12] = [
+        Register::AX,
+        Register::CX,
+        Register::DX,
+        Register::SI,
+        Register::DI,
+        Register::R8,
+        Register::R9,
+        Register::BX,
+        Register::R12,
+        Register::R13,
+        Register::R14,
+        Register::R15,
+];
 
-                        TACTILEInstruction::Binary(binop, src1, src2, mut dst) => match binop {
-                                Binop::Divide => temp_instructions.extend([
-                                        ASMInstruction::Mov(val_to_op(*src1), Operand::Register(Register::AX)),
-                                        ASMInstruction::Cdq,
-                                        ASMInstruction::IDiv(val_to_op(*src2)),
-                                        ASMInstruction::Mov(Operand::Register(Register::AX), val_to_op(dst)),
-                                ]),
-                                Binop::DivideAssign => {
-                                        dst = *src1;
-                                        temp_instructions.extend([
-                                                ASMInstruction::Mov(val_to_op(*src1), Operand::Register(Register::AX)),
-                                                ASMInstruction::Cdq,
-                                                ASMInstruction::IDiv(val_to_op(*src2)),
-                                                ASMInstruction::Mov(Operand::Register(Register::AX), val_to_op(dst)),
-                                        ]);
+// the registers the first six integer arguments are passed in
+static ARGUMENT_REGISTERS: [Register; 6] = [
+        Register::DI,
+        Register::SI,
+        Register::DX,
+        Register::CX,
+        Register::R8,
+        Register::R9,
+];
+
+// a variadic function saves ARGUMENT_REGISTERS at the bottom of its frame, which is the start of the register save area
+// va_arg reads them from. the floating point registers that would follow are never saved
+const GP_SAVE_AREA: usize = 48;
+// the end of the floating point part of the save area, so va_arg never looks for anything there
+const FP_SAVE_AREA_END: usize = GP_SAVE_AREA + 8 * 16;
+
+fn function_to_asm(value: TACTILEFunctionDefinition, symbols: &HashMap<Identifier, Symbol>) -> ASMFunction {
+        let identifier = value.identifier;
+        let mut temp_instructions = vec![];
+
+        let named_params = value.params.len();
+        if value.variadic {
+                for (idx, register) in ARGUMENT_REGISTERS.into_iter().enumerate() {
+                        temp_instructions.push(ASMInstruction::Mov(
+                                AsmType::Quadword,
+                                Operand::Register(register),
+                                Operand::Stack(GP_SAVE_AREA - idx * 8),
+                        ));
+                }
+        }
+
+        // parameters after the sixth were pushed by the caller, and sit above the return address and saved rbp
+        for (idx, param) in value.params.iter().enumerate() {
+                let src = match ARGUMENT_REGISTERS.get(idx) {
+                        Some(&register) => Operand::Register(register),
+                        None => Operand::Memory(
+                                Register::BP,
+                                i32::try_from(16 + 8 * (idx - ARGUMENT_REGISTERS.len())).expect("too many parameters"),
+                        ),
+                };
+                temp_instructions.push(ASMInstruction::Mov(
+                        AsmType::from(symbols[param].c_type),
+                        src,
+                        Operand::Pseudo(param.0),
+                ));
+        }
+
+        let asm_type = |value: &Value| AsmType::from(val_to_type(*value, symbols));
+
+        let from_tactile = |value: &TACTILEInstruction| match value {
+                TACTILEInstruction::Return(Some(val)) => temp_instructions.extend([
+                        ASMInstruction::Mov(asm_type(val), val_to_op(*val), Operand::Register(Register::AX)),
+                        ASMInstruction::Ret,
+                ]),
+                TACTILEInstruction::Return(None) => temp_instructions.push(ASMInstruction::Ret),
+                TACTILEInstruction::Unary(Unop::Not, src, dst) => temp_instructions.extend([
+                        ASMInstruction::Cmp(asm_type(src), ZERO, val_to_op(*src)),
+                        ASMInstruction::Mov(asm_type(dst), ZERO, val_to_op(*dst)),
+                        ASMInstruction::SetCC(CondCode::E, val_to_op(*dst)),
+                ]),
+                TACTILEInstruction::Unary(unop, src, dst) => {
+                        let op = match unop {
+                                Unop::Negate => ASMUnary::Negate,
+                                Unop::Complement => ASMUnary::Complement,
+                                Unop::IncrementPre | Unop::IncrementPost => ASMUnary::Increment,
+                                Unop::DecrementPre | Unop::DecrementPost => ASMUnary::Decrement,
+                                Unop::Not | Unop::Plus | Unop::Deref | Unop::AddressOf => unreachable!(),
+                        };
+
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(asm_type(dst), val_to_op(*src), val_to_op(*dst)),
+                                ASMInstruction::Unary(op, asm_type(dst), val_to_op(*dst)),
+                        ]);
+                }
+
+                TACTILEInstruction::Binary(binop, src1, src2, mut dst) => {
+                        let operand_type = val_to_type(*src1, symbols);
+                        let t = AsmType::from(operand_type);
+                        let signed = operand_type.is_signed();
+
+                        let comparison = |signed_cc, unsigned_cc| {
+                                [
+                                        ASMInstruction::Cmp(t, val_to_op(*src2), val_to_op(*src1)),
+                                        ASMInstruction::Mov(asm_type(&dst), ZERO, val_to_op(dst)),
+                                        ASMInstruction::SetCC(
+                                                if signed { signed_cc } else { unsigned_cc },
+                                                val_to_op(dst),
+                                        ),
+                                ]
+                        };
+
+                        match binop {
+                                Binop::Divide | Binop::DivideAssign | Binop::Remainder | Binop::RemainderAssign => {
+                                        if matches!(binop, Binop::DivideAssign | Binop::RemainderAssign) {
+                                                dst = *src1;
+                                        }
+                                        let result = if matches!(binop, Binop::Divide | Binop::DivideAssign) {
+                                                Register::AX
+                                        } else {
+                                                Register::DX
+                                        };
+
+                                        temp_instructions.push(ASMInstruction::Mov(
+                                                t,
+                                                val_to_op(*src1),
+                                                Operand::Register(Register::AX),
+                                        ));
+                                        if signed {
+                                                temp_instructions.extend([
+                                                        ASMInstruction::Cdq(t),
+                                                        ASMInstruction::IDiv(t, val_to_op(*src2)),
+                                                ]);
+                                        } else {
+                                                temp_instructions.extend([
+                                                        ASMInstruction::Mov(t, ZERO, Operand::Register(Register::DX)),
+                                                        ASMInstruction::Div(t, val_to_op(*src2)),
+                                                ]);
+                                        }
+                                        temp_instructions.push(ASMInstruction::Mov(
+                                                t,
+                                                Operand::Register(result),
+                                                val_to_op(dst),
+                                        ));
                                 }
-                                Binop::Remainder => temp_instructions.extend([
-                                        ASMInstruction::Mov(val_to_op(*src1), Operand::Register(Register::AX)),
-                                        ASMInstruction::Cdq,
-                                        ASMInstruction::IDiv(val_to_op(*src2)),
-                                        ASMInstruction::Mov(Operand::Register(Register::DX), val_to_op(dst)),
-                                ]),
-                                Binop::RemainderAssign => {
-                                        dst = *src1;
-                                        temp_instructions.extend([
-                                                ASMInstruction::Mov(val_to_op(*src1), Operand::Register(Register::AX)),
-                                                ASMInstruction::Cdq,
-                                                ASMInstruction::IDiv(val_to_op(*src2)),
-                                                ASMInstruction::Mov(Operand::Register(Register::DX), val_to_op(dst)),
-                                        ]);
+                                Binop::MoreThan => temp_instructions.extend(comparison(CondCode::G, CondCode::A)),
+                                Binop::MoreThanOrEqual => {
+                                        temp_instructions.extend(comparison(CondCode::GE, CondCode::AE))
+                                }
+                                Binop::EqualTo => temp_instructions.extend(comparison(CondCode::E, CondCode::E)),
+                                Binop::NotEqualTo => temp_instructions.extend(comparison(CondCode::NE, CondCode::NE)),
+                                Binop::LessThan => temp_instructions.extend(comparison(CondCode::L, CondCode::B)),
+                                Binop::LessThanOrEqual => {
+                                        temp_instructions.extend(comparison(CondCode::LE, CondCode::BE))
                                 }
-                                Binop::MoreThan => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::G, val_to_op(dst)),
-                                ]),
-                                Binop::MoreThanOrEqual => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::GE, val_to_op(dst)),
-                                ]),
-                                Binop::EqualTo => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::E, val_to_op(dst)),
-                                ]),
-                                Binop::NotEqualTo => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::NE, val_to_op(dst)),
-                                ]),
-                                Binop::LessThan => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::L, val_to_op(dst)),
-                                ]),
-                                Binop::LessThanOrEqual => temp_instructions.extend([
-                                        ASMInstruction::Cmp(val_to_op(*src2), val_to_op(*src1)),
-                                        ASMInstruction::Mov(Operand::Imm(Constant::S(0)), val_to_op(dst)),
-                                        ASMInstruction::SetCC(CondCode::LE, val_to_op(dst)),
-                                ]),
                                 _ => {
-                                        let temp = ASMBinary::try_from(*binop).expect("LOGICBUGGGG");
+                                        let mut temp = ASMBinary::try_from(*binop).expect("LOGICBUGGGG");
                                         match temp {
                                                 ASMBinary::AddAssign
                                                 | ASMBinary::SubtractAssign
@@ -138,170 +260,794 @@ impl From<TACTILEFunctionDefinition> for ASMFunction {
                                                 | ASMBinary::Multiply
                                                 | ASMBinary::LeftShift
                                                 | ASMBinary::RightShift
+                                                | ASMBinary::ArithmeticRightShift
                                                 | ASMBinary::Or
                                                 | ASMBinary::XOr
                                                 | ASMBinary::And => {}
                                         }
+                                        // >> on a signed value keeps the sign bit
+                                        if matches!(temp, ASMBinary::RightShift | ASMBinary::RightShiftAssign) && signed
+                                        {
+                                                temp = ASMBinary::ArithmeticRightShift;
+                                        }
                                         temp_instructions.extend([
-                                                ASMInstruction::Mov(val_to_op(*src1), val_to_op(dst)),
-                                                ASMInstruction::Binary(
-                                                        ASMBinary::try_from(*binop).expect("LogicBUGGG"),
-                                                        val_to_op(*src2),
-                                                        val_to_op(dst),
-                                                ),
+                                                ASMInstruction::Mov(t, val_to_op(*src1), val_to_op(dst)),
+                                                ASMInstruction::Binary(temp, t, val_to_op(*src2), val_to_op(dst)),
                                         ]);
                                 }
-                        },
-                        TACTILEInstruction::Jump(label) => temp_instructions.push(ASMInstruction::Jmp(*label)),
-                        TACTILEInstruction::Copy(src, dst) => {
-                                temp_instructions.push(ASMInstruction::Mov(val_to_op(*src), val_to_op(*dst)))
                         }
-                        TACTILEInstruction::L(label) => temp_instructions.push(ASMInstruction::Label(*label)),
-                        TACTILEInstruction::JumpIfZero(value, label) => temp_instructions.extend([
-                                ASMInstruction::Cmp(Operand::Imm(Constant::S(0)), val_to_op(*value)),
-                                ASMInstruction::JmpCC(CondCode::E, *label),
-                        ]),
-                        TACTILEInstruction::JumpIfNotZero(value, label) => temp_instructions.extend([
-                                ASMInstruction::Cmp(Operand::Imm(Constant::S(0)), val_to_op(*value)),
-                                ASMInstruction::JmpCC(CondCode::NE, *label),
+                }
+                TACTILEInstruction::Jump(label) => temp_instructions.push(ASMInstruction::Jmp(*label)),
+                TACTILEInstruction::Copy(src, dst) => {
+                        temp_instructions.push(ASMInstruction::Mov(asm_type(dst), val_to_op(*src), val_to_op(*dst)))
+                }
+                TACTILEInstruction::SignExtend(src, dst) => temp_instructions.push(ASMInstruction::Movsx(
+                        asm_type(src),
+                        asm_type(dst),
+                        val_to_op(*src),
+                        val_to_op(*dst),
+                )),
+                // writing to a 32 bit register clears the upper half, so there's no movzlq
+                TACTILEInstruction::ZeroExtend(src, dst) if asm_type(src) == AsmType::Longword => temp_instructions
+                        .extend([
+                                ASMInstruction::Mov(
+                                        AsmType::Longword,
+                                        val_to_op(*src),
+                                        Operand::Register(Register::R11),
+                                ),
+                                ASMInstruction::Mov(
+                                        AsmType::Quadword,
+                                        Operand::Register(Register::R11),
+                                        val_to_op(*dst),
+                                ),
                         ]),
-                        TACTILEInstruction::F(function_call) => todo!(),
-                };
+                TACTILEInstruction::ZeroExtend(src, dst) => temp_instructions.push(ASMInstruction::MovZeroExtend(
+                        asm_type(src),
+                        asm_type(dst),
+                        val_to_op(*src),
+                        val_to_op(*dst),
+                )),
+                TACTILEInstruction::Truncate(src, dst) => {
+                        let src = match src {
+                                Value::Constant(constant) => {
+                                        Operand::Imm(Constant::new(constant.as_u64(), val_to_type(*dst, symbols)))
+                                }
+                                Value::Var(_) => val_to_op(*src),
+                        };
+                        temp_instructions.push(ASMInstruction::Mov(asm_type(dst), src, val_to_op(*dst)))
+                }
+                TACTILEInstruction::L(label) => temp_instructions.push(ASMInstruction::Label(*label)),
+                TACTILEInstruction::JumpIfZero(value, label) => temp_instructions.extend([
+                        ASMInstruction::Cmp(asm_type(value), ZERO, val_to_op(*value)),
+                        ASMInstruction::JmpCC(CondCode::E, *label),
+                ]),
+                TACTILEInstruction::JumpIfNotZero(value, label) => temp_instructions.extend([
+                        ASMInstruction::Cmp(asm_type(value), ZERO, val_to_op(*value)),
+                        ASMInstruction::JmpCC(CondCode::NE, *label),
+                ]),
+                TACTILEInstruction::F(FunctionCall { callee, args, dst }) => {
+                        let (register_args, stack_args) = args.split_at(args.len().min(ARGUMENT_REGISTERS.len()));
 
-                let mut stack_max: usize = 0;
+                        // rsp has to be 16 byte aligned at the call, and every stack argument takes 8 bytes
+                        let padding = if stack_args.len() % 2 == 1 { 8 } else { 0 };
+                        if padding != 0 {
+                                temp_instructions.push(ASMInstruction::AllocateStack(padding));
+                        }
 
-                () = value.instructions.iter().map(from_tactile).collect();
+                        for arg in stack_args.iter().rev() {
+                                let t = asm_type(arg);
+                                let op = val_to_op(*arg);
+                                // pushing a smaller variable from memory would read past its end
+                                if t == AsmType::Quadword || matches!(op, Operand::Imm(_)) {
+                                        temp_instructions.push(ASMInstruction::Push(op));
+                                } else {
+                                        temp_instructions.extend([
+                                                ASMInstruction::Mov(t, op, Operand::Register(Register::AX)),
+                                                ASMInstruction::Push(Operand::Register(Register::AX)),
+                                        ]);
+                                }
+                        }
+                        for (arg, register) in register_args.iter().zip(ARGUMENT_REGISTERS) {
+                                temp_instructions.push(ASMInstruction::Mov(
+                                        asm_type(arg),
+                                        val_to_op(*arg),
+                                        Operand::Register(register),
+                                ));
+                        }
 
-                let temp_instructions = temp_instructions.iter().map(|&f| pseudo_pass(f, &mut stack_max));
+                        // al holds how many vector registers a variadic callee gets, which is always none. it's
+                        // harmless for everything else, so it doesn't matter whether the callee is variadic
+                        temp_instructions.push(ASMInstruction::Mov(
+                                AsmType::Longword,
+                                ZERO,
+                                Operand::Register(Register::AX),
+                        ));
+                        // r11 isn't used to pass anything, so the function pointer can wait there
+                        match callee {
+                                Callee::Direct(id) => temp_instructions.push(ASMInstruction::Call(*id)),
+                                Callee::Indirect(pointer) => temp_instructions.extend([
+                                        ASMInstruction::Mov(
+                                                AsmType::Quadword,
+                                                val_to_op(*pointer),
+                                                Operand::Register(Register::R11),
+                                        ),
+                                        ASMInstruction::CallIndirect(Operand::Register(Register::R11)),
+                                ]),
+                        }
 
-                let mut instructions = Vec::with_capacity(temp_instructions.len() * 2);
-                instructions.push(ASMInstruction::AllocateStack(0));
+                        let pushed = 8 * stack_args.len() + padding;
+                        if pushed != 0 {
+                                temp_instructions.push(ASMInstruction::DeallocateStack(pushed));
+                        }
+                        if let Some(dst) = dst {
+                                temp_instructions.push(ASMInstruction::Mov(
+                                        asm_type(dst),
+                                        Operand::Register(Register::AX),
+                                        val_to_op(*dst),
+                                ));
+                        }
+                }
+                // the named parameters used up the first registers, and any after the sixth are on the stack above the
+                // return address
+                TACTILEInstruction::VaStart(ap) => {
+                        let gp_offset = 8 * named_params.min(ARGUMENT_REGISTERS.len());
+                        let overflow_arg_area = 16 + 8 * named_params.saturating_sub(ARGUMENT_REGISTERS.len());
+                        let r11 = Operand::Register(Register::R11);
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(
+                                        AsmType::Longword,
+                                        Operand::Imm(Constant::new(gp_offset as u64, Type::UInt)),
+                                        va_list_member(*ap, 0),
+                                ),
+                                ASMInstruction::Mov(
+                                        AsmType::Longword,
+                                        Operand::Imm(Constant::new(FP_SAVE_AREA_END as u64, Type::UInt)),
+                                        va_list_member(*ap, 4),
+                                ),
+                                ASMInstruction::Lea(
+                                        Operand::Memory(
+                                                Register::BP,
+                                                i32::try_from(overflow_arg_area).expect("too many parameters"),
+                                        ),
+                                        r11,
+                                ),
+                                ASMInstruction::Mov(AsmType::Quadword, r11, va_list_member(*ap, 8)),
+                                ASMInstruction::Lea(Operand::Stack(GP_SAVE_AREA), r11),
+                                ASMInstruction::Mov(AsmType::Quadword, r11, va_list_member(*ap, 16)),
+                        ]);
+                }
+                // takes the next register from the save area while there are any left, and from the stack otherwise,
+                // leaving the address of the argument in r11
+                TACTILEInstruction::VaArg(ap, dst, stack_label, end_label) => {
+                        let r10 = Operand::Register(Register::R10);
+                        let r11 = Operand::Register(Register::R11);
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(AsmType::Longword, va_list_member(*ap, 0), r10),
+                                ASMInstruction::Cmp(
+                                        AsmType::Longword,
+                                        Operand::Imm(Constant::new(GP_SAVE_AREA as u64, Type::UInt)),
+                                        r10,
+                                ),
+                                ASMInstruction::JmpCC(CondCode::AE, *stack_label),
+                                ASMInstruction::Mov(AsmType::Quadword, va_list_member(*ap, 16), r11),
+                                ASMInstruction::Binary(ASMBinary::Add, AsmType::Quadword, r10, r11),
+                                ASMInstruction::Binary(
+                                        ASMBinary::Add,
+                                        AsmType::Longword,
+                                        Operand::Imm(Constant::UInt(8)),
+                                        va_list_member(*ap, 0),
+                                ),
+                                ASMInstruction::Jmp(*end_label),
+                                ASMInstruction::Label(*stack_label),
+                                ASMInstruction::Mov(AsmType::Quadword, va_list_member(*ap, 8), r11),
+                                ASMInstruction::Binary(
+                                        ASMBinary::Add,
+                                        AsmType::Quadword,
+                                        Operand::Imm(Constant::ULong(8)),
+                                        va_list_member(*ap, 8),
+                                ),
+                                ASMInstruction::Label(*end_label),
+                                ASMInstruction::Mov(asm_type(dst), Operand::Memory(Register::R11, 0), val_to_op(*dst)),
+                        ]);
+                }
+                TACTILEInstruction::VaCopy(src, dst) => {
+                        for offset in [0, 8, 16] {
+                                temp_instructions.push(ASMInstruction::Mov(
+                                        AsmType::Quadword,
+                                        va_list_member(*src, offset),
+                                        va_list_member(*dst, offset),
+                                ));
+                        }
+                }
+                TACTILEInstruction::FunctionAddress(id, dst) => temp_instructions.extend([
+                        ASMInstruction::FunctionAddress(*id, Operand::Register(Register::R11)),
+                        ASMInstruction::Mov(AsmType::Quadword, Operand::Register(Register::R11), val_to_op(*dst)),
+                ]),
+                // popcnt needs a CPU newer than x86-64 itself, so the bits are counted in parallel instead: in
+                // pairs, then nibbles, then bytes, which the multiply adds up into the top byte
+                TACTILEInstruction::Popcount(src, dst) => {
+                        let eax = Operand::Register(Register::AX);
+                        let edx = Operand::Register(Register::DX);
+                        let binary = |op, imm| {
+                                ASMInstruction::Binary(op, AsmType::Longword, Operand::Imm(Constant::Int(imm)), edx)
+                        };
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(AsmType::Longword, val_to_op(*src), eax),
+                                ASMInstruction::Mov(AsmType::Longword, eax, edx),
+                                binary(ASMBinary::RightShift, 1),
+                                binary(ASMBinary::And, 0x5555_5555),
+                                ASMInstruction::Binary(ASMBinary::Subtract, AsmType::Longword, edx, eax),
+                                ASMInstruction::Mov(AsmType::Longword, eax, edx),
+                                binary(ASMBinary::RightShift, 2),
+                                binary(ASMBinary::And, 0x3333_3333),
+                                ASMInstruction::Binary(
+                                        ASMBinary::And,
+                                        AsmType::Longword,
+                                        Operand::Imm(Constant::Int(0x3333_3333)),
+                                        eax,
+                                ),
+                                ASMInstruction::Binary(ASMBinary::Add, AsmType::Longword, edx, eax),
+                                ASMInstruction::Mov(AsmType::Longword, eax, edx),
+                                binary(ASMBinary::RightShift, 4),
+                                ASMInstruction::Binary(ASMBinary::Add, AsmType::Longword, eax, edx),
+                                binary(ASMBinary::And, 0x0F0F_0F0F),
+                                binary(ASMBinary::Multiply, 0x0101_0101),
+                                binary(ASMBinary::RightShift, 24),
+                                ASMInstruction::Mov(AsmType::Longword, edx, val_to_op(*dst)),
+                        ]);
+                }
+                // the highest set bit is 31 minus the number of zeros above it, and subtracting from 31 is the same
+                // as flipping the low 5 bits
+                TACTILEInstruction::CountLeadingZeros(src, dst) => temp_instructions.extend([
+                        ASMInstruction::Bsr(AsmType::Longword, val_to_op(*src), val_to_op(*dst)),
+                        ASMInstruction::Binary(
+                                ASMBinary::XOr,
+                                AsmType::Longword,
+                                Operand::Imm(Constant::Int(31)),
+                                val_to_op(*dst),
+                        ),
+                ]),
+                TACTILEInstruction::Unreachable => temp_instructions.push(ASMInstruction::Ud2),
+                TACTILEInstruction::Fence => temp_instructions.push(ASMInstruction::Mfence),
+                // xchg with memory is locked without being asked to be
+                TACTILEInstruction::AtomicExchange(src, object, dst)
+                | TACTILEInstruction::AtomicFetchAdd(src, object, dst) => {
+                        let t = asm_type(object);
+                        let r10 = Operand::Register(Register::R10);
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(t, val_to_op(*src), r10),
+                                if let TACTILEInstruction::AtomicExchange(..) = value {
+                                        ASMInstruction::Xchg(t, r10, val_to_op(*object))
+                                } else {
+                                        ASMInstruction::LockXadd(t, r10, val_to_op(*object))
+                                },
+                                ASMInstruction::Mov(t, r10, val_to_op(*dst)),
+                        ]);
+                }
+                TACTILEInstruction::AtomicCompareExchange(object, expected, desired, dst) => {
+                        let t = asm_type(object);
+                        let ax = Operand::Register(Register::AX);
+                        let r10 = Operand::Register(Register::R10);
+                        temp_instructions.extend([
+                                ASMInstruction::Mov(t, val_to_op(*expected), ax),
+                                ASMInstruction::Mov(t, val_to_op(*desired), r10),
+                                ASMInstruction::LockCmpxchg(t, r10, val_to_op(*object)),
+                                // when it succeeded ax still holds what was expected, so writing it back changes nothing
+                                ASMInstruction::Mov(t, ax, val_to_op(*expected)),
+                                ASMInstruction::Mov(asm_type(dst), ZERO, val_to_op(*dst)),
+                                ASMInstruction::SetCC(CondCode::E, val_to_op(*dst)),
+                        ]);
+                }
+                TACTILEInstruction::Asm(AsmStatement {
+                        pieces,
+                        operands,
+                        clobbered,
+                }) => {
+                        // nothing is kept in registers between instructions, so "memory" and "cc" need no handling.
+                        // a memory operand that's a thread-local variable from another file is at an address loaded
+                        // from the GOT, which takes a register from the same ones
+                        let clobbered: Vec<_> = clobbered.iter().filter_map(|f| Register::named(f)).collect();
+                        let mut free = ASM_REGISTERS.iter().filter(|f| !clobbered.contains(f));
+                        let registers: Vec<_> = operands
+                                .iter()
+                                .map(|(constraint, value)| {
+                                        (constraint.kind == OperandKind::Register
+                                                || initial_exec(*value, symbols).is_some())
+                                        .then(|| *free.next().expect("type checker counts registers"))
+                                })
+                                .collect();
 
-                () = temp_instructions.map(|f| last_pass(f, &mut instructions)).collect();
+                        // the callee saved registers the asm touches have to survive it
+                        let mut saved: Vec<_> = registers.iter().flatten().chain(&clobbered).copied().collect();
+                        saved.retain(|f| f.is_callee_saved());
+                        saved.sort_by_key(|&f| f as u8);
+                        saved.dedup();
+                        let padding = saved.len() % 2 == 1;
+                        if padding {
+                                temp_instructions.push(ASMInstruction::AllocateStack(8));
+                        }
+                        for &register in &saved {
+                                temp_instructions.push(ASMInstruction::Push(Operand::Register(register)));
+                        }
 
-                instructions[0] = ASMInstruction::AllocateStack(stack_max);
+                        let mut template_operands = Vec::with_capacity(operands.len());
+                        for ((constraint, value), register) in operands.iter().zip(&registers) {
+                                let t = asm_type(value);
+                                let op = match (register, initial_exec(*value, symbols)) {
+                                        (Some(register), Some(name)) => {
+                                                temp_instructions.push(ASMInstruction::Mov(
+                                                        AsmType::Quadword,
+                                                        Operand::GotTpoff(name),
+                                                        Operand::Register(*register),
+                                                ));
+                                                Operand::ThreadLocalAt(*register)
+                                        }
+                                        (Some(register), None) => {
+                                                let op = Operand::Register(*register);
+                                                if constraint.read {
+                                                        temp_instructions.push(ASMInstruction::Mov(
+                                                                t,
+                                                                val_to_op(*value),
+                                                                op,
+                                                        ));
+                                                }
+                                                op
+                                        }
+                                        (None, _) => val_to_op(*value),
+                                };
+                                template_operands.push((t, op));
+                        }
+                        temp_instructions.push(ASMInstruction::InlineAsm(pieces.clone(), template_operands));
+                        for ((constraint, value), register) in operands.iter().zip(&registers) {
+                                if let (Some(register), OperandKind::Register, true) =
+                                        (register, constraint.kind, constraint.written)
+                                {
+                                        temp_instructions.push(ASMInstruction::Mov(
+                                                asm_type(value),
+                                                Operand::Register(*register),
+                                                val_to_op(*value),
+                                        ));
+                                }
+                        }
+
+                        for &register in saved.iter().rev() {
+                                temp_instructions.push(ASMInstruction::Pop(register));
+                        }
+                        if padding {
+                                temp_instructions.push(ASMInstruction::DeallocateStack(8));
+                        }
+                }
+        };
+
+        () = value.instructions.iter().map(from_tactile).collect();
+
+        let realign = realignment(&temp_instructions, symbols);
+        let mut frame = StackFrame {
+                offsets: HashMap::new(),
+                size: if value.variadic { GP_SAVE_AREA } else { 0 },
+                bias: if realign.is_some() { 8 } else { 0 },
+                symbols,
+        };
+        let temp_instructions: Vec<_> = if realign.is_some() {
+                let mut realigned = Vec::with_capacity(temp_instructions.len());
+                () = temp_instructions
+                        .into_iter()
+                        .map(|f| realigned_pass(f, &mut realigned))
+                        .collect();
+                realigned
+        } else {
+                temp_instructions
+        };
+        let temp_instructions: Vec<_> = temp_instructions
+                .into_iter()
+                .map(|f| pseudo_pass(f, &mut frame))
+                .collect();
+
+        let mut instructions = Vec::with_capacity(temp_instructions.len() * 2);
+        instructions.push(ASMInstruction::AllocateStack(0));
+
+        () = temp_instructions
+                .into_iter()
+                .map(|f| last_pass(f, &mut instructions))
+                .collect();
+        let mut with_thread_locals = Vec::with_capacity(instructions.len());
+        () = instructions
+                .into_iter()
+                .map(|f| thread_local_pass(f, &mut with_thread_locals))
+                .collect();
+        let mut instructions = with_thread_locals;
+
+        // the stack pointer has to stay 16 byte aligned
+        instructions[0] = ASMInstruction::AllocateStack((frame.size + frame.bias).next_multiple_of(16) - frame.bias);
+
+        ASMFunction {
+                identifier,
+                global: value.global,
+                instructions,
+                realign,
+        }
+}
+
+// the strictest alignment of the automatic variables, when it's more than the stack has. the frame is then
+// realigned: rsp is masked down to it and a second frame is pushed on top, so rbp is 8 below a multiple of it
+fn realignment(instructions: &[ASMInstruction], symbols: &HashMap<Identifier, Symbol>) -> Option<usize> {
+        let mut alignment = MAX_ALIGNMENT;
+        for i in instructions {
+                map_operands(i.clone(), |f| {
+                        if let Operand::Pseudo(n) | Operand::PseudoMember(n, _) = f {
+                                let symbol = symbols[&Identifier(n)];
+                                if symbol.storage.is_none() {
+                                        alignment = alignment.max(symbol.alignment());
+                                }
+                        }
+                        f
+                });
+        }
+        (alignment > MAX_ALIGNMENT).then_some(alignment)
+}
+
+// in a realigned frame, the arguments on the stack are above the outer frame, whose base is what the inner one saved
+fn realigned_pass(i: ASMInstruction, instructions: &mut Vec<ASMInstruction>) {
+        let mut incoming = false;
+        let i = map_operands(i, |f| match f {
+                Operand::Memory(Register::BP, displacement) if displacement > 0 => {
+                        incoming = true;
+                        Operand::Memory(Register::R11, displacement)
+                }
+                _ => f,
+        });
+        if incoming {
+                instructions.push(ASMInstruction::Mov(
+                        AsmType::Quadword,
+                        Operand::Memory(Register::BP, 0),
+                        Operand::Register(Register::R11),
+                ));
+        }
+        instructions.push(i);
+}
+
+fn is_memory(operand: Operand) -> bool {
+        matches!(
+                operand,
+                Operand::Stack(_)
+                        | Operand::Data(..)
+                        | Operand::Memory(..)
+                        | Operand::ThreadLocal(..)
+                        | Operand::InitialExec(_)
+                        | Operand::ThreadLocalAt(_)
+        )
+}
 
-                ASMFunction {
-                        identifier,
-                        instructions,
+// instructions other than mov can only take 32 bit immediates, which get sign extended to 64 bits
+fn is_large_imm(operand: Operand, asm_type: AsmType) -> bool {
+        match operand {
+                Operand::Imm(constant) => {
+                        asm_type == AsmType::Quadword && i32::try_from(constant.as_u64() as i64).is_err()
                 }
+                _ => false,
         }
 }
 
 fn last_pass(i: ASMInstruction, instructions: &mut Vec<ASMInstruction>) {
+        let r10 = Operand::Register(Register::R10);
+        let r11 = Operand::Register(Register::R11);
+
         match i {
-                ASMInstruction::Mov(Operand::Stack(src), Operand::Stack(dest)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Stack(src),
-                                Operand::Register(Register::R10),
-                        ));
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Register(Register::R10),
-                                Operand::Stack(dest),
-                        ));
+                ASMInstruction::Mov(t, src, dst) if is_memory(src) && is_memory(dst) => {
+                        instructions.push(ASMInstruction::Mov(t, src, r10));
+                        instructions.push(ASMInstruction::Mov(t, r10, dst));
                 }
-                ASMInstruction::IDiv(Operand::Imm(aconstant)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Imm(aconstant),
-                                Operand::Register(Register::R10),
-                        ));
-                        instructions.push(ASMInstruction::IDiv(Operand::Register(Register::R10)));
+                ASMInstruction::Mov(t, src, dst) if is_large_imm(src, t) && is_memory(dst) => {
+                        instructions.push(ASMInstruction::Mov(t, src, r10));
+                        instructions.push(ASMInstruction::Mov(t, r10, dst));
                 }
-                ASMInstruction::Binary(ASMBinary::Add, Operand::Stack(src), Operand::Stack(dst)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Stack(src),
-                                Operand::Register(Register::R10),
-                        ));
-                        instructions.push(ASMInstruction::Binary(
-                                ASMBinary::Add,
-                                Operand::Register(Register::R10),
-                                Operand::Stack(dst),
-                        ));
+                ASMInstruction::Movsx(src_type, dst_type, src, dst) => {
+                        let src = if let Operand::Imm(_) = src {
+                                instructions.push(ASMInstruction::Mov(src_type, src, r10));
+                                r10
+                        } else {
+                                src
+                        };
+                        if let Operand::Register(_) = dst {
+                                instructions.push(ASMInstruction::Movsx(src_type, dst_type, src, dst));
+                        } else {
+                                instructions.push(ASMInstruction::Movsx(src_type, dst_type, src, r11));
+                                instructions.push(ASMInstruction::Mov(dst_type, r11, dst));
+                        }
                 }
-                ASMInstruction::Binary(ASMBinary::Subtract, Operand::Stack(src), Operand::Stack(dst)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Stack(src),
-                                Operand::Register(Register::R10),
-                        ));
-                        instructions.push(ASMInstruction::Binary(
-                                ASMBinary::Subtract,
-                                Operand::Register(Register::R10),
-                                Operand::Stack(dst),
-                        ));
+                ASMInstruction::MovZeroExtend(src_type, dst_type, src, dst) => {
+                        let src = if let Operand::Imm(_) = src {
+                                instructions.push(ASMInstruction::Mov(src_type, src, r10));
+                                r10
+                        } else {
+                                src
+                        };
+                        if let Operand::Register(_) = dst {
+                                instructions.push(ASMInstruction::MovZeroExtend(src_type, dst_type, src, dst));
+                        } else {
+                                instructions.push(ASMInstruction::MovZeroExtend(src_type, dst_type, src, r11));
+                                instructions.push(ASMInstruction::Mov(dst_type, r11, dst));
+                        }
                 }
-                ASMInstruction::Binary(ASMBinary::Multiply, src, Operand::Stack(dst)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Stack(dst),
-                                Operand::Register(Register::R11),
-                        ));
-                        instructions.push(ASMInstruction::Binary(
-                                ASMBinary::Multiply,
-                                src,
-                                Operand::Register(Register::R11),
-                        ));
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Register(Register::R11),
-                                Operand::Stack(dst),
-                        ));
+                // it can't take an immediate, and only writes to a register
+                ASMInstruction::Bsr(t, src, dst) => {
+                        let src = if let Operand::Imm(_) = src {
+                                instructions.push(ASMInstruction::Mov(t, src, r10));
+                                r10
+                        } else {
+                                src
+                        };
+                        if let Operand::Register(_) = dst {
+                                instructions.push(ASMInstruction::Bsr(t, src, dst));
+                        } else {
+                                instructions.push(ASMInstruction::Bsr(t, src, r11));
+                                instructions.push(ASMInstruction::Mov(t, r11, dst));
+                        }
                 }
-                ASMInstruction::Cmp(Operand::Stack(op1), Operand::Stack(op2)) => {
-                        instructions.push(ASMInstruction::Mov(
-                                Operand::Stack(op1),
-                                Operand::Register(Register::R10),
-                        ));
-                        instructions.push(ASMInstruction::Cmp(
-                                Operand::Register(Register::R10),
-                                Operand::Stack(op2),
-                        ));
+                ASMInstruction::IDiv(t, Operand::Imm(aconstant)) => {
+                        instructions.push(ASMInstruction::Mov(t, Operand::Imm(aconstant), r10));
+                        instructions.push(ASMInstruction::IDiv(t, r10));
+                }
+                ASMInstruction::Div(t, Operand::Imm(aconstant)) => {
+                        instructions.push(ASMInstruction::Mov(t, Operand::Imm(aconstant), r10));
+                        instructions.push(ASMInstruction::Div(t, r10));
+                }
+                // the shift count has to be an immediate or in %cl
+                ASMInstruction::Binary(
+                        op @ (ASMBinary::LeftShift
+                        | ASMBinary::LeftShiftAssign
+                        | ASMBinary::RightShift
+                        | ASMBinary::RightShiftAssign
+                        | ASMBinary::ArithmeticRightShift),
+                        t,
+                        src,
+                        dst,
+                ) if !matches!(src, Operand::Imm(_)) => {
+                        instructions.push(ASMInstruction::Mov(t, src, Operand::Register(Register::CX)));
+                        instructions.push(ASMInstruction::Binary(op, t, Operand::Register(Register::CX), dst));
+                }
+                ASMInstruction::Binary(op @ (ASMBinary::Multiply | ASMBinary::MultiplyAssign), t, src, dst)
+                        if is_memory(dst) =>
+                {
+                        let src = if is_large_imm(src, t) {
+                                instructions.push(ASMInstruction::Mov(t, src, r10));
+                                r10
+                        } else {
+                                src
+                        };
+                        instructions.push(ASMInstruction::Mov(t, dst, r11));
+                        instructions.push(ASMInstruction::Binary(op, t, src, r11));
+                        instructions.push(ASMInstruction::Mov(t, r11, dst));
                 }
-                ASMInstruction::Cmp(op1, Operand::Imm(op2)) => {
-                        instructions.push(ASMInstruction::Mov(Operand::Imm(op2), Operand::Register(Register::R11)));
-                        instructions.push(ASMInstruction::Cmp(op1, Operand::Register(Register::R11)));
+                ASMInstruction::Binary(op, t, src, dst)
+                        if (is_memory(src) && is_memory(dst)) || is_large_imm(src, t) =>
+                {
+                        instructions.push(ASMInstruction::Mov(t, src, r10));
+                        instructions.push(ASMInstruction::Binary(op, t, r10, dst));
+                }
+                ASMInstruction::Cmp(t, op1, op2) if (is_memory(op1) && is_memory(op2)) || is_large_imm(op1, t) => {
+                        instructions.push(ASMInstruction::Mov(t, op1, r10));
+                        last_pass(ASMInstruction::Cmp(t, r10, op2), instructions);
+                }
+                // push only takes 32 bit immediates
+                ASMInstruction::Push(src) if is_large_imm(src, AsmType::Quadword) => {
+                        instructions.push(ASMInstruction::Mov(AsmType::Quadword, src, r10));
+                        instructions.push(ASMInstruction::Push(r10));
+                }
+                ASMInstruction::Cmp(t, op1, Operand::Imm(op2)) => {
+                        instructions.push(ASMInstruction::Mov(t, Operand::Imm(op2), r11));
+                        instructions.push(ASMInstruction::Cmp(t, op1, r11));
                 }
                 _ => instructions.push(i),
         }
 }
 
-fn pseudo_pass(value: ASMInstruction, stack_max: &mut usize) -> ASMInstruction {
+// a thread-local variable defined in another file is at an offset from %fs that has to be loaded from the GOT first.
+// last_pass has left at most one memory operand, so an instruction needs one register for it, and inline asm has
+// already been given its own. a load into a register can use that register, and otherwise it's whichever of r11 and
+// r10 the instruction doesn't use, as neither is kept between instructions unless the next one uses it
+fn thread_local_pass(i: ASMInstruction, instructions: &mut Vec<ASMInstruction>) {
+        let loaded = match i {
+                ASMInstruction::Mov(_, Operand::InitialExec(_), Operand::Register(register))
+                | ASMInstruction::Movsx(_, _, Operand::InitialExec(_), Operand::Register(register))
+                | ASMInstruction::MovZeroExtend(_, _, Operand::InitialExec(_), Operand::Register(register)) => {
+                        Some(register)
+                }
+                _ => None,
+        };
+        let mut used = vec![];
+        let mut name = None;
+        let i = map_operands(i, |operand| {
+                match operand {
+                        Operand::Register(register) | Operand::Memory(register, _) => used.push(register),
+                        Operand::InitialExec(initial_exec) => name = Some(initial_exec),
+                        _ => {}
+                }
+                operand
+        });
+        let Some(name) = name else {
+                instructions.push(i);
+                return;
+        };
+
+        let register = loaded
+                .into_iter()
+                .chain([Register::R11, Register::R10].into_iter().filter(|f| !used.contains(f)))
+                .next()
+                .expect("an instruction with a memory operand uses at most one of r10 and r11");
+        instructions.push(ASMInstruction::Mov(
+                AsmType::Quadword,
+                Operand::GotTpoff(name),
+                Operand::Register(register),
+        ));
+        instructions.push(map_operands(i, |operand| match operand {
+                Operand::InitialExec(_) => Operand::ThreadLocalAt(register),
+                _ => operand,
+        }));
+}
+
+// the name of a thread-local variable that's only declared here, and so is found through the GOT
+fn initial_exec(value: Value, symbols: &HashMap<Identifier, Symbol>) -> Option<AIdentifier> {
+        let Value::Var(identifier) = value else {
+                return None;
+        };
+        symbols[&identifier]
+                .storage
+                .filter(|f| f.thread_local && f.init == StaticInit::NoInitializer)
+                .map(|f| f.name)
+}
+
+// the same instruction with f applied to each of its operands
+fn map_operands(i: ASMInstruction, mut f: impl FnMut(Operand) -> Operand) -> ASMInstruction {
+        match i {
+                ASMInstruction::Mov(t, src, dst) => ASMInstruction::Mov(t, f(src), f(dst)),
+                ASMInstruction::Movsx(src_type, dst_type, src, dst) => {
+                        ASMInstruction::Movsx(src_type, dst_type, f(src), f(dst))
+                }
+                ASMInstruction::MovZeroExtend(src_type, dst_type, src, dst) => {
+                        ASMInstruction::MovZeroExtend(src_type, dst_type, f(src), f(dst))
+                }
+                ASMInstruction::Lea(src, dst) => ASMInstruction::Lea(f(src), f(dst)),
+                ASMInstruction::Unary(op, t, operand) => ASMInstruction::Unary(op, t, f(operand)),
+                ASMInstruction::Push(operand) => ASMInstruction::Push(f(operand)),
+                ASMInstruction::CallIndirect(operand) => ASMInstruction::CallIndirect(f(operand)),
+                ASMInstruction::FunctionAddress(name, dst) => ASMInstruction::FunctionAddress(name, f(dst)),
+                ASMInstruction::Cmp(t, left, right) => ASMInstruction::Cmp(t, f(left), f(right)),
+                ASMInstruction::Binary(op, t, src, dst) => ASMInstruction::Binary(op, t, f(src), f(dst)),
+                ASMInstruction::IDiv(t, operand) => ASMInstruction::IDiv(t, f(operand)),
+                ASMInstruction::Div(t, operand) => ASMInstruction::Div(t, f(operand)),
+                ASMInstruction::SetCC(cond_code, operand) => ASMInstruction::SetCC(cond_code, f(operand)),
+                ASMInstruction::Bsr(t, src, dst) => ASMInstruction::Bsr(t, f(src), f(dst)),
+                ASMInstruction::Xchg(t, src, dst) => ASMInstruction::Xchg(t, f(src), f(dst)),
+                ASMInstruction::LockXadd(t, src, dst) => ASMInstruction::LockXadd(t, f(src), f(dst)),
+                ASMInstruction::LockCmpxchg(t, src, dst) => ASMInstruction::LockCmpxchg(t, f(src), f(dst)),
+                ASMInstruction::InlineAsm(pieces, operands) => ASMInstruction::InlineAsm(
+                        pieces,
+                        operands.into_iter().map(|(t, operand)| (t, f(operand))).collect(),
+                ),
+                ASMInstruction::AllocateStack(_)
+                | ASMInstruction::DeallocateStack(_)
+                | ASMInstruction::Call(_)
+                | ASMInstruction::Cdq(_)
+                | ASMInstruction::Jmp(_)
+                | ASMInstruction::JmpCC(..)
+                | ASMInstruction::Label(_)
+                | ASMInstruction::Ret
+                | ASMInstruction::Ud2
+                | ASMInstruction::Mfence
+                | ASMInstruction::Pop(_) => i,
+        }
+}
+
+// hands out stack slots to pseudo registers, aligned to their type's alignment
+struct StackFrame<'a> {
+        offsets: HashMap<usize, usize>,
+        size: usize,
+        // how far rbp is below a multiple of every alignment in the frame
+        bias: usize,
+        symbols: &'a HashMap<Identifier, Symbol>,
+}
+
+fn pseudo_pass(value: ASMInstruction, frame: &mut StackFrame) -> ASMInstruction {
         match value {
-                ASMInstruction::Mov(left, right) => {
-                        let left = pseudo_to_stack_operand(left, stack_max);
-                        let right = pseudo_to_stack_operand(right, stack_max);
+                ASMInstruction::Mov(t, left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
 
-                        ASMInstruction::Mov(left, right)
+                        ASMInstruction::Mov(t, left, right)
                 }
-                ASMInstruction::Cmp(left, right) => {
-                        let left = pseudo_to_stack_operand(left, stack_max);
-                        let right = pseudo_to_stack_operand(right, stack_max);
+                ASMInstruction::Movsx(src_type, dst_type, left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
 
-                        ASMInstruction::Cmp(left, right)
+                        ASMInstruction::Movsx(src_type, dst_type, left, right)
+                }
+                ASMInstruction::MovZeroExtend(src_type, dst_type, left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
+
+                        ASMInstruction::MovZeroExtend(src_type, dst_type, left, right)
+                }
+                ASMInstruction::Lea(left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
+
+                        ASMInstruction::Lea(left, right)
+                }
+                ASMInstruction::Bsr(t, left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
+
+                        ASMInstruction::Bsr(t, left, right)
+                }
+                ASMInstruction::Cmp(t, left, right) => {
+                        let left = pseudo_to_stack_operand(left, frame);
+                        let right = pseudo_to_stack_operand(right, frame);
+
+                        ASMInstruction::Cmp(t, left, right)
+                }
+                ASMInstruction::Xchg(t, left, right) => {
+                        ASMInstruction::Xchg(t, left, pseudo_to_stack_operand(right, frame))
+                }
+                ASMInstruction::LockXadd(t, left, right) => {
+                        ASMInstruction::LockXadd(t, left, pseudo_to_stack_operand(right, frame))
+                }
+                ASMInstruction::LockCmpxchg(t, left, right) => {
+                        ASMInstruction::LockCmpxchg(t, left, pseudo_to_stack_operand(right, frame))
                 }
                 ASMInstruction::SetCC(left, right) => {
-                        let right = pseudo_to_stack_operand(right, stack_max);
+                        let right = pseudo_to_stack_operand(right, frame);
 
                         ASMInstruction::SetCC(left, right)
                 }
-                ASMInstruction::Unary(unop, operand) => {
-                        ASMInstruction::Unary(unop, pseudo_to_stack_operand(operand, stack_max))
+                ASMInstruction::Unary(unop, t, operand) => {
+                        ASMInstruction::Unary(unop, t, pseudo_to_stack_operand(operand, frame))
                 }
-                ASMInstruction::Binary(binop, left, right) => ASMInstruction::Binary(
+                ASMInstruction::Binary(binop, t, left, right) => ASMInstruction::Binary(
                         binop,
-                        pseudo_to_stack_operand(left, stack_max),
-                        pseudo_to_stack_operand(right, stack_max),
+                        t,
+                        pseudo_to_stack_operand(left, frame),
+                        pseudo_to_stack_operand(right, frame),
+                ),
+                ASMInstruction::Push(operand) => ASMInstruction::Push(pseudo_to_stack_operand(operand, frame)),
+                ASMInstruction::IDiv(t, left) => ASMInstruction::IDiv(t, pseudo_to_stack_operand(left, frame)),
+                ASMInstruction::Div(t, left) => ASMInstruction::Div(t, pseudo_to_stack_operand(left, frame)),
+                ASMInstruction::InlineAsm(pieces, operands) => ASMInstruction::InlineAsm(
+                        pieces,
+                        operands.into_iter()
+                                .map(|(t, operand)| (t, pseudo_to_stack_operand(operand, frame)))
+                                .collect(),
                 ),
-                ASMInstruction::IDiv(left) => ASMInstruction::IDiv(pseudo_to_stack_operand(left, stack_max)),
                 _ => value,
         }
 }
 
-fn pseudo_to_stack_operand(value: Operand, stack_max: &mut usize) -> Operand {
+fn pseudo_to_stack_operand(value: Operand, frame: &mut StackFrame) -> Operand {
         match value {
                 Operand::Pseudo(n) => {
-                        if *stack_max < n {
-                                *stack_max = n;
+                        if let Some(storage) = frame.symbols[&Identifier(n)].storage {
+                                let suffix = storage.local.then_some(n);
+                                return match (storage.thread_local, storage.init) {
+                                        (false, _) => Operand::Data(storage.name, suffix),
+                                        (true, StaticInit::NoInitializer) => Operand::InitialExec(storage.name),
+                                        (true, _) => Operand::ThreadLocal(storage.name, suffix),
+                                };
+                        }
+                        if let Some(&offset) = frame.offsets.get(&n) {
+                                return Operand::Stack(offset);
                         }
-                        Operand::Stack(n)
+                        let symbol = frame.symbols[&Identifier(n)];
+                        frame.size = (frame.size + frame.bias + symbol.c_type.size())
+                                .next_multiple_of(symbol.alignment())
+                                - frame.bias;
+                        frame.offsets.insert(n, frame.size);
+                        Operand::Stack(frame.size)
                 }
+                // the slot starts offset bytes below the base pointer, and its members are above that
+                Operand::PseudoMember(n, member_offset) => match pseudo_to_stack_operand(Operand::Pseudo(n), frame) {
+                        Operand::Stack(offset) => Operand::Stack(offset - member_offset),
+                        _ => unreachable!("type checker makes va_lists automatic"),
+                },
                 _ => value,
         }
 }
@@ -1,5 +1,6 @@
 use crate::{
-        parse::nodes::{AIdentifier, Binop},
+        parse::nodes::{AIdentifier, Binop, TemplatePiece},
+        semantic_analysis::type_checker::Type,
         tactile::tree::{Constant, Label},
 };
 
@@ -15,16 +16,105 @@ pub enum Operand {
         Register(Register),
         //usize is number of temporary variable
         Pseudo(usize),
-        //usize is stack size
+        //usize is how many bytes below the base pointer it is
         Stack(usize),
+        // name of the symbol, and for static locals the number that makes it unique
+        Data(AIdentifier, Option<usize>),
+        // the bytes of a temporary variable too big for a register, starting at the given offset into it
+        PseudoMember(usize, usize),
+        // the address in a register plus a displacement
+        Memory(Register, i32),
+        // a thread-local variable defined in this file, which the linker puts at a fixed offset from the thread
+        // pointer in %fs (local-exec)
+        ThreadLocal(AIdentifier, Option<usize>),
+        // one defined somewhere else, whose offset is in the GOT (initial-exec). thread_local_pass loads it into a
+        // register, and the variable is then at ThreadLocalAt that register
+        InitialExec(AIdentifier),
+        ThreadLocalAt(Register),
+        // the GOT entry of an InitialExec variable
+        GotTpoff(AIdentifier),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
         AX,
+        CX,
         DX,
+        DI,
+        SI,
+        R8,
+        R9,
         R10,
         R11,
+        BP,
+        // callee saved, so only ever used by inline asm, which saves them first
+        BX,
+        R12,
+        R13,
+        R14,
+        R15,
+}
+
+impl Register {
+        // the register an asm clobber names, by any of its names. rsp and rbp hold the stack frame, so they can't be
+        // clobbered
+        pub fn named(name: &[u8]) -> Option<Register> {
+                let name = name.strip_prefix(b"%").unwrap_or(name);
+                Some(match name {
+                        b"rax" | b"eax" | b"ax" | b"al" | b"ah" => Register::AX,
+                        b"rbx" | b"ebx" | b"bx" | b"bl" | b"bh" => Register::BX,
+                        b"rcx" | b"ecx" | b"cx" | b"cl" | b"ch" => Register::CX,
+                        b"rdx" | b"edx" | b"dx" | b"dl" | b"dh" => Register::DX,
+                        b"rsi" | b"esi" | b"si" | b"sil" => Register::SI,
+                        b"rdi" | b"edi" | b"di" | b"dil" => Register::DI,
+                        b"r8" | b"r8d" | b"r8w" | b"r8b" => Register::R8,
+                        b"r9" | b"r9d" | b"r9w" | b"r9b" => Register::R9,
+                        b"r10" | b"r10d" | b"r10w" | b"r10b" => Register::R10,
+                        b"r11" | b"r11d" | b"r11w" | b"r11b" => Register::R11,
+                        b"r12" | b"r12d" | b"r12w" | b"r12b" => Register::R12,
+                        b"r13" | b"r13d" | b"r13w" | b"r13b" => Register::R13,
+                        b"r14" | b"r14d" | b"r14w" | b"r14b" => Register::R14,
+                        b"r15" | b"r15d" | b"r15w" | b"r15b" => Register::R15,
+                        _ => return None,
+                })
+        }
+
+        pub fn is_callee_saved(self) -> bool {
+                matches!(
+                        self,
+                        Register::BX | Register::R12 | Register::R13 | Register::R14 | Register::R15
+                )
+        }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmType {
+        Byte,
+        Word,
+        Longword,
+        Quadword,
+}
+
+impl AsmType {
+        pub fn size(&self) -> usize {
+                match self {
+                        AsmType::Byte => 1,
+                        AsmType::Word => 2,
+                        AsmType::Longword => 4,
+                        AsmType::Quadword => 8,
+                }
+        }
+}
+
+impl From<Type> for AsmType {
+        fn from(value: Type) -> Self {
+                match value.size() {
+                        1 => AsmType::Byte,
+                        2 => AsmType::Word,
+                        4 => AsmType::Longword,
+                        _ => AsmType::Quadword,
+                }
+        }
 }
 impl From<Register> for Operand {
         fn from(value: Register) -> Self {
@@ -32,21 +122,48 @@ impl From<Register> for Operand {
         }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum ASMInstruction {
         // src, dst
-        Mov(Operand, Operand),
-        Unary(ASMUnary, Operand),
+        Mov(AsmType, Operand, Operand),
+        // src type, dst type, src, dst
+        Movsx(AsmType, AsmType, Operand, Operand),
+        MovZeroExtend(AsmType, AsmType, Operand, Operand),
+        // src, dst, where dst is a register
+        Lea(Operand, Operand),
+        Unary(ASMUnary, AsmType, Operand),
         AllocateStack(usize),
-        Cmp(Operand, Operand),
-        Binary(ASMBinary, Operand, Operand),
-        IDiv(Operand),
-        Cdq,
+        DeallocateStack(usize),
+        // always 8 bytes
+        Push(Operand),
+        Call(AIdentifier),
+        // call *operand
+        CallIndirect(Operand),
+        // function, dst, where dst is a register
+        FunctionAddress(AIdentifier, Operand),
+        Cmp(AsmType, Operand, Operand),
+        Binary(ASMBinary, AsmType, Operand, Operand),
+        IDiv(AsmType, Operand),
+        Div(AsmType, Operand),
+        Cdq(AsmType),
         Jmp(Label),
         JmpCC(CondCode, Label),
         SetCC(CondCode, Operand),
         Label(Label),
         Ret,
+        // the index of the highest set bit, src, dst, where dst is a register
+        Bsr(AsmType, Operand, Operand),
+        // an invalid instruction, which traps
+        Ud2,
+        // src, dst, where src is a register and dst is the atomic object. dst's old value ends up in src
+        Xchg(AsmType, Operand, Operand),
+        LockXadd(AsmType, Operand, Operand),
+        // replaces dst with src if it holds what's in ax, and otherwise loads it into ax. sets ZF if it replaced it
+        LockCmpxchg(AsmType, Operand, Operand),
+        Mfence,
+        Pop(Register),
+        // the template and the operands it refers to, each with the size it's used at
+        InlineAsm(Vec<TemplatePiece>, Vec<(AsmType, Operand)>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -57,6 +174,10 @@ pub enum CondCode {
         GE,
         L,
         LE,
+        A,
+        AE,
+        B,
+        BE,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -66,6 +187,7 @@ pub enum ASMBinary {
         Multiply,
         LeftShift,
         RightShift,
+        ArithmeticRightShift,
         Or,
         XOr,
         And,
@@ -82,7 +204,6 @@ pub enum ASMBinary {
 pub enum ASMUnary {
         Increment,
         Decrement,
-        Not,
         Negate,
         Complement,
 }
@@ -118,9 +239,31 @@ impl TryFrom<Binop> for ASMBinary {
 #[derive(Debug, Clone)]
 pub struct ASMProgram {
         pub functions: Vec<ASMFunction>,
+        pub static_variables: Vec<ASMStaticVariable>,
 }
 #[derive(Debug, Clone)]
 pub struct ASMFunction {
         pub identifier: AIdentifier,
+        pub global: bool,
         pub instructions: Vec<ASMInstruction>,
+        // what the frame is realigned to when an automatic variable is aligned more strictly than the stack
+        pub realign: Option<usize>,
+}
+#[derive(Debug, Clone)]
+pub struct ASMStaticVariable {
+        pub name: AIdentifier,
+        pub suffix: Option<usize>,
+        pub global: bool,
+        pub read_only: bool,
+        pub thread_local: bool,
+        pub alignment: usize,
+        pub init: ASMStaticInit,
+}
+
+// what a static variable holds before the program starts
+#[derive(Debug, Clone, Copy)]
+pub enum ASMStaticInit {
+        Constant(Constant),
+        // a function's address, which takes up 8 bytes whatever it points to
+        FunctionAddress(AIdentifier),
 }
@@ -1,8 +1,11 @@
 use crate::{
-        parse::nodes::{AConstant, AIdentifier},
+        parse::nodes::{AIdentifier, TemplatePiece},
         tactile::tree::Constant,
         toasm::{
-                nodes::{ASMBinary, ASMFunction, ASMInstruction, ASMUnary, CondCode, Operand, Register},
+                nodes::{
+                        ASMBinary, ASMFunction, ASMInstruction, ASMStaticInit, ASMStaticVariable, ASMUnary, AsmType,
+                        CondCode, Operand, Register,
+                },
                 Compiled,
         },
         State,
@@ -16,173 +19,512 @@ impl State for Written {}
 pub fn write(state: Compiled, code: &[u8]) -> Written {
         let mut written = vec![];
 
-        for i in state.program.functions {
-                written.extend(func_to_vec(i, code));
+        let defined: Vec<_> = state
+                .program
+                .functions
+                .iter()
+                .map(|f| &code[f.identifier.start..f.identifier.start + f.identifier.len])
+                .collect();
+        for i in &state.program.functions {
+                written.extend(func_to_vec(i, code, &defined));
         }
+        for i in state.program.static_variables {
+                written.extend(static_variable_to_vec(i, code));
+        }
+
+        written.extend_from_slice(b"\t.section .note.GNU-stack,\"\",@progbits\n");
 
         Written { code: written }
 }
 
-pub static EAX: &[u8] = b"%eax";
-pub static AX: &[u8] = b"%eax";
-
-pub static R10D: &[u8] = b"%r10d";
-pub static R10B: &[u8] = b"%r10b";
-
-pub static DX: &[u8] = b"%edx";
-pub static DL: &[u8] = b"%dl";
-
-pub static R11D: &[u8] = b"%r11d";
-pub static R11B: &[u8] = b"%r11b";
-
 pub static PERCENT: u8 = b'%';
 pub static DOLLAR: u8 = b'$';
 
-pub static NOTL: &[u8] = b"\tnotl ";
-pub static NEGL: &[u8] = b"\tnegl ";
-pub static ADDL: &[u8] = b"\taddl ";
-pub static SUBL: &[u8] = b"\tsubl ";
-pub static IMULL: &[u8] = b"\timull ";
-pub static LEFTSHIFTL: &[u8] = b"\tshll ";
-pub static RIGHTSHIFTL: &[u8] = b"\tshrl ";
-pub static ANDL: &[u8] = b"\tandl ";
-pub static ORL: &[u8] = b"\torl ";
-pub static XORL: &[u8] = b"\txorl ";
-pub static CMPL: &[u8] = b"\tcmpl ";
+pub static NOT: &[u8] = b"\tnot";
+pub static NEG: &[u8] = b"\tneg";
+pub static ADD: &[u8] = b"\tadd";
+pub static SUB: &[u8] = b"\tsub";
+pub static IMUL: &[u8] = b"\timul";
+pub static LEFTSHIFT: &[u8] = b"\tshl";
+pub static RIGHTSHIFT: &[u8] = b"\tshr";
+pub static ARITHMETICRIGHTSHIFT: &[u8] = b"\tsar";
+pub static AND: &[u8] = b"\tand";
+pub static OR: &[u8] = b"\tor";
+pub static XOR: &[u8] = b"\txor";
+pub static CMP: &[u8] = b"\tcmp";
 pub static JMP: &[u8] = b"\tjmp ";
-pub static INCL: &[u8] = b"\tincl ";
-pub static DECL: &[u8] = b"\tdecl ";
+pub static INC: &[u8] = b"\tinc";
+pub static DEC: &[u8] = b"\tdec";
+pub static MOV: &[u8] = b"\tmov";
+
+pub static IDIV: &[u8] = b"\tidiv";
+pub static DIV: &[u8] = b"\tdiv";
 
-pub static DIVL: &[u8] = b"\tidivl ";
+pub static BSR: &[u8] = b"\tbsr";
+pub static UD2: &[u8] = b"\tud2\n";
+pub static XCHG: &[u8] = b"\txchg";
+pub static LOCK_XADD: &[u8] = b"\tlock xadd";
+pub static LOCK_CMPXCHG: &[u8] = b"\tlock cmpxchg";
+pub static MFENCE: &[u8] = b"\tmfence\n";
 
 pub static CDQ: &[u8] = b"\tcdq\n";
+pub static CQO: &[u8] = b"\tcqo\n";
+
+pub static GLOBL: &[u8] = b"\t.globl ";
+pub static LOCAL: &[u8] = b"\t.local ";
+pub static TEXT: &[u8] = b"\t.text\n";
+pub static DATA: &[u8] = b"\t.data\n";
+pub static BSS: &[u8] = b"\t.bss\n";
+pub static RODATA: &[u8] = b"\t.section .rodata\n";
+pub static DATA_REL_RO: &[u8] = b"\t.section .data.rel.ro,\"aw\"\n";
+pub static TDATA: &[u8] = b"\t.section .tdata,\"awT\",@progbits\n";
+pub static TBSS: &[u8] = b"\t.section .tbss,\"awT\",@nobits\n";
 
+pub static SETUP: &[u8] = b"\tpushq %rbp\n\tmovq %rsp, %rbp\n";
 pub static TEARDOWN: &[u8] = b"\tmovq %rbp, %rsp\n\tpopq %rbp\n\tret\n";
+// leaves the inner frame of a realigned function, so TEARDOWN can leave the outer one
+pub static LEAVE_REALIGNED: &[u8] = b"\tmovq %rbp, %rsp\n\tpopq %rbp\n";
 
-// we hold the stack in the tactile stage as the number of variables on the stack. However, the all the variables are QWords, so you need to subtract from top of stack
+// stack operands hold how far below the base pointer the variable starts
 #[allow(clippy::cast_possible_wrap)]
 #[allow(clippy::cast_possible_truncation)]
 fn the_real_stack(val: usize) -> i32 {
-        -((val as i32 + 1) * 4)
+        -(val as i32)
+}
+
+fn suffix(asm_type: AsmType) -> u8 {
+        match asm_type {
+                AsmType::Byte => b'b',
+                AsmType::Word => b'w',
+                AsmType::Longword => b'l',
+                AsmType::Quadword => b'q',
+        }
+}
+
+fn register_name(register: Register, asm_type: AsmType) -> &'static [u8] {
+        match (register, asm_type) {
+                (Register::AX, AsmType::Byte) => b"%al",
+                (Register::AX, AsmType::Word) => b"%ax",
+                (Register::AX, AsmType::Longword) => b"%eax",
+                (Register::AX, AsmType::Quadword) => b"%rax",
+                (Register::CX, AsmType::Byte) => b"%cl",
+                (Register::CX, AsmType::Word) => b"%cx",
+                (Register::CX, AsmType::Longword) => b"%ecx",
+                (Register::CX, AsmType::Quadword) => b"%rcx",
+                (Register::DX, AsmType::Byte) => b"%dl",
+                (Register::DX, AsmType::Word) => b"%dx",
+                (Register::DX, AsmType::Longword) => b"%edx",
+                (Register::DX, AsmType::Quadword) => b"%rdx",
+                (Register::DI, AsmType::Byte) => b"%dil",
+                (Register::DI, AsmType::Word) => b"%di",
+                (Register::DI, AsmType::Longword) => b"%edi",
+                (Register::DI, AsmType::Quadword) => b"%rdi",
+                (Register::SI, AsmType::Byte) => b"%sil",
+                (Register::SI, AsmType::Word) => b"%si",
+                (Register::SI, AsmType::Longword) => b"%esi",
+                (Register::SI, AsmType::Quadword) => b"%rsi",
+                (Register::R8, AsmType::Byte) => b"%r8b",
+                (Register::R8, AsmType::Word) => b"%r8w",
+                (Register::R8, AsmType::Longword) => b"%r8d",
+                (Register::R8, AsmType::Quadword) => b"%r8",
+                (Register::R9, AsmType::Byte) => b"%r9b",
+                (Register::R9, AsmType::Word) => b"%r9w",
+                (Register::R9, AsmType::Longword) => b"%r9d",
+                (Register::R9, AsmType::Quadword) => b"%r9",
+                (Register::R10, AsmType::Byte) => b"%r10b",
+                (Register::R10, AsmType::Word) => b"%r10w",
+                (Register::R10, AsmType::Longword) => b"%r10d",
+                (Register::R10, AsmType::Quadword) => b"%r10",
+                (Register::R11, AsmType::Byte) => b"%r11b",
+                (Register::R11, AsmType::Word) => b"%r11w",
+                (Register::R11, AsmType::Longword) => b"%r11d",
+                (Register::R11, AsmType::Quadword) => b"%r11",
+                (Register::BX, AsmType::Byte) => b"%bl",
+                (Register::BX, AsmType::Word) => b"%bx",
+                (Register::BX, AsmType::Longword) => b"%ebx",
+                (Register::BX, AsmType::Quadword) => b"%rbx",
+                (Register::R12, AsmType::Byte) => b"%r12b",
+                (Register::R12, AsmType::Word) => b"%r12w",
+                (Register::R12, AsmType::Longword) => b"%r12d",
+                (Register::R12, AsmType::Quadword) => b"%r12",
+                (Register::R13, AsmType::Byte) => b"%r13b",
+                (Register::R13, AsmType::Word) => b"%r13w",
+                (Register::R13, AsmType::Longword) => b"%r13d",
+                (Register::R13, AsmType::Quadword) => b"%r13",
+                (Register::R14, AsmType::Byte) => b"%r14b",
+                (Register::R14, AsmType::Word) => b"%r14w",
+                (Register::R14, AsmType::Longword) => b"%r14d",
+                (Register::R14, AsmType::Quadword) => b"%r14",
+                (Register::R15, AsmType::Byte) => b"%r15b",
+                (Register::R15, AsmType::Word) => b"%r15w",
+                (Register::R15, AsmType::Longword) => b"%r15d",
+                (Register::R15, AsmType::Quadword) => b"%r15",
+                (Register::BP, AsmType::Byte) => b"%bpl",
+                (Register::BP, AsmType::Word) => b"%bp",
+                (Register::BP, AsmType::Longword) => b"%ebp",
+                (Register::BP, AsmType::Quadword) => b"%rbp",
+        }
+}
+
+// immediates are written as signed values of their own width, which the assembler accepts for every size
+fn constant_to_string(constant: Constant) -> String {
+        match constant {
+                Constant::Char(n) => n.to_string(),
+                Constant::UChar(n) => n.to_string(),
+                Constant::Short(n) => n.to_string(),
+                Constant::UShort(n) => n.to_string(),
+                Constant::Int(n) => n.to_string(),
+                Constant::UInt(n) => n.to_string(),
+                Constant::Long(n) => n.to_string(),
+                #[allow(clippy::cast_possible_wrap)]
+                Constant::ULong(n) => (n as i64).to_string(),
+        }
+}
+
+// static locals get a number after their name, which can't clash with anything in C as identifiers can't contain dots
+fn symbol_name(code: &[u8], name: AIdentifier, suffix: Option<usize>) -> Vec<u8> {
+        let AIdentifier { start, len, .. } = name;
+        let mut symbol = code[start..start + len].to_vec();
+        if let Some(suffix) = suffix {
+                symbol.push(b'.');
+                symbol.extend_from_slice(&suffix.to_string().into_bytes());
+        }
+        symbol
 }
 
-fn func_to_vec(function: ASMFunction, code: &[u8]) -> Vec<u8> {
+fn static_variable_to_vec(variable: ASMStaticVariable, code: &[u8]) -> Vec<u8> {
+        let mut written = Vec::new();
+        let name = symbol_name(code, variable.name, variable.suffix);
+        let (size, zero) = match variable.init {
+                ASMStaticInit::Constant(constant) => (AsmType::from(constant.c_type()).size(), constant.as_u64() == 0),
+                ASMStaticInit::FunctionAddress(_) => (8, false),
+        };
+        let relocated = matches!(variable.init, ASMStaticInit::FunctionAddress(_));
+
+        written.extend_from_slice(if variable.global { GLOBL } else { LOCAL });
+        written.extend_from_slice(&name);
+        written.push(b'\n');
+        // there's no read-only section for thread-local variables, as each thread's copy is made at runtime. an address
+        // in a position independent executable is only known once it's loaded, so a const one can't go in .rodata
+        written.extend_from_slice(match (variable.thread_local, variable.read_only, zero) {
+                (true, _, true) => TBSS,
+                (true, _, false) => TDATA,
+                (false, true, _) if relocated => DATA_REL_RO,
+                (false, true, _) => RODATA,
+                (false, false, true) => BSS,
+                (false, false, false) => DATA,
+        });
+        written.extend_from_slice(b"\t.balign ");
+        written.extend_from_slice(&variable.alignment.to_string().into_bytes());
+        written.push(b'\n');
+        written.extend_from_slice(&name);
+        written.extend_from_slice(b":\n");
+
+        // .rodata can't hold uninitialized space the way .bss does, but .zero works anywhere
+        if zero {
+                written.extend_from_slice(b"\t.zero ");
+                written.extend_from_slice(&size.to_string().into_bytes());
+        } else {
+                written.extend_from_slice(match size {
+                        1 => b"\t.byte ",
+                        2 => b"\t.short ",
+                        4 => b"\t.long ",
+                        _ => b"\t.quad ",
+                });
+                match variable.init {
+                        ASMStaticInit::Constant(constant) => {
+                                written.extend_from_slice(&constant_to_string(constant).into_bytes())
+                        }
+                        ASMStaticInit::FunctionAddress(function) => {
+                                written.extend_from_slice(&code[function.start..function.start + function.len])
+                        }
+                }
+        }
+        written.push(b'\n');
+
+        written
+}
+
+// defined holds the names of every function in this file, as the rest are called through the PLT and have their
+// addresses taken through the GOT
+fn func_to_vec(function: &ASMFunction, code: &[u8], defined: &[&[u8]]) -> Vec<u8> {
         let mut instructions = Vec::new();
 
-        instructions.extend_from_slice(b"\t.globl ");
+        instructions.extend_from_slice(if function.global { GLOBL } else { LOCAL });
 
-        let AIdentifier { start, len } = function.identifier;
+        let AIdentifier { start, len, .. } = function.identifier;
         let identifier = &code[start..start + len];
         instructions.extend_from_slice(identifier);
         instructions.push(b'\n');
+        instructions.extend_from_slice(TEXT);
         instructions.extend_from_slice(identifier);
         instructions.push(b':');
         instructions.push(b'\n');
+        instructions.extend_from_slice(SETUP);
+        if let Some(alignment) = function.realign {
+                instructions.extend_from_slice(format!("\tandq $-{alignment}, %rsp\n").as_bytes());
+                instructions.extend_from_slice(SETUP);
+        }
 
-        let extend_from_operand = |value, instructions: &mut Vec<u8>, setcc: bool| match value {
-                Operand::Imm(Constant::A(AConstant { start, len })) => {
+        let extend_from_operand = |value, instructions: &mut Vec<u8>, asm_type| match value {
+                Operand::Imm(constant) => {
                         instructions.push(DOLLAR);
-                        instructions.extend_from_slice(&code[start..start + len]);
+                        instructions.extend_from_slice(&constant_to_string(constant).into_bytes());
                 }
-                Operand::Imm(Constant::S(n)) => {
-                        instructions.push(DOLLAR);
-                        instructions.extend_from_slice(&n.to_string().into_bytes());
-                }
-                Operand::Register(register) => instructions.extend(match (register, setcc) {
-                        (Register::AX, false) => EAX,
-                        (Register::R10, false) => R10D,
-                        (Register::DX, _) => DX,
-                        (Register::R11, false) => R11D,
-                        (Register::AX, true) => AX,
-                        (Register::R10, true) => R10B,
-                        (Register::R11, true) => R11B,
-                }),
+                Operand::Register(register) => instructions.extend(register_name(register, asm_type)),
                 Operand::Stack(stack_value) => {
                         let val = the_real_stack(stack_value).to_string().into_bytes();
                         instructions.extend(val);
                         instructions.extend_from_slice(b"(%rbp)");
                 }
-                Operand::Pseudo(_) => panic!("Logic Bug"),
+                Operand::Data(name, suffix) => {
+                        instructions.extend(symbol_name(code, name, suffix));
+                        instructions.extend_from_slice(b"(%rip)");
+                }
+                // addresses are always 64 bits
+                Operand::Memory(register, displacement) => {
+                        instructions.extend_from_slice(&displacement.to_string().into_bytes());
+                        instructions.push(b'(');
+                        instructions.extend(register_name(register, AsmType::Quadword));
+                        instructions.push(b')');
+                }
+                Operand::ThreadLocal(name, suffix) => {
+                        instructions.extend_from_slice(b"%fs:");
+                        instructions.extend(symbol_name(code, name, suffix));
+                        instructions.extend_from_slice(b"@tpoff");
+                }
+                Operand::ThreadLocalAt(register) => {
+                        instructions.extend_from_slice(b"%fs:(");
+                        instructions.extend(register_name(register, AsmType::Quadword));
+                        instructions.push(b')');
+                }
+                Operand::GotTpoff(name) => {
+                        instructions.extend(symbol_name(code, name, None));
+                        instructions.extend_from_slice(b"@gottpoff(%rip)");
+                }
+                Operand::Pseudo(_) | Operand::PseudoMember(..) | Operand::InitialExec(_) => panic!("Logic Bug"),
         };
 
-        for i in function.instructions {
-                instruction_to_extension(i, &mut instructions, extend_from_operand);
-        }
+        // writes the name of a function and says whether it's defined in this file
+        let extend_from_function = |name: AIdentifier, instructions: &mut Vec<u8>| {
+                let name = &code[name.start..name.start + name.len];
+                instructions.extend_from_slice(name);
+                defined.contains(&name)
+        };
 
-        instructions.extend_from_slice(b".section .note.GNU-stack,\"\",@progbits");
+        for i in &function.instructions {
+                if matches!(i, ASMInstruction::Ret) && function.realign.is_some() {
+                        instructions.extend_from_slice(LEAVE_REALIGNED);
+                }
+                instruction_to_extension(i, &mut instructions, extend_from_operand, extend_from_function);
+        }
 
         instructions
 }
 
 fn instruction_to_extension(
-        i: ASMInstruction,
+        i: &ASMInstruction,
         instructions: &mut Vec<u8>,
-        extend_from_operand: impl Fn(Operand, &mut Vec<u8>, bool),
+        extend_from_operand: impl Fn(Operand, &mut Vec<u8>, AsmType),
+        extend_from_function: impl Fn(AIdentifier, &mut Vec<u8>) -> bool,
 ) {
-        match i {
-                ASMInstruction::Mov(src, dst) => {
-                        instructions.extend_from_slice(b"\tmovl ");
-                        extend_from_operand(src, instructions, false);
+        match *i {
+                ASMInstruction::Mov(t, src, dst) => {
+                        instructions.extend_from_slice(MOV);
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(src, instructions, t);
+                        instructions.push(b',');
+                        extend_from_operand(dst, instructions, t);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Movsx(src_type, dst_type, src, dst) => {
+                        instructions.extend_from_slice(b"\tmovs");
+                        instructions.extend([suffix(src_type), suffix(dst_type), b' ']);
+                        extend_from_operand(src, instructions, src_type);
+                        instructions.push(b',');
+                        extend_from_operand(dst, instructions, dst_type);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::MovZeroExtend(src_type, dst_type, src, dst) => {
+                        instructions.extend_from_slice(b"\tmovz");
+                        instructions.extend([suffix(src_type), suffix(dst_type), b' ']);
+                        extend_from_operand(src, instructions, src_type);
+                        instructions.push(b',');
+                        extend_from_operand(dst, instructions, dst_type);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Lea(src, dst) => {
+                        instructions.extend_from_slice(b"\tleaq ");
+                        extend_from_operand(src, instructions, AsmType::Quadword);
                         instructions.push(b',');
-                        extend_from_operand(dst, instructions, false);
+                        extend_from_operand(dst, instructions, AsmType::Quadword);
                         instructions.push(b'\n');
                 }
-                ASMInstruction::Unary(unop, operand) => {
+                ASMInstruction::Unary(unop, t, operand) => {
                         let op = match unop {
-                                ASMUnary::Increment => INCL,
-                                ASMUnary::Decrement => DECL,
-                                ASMUnary::Not => NOTL,
-                                ASMUnary::Negate => NEGL,
-                                ASMUnary::Complement => NOTL,
+                                ASMUnary::Increment => INC,
+                                ASMUnary::Decrement => DEC,
+                                ASMUnary::Negate => NEG,
+                                ASMUnary::Complement => NOT,
                         };
                         instructions.extend_from_slice(op);
-                        extend_from_operand(operand, instructions, false);
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(operand, instructions, t);
                         instructions.push(b'\n');
                 }
                 ASMInstruction::AllocateStack(n) => {
                         if n == 0 {
                                 return;
                         }
-                        instructions.extend(b"\tpushq %rbp\n\tmovq %rsp, %rbp\n\tsubq $");
-                        instructions.extend_from_slice(&(-the_real_stack(n)).to_string().into_bytes());
+                        instructions.extend(b"\tsubq $");
+                        instructions.extend_from_slice(&n.to_string().into_bytes());
+                        instructions.extend(b", %rsp\n");
+                }
+                ASMInstruction::DeallocateStack(n) => {
+                        instructions.extend(b"\taddq $");
+                        instructions.extend_from_slice(&n.to_string().into_bytes());
                         instructions.extend(b", %rsp\n");
                 }
+                ASMInstruction::Push(operand) => {
+                        instructions.extend_from_slice(b"\tpushq ");
+                        extend_from_operand(operand, instructions, AsmType::Quadword);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Pop(register) => {
+                        instructions.extend_from_slice(b"\tpopq ");
+                        instructions.extend_from_slice(register_name(register, AsmType::Quadword));
+                        instructions.push(b'\n');
+                }
+                // %N is written the way it would be in an instruction of the operand's size, unless a modifier
+                // asks for another
+                ASMInstruction::InlineAsm(ref pieces, ref operands) => {
+                        instructions.push(b'\t');
+                        for piece in pieces {
+                                match piece {
+                                        TemplatePiece::Text(text) => instructions.extend_from_slice(text),
+                                        &TemplatePiece::Operand(n, modifier) => {
+                                                let (t, operand) = operands[n];
+                                                let t = match modifier {
+                                                        Some(b'b') => AsmType::Byte,
+                                                        Some(b'w') => AsmType::Word,
+                                                        Some(b'k') => AsmType::Longword,
+                                                        Some(b'q') => AsmType::Quadword,
+                                                        _ => t,
+                                                };
+                                                extend_from_operand(operand, instructions, t);
+                                        }
+                                }
+                        }
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Call(name) => {
+                        instructions.extend_from_slice(b"\tcall ");
+                        if !extend_from_function(name, instructions) {
+                                instructions.extend_from_slice(b"@PLT");
+                        }
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::CallIndirect(operand) => {
+                        instructions.extend_from_slice(b"\tcall *");
+                        extend_from_operand(operand, instructions, AsmType::Quadword);
+                        instructions.push(b'\n');
+                }
+                // a function in another object could be in a shared library, so its address comes from the GOT
+                ASMInstruction::FunctionAddress(name, dst) => {
+                        let mut symbol = vec![];
+                        if extend_from_function(name, &mut symbol) {
+                                instructions.extend_from_slice(b"\tleaq ");
+                                instructions.extend(symbol);
+                        } else {
+                                instructions.extend_from_slice(b"\tmovq ");
+                                instructions.extend(symbol);
+                                instructions.extend_from_slice(b"@GOTPCREL");
+                        }
+                        instructions.extend_from_slice(b"(%rip),");
+                        extend_from_operand(dst, instructions, AsmType::Quadword);
+                        instructions.push(b'\n');
+                }
                 ASMInstruction::Ret => instructions.extend_from_slice(TEARDOWN),
-                ASMInstruction::Binary(asmbinary, src, dst) => {
+                ASMInstruction::Binary(asmbinary, t, src, dst) => {
                         instructions.extend(match asmbinary {
-                                ASMBinary::Add => ADDL,
-                                ASMBinary::Subtract => SUBL,
-                                ASMBinary::Multiply => IMULL,
-                                ASMBinary::LeftShift => LEFTSHIFTL,
-                                ASMBinary::RightShift => RIGHTSHIFTL,
-                                ASMBinary::Or => ORL,
-                                ASMBinary::XOr => XORL,
-                                ASMBinary::And => ANDL,
-                                ASMBinary::AddAssign => ADDL,
-                                ASMBinary::SubtractAssign => SUBL,
-                                ASMBinary::MultiplyAssign => IMULL,
-                                ASMBinary::LeftShiftAssign => LEFTSHIFTL,
-                                ASMBinary::RightShiftAssign => RIGHTSHIFTL,
-                                ASMBinary::BitwiseAndAssign => ANDL,
-                                ASMBinary::BitwiseOrAssign => ORL,
-                                ASMBinary::BitwiseXOrAssign => XORL,
+                                ASMBinary::Add => ADD,
+                                ASMBinary::Subtract => SUB,
+                                ASMBinary::Multiply => IMUL,
+                                ASMBinary::LeftShift => LEFTSHIFT,
+                                ASMBinary::RightShift => RIGHTSHIFT,
+                                ASMBinary::ArithmeticRightShift => ARITHMETICRIGHTSHIFT,
+                                ASMBinary::Or => OR,
+                                ASMBinary::XOr => XOR,
+                                ASMBinary::And => AND,
+                                ASMBinary::AddAssign => ADD,
+                                ASMBinary::SubtractAssign => SUB,
+                                ASMBinary::MultiplyAssign => IMUL,
+                                ASMBinary::LeftShiftAssign => LEFTSHIFT,
+                                ASMBinary::RightShiftAssign => RIGHTSHIFT,
+                                ASMBinary::BitwiseAndAssign => AND,
+                                ASMBinary::BitwiseOrAssign => OR,
+                                ASMBinary::BitwiseXOrAssign => XOR,
                         });
-                        extend_from_operand(src, instructions, false);
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        // shift counts in a register are always %cl
+                        let src_type = match asmbinary {
+                                ASMBinary::LeftShift
+                                | ASMBinary::RightShift
+                                | ASMBinary::ArithmeticRightShift
+                                | ASMBinary::LeftShiftAssign
+                                | ASMBinary::RightShiftAssign => AsmType::Byte,
+                                _ => t,
+                        };
+                        extend_from_operand(src, instructions, src_type);
                         instructions.push(b',');
-                        extend_from_operand(dst, instructions, false);
+                        extend_from_operand(dst, instructions, t);
                         instructions.push(b'\n');
                 }
-                ASMInstruction::IDiv(operand) => {
-                        instructions.extend_from_slice(DIVL);
-                        extend_from_operand(operand, instructions, false);
+                ASMInstruction::IDiv(t, operand) | ASMInstruction::Div(t, operand) => {
+                        instructions.extend_from_slice(if let ASMInstruction::IDiv(..) = i { IDIV } else { DIV });
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(operand, instructions, t);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Bsr(t, src, dst) => {
+                        instructions.extend_from_slice(BSR);
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(src, instructions, t);
+                        instructions.push(b',');
+                        extend_from_operand(dst, instructions, t);
+                        instructions.push(b'\n');
+                }
+                ASMInstruction::Ud2 => instructions.extend_from_slice(UD2),
+                ASMInstruction::Mfence => instructions.extend_from_slice(MFENCE),
+                ASMInstruction::Xchg(t, src, dst)
+                | ASMInstruction::LockXadd(t, src, dst)
+                | ASMInstruction::LockCmpxchg(t, src, dst) => {
+                        instructions.extend_from_slice(match *i {
+                                ASMInstruction::Xchg(..) => XCHG,
+                                ASMInstruction::LockXadd(..) => LOCK_XADD,
+                                _ => LOCK_CMPXCHG,
+                        });
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(src, instructions, t);
+                        instructions.push(b',');
+                        extend_from_operand(dst, instructions, t);
                         instructions.push(b'\n');
                 }
-                ASMInstruction::Cdq => instructions.extend_from_slice(CDQ),
-                ASMInstruction::Cmp(op1, op2) => {
-                        instructions.extend_from_slice(CMPL);
-                        extend_from_operand(op1, instructions, false);
+                ASMInstruction::Cdq(AsmType::Quadword) => instructions.extend_from_slice(CQO),
+                ASMInstruction::Cdq(_) => instructions.extend_from_slice(CDQ),
+                ASMInstruction::Cmp(t, op1, op2) => {
+                        instructions.extend_from_slice(CMP);
+                        instructions.push(suffix(t));
+                        instructions.push(b' ');
+                        extend_from_operand(op1, instructions, t);
                         instructions.push(b',');
-                        extend_from_operand(op2, instructions, false);
+                        extend_from_operand(op2, instructions, t);
                         instructions.push(b'\n');
                 }
                 ASMInstruction::Jmp(label) => {
@@ -201,7 +543,7 @@ fn instruction_to_extension(
                 ASMInstruction::SetCC(cond_code, op1) => {
                         instructions.extend_from_slice(b"\tset");
                         instructions.extend_from_slice(cond_code_to_slice(cond_code));
-                        extend_from_operand(op1, instructions, true);
+                        extend_from_operand(op1, instructions, AsmType::Byte);
                         instructions.push(b'\n');
                 }
                 ASMInstruction::Label(label) => {
@@ -220,5 +562,9 @@ fn cond_code_to_slice(cond_code: CondCode) -> &'static [u8] {
                 CondCode::GE => b"ge ",
                 CondCode::L => b"l ",
                 CondCode::LE => b"le ",
+                CondCode::A => b"a ",
+                CondCode::AE => b"ae ",
+                CondCode::B => b"b ",
+                CondCode::BE => b"be ",
         }
 }
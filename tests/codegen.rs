@@ -0,0 +1,748 @@
+use std::{
+        fs,
+        path::PathBuf,
+        process::Command,
+        sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rcc::{
+        initialize::{Initialized, Operation, Standard},
+        lex::lex,
+        parse::parse_program,
+        semantic_analysis::analyze,
+        tactile::tactile,
+        toasm::asm,
+        write::write,
+        Program,
+};
+
+// the asm rcc writes for code, like with -S
+fn compile(code: &str) -> String {
+        let program = Program {
+                operation: Operation::Compile,
+                state: Initialized {
+                        code: code.as_bytes().to_vec(),
+                        std: Standard::C17,
+                },
+                obj: false,
+        };
+        let parsed = parse_program(lex(program).unwrap()).unwrap();
+        let (_, analyzed, max_label) = analyze(parsed.state.program, code.as_bytes(), parsed.state.std).unwrap();
+        let program = Program {
+                operation: parsed.operation,
+                state: analyzed,
+                obj: false,
+        };
+
+        let compiled = asm(tactile(program, max_label).state);
+        String::from_utf8(write(compiled, code.as_bytes()).code).unwrap()
+}
+
+// the exit status of code compiled by rcc
+fn run(code: &str) -> i32 {
+        run_with(code, None)
+}
+
+// the exit status of code compiled by rcc, linked with other compiled by cc, which is how calls across the two are
+// checked against the ABI
+fn run_with(code: &str, other: Option<&str>) -> i32 {
+        // the tests run in parallel, so every program gets a name of its own
+        static PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("codegen{}", PROGRAMS.fetch_add(1, Ordering::Relaxed));
+        let path = |extension: &str| PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.{extension}"));
+
+        fs::write(path("s"), compile(code)).unwrap();
+        let mut cc = Command::new("cc");
+        cc.arg(path("s"));
+        if let Some(other) = other {
+                fs::write(path("c"), other).unwrap();
+                cc.args(["-O0", "-fno-omit-frame-pointer"]).arg(path("c"));
+        }
+        assert!(cc.arg("-o").arg(path("out")).status().unwrap().success());
+
+        Command::new(path("out")).status().unwrap().code().unwrap()
+}
+
+#[test]
+fn integers_convert_and_operate_by_size_and_signedness() {
+        let code = "
+int main(void) {
+        signed char c = -1;
+        unsigned char u = 255;
+        short s = -2;
+        unsigned short us = 65535;
+        long l = -3;
+        unsigned int ui = 4000000000u;
+        unsigned char t = 511;
+        long long big = 1LL << 40;
+        if (c + u != 254)
+                return 1;
+        if (s * us != -131070)
+                return 2;
+        if (t != 255)
+                return 3;
+        if (ui / 3u != 1333333333u || ui % 7u != 4000000000u % 7u)
+                return 4;
+        if (-7 / 2 != -3 || -7 % 2 != -1)
+                return 5;
+        if (!(ui > 1) || -1 < 0u)
+                return 6;
+        if (ui >> 31 != 1 || -8 >> 1 != -4)
+                return 7;
+        if (l + ui != 3999999997 || big != 1099511627776)
+                return 8;
+        return 0;
+}";
+        assert_eq!(run(code), 0);
+
+        // promotions sign or zero extend, and so does converting int to long
+        let extended = compile("long f(signed char c, unsigned char u) { return c + u; }");
+        assert!(extended.contains("movsbl"));
+        assert!(extended.contains("movzbl"));
+        assert!(extended.contains("movslq"));
+
+        // unsigned operands divide, compare and shift right as unsigned
+        let unsigned = compile("unsigned f(unsigned a, unsigned b) { return a / b + (a > b) + (a < b) + (a >> b); }");
+        let signed = compile("int f(int a, int b) { return a / b + (a > b) + (a < b) + (a >> b); }");
+        for (unsigned_instruction, signed_instruction) in
+                [("divl", "idivl"), ("seta", "setg"), ("setb", "setl"), ("shrl", "sarl")]
+        {
+                assert!(unsigned.contains(unsigned_instruction) && !unsigned.contains(signed_instruction));
+                assert!(signed.contains(signed_instruction));
+        }
+}
+
+#[test]
+fn casts_convert_and_sizeof_is_a_constant() {
+        let code = "
+int main(void) {
+        long l = 4294967298;
+        int n = 0;
+        if ((int)l != 2)
+                return 1;
+        if ((unsigned char)-1 != 255 || (signed char)200 != -56)
+                return 2;
+        if ((long)(unsigned)-1 != 4294967295 || (long)-1 != -1)
+                return 3;
+        if ((_Bool)256 != 1 || (_Bool)0 != 0)
+                return 4;
+        if (sizeof(char) != 1 || sizeof(short) != 2 || sizeof(int) != 4 || sizeof l != 8)
+                return 5;
+        if (_Alignof(long long) != 8 || sizeof(1 + 1L) != 8)
+                return 6;
+        (void)sizeof(n = 5);
+        if (n != 0)
+                return 7;
+        return (int)sizeof(unsigned short) - 2;
+}";
+        assert_eq!(run(code), 0);
+
+        // a signed int is sign extended, an unsigned one is zero extended by a 32-bit move, and truncating only reads
+        // the low bytes
+        assert!(compile("long f(int i) { return (long)i; }").contains("movslq"));
+        let zero_extended = compile("unsigned long f(unsigned u) { return (unsigned long)u; }");
+        assert!(zero_extended.contains("movl -4(%rbp),%r11d") && !zero_extended.contains("movslq"));
+        assert!(compile("char f(long l) { return (char)l; }").contains("movb -8(%rbp)"));
+
+        // sizeof and _Alignof are worked out before the program runs
+        let sizes = compile("unsigned long f(void) { return sizeof(long) + _Alignof(short); }");
+        assert!(sizes.contains("$8") && sizes.contains("$2"));
+}
+
+#[test]
+fn storage_classes_decide_linkage_and_where_objects_live() {
+        let code = "
+int initialized = 5;
+int tentative;
+int tentative;
+static long internal = 7;
+const int limit = 6;
+extern int from_cc;
+int sum_in_cc(void);
+static int helper(void) { return 1; }
+int counter(void) {
+        static int calls;
+        static const int step = 1;
+        calls = calls + step;
+        return calls;
+}
+int main(void) {
+        extern int tentative;
+        counter();
+        counter();
+        if (counter() != 3)
+                return 1;
+        if (initialized + internal + helper() + tentative != 13)
+                return 2;
+        tentative = 4;
+        if (sum_in_cc() != 19)
+                return 3;
+        if (limit * 2 != 12)
+                return 4;
+        return from_cc - 10;
+}";
+        // the names with internal linkage are in both files, and they don't clash
+        let other = "
+extern int initialized, tentative;
+int from_cc = 10;
+static int internal = 100;
+static int helper(void) { return internal; }
+int sum_in_cc(void) { return from_cc + initialized + tentative + helper() - 100; }";
+        assert_eq!(run_with(code, Some(other)), 0);
+
+        // initialized objects go in .data, const ones in .rodata and the rest in .bss, and only those with external
+        // linkage are global
+        let compiled = compile(code);
+        for directives in [
+                ".globl initialized\n\t.data",
+                ".globl limit\n\t.section .rodata",
+                ".globl tentative\n\t.bss",
+                ".local internal\n\t.data",
+                ".local helper\n\t.text",
+                ".globl counter\n\t.text",
+        ] {
+                assert!(compiled.contains(directives), "expected {directives}");
+        }
+        // a static local gets a name of its own, so another with the same name can't clash with it, and an extern
+        // declaration defines nothing
+        assert!(compiled.contains(".local calls."));
+        assert!(!compiled.contains("from_cc:"));
+        let (_, step) = compiled.split_once(".local step.").unwrap();
+        assert!(step.split_once('\n').unwrap().1.starts_with("\t.section .rodata\n"));
+}
+
+#[test]
+fn goto_jumps_to_its_label_in_the_same_function() {
+        let code = "
+int count_down(int n) {
+        int steps = 0;
+again:
+        if (n == 0)
+                goto out;
+        n = n - 1;
+        steps = steps + 1;
+        goto again;
+out:
+        return steps;
+}
+int skip(int x) {
+        goto out;
+        x = 100;
+        {
+        inner:
+                x = x + 1;
+                if (x < 3)
+                        goto inner;
+        }
+out:
+        if (x == 0)
+                goto inner;
+        return x;
+}
+int main(void) {
+        if (count_down(5) != 5)
+                return 1;
+        if (skip(0) != 3)
+                return 2;
+        goto end;
+        return 3;
+end:;
+}";
+        assert_eq!(run(code), 0);
+
+        // every function has labels of its own, so the two outs become different local labels
+        let compiled = compile(code);
+        let mut labels: Vec<_> = compiled
+                .lines()
+                .filter(|f| f.starts_with(".L") && f.ends_with(':'))
+                .collect();
+        let count = labels.len();
+        labels.sort_unstable();
+        labels.dedup();
+        assert_eq!(labels.len(), count);
+        assert!(!compiled.contains("out:") && !compiled.contains("inner:"));
+}
+
+#[test]
+fn switch_jumps_to_its_cases_wherever_they_are_in_its_body() {
+        let code = "
+int copy(int count) {
+        int copied = 0;
+        int n = (count + 3) / 4;
+        switch (count % 4) {
+        case 0:
+                do {
+                        copied = copied + 1;
+                case 3:
+                        copied = copied + 1;
+                case 2:
+                        copied = copied + 1;
+                case 1:
+                        copied = copied + 1;
+                } while ((n = n - 1) > 0);
+        }
+        return copied;
+}
+int classify(int c) {
+        switch (c) {
+        default:
+                return 0;
+        case 'a':
+                return 1;
+        case 1 + 2: {
+                int doubled = c * 2;
+                return doubled;
+        }
+        case 10:
+        case 11:
+                c = c + 1;
+        case 12:
+                return c;
+        }
+}
+int main(void) {
+        unsigned u = 4294967295u;
+        long l = 4294967296;
+        if (copy(7) != 7 || copy(8) != 8 || copy(1) != 1)
+                return 1;
+        if (classify('a') != 1 || classify(3) != 6 || classify(10) != 11 || classify(12) != 12 || classify(5) != 0)
+                return 2;
+        switch (u) {
+        case -1:
+                break;
+        default:
+                return 3;
+        }
+        switch (l) {
+        case 0:
+                return 4;
+        case 4294967296:
+                break;
+        }
+        return 0;
+}";
+        // the cases inside the loop are Duff's device, the default comes first without catching the cases after it,
+        // and -1 is converted to the unsigned int it's compared with
+        assert_eq!(run(code), 0);
+}
+
+#[test]
+fn calls_follow_the_system_v_abi_both_ways() {
+        let code = "
+int seven_in_cc(int a, int b, int c, int d, int e, int f, int g);
+int aligned(void);
+long sum(int count, ...);
+int calls_back(void);
+long eight(char a, short b, int c, long d, unsigned char e, unsigned short f, unsigned g, long h) {
+        return a + b + c + d + e + f + g + h * 100;
+}
+int padded(int a, int b, int c, int d, int e, int f, int g) {
+        char odd = g;
+        return aligned() + odd - a - b - c - d - e - f;
+}
+int main(void) {
+        if (seven_in_cc(1, 2, 3, 4, 5, 6, 7) != 1234567)
+                return 1;
+        if (!aligned() || padded(1, 2, 3, 4, 5, 6, 21) != 1)
+                return 2;
+        if (sum(3, 10L, 20L, 30L) != 60)
+                return 3;
+        if (!calls_back())
+                return 4;
+        return 0;
+}";
+        // cc checks that the stack is 16-byte aligned at every call rcc makes, 3.2.2 of the psABI, reads the variadic
+        // arguments with stdarg.h and calls eight with arguments of every size, some of them on the stack
+        let other = "
+#include <stdarg.h>
+long eight(char a, short b, int c, long d, unsigned char e, unsigned short f, unsigned g, long h);
+int seven_in_cc(int a, int b, int c, int d, int e, int f, int g) {
+        return (((((a * 10 + b) * 10 + c) * 10 + d) * 10 + e) * 10 + f) * 10 + g;
+}
+int aligned(void) {
+        return (unsigned long)__builtin_frame_address(0) % 16 == 0;
+}
+long sum(int count, ...) {
+        va_list args;
+        long total = 0;
+        va_start(args, count);
+        for (int i = 0; i < count; i++)
+                total += va_arg(args, long);
+        va_end(args);
+        return total;
+}
+int calls_back(void) {
+        return eight(-1, -2, 3, 4, 255, 65535, 4294967295u, 8) == 4295033889;
+}";
+        assert_eq!(run_with(code, Some(other)), 0);
+
+        // the first six arguments go in registers, in order, and the rest are pushed last to first, with padding when
+        // there's an odd number of them
+        let compiled = compile(code);
+        let call = compiled.find("call seven_in_cc@PLT").unwrap();
+        let setup = &compiled[compiled[..call].rfind("subq $8, %rsp").unwrap()..call];
+        let mut at = 0;
+        for argument in [
+                "pushq $7", "$1,%edi", "$2,%esi", "$3,%edx", "$4,%ecx", "$5,%r8d", "$6,%r9d",
+        ] {
+                at += setup[at..]
+                        .find(argument)
+                        .unwrap_or_else(|| panic!("expected {argument} in order"));
+        }
+        assert!(compiled[call..].starts_with("call seven_in_cc@PLT\n\taddq $16, %rsp"));
+
+        // %al is how many vector registers a variadic call uses, which is none
+        let call = compiled.find("call sum@PLT").unwrap();
+        assert!(compiled[..call].ends_with("movl $0,%eax\n\t"));
+
+        // the callee finds its stack arguments above the return address and the saved %rbp
+        assert!(compiled.contains("movl 16(%rbp),%r10d") && compiled.contains("movq 24(%rbp),%r10"));
+}
+
+#[test]
+fn function_pointers_are_called_indirectly_and_can_initialize_statics() {
+        let code = "
+static int twice(int x) { return 2 * x; }
+int inc(int x) { return x + 1; }
+int (*from_rcc)(int) = inc;
+static int (*const read_only)(int) = &twice;
+int (*null)(int) = 0;
+int apply(int (*f)(int), int x);
+int (*pick(int which))(int) { return which ? twice : inc; }
+int main(void) {
+        static int (*local)(int) = *twice;
+        int (*f)(int) = pick(0);
+        if (local(from_rcc(3)) != 8 || read_only(1) != 2 || null)
+                return 1;
+        if (f(4) != 5 || pick(1)(4) != 8 || (*f)(1) != 2)
+                return 2;
+        return apply(twice, 5) - 11;
+}";
+        let other = "
+extern int (*from_rcc)(int);
+int apply(int (*f)(int), int x) { return f(x) + from_rcc(0); }";
+        assert_eq!(run_with(code, Some(other)), 0);
+
+        // a call through a pointer goes through r11, and a static initialized with a function's address holds a
+        // relocation for it, which a const one can't have in .rodata
+        let compiled = compile(code);
+        assert!(compiled.contains("call *%r11"));
+        for directives in [
+                ".globl from_rcc\n\t.data\n\t.balign 8\nfrom_rcc:\n\t.quad inc\n",
+                ".local read_only\n\t.section .data.rel.ro,\"aw\"\n\t.balign 8\nread_only:\n\t.quad twice\n",
+                ".local local.",
+                ".globl null\n\t.bss",
+        ] {
+                assert!(compiled.contains(directives), "expected {directives}");
+        }
+}
+
+#[test]
+fn automatic_variables_aligned_past_the_stack_realign_the_frame() {
+        let code = "
+int sum(int n, ...) {
+        __attribute__((aligned(64))) int big = 0;
+        long at;
+        __builtin_va_list ap;
+        __builtin_va_start(ap, n);
+        for (int i = 0; i < n; i++)
+                big += __builtin_va_arg(ap, int);
+        __builtin_va_end(ap);
+        __asm__(\"leaq %1, %0\" : \"=r\"(at) : \"m\"(big));
+        return at & 63 ? -1 : big;
+}
+int eight(int a, int b, int c, int d, int e, int f, int g, int h) {
+        __attribute__((aligned(128))) long x = g * 10 + h;
+        char pad = 1;
+        __attribute__((aligned(32))) short y = a;
+        long at_x;
+        long at_y;
+        __asm__(\"leaq %2, %0\\n\\tleaq %3, %1\" : \"=r\"(at_x), \"=r\"(at_y) : \"m\"(x), \"m\"(y));
+        if (at_x & 127 || at_y & 31)
+                return -1;
+        return x + y + pad + b + c + d + e + f;
+}
+int main(void) {
+        if (sum(9, 1, 2, 3, 4, 5, 6, 7, 8, 9) != 45)
+                return 1;
+        return eight(1, 2, 3, 4, 5, 6, 7, 8) - 100;
+}";
+        assert_eq!(run(code), 0);
+
+        // rsp is masked down and a second frame pushed on it, which every return leaves first
+        let compiled = compile(code);
+        assert!(compiled.contains("\tmovq %rsp, %rbp\n\tandq $-64, %rsp\n\tpushq %rbp\n\tmovq %rsp, %rbp\n"));
+        assert!(compiled.contains("\tandq $-128, %rsp\n"));
+        assert!(compiled.contains("\tmovq %rbp, %rsp\n\tpopq %rbp\n\tmovq %rbp, %rsp\n\tpopq %rbp\n\tret\n"));
+}
+
+#[test]
+fn popcount_runs_without_popcnt_and_clz_is_bsr() {
+        let code = "
+int naive(unsigned x) {
+        int n = 0;
+        for (; x; x >>= 1)
+                n += x & 1;
+        return n;
+}
+int main(void) {
+        unsigned x = 1;
+        for (int i = 0; i < 1000; i++) {
+                x = x * 1103515245u + 12345u;
+                if (__builtin_popcount(x) != naive(x) || __builtin_popcount(~x) != 32 - naive(x))
+                        return 1;
+        }
+        if (__builtin_popcount(0) != 0 || __builtin_popcount(0xFFFFFFFFu) != 32)
+                return 2;
+        if (__builtin_clz(1) != 31 || __builtin_clz(0x00F00000u) != 8 || __builtin_clz(x | 0x80000000u) != 0)
+                return 3;
+        return 0;
+}";
+        assert_eq!(run(code), 0);
+
+        // popcnt isn't part of baseline x86-64, so the bits are summed in parallel instead
+        let compiled = compile(code);
+        assert!(!compiled.contains("popcnt"));
+        for instruction in [
+                "andl $1431655765,%edx",
+                "imull $16843009,%edx",
+                "shrl $24,%edx",
+                "bsrl ",
+        ] {
+                assert!(compiled.contains(instruction), "expected {instruction}");
+        }
+}
+
+#[test]
+fn inline_asm_substitutes_operands_and_saves_what_it_clobbers() {
+        let code = "
+long add(long a, long b) {
+        long r;
+        __asm__(\"movq %1, %0\\n\\taddq %2, %0\" : \"=r\"(r) : \"r\"(a), \"r\"(b));
+        return r;
+}
+int low(long x) {
+        int r;
+        __asm__(\"movl %k1, %0\" : \"=r\"(r) : \"r\"(x));
+        return r;
+}
+long keep(long x) {
+        long r;
+        __asm__(\"movq %1, %%rbx\\n\\tmovq %%rbx, %0\" : \"=r\"(r) : \"r\"(x) : \"rbx\");
+        return r;
+}
+int main(void) {
+        long before;
+        long after;
+        int m = 5;
+        if (add(40, 2) != 42 || low(0x100000007L) != 7)
+                return 1;
+        __asm__(\"addl %1, %0\" : \"+m\"(m) : \"i\"(3));
+        if (m != 8)
+                return 2;
+        __asm__(\"movq %%rbx, %0\" : \"=r\"(before));
+        if (keep(before + 1) != before + 1)
+                return 3;
+        __asm__(\"movq %%rbx, %0\" : \"=r\"(after));
+        return after != before;
+}";
+        assert_eq!(run(code), 0);
+
+        // outputs take registers before inputs, %k1 is the 32 bit name of the second one, and a clobbered callee
+        // saved register is pushed around the asm, with padding to keep the stack aligned
+        let compiled = compile(code);
+        for asm in [
+                "\tmovq %rcx, %rax\n\taddq %rdx, %rax\n",
+                "\tmovl %ecx, %eax\n",
+                "\taddl $3, -4(%rbp)\n",
+                "\tsubq $8, %rsp\n\tpushq %rbx\n",
+                "\tpopq %rbx\n\taddq $8, %rsp\n",
+        ] {
+                assert!(compiled.contains(asm), "expected {asm}");
+        }
+}
+
+#[test]
+fn atomics_are_locked_read_modify_writes_and_seq_cst_stores_are_fenced() {
+        let code = "
+_Atomic int counter = 5;
+_Atomic long big;
+int plain;
+int main(void) {
+        int expected = 7;
+        int old;
+        counter += 2;
+        if (counter != 7)
+                return 1;
+        counter++;
+        counter -= 1;
+        counter *= 3;
+        if (counter != 21)
+                return 2;
+        old = __atomic_fetch_add(&counter, 4, 5);
+        if (old != 21 || __atomic_load_n(&counter, 2) != 25)
+                return 3;
+        if (__atomic_compare_exchange_n(&counter, &expected, 9, 0, 5, 5) || expected != 25)
+                return 4;
+        if (!__atomic_compare_exchange_n(&counter, &expected, 9, 0, 5, 5) || counter != 9)
+                return 5;
+        if (__atomic_exchange_n(&counter, 1, 5) != 9)
+                return 6;
+        __atomic_store_n(&plain, 3, 0);
+        __atomic_store_n(&plain, 4, 5);
+        big = 1L << 40;
+        __atomic_thread_fence(5);
+        return counter + plain + (big >> 40) - 6;
+}";
+        assert_eq!(run(code), 0);
+
+        // += and fetch_add are lock xadd, *= is a lock cmpxchg loop, and a seq_cst store is followed by mfence
+        // where a relaxed one isn't
+        let compiled = compile(code);
+        for asm in [
+                "\tlock xaddl %r10d,counter(%rip)\n",
+                "\tlock cmpxchgl %r10d,counter(%rip)\n",
+                "\txchgl %r10d,counter(%rip)\n",
+                "\tmovl $3,plain(%rip)\n\tmovl $4,plain(%rip)\n\tmfence\n",
+                "\tmovq %r10,big(%rip)\n\tmfence\n",
+        ] {
+                assert!(compiled.contains(asm), "expected {asm}");
+        }
+}
+
+#[test]
+fn thread_locals_are_per_thread_and_addressed_through_fs() {
+        let code = "
+_Thread_local int counter = 3;
+_Thread_local long zeroed;
+extern _Thread_local int elsewhere;
+int in_thread(void);
+int bump(void) {
+        static _Thread_local int calls;
+        return ++calls;
+}
+int main(void) {
+        counter += 4;
+        zeroed = 2;
+        elsewhere += counter;
+        bump();
+        if (counter + zeroed + bump() + elsewhere != 27)
+                return 1;
+        return in_thread() - 2;
+}";
+        // a new thread starts with its own copies, initialized the same way
+        let other = "
+#include <pthread.h>
+_Thread_local int elsewhere = 9;
+int bump(void);
+static void *run(void *arg) {
+        bump();
+        return (void *)(long)(bump() + elsewhere - 9);
+}
+int in_thread(void) {
+        pthread_t thread;
+        void *result;
+        pthread_create(&thread, 0, run, 0);
+        pthread_join(thread, &result);
+        return (int)(long)result;
+}";
+        assert_eq!(run_with(code, Some(other)), 0);
+
+        // one defined in this file is at a fixed offset from %fs, and one from somewhere else at an offset loaded
+        // from the GOT. initialized ones go in .tdata and the rest in .tbss
+        let compiled = compile(code);
+        for asm in [
+                "\taddl $4,%fs:counter@tpoff\n",
+                "\tincl %fs:calls.",
+                "\tmovq elsewhere@gottpoff(%rip),%r11\n\taddl %r10d,%fs:(%r11)\n",
+                ".globl counter\n\t.section .tdata,\"awT\",@progbits\n\t.balign 4\ncounter:\n\t.long 3\n",
+                ".globl zeroed\n\t.section .tbss,\"awT\",@nobits\n\t.balign 8\nzeroed:\n\t.zero 8\n",
+        ] {
+                assert!(compiled.contains(asm), "expected {asm}");
+        }
+        let (_, calls) = compiled.split_once(".local calls.").unwrap();
+        assert!(calls.split_once('\n').unwrap().1.starts_with("\t.section .tbss,"));
+}
+
+#[test]
+fn va_arg_moves_from_the_register_save_area_to_the_stack() {
+        let code = "
+long from_cc(void);
+long weigh(int count, ...) {
+        __builtin_va_list ap;
+        __builtin_va_list rest;
+        long total = 0;
+        __builtin_va_start(ap, count);
+        for (int i = 1; i <= count; i++) {
+                if (i == 4)
+                        __builtin_va_copy(rest, ap);
+                total += i * __builtin_va_arg(ap, long);
+        }
+        __builtin_va_end(ap);
+        for (int i = 4; i <= count; i++)
+                total -= __builtin_va_arg(rest, long);
+        __builtin_va_end(rest);
+        return total;
+}
+int last(int count, ...) {
+        __builtin_va_list ap;
+        int value = 0;
+        __builtin_va_start(ap, count);
+        while (count--)
+                value = __builtin_va_arg(ap, int);
+        __builtin_va_end(ap);
+        return value;
+}
+int main(void) {
+        if (weigh(10, 1L, 2L, 3L, 4L, 5L, 6L, 7L, 8L, 9L, 10L) != 336 || weigh(2, 5L, 6L) != 17)
+                return 1;
+        if (last(9, 1, 2, 3, 4, 5, 6, 7, 8, -9) != -9 || last(1, 4) != 4)
+                return 2;
+        return from_cc() != 336;
+}";
+        // five arguments after count fit in registers, the rest are read from the caller's frame, and a copy taken
+        // part way through carries on from the same place
+        let other = "
+long weigh(int count, ...);
+long from_cc(void) {
+        return weigh(10, 1L, 2L, 3L, 4L, 5L, 6L, 7L, 8L, 9L, 10L);
+}";
+        assert_eq!(run_with(code, Some(other)), 0);
+
+        // gp_offset is 48 once the six integer registers are used up
+        let compiled = compile(code);
+        assert!(compiled.contains("\tcmpl $48,%r10d\n\tjae "));
+}
+
+#[test]
+fn generic_selection_chooses_after_lvalue_conversion() {
+        let code = "
+const int limit = 7;
+long twice(long x) { return 2 * x; }
+int main(void) {
+        const int c = 3;
+        volatile long v = 4;
+        char ch = 'a';
+        unsigned u = 1;
+        if (_Generic(c, int: 1, const int: 2, default: 3) != 1)
+                return 1;
+        if (_Generic(v, long: 1, volatile long: 2, default: 3) != 1)
+                return 2;
+        if (_Generic(ch, int: 1, char: 2, default: 3) != 2)
+                return 3;
+        if (_Generic(ch + 1, int: 1, char: 2, default: 3) != 1)
+                return 4;
+        if (_Generic(u, int: 1, default: 3) != 3)
+                return 5;
+        if (_Generic(limit, int: limit, default: 0) != 7)
+                return 6;
+        if (_Generic(twice, long (*)(long): twice, default: 0)(5) != 10)
+                return 7;
+        if (_Generic(u++, unsigned: u, default: 0) != 1)
+                return 8;
+        return 0;
+}";
+        // qualifiers are dropped as for any other rvalue, but nothing is promoted, and the controlling expression is
+        // never evaluated
+        assert_eq!(run(code), 0);
+}
@@ -0,0 +1,756 @@
+use rcc::{
+        initialize::{Initialized, Operation, Standard},
+        lex::{lex, tokentype::AtomicOp},
+        parse::{
+                nodes::{
+                        AConstant, AExpression, AFactor, AProgram, AStatement, AtomicBuiltin, Binop, BlockItem,
+                        Builtin, Declaration, Designator, ForInit, Initializer, StaticAssert, StorageClass, Unop,
+                },
+                parse_program, Error,
+        },
+        semantic_analysis::type_checker::{FuncType, Qualifiers, Type},
+        Program,
+};
+
+fn parse(code: &str) -> Result<AProgram, Error> {
+        parse_as(code, Standard::C17)
+}
+
+fn parse_as(code: &str, std: Standard) -> Result<AProgram, Error> {
+        let program = Program {
+                operation: Operation::ParseToCTree,
+                state: Initialized {
+                        code: code.as_bytes().to_vec(),
+                        std,
+                },
+                obj: false,
+        };
+
+        Ok(parse_program(lex(program).unwrap())?.state.program)
+}
+
+fn body(program: AProgram) -> Vec<BlockItem> {
+        let Declaration::F(function) = program.declarations.into_iter().next().unwrap() else {
+                panic!("expected a function")
+        };
+
+        function.body.unwrap().0
+}
+
+// the expression in "return <exp>;"
+fn expression(exp: &str) -> AExpression {
+        let code = format!("int main(void) {{ return {exp}; }}");
+        let Some(BlockItem::S(AStatement::Return(Some(expr)))) = body(parse(&code).unwrap()).into_iter().next() else {
+                panic!("expected a return statement")
+        };
+
+        expr
+}
+
+// the expressions are wrapped in parentheses and unary operators, so this gets to the one underneath
+fn unwrap_factor(expr: &AExpression) -> &AExpression {
+        match expr {
+                AExpression::F(AFactor::Expr(inner)) => unwrap_factor(inner),
+                _ => expr,
+        }
+}
+
+#[test]
+fn comma_has_the_lowest_precedence() {
+        let AExpression::BinOp(Binop::Comma, left, right) = expression("a = 1, b = 2") else {
+                panic!("expected a comma expression")
+        };
+
+        assert!(matches!(*left, AExpression::Assignment(..)));
+        assert!(matches!(*right, AExpression::Assignment(..)));
+}
+
+#[test]
+fn comma_is_left_associative() {
+        let AExpression::BinOp(Binop::Comma, left, right) = expression("1, 2, 3") else {
+                panic!("expected a comma expression")
+        };
+
+        assert!(matches!(*left, AExpression::BinOp(Binop::Comma, ..)));
+        assert!(matches!(*right, AExpression::F(AFactor::Constant(_))));
+}
+
+#[test]
+fn comma_in_for_header() {
+        let code = "int main(void) { int i; int j; for (i = 0, j = 1; i < j; i++, j--) ; return 0; }";
+        let Some(BlockItem::S(AStatement::F(for_statement, _))) = body(parse(code).unwrap()).into_iter().nth(2) else {
+                panic!("expected a for statement")
+        };
+
+        assert!(matches!(
+                for_statement.init,
+                ForInit::E(Some(AExpression::BinOp(Binop::Comma, ..)))
+        ));
+        assert!(matches!(for_statement.post, Some(AExpression::BinOp(Binop::Comma, ..))));
+}
+
+#[test]
+fn comma_separates_arguments() {
+        let AExpression::FunctionCall(_, Some(args), _) = unwrap_factor(&expression("f(a, b = 2, c)")).clone() else {
+                panic!("expected a call")
+        };
+        assert_eq!(args.len(), 3);
+        assert!(matches!(args[1], AExpression::Assignment(..)));
+
+        let AExpression::FunctionCall(_, Some(args), _) = unwrap_factor(&expression("f((a, b))")).clone() else {
+                panic!("expected a call")
+        };
+        assert_eq!(args.len(), 1);
+        assert!(matches!(unwrap_factor(&args[0]), AExpression::BinOp(Binop::Comma, ..)));
+}
+
+#[test]
+fn comma_in_parenthesized_initializer() {
+        assert!(parse("int main(void) { int x = (1, 2); return x; }").is_ok());
+        assert!(parse("int main(void) { int x = 1, 2; return x; }").is_err());
+}
+
+#[test]
+fn prefix_and_postfix_increment_and_decrement() {
+        let cases = [
+                ("++x", Unop::IncrementPre),
+                ("--x", Unop::DecrementPre),
+                ("x++", Unop::IncrementPost),
+                ("x--", Unop::DecrementPost),
+        ];
+
+        for (exp, unop) in cases {
+                let AExpression::F(AFactor::Unop(parsed, inner)) = expression(exp) else {
+                        panic!("expected a unary operation for {exp}")
+                };
+                assert_eq!(parsed, unop, "{exp}");
+                assert!(matches!(*inner, AFactor::Id(_)), "{exp}");
+        }
+}
+
+#[test]
+fn postfix_binds_tighter_than_prefix() {
+        let AExpression::F(AFactor::Unop(Unop::Negate, inner)) = expression("-x++") else {
+                panic!("expected a negation")
+        };
+        assert!(matches!(*inner, AFactor::Unop(Unop::IncrementPost, _)));
+
+        let AExpression::F(AFactor::Unop(Unop::IncrementPre, inner)) = expression("++x--") else {
+                panic!("expected a prefix increment")
+        };
+        assert!(matches!(*inner, AFactor::Unop(Unop::DecrementPost, _)));
+}
+
+#[test]
+fn postfix_applies_to_parenthesized_expressions() {
+        let AExpression::F(AFactor::Unop(Unop::IncrementPost, inner)) = expression("(x)++") else {
+                panic!("expected a postfix increment")
+        };
+        assert!(matches!(*inner, AFactor::Expr(_)));
+}
+
+#[test]
+fn cast_and_sizeof_apply_to_postfix_expressions() {
+        let AExpression::Cast(Type::Long, inner) = unwrap_factor(&expression("(long)x++")).clone() else {
+                panic!("expected a cast")
+        };
+        assert!(matches!(*inner, AExpression::F(AFactor::Unop(Unop::IncrementPost, _))));
+
+        let AExpression::SizeOf(inner) = unwrap_factor(&expression("sizeof x--")).clone() else {
+                panic!("expected sizeof")
+        };
+        assert!(matches!(*inner, AExpression::F(AFactor::Unop(Unop::DecrementPost, _))));
+}
+
+#[test]
+fn unary_plus() {
+        let AExpression::F(AFactor::Unop(Unop::Plus, inner)) = expression("+-x") else {
+                panic!("expected unary plus")
+        };
+        assert!(matches!(*inner, AFactor::Unop(Unop::Negate, _)));
+
+        assert!(matches!(expression("a + +b"), AExpression::BinOp(Binop::Add, ..)));
+}
+
+#[test]
+fn conditional_middle_operand_is_a_full_expression() {
+        let AExpression::C(conditional) = expression("a ? b, c : d") else {
+                panic!("expected a conditional")
+        };
+        assert!(matches!(*conditional.True, AExpression::BinOp(Binop::Comma, ..)));
+
+        let AExpression::BinOp(Binop::Comma, left, _) = expression("a ? b : c, d") else {
+                panic!("expected a comma expression")
+        };
+        assert!(matches!(*left, AExpression::C(_)));
+}
+
+#[test]
+fn initializer_lists_with_designators() {
+        let code = "int main(void) { int x = { [1] = 2, .y = { 3, }, .z[0].w = 4, 5 }; return x; }";
+        let Some(BlockItem::D(Declaration::V(declaration))) = body(parse(code).unwrap()).into_iter().next() else {
+                panic!("expected a declaration")
+        };
+        let Some(Initializer::Compound(list)) = declaration.init else {
+                panic!("expected an initializer list")
+        };
+
+        assert_eq!(list.len(), 4);
+        assert!(matches!(list[0].0[..], [Designator::Index(_)]));
+        assert!(
+                matches!(&list[1], (designators, Initializer::Compound(inner)) if designators.len() == 1 && inner.len() == 1)
+        );
+        assert!(matches!(
+                list[2].0[..],
+                [Designator::Member(_), Designator::Index(_), Designator::Member(_)]
+        ));
+        assert!(matches!(&list[3], (designators, Initializer::Single(_)) if designators.is_empty()));
+}
+
+#[test]
+fn compound_literals_are_postfix_expressions() {
+        let AExpression::F(AFactor::Unop(Unop::IncrementPost, inner)) = expression("(int){ 1 }++") else {
+                panic!("expected a postfix increment")
+        };
+        assert!(matches!(
+                unwrap_factor(&AExpression::F(*inner)),
+                AExpression::CompoundLiteral(Type::Int, _)
+        ));
+
+        assert!(matches!(
+                unwrap_factor(&expression("(long){}")),
+                AExpression::CompoundLiteral(Type::Long, _)
+        ));
+}
+
+#[test]
+fn variadic_parameter_lists() {
+        let program = parse("int f(int a, ...); int g(int a, int b);").unwrap();
+        let variadic: Vec<_> = program
+                .declarations
+                .iter()
+                .map(|f| match f {
+                        Declaration::F(function) => function.func_type.variadic,
+                        _ => panic!("expected a function"),
+                })
+                .collect();
+        assert_eq!(variadic, [true, false]);
+
+        assert!(parse("int f(int a, );").is_err());
+        assert!(parse("int f(...);").is_err());
+}
+
+#[test]
+fn typedef_names_and_stdarg_builtins() {
+        let code = "typedef __builtin_va_list va_list; typedef va_list va_list;
+                int f(int n, ...) { va_list ap; __builtin_va_start(ap, n); return __builtin_va_arg(ap, long); }";
+        let program = parse(code).unwrap();
+        assert_eq!(program.declarations.len(), 1);
+
+        let items = body(program);
+        assert!(matches!(
+                &items[0],
+                BlockItem::D(Declaration::V(declaration)) if declaration.var_type == Type::VaList
+        ));
+        assert!(matches!(
+                &items[1],
+                BlockItem::S(AStatement::Expr(expr)) if matches!(unwrap_factor(expr), AExpression::Builtin(Builtin::VaStart(_)))
+        ));
+        assert!(matches!(
+                &items[2],
+                BlockItem::S(AStatement::Return(Some(expr)))
+                        if matches!(unwrap_factor(expr), AExpression::Builtin(Builtin::VaArg(_, Type::Long)))
+        ));
+}
+
+#[test]
+fn typedef_names_are_scoped() {
+        // a declaration in an inner scope hides the typedef name until the end of that scope
+        let items = body(parse("typedef int T; int main(void) { int T = 3; return T * 2; }").unwrap());
+        assert!(matches!(
+                &items[1],
+                BlockItem::S(AStatement::Return(Some(AExpression::BinOp(Binop::Multiply, left, _))))
+                        if matches!(**left, AExpression::F(AFactor::Id(_)))
+        ));
+        let items = body(parse("typedef int T; int main(void) { { int T = 3; } T y = 4; return y; }").unwrap());
+        assert!(matches!(
+                &items[1],
+                BlockItem::D(Declaration::V(declaration)) if declaration.var_type == Type::Int
+        ));
+        assert!(parse("typedef int T; int f(int T) { return T; } T g(void);").is_ok());
+        assert!(parse("typedef int T; int f(void) { for (int T = 0; T < 3; T++) {} T x = 1; return x; }").is_ok());
+
+        // and a typedef in a block is only a type name until the end of the block
+        let items = body(parse("int main(void) { typedef long L; L x = 1; { typedef char L; L y; } L z; }").unwrap());
+        let types: Vec<_> = items
+                .iter()
+                .map(|f| match f {
+                        BlockItem::D(Declaration::V(declaration)) => declaration.var_type,
+                        BlockItem::S(AStatement::Compound(block)) => match &block.0[..] {
+                                [BlockItem::D(Declaration::V(declaration))] => declaration.var_type,
+                                _ => panic!("expected one declaration"),
+                        },
+                        _ => panic!("expected a declaration or block"),
+                })
+                .collect();
+        assert_eq!(types, [Type::Long, Type::Char, Type::Long]);
+        assert!(matches!(
+                parse("int main(void) { { typedef int T; } T x; }"),
+                Err(Error::InvalidTokenAt(..))
+        ));
+
+        // the same scope can only repeat a typedef of the same type, and can't use the name for anything else
+        assert!(parse("int main(void) { typedef int T; typedef int T; { typedef long T; } }").is_ok());
+        assert!(matches!(
+                parse("int main(void) { typedef int T; typedef long T; }"),
+                Err(Error::ConflictingTypedef(_))
+        ));
+        assert!(matches!(
+                parse("typedef int T; int T;"),
+                Err(Error::TypedefRedeclared(_))
+        ));
+        assert!(matches!(
+                parse("int T; typedef int T;"),
+                Err(Error::TypedefRedeclared(_))
+        ));
+}
+
+#[test]
+fn function_types() {
+        let program =
+                parse("void f(); char g(void); long h(int, unsigned char c, ...); void i(void) { return; }").unwrap();
+        let types: Vec<_> = program
+                .declarations
+                .iter()
+                .map(|f| match f {
+                        Declaration::F(function) => function.func_type.clone(),
+                        _ => panic!("expected a function"),
+                })
+                .collect();
+        let func_type = |ret, params: &[Type], variadic, prototyped| FuncType {
+                ret,
+                params: params.to_vec(),
+                variadic,
+                prototyped,
+        };
+        assert_eq!(
+                types,
+                [
+                        func_type(Type::Void, &[], false, false),
+                        func_type(Type::Char, &[], false, true),
+                        func_type(Type::Long, &[Type::Int, Type::UChar], true, true),
+                        func_type(Type::Void, &[], false, true),
+                ]
+        );
+
+        assert!(parse("int f(void x);").is_err());
+        assert!(parse("int f(int, void);").is_err());
+        assert!(parse("int f(int) { return 0; }").is_err());
+        assert!(parse("void unsigned f(void);").is_err());
+}
+
+#[test]
+fn type_qualifiers() {
+        let program = parse("volatile int const x; typedef const long cl; cl volatile y; int f(const int a, int b);")
+                .unwrap();
+        let qualifiers = |constant, volatile| Qualifiers {
+                constant,
+                volatile,
+                atomic: false,
+        };
+        let Declaration::V(x) = &program.declarations[0] else {
+                panic!("expected a variable")
+        };
+        assert_eq!((x.var_type, x.qualifiers), (Type::Int, qualifiers(true, true)));
+        let Declaration::V(y) = &program.declarations[1] else {
+                panic!("expected a variable")
+        };
+        assert_eq!((y.var_type, y.qualifiers), (Type::Long, qualifiers(true, true)));
+        let Declaration::F(f) = &program.declarations[2] else {
+                panic!("expected a function")
+        };
+        assert_eq!(f.func_type.params, [Type::Int, Type::Int]);
+        assert_eq!(f.params[0].1, qualifiers(true, false));
+
+        assert!(matches!(
+                unwrap_factor(&expression("(const char)1")),
+                AExpression::Cast(Type::Char, _)
+        ));
+        assert!(parse("restrict int x;").is_err());
+}
+
+#[test]
+fn function_pointers() {
+        let program = parse("int (*fp)(int); long (*pick(int which))(void); int apply(int op(int), int x);").unwrap();
+        let unary = FuncType {
+                ret: Type::Int,
+                params: vec![Type::Int],
+                variadic: false,
+                prototyped: true,
+        };
+        let Declaration::V(fp) = &program.declarations[0] else {
+                panic!("expected a variable")
+        };
+        assert_eq!(fp.var_type, Type::Func(unary.clone().intern(), false).pointer_to());
+        let Declaration::F(pick) = &program.declarations[1] else {
+                panic!("expected a function")
+        };
+        assert_eq!(pick.func_type.params, [Type::Int]);
+        let Type::Pointer(Type::Func(returned, _)) = pick.func_type.ret else {
+                panic!("expected a function pointer")
+        };
+        assert_eq!((returned.ret, returned.params.len()), (Type::Long, 0));
+        // a parameter declared as a function is a pointer to one
+        let Declaration::F(apply) = &program.declarations[2] else {
+                panic!("expected a function")
+        };
+        assert_eq!(
+                apply.func_type.params[0],
+                Type::Func(unary.clone().intern(), false).pointer_to()
+        );
+        // function types are only allocated once
+        assert!(std::ptr::eq(unary.clone().intern(), unary.clone().intern()));
+        // and so are the types pointers point to
+        let (Type::Pointer(first), Type::Pointer(second)) =
+                (fp.var_type, Type::Func(unary.intern(), false).pointer_to())
+        else {
+                panic!("expected pointers")
+        };
+        assert!(std::ptr::eq(first, second));
+
+        let call = expression("(*fp)(1)(2)");
+        let AExpression::FunctionCall(callee, Some(args), _) = unwrap_factor(&call) else {
+                panic!("expected a call")
+        };
+        assert_eq!(args.len(), 1);
+        assert!(matches!(unwrap_factor(callee), AExpression::FunctionCall(..)));
+        assert!(matches!(
+                unwrap_factor(&expression("&f")),
+                AExpression::F(AFactor::Unop(Unop::AddressOf, _))
+        ));
+        assert!(matches!(
+                unwrap_factor(&expression("(int (*)(void))0")),
+                AExpression::Cast(Type::Pointer(Type::Func(..)), _)
+        ));
+        assert!(parse("int *p;").is_err());
+        assert!(parse("int (*fp)(int) { return 0; }").is_err());
+        // an abstract declarator at the end of the file
+        assert!(matches!(parse("int"), Err(Error::NotEnoughTokens)));
+}
+
+#[test]
+fn static_assertions() {
+        // static_assert is only a keyword since C23
+        let program = parse_as(
+                "_Static_assert(sizeof(long) == 8, \"long \" \"is 64 bits\"); int main(void) { static_assert(1); return 0; }",
+                Standard::C23,
+        )
+        .unwrap();
+        let Declaration::A(assertion) = &program.declarations[0] else {
+                panic!("expected a static assertion")
+        };
+        let message = assertion.message.unwrap();
+        assert_eq!(message.len, "\"long \" \"is 64 bits\"".len());
+        assert!(matches!(assertion.condition, AExpression::BinOp(Binop::EqualTo, ..)));
+        let Declaration::F(main) = &program.declarations[1] else {
+                panic!("expected a function")
+        };
+        assert!(matches!(
+                main.body.as_ref().unwrap().0[0],
+                BlockItem::D(Declaration::A(StaticAssert { message: None, .. }))
+        ));
+
+        assert!(parse("_Static_assert(1, 2);").is_err());
+        assert!(parse("_Static_assert(1, \"a\" 2);").is_err());
+}
+
+#[test]
+fn c23_declarations_and_constants() {
+        let code = "[[nodiscard(\"check it\"), gnu::cold]] bool f(void); constexpr long n = 0b1010'0101; \
+                    typeof(n) m; typeof_unqual(const int) u; \
+                    int main(void) { switch (n) { case 1: [[fallthrough]]; case 2: return nullptr == 0; } return true; }";
+        let program = parse_as(code, Standard::C23).unwrap();
+        let Declaration::F(f) = &program.declarations[0] else {
+                panic!("expected a function")
+        };
+        assert_eq!(f.func_type.ret, Type::Bool);
+        assert!(f.attributes.nodiscard.unwrap().is_some());
+        let Declaration::V(n) = &program.declarations[1] else {
+                panic!("expected a variable")
+        };
+        assert!(n.constexpr && n.qualifiers.constant);
+        let Some(Initializer::Single(AExpression::F(AFactor::Constant(constant)))) = n.init else {
+                panic!("expected a constant")
+        };
+        assert_eq!(AConstant::evaluate(&constant, code.as_bytes()), Some((0xa5, Type::Int)));
+        // typeof an expression is only worked out by the type checker, but typeof a type name is that type
+        let Declaration::V(m) = &program.declarations[2] else {
+                panic!("expected a variable")
+        };
+        assert!(matches!(m.var_type, Type::Typeof(_)));
+        let Declaration::V(u) = &program.declarations[3] else {
+                panic!("expected a variable")
+        };
+        assert_eq!((u.var_type, u.qualifiers), (Type::Int, Qualifiers::default()));
+        let Declaration::F(main) = &program.declarations[4] else {
+                panic!("expected a function")
+        };
+        let BlockItem::S(AStatement::S(switch)) = &main.body.as_ref().unwrap().0[0] else {
+                panic!("expected a switch")
+        };
+        let AStatement::Compound(cases) = &*switch.body else {
+                panic!("expected a block")
+        };
+        let BlockItem::S(AStatement::Case(_, first, _)) = &cases.0[0] else {
+                panic!("expected a case")
+        };
+        assert!(matches!(**first, AStatement::Fallthrough));
+
+        // none of it is a keyword before C23, and fallthrough has to be on its own
+        assert!(parse("bool b;").is_err());
+        assert!(parse_as("int f(void) { [[fallthrough]] return 0; }", Standard::C23).is_err());
+        assert!(parse_as("[[maybe_unused(1)]] int x;", Standard::C23).is_err());
+        assert!(parse_as("constexpr int f(void);", Standard::C23).is_err());
+        assert!(parse_as("extern constexpr int x = 1;", Standard::C23).is_err());
+}
+
+#[test]
+fn gnu_attributes_and_builtins() {
+        let program = parse(
+                "static __attribute__((unused)) int x __attribute__((aligned(8), packed));
+                 __attribute__((__noreturn__)) void die(int code __attribute__((unused)));
+                 int main(void) { __builtin_unreachable(); return __builtin_expect(__builtin_popcount(3u), 2)
+                                  + __builtin_clz(1u); }",
+        )
+        .unwrap();
+        let Declaration::V(x) = &program.declarations[0] else {
+                panic!("expected a variable")
+        };
+        assert!(x.attributes.maybe_unused);
+        assert!(matches!(x.attributes.aligned, Some(Some(_))));
+        let Declaration::F(die) = &program.declarations[1] else {
+                panic!("expected a function")
+        };
+        assert!(die.attributes.noreturn);
+        let Declaration::F(main) = &program.declarations[2] else {
+                panic!("expected a function")
+        };
+        let items = &main.body.as_ref().unwrap().0;
+        let BlockItem::S(AStatement::Expr(AExpression::F(AFactor::Expr(unreachable)))) = &items[0] else {
+                panic!("expected an expression")
+        };
+        assert!(matches!(**unreachable, AExpression::Builtin(Builtin::Unreachable)));
+        let BlockItem::S(AStatement::Return(Some(AExpression::BinOp(Binop::Add, expect, _)))) = &items[1] else {
+                panic!("expected a return")
+        };
+        let AExpression::F(AFactor::Expr(expect)) = &**expect else {
+                panic!("expected a builtin")
+        };
+        assert!(matches!(**expect, AExpression::Builtin(Builtin::Expect(..))));
+
+        assert!(parse("int x __attribute__((noreturn(1)));").is_err());
+        assert!(parse("int x __attribute__((aligned(1));").is_err());
+        assert!(parse("int f(void) { return __builtin_offsetof(int); }").is_err());
+}
+
+#[test]
+fn inline_asm() {
+        let items = body(parse(
+                "int main(void) { int a; int b; __asm__ __volatile__(\"addl %1, %0\" : \"+r\"(a) : \"r\"(b), \"i\"(3)
+                                          : \"rbx\", \"cc\"); asm(\"nop\"); asm(\"\" ::: \"memory\"); return a; }",
+        )
+        .unwrap());
+        let BlockItem::S(AStatement::Asm(extended)) = &items[2] else {
+                panic!("expected asm")
+        };
+        assert!(!extended.basic);
+        assert_eq!(extended.outputs.len(), 1);
+        assert_eq!(extended.inputs.len(), 2);
+        assert_eq!(extended.clobbers.len(), 2);
+        let BlockItem::S(AStatement::Asm(basic)) = &items[3] else {
+                panic!("expected asm")
+        };
+        assert!(basic.basic);
+        let BlockItem::S(AStatement::Asm(clobbers_only)) = &items[4] else {
+                panic!("expected asm")
+        };
+        assert!(!clobbers_only.basic && clobbers_only.outputs.is_empty() && clobbers_only.clobbers.len() == 1);
+
+        assert!(parse("int main(void) { asm(\"nop\") }").is_err());
+        assert!(parse("int main(void) { int a; asm(\"\" : \"=r\" a); }").is_err());
+        assert!(parse("int main(void) { asm(nop); }").is_err());
+}
+
+#[test]
+fn statement_expressions() {
+        let items = body(parse(
+                "int main(void) { int x = ({ int t = 2; t * t; }); ({ x++; ; }); __typeof__(x) y = x; return y; }",
+        )
+        .unwrap());
+        let BlockItem::D(Declaration::V(x)) = &items[0] else {
+                panic!("expected a declaration")
+        };
+        let Some(Initializer::Single(AExpression::F(AFactor::Expr(init)))) = &x.init else {
+                panic!("expected an initializer")
+        };
+        let AExpression::StatementExpr(block, Some(value)) = &**init else {
+                panic!("expected a statement expression")
+        };
+        assert_eq!(block.0.len(), 1);
+        assert!(matches!(**value, AExpression::BinOp(Binop::Multiply, ..)));
+        // the last statement isn't an expression, so there's no value
+        let BlockItem::S(AStatement::Expr(AExpression::F(AFactor::Expr(void)))) = &items[1] else {
+                panic!("expected an expression statement")
+        };
+        assert!(matches!(**void, AExpression::StatementExpr(_, None)));
+        let BlockItem::D(Declaration::V(y)) = &items[2] else {
+                panic!("expected a declaration")
+        };
+        assert!(matches!(y.var_type, Type::Typeof(_)));
+
+        assert!(parse("int main(void) { return ({ 1; }; }").is_err());
+        assert!(parse("int main(void) { return ({ 1 }); }").is_err());
+}
+
+#[test]
+fn generic_selection() {
+        let AExpression::F(AFactor::Expr(call)) = expression("_Generic(x, int: f, const long: g, default: h)(x)")
+        else {
+                panic!("expected a call")
+        };
+        let AExpression::FunctionCall(callee, ..) = *call else {
+                panic!("expected a call")
+        };
+        let AExpression::F(AFactor::Expr(selection)) = *callee else {
+                panic!("expected a generic selection")
+        };
+        let AExpression::Generic(selection) = *selection else {
+                panic!("expected a generic selection")
+        };
+        assert!(matches!(selection.controlling, AExpression::F(AFactor::Id(_))));
+        let types: Vec<_> = selection
+                .associations
+                .iter()
+                .map(|(association_type, _)| *association_type)
+                .collect();
+        assert_eq!(
+                types,
+                [
+                        Some((Type::Int, Qualifiers::default())),
+                        Some((
+                                Type::Long,
+                                Qualifiers {
+                                        constant: true,
+                                        volatile: false,
+                                        atomic: false
+                                }
+                        )),
+                        None
+                ]
+        );
+
+        assert!(parse("int main(void) { return _Generic(1); }").is_err());
+        assert!(parse("int main(void) { return _Generic(1, int 1); }").is_err());
+        assert!(parse("int main(void) { return _Generic(1, int: 1,); }").is_err());
+}
+
+#[test]
+fn atomics() {
+        let program = parse("_Atomic int x; _Atomic(long) const y; volatile _Atomic unsigned z;").unwrap();
+        let types: Vec<_> = program
+                .declarations
+                .iter()
+                .map(|f| match f {
+                        Declaration::V(f) => (f.var_type, f.qualifiers.constant, f.qualifiers.atomic),
+                        _ => panic!("expected a variable"),
+                })
+                .collect();
+        assert_eq!(
+                types,
+                [
+                        (Type::Int, false, true),
+                        (Type::Long, true, true),
+                        (Type::UInt, false, true)
+                ]
+        );
+
+        let expr = expression("__atomic_fetch_add(&x, 1, 5)");
+        let AExpression::Builtin(Builtin::Atomic(atomic)) = unwrap_factor(&expr) else {
+                panic!("expected an atomic builtin")
+        };
+        assert!(matches!(
+                **atomic,
+                AtomicBuiltin {
+                        op: AtomicOp::FetchAdd,
+                        c11: false,
+                        object: Some(_),
+                        expected: None,
+                        value: Some(_),
+                        failure_order: None,
+                        ..
+                }
+        ));
+        let expr = expression("__c11_atomic_compare_exchange_strong(&x, &e, 2, 5, 5)");
+        let AExpression::Builtin(Builtin::Atomic(atomic)) = unwrap_factor(&expr) else {
+                panic!("expected an atomic builtin")
+        };
+        assert!(matches!(
+                **atomic,
+                AtomicBuiltin {
+                        op: AtomicOp::CompareExchange,
+                        c11: true,
+                        expected: Some(_),
+                        failure_order: Some(_),
+                        ..
+                }
+        ));
+        assert!(matches!(
+                unwrap_factor(&expression("__atomic_thread_fence(5)")),
+                AExpression::Builtin(Builtin::Atomic(_))
+        ));
+
+        assert!(parse("int main(void) { return __atomic_load_n(x, 5); }").is_err());
+        assert!(parse("int main(void) { return __atomic_load_n(&x); }").is_err());
+        assert!(matches!(parse("_Atomic(const int) x;"), Err(Error::QualifiedAtomic(_))));
+}
+
+#[test]
+fn thread_local_storage() {
+        let program =
+                parse("_Thread_local int a; static _Thread_local long b = 1; extern _Thread_local int c;").unwrap();
+        let declared: Vec<_> = program
+                .declarations
+                .iter()
+                .map(|f| match f {
+                        Declaration::V(f) => (f.thread_local, f.storage_class),
+                        _ => panic!("expected a variable"),
+                })
+                .collect();
+        assert_eq!(
+                declared,
+                [
+                        (true, None),
+                        (true, Some(StorageClass::Static)),
+                        (true, Some(StorageClass::Extern))
+                ]
+        );
+        assert!(parse_as("thread_local int x;", Standard::C23).is_ok());
+        assert!(parse("thread_local int x;").is_err());
+
+        assert!(matches!(
+                parse("_Thread_local int f(void);"),
+                Err(Error::InvalidThreadLocal(_))
+        ));
+        assert!(matches!(
+                parse_as("constexpr thread_local int x = 1;", Standard::C23),
+                Err(Error::InvalidThreadLocal(_))
+        ));
+        assert!(matches!(
+                parse("_Thread_local static _Thread_local int x;"),
+                Err(Error::MultipleStorageClasses(_))
+        ));
+        assert!(matches!(
+                parse("int main(void) { for (static _Thread_local int i = 0;;); }"),
+                Err(Error::InvalidForInit(_))
+        ));
+}
@@ -0,0 +1,421 @@
+use rcc::{
+        initialize::{Initialized, Operation, Standard},
+        lex::lex,
+        parse::{
+                nodes::{AProgram, Binop, BreakType, ConstantError},
+                parse_program,
+        },
+        semantic_analysis::{
+                self, analyze,
+                hir::{Category, HExpression, HExpressionKind, HProgram, HStatement, HSwitch},
+                type_checker::Type,
+                Warning,
+        },
+        tactile::tree::{Constant, StaticInit},
+        Program,
+};
+
+fn parse(code: &str) -> AProgram {
+        parse_as(code, Standard::C17)
+}
+
+fn parse_as(code: &str, std: Standard) -> AProgram {
+        let program = Program {
+                operation: Operation::ParseToCTree,
+                state: Initialized {
+                        code: code.as_bytes().to_vec(),
+                        std,
+                },
+                obj: false,
+        };
+
+        parse_program(lex(program).unwrap()).unwrap().state.program
+}
+
+fn analyze_code(code: &str) -> Result<HProgram, semantic_analysis::Error> {
+        analyze_as(code, Standard::C17)
+}
+
+fn analyze_as(code: &str, std: Standard) -> Result<HProgram, semantic_analysis::Error> {
+        analyze(parse_as(code, std), code.as_bytes(), std).map(|(_, analyzed, _)| analyzed.program)
+}
+
+#[test]
+fn symbols_are_resolved_by_scope() {
+        let code = "int x; int f(int x) { { int x = 1; } { int y = x; extern int x; return x + y; } }";
+        let (table, analyzed, _) = analyze(parse(code), code.as_bytes(), Standard::C17).unwrap();
+        let [function] = &analyzed.program.functions[..] else {
+                panic!("expected a function")
+        };
+        let file = analyzed
+                .program
+                .variables
+                .iter()
+                .find_map(|(&symbol, variable)| variable.storage.is_some().then_some(symbol))
+                .unwrap();
+        let param = function.params[0];
+        let [HStatement::Block(first), HStatement::Block(second)] = &function.body[..] else {
+                panic!("expected two blocks")
+        };
+        let [HStatement::Initialize(inner, _)] = &first[..] else {
+                panic!("expected an initialization")
+        };
+        // the extern declaration leaves nothing behind
+        let [HStatement::Initialize(
+                y,
+                HExpression {
+                        kind: HExpressionKind::Var(before),
+                        ..
+                },
+        ), HStatement::Return(Some(HExpression {
+                kind: HExpressionKind::Binary(Binop::Add, used, _),
+                ..
+        }))] = &second[..]
+        else {
+                panic!("expected an initialization and a return")
+        };
+        let HExpressionKind::Var(used) = used.kind else {
+                panic!("expected a variable")
+        };
+
+        // the parameter is in sight until the extern declaration, which is the file scope variable again
+        assert_eq!(*before, param);
+        assert_eq!(used, file);
+        assert_ne!(*inner, param);
+        assert_ne!(*inner, file);
+        assert_ne!(*y, param);
+        assert_eq!(table.count(), 5);
+
+        let code = "int main(void) { { int a; } return a; }";
+        assert!(matches!(
+                analyze(parse(code), code.as_bytes(), Standard::C17),
+                Err(semantic_analysis::Error::UndeclaredIdentifier(..))
+        ));
+}
+
+#[test]
+fn typed_tree_makes_conversions_explicit() {
+        let code = "long f(char c) { return c + 1; }";
+        let (_, analyzed, _) = analyze(parse(code), code.as_bytes(), Standard::C17).unwrap();
+        let [HStatement::Return(Some(ret))] = &analyzed.program.functions[0].body[..] else {
+                panic!("expected a return")
+        };
+
+        // the sum is done in int and converted to the return type, and the char is promoted first
+        let HExpressionKind::Convert(sum) = &ret.kind else {
+                panic!("expected a conversion")
+        };
+        assert_eq!((ret.c_type, ret.category), (Type::Long, Category::Rvalue));
+        let HExpressionKind::Binary(Binop::Add, left, right) = &sum.kind else {
+                panic!("expected an addition")
+        };
+        assert_eq!(sum.c_type, Type::Int);
+        assert_eq!(right.c_type, Type::Int);
+        let HExpressionKind::Convert(c) = &left.kind else {
+                panic!("expected a promotion")
+        };
+        assert_eq!(left.c_type, Type::Int);
+        assert!(matches!(c.kind, HExpressionKind::Var(_)));
+        assert_eq!((c.c_type, c.category), (Type::Char, Category::Lvalue));
+
+        // a later "()" declaration doesn't lose the prototype, in the tree any more than in the checks
+        let program = analyze_code("int f(long a); int f(); void g(void) { f; }").unwrap();
+        let [HStatement::Expr(f)] = &program.functions[0].body[..] else {
+                panic!("expected an expression statement")
+        };
+        let (HExpressionKind::FunctionAddress(_), Type::Pointer(Type::Func(func_type, _))) = (&f.kind, f.c_type) else {
+                panic!("expected the address of f")
+        };
+        assert!(func_type.prototyped);
+        assert_eq!(func_type.params, [Type::Long]);
+}
+
+#[test]
+fn jumps_are_checked_against_what_encloses_them() {
+        assert!(matches!(
+                analyze_code("int main(void) { if (1) { break; } }"),
+                Err(semantic_analysis::Error::BreakOutsideLoop)
+        ));
+        assert!(matches!(
+                analyze_code("int main(void) { continue; }"),
+                Err(semantic_analysis::Error::ContinueOutsideLoop)
+        ));
+        assert!(matches!(
+                analyze_code("int main(void) { switch (1) { case 1: continue; } }"),
+                Err(semantic_analysis::Error::ContinueInSwitch)
+        ));
+
+        // continue skips over the switch to the loop around it, while break only leaves the switch
+        let program =
+                analyze_code("int main(void) { while (1) switch (1) { case 1: continue; default: break; } }").unwrap();
+        let [HStatement::While(_, body, loop_label)] = &program.functions[0].body[..] else {
+                panic!("expected a loop")
+        };
+        let HStatement::Switch(HSwitch {
+                body: switch_body,
+                label: switch_label,
+                ..
+        }) = &**body
+        else {
+                panic!("expected a switch")
+        };
+        let HStatement::Block(items) = &**switch_body else {
+                panic!("expected a block")
+        };
+        let [HStatement::Labeled(_, continue_statement), HStatement::Labeled(_, break_statement)] = &items[..] else {
+                panic!("expected two labeled statements")
+        };
+        let HStatement::Continue(continued) = **continue_statement else {
+                panic!("expected a continue")
+        };
+        let HStatement::Break(broken, BreakType::Switch) = **break_statement else {
+                panic!("expected a break out of the switch")
+        };
+        assert_eq!(continued.0, loop_label.0);
+        assert_eq!(broken.0, switch_label.0);
+
+        // case values are converted to the promoted type of the switch before they're compared, 6.8.4.2p5
+        let program =
+                analyze_code("int f(unsigned char c) { switch (c) { case 256 + 'a': case 'a': return 1; } }").unwrap();
+        let [HStatement::Switch(HSwitch { cases, .. })] = &program.functions[0].body[..] else {
+                panic!("expected a switch")
+        };
+        assert!(matches!(cases[..], [(Constant::Int(353), _), (Constant::Int(97), _)]));
+        assert!(matches!(
+                analyze_code("int f(unsigned u) { switch (u) { case -1: case 4294967295u: return 1; } }"),
+                Err(semantic_analysis::Error::DuplicateCase(Constant::UInt(u32::MAX)))
+        ));
+        assert!(matches!(
+                analyze_code("int f(int x) { switch (x) { case 3: { case 1 + 2: return 1; } } }"),
+                Err(semantic_analysis::Error::DuplicateCase(Constant::Int(3)))
+        ));
+        assert!(matches!(
+                analyze_code("int f(int x) { switch (x) { default: case 1: default: return 1; } }"),
+                Err(semantic_analysis::Error::DuplicateDefault)
+        ));
+}
+
+#[test]
+fn only_automatic_objects_are_limited_to_what_the_frame_can_be_realigned_to() {
+        assert!(analyze_code("int g __attribute__((aligned(64))) = 1;").is_ok());
+        assert!(analyze_code("int main(void) { static int s __attribute__((aligned(64))); return s; }").is_ok());
+        assert!(analyze_code("int main(void) { int a __attribute__((aligned(32))) = 0; return a; }").is_ok());
+        assert!(matches!(
+                analyze_code("int main(void) { int a __attribute__((aligned(1L << 32))) = 0; return a; }"),
+                Err(semantic_analysis::Error::UnsupportedAlignment(_, 0x1_0000_0000))
+        ));
+}
+
+#[test]
+fn a_switch_with_a_default_that_always_returns_does_not_fall_through() {
+        let warnings = |code: &str| analyze(parse(code), code.as_bytes(), Standard::C17).unwrap().1.warnings;
+        assert!(warnings("long sw(long x) { switch (x) { case 1: return 1; default: return 4; } }").is_empty());
+        assert!(matches!(
+                warnings("long sw(long x) { switch (x) { case 1: return 1; } }")[..],
+                [Warning::MissingReturn(_)]
+        ));
+        assert!(matches!(
+                warnings("long sw(long x) { switch (x) { case 1: break; default: return 4; } }")[..],
+                [Warning::MissingReturn(_)]
+        ));
+}
+
+#[test]
+fn signed_left_shifts_in_constant_expressions_are_checked_for_overflow() {
+        assert!(analyze_code("int x = 1 << 30; long y = 1L << 62; unsigned z = 1u << 31;").is_ok());
+        for code in [
+                "int x = 1 << 31;",
+                "int x = 3 << 30;",
+                "int x = -1 << 1;",
+                "long x = 1L << 63;",
+        ] {
+                assert!(matches!(
+                        analyze_code(code),
+                        Err(semantic_analysis::Error::InvalidConstantExpression(
+                                ConstantError::Overflow(_)
+                        ))
+                ));
+        }
+}
+
+#[test]
+fn statement_expressions_can_jump_out_of_enclosing_loops_but_not_hold_their_cases() {
+        assert!(analyze_code("int f(int x) { while (1) { ({ if (x) break; 0; }); } return 0; }").is_ok());
+        assert!(analyze_code("int f(int x) { for (;;) switch (x) { case 1: ({ continue; }); } }").is_ok());
+        assert!(matches!(
+                analyze_code("int f(int x) { ({ break; }); return x; }"),
+                Err(semantic_analysis::Error::BreakOutsideLoop)
+        ));
+        assert!(matches!(
+                analyze_code("int f(int x) { switch (x) { case 1: ({ case 2: 0; }); } return 0; }"),
+                Err(semantic_analysis::Error::CaseOutsideSwitch)
+        ));
+}
+
+#[test]
+fn thread_locals_from_other_files_take_an_asm_register_for_their_address() {
+        // eleven register outputs and one thread-local, with whatever the asm clobbers
+        let code = |tls: &str, clobber: &str| {
+                let locals: String = (0..11).map(|f| format!("int r{f}; ")).collect();
+                let outputs: Vec<_> = (0..11).map(|f| format!("\"=r\"(r{f})")).collect();
+                format!(
+                        "{tls} int main(void) {{ {locals}__asm__(\"\" : {}, \"=m\"(a) : : \"{clobber}\"); return 0; }}",
+                        outputs.join(", ")
+                )
+        };
+        assert!(analyze_code(&code("extern _Thread_local int a;", "r11")).is_ok());
+        assert!(matches!(
+                analyze_code(&code("extern _Thread_local int a;", "rbx")),
+                Err(semantic_analysis::Error::TooManyAsmRegisters(12, 11))
+        ));
+        // one defined in this file is at a fixed offset from %fs
+        assert!(analyze_code(&code("_Thread_local int a = 1;", "rbx")).is_ok());
+}
+
+#[test]
+fn empty_initializers_are_only_allowed_in_c23() {
+        let code = "long c = {}; int main(void) { int x = {}; return (int){} + x + c; }";
+        assert!(matches!(
+                analyze_code(code),
+                Err(semantic_analysis::Error::EmptyInitializer(Type::Long))
+        ));
+        assert!(matches!(
+                analyze_code("int main(void) { return (int){}; }"),
+                Err(semantic_analysis::Error::EmptyInitializer(Type::Int))
+        ));
+
+        // in C23 they zero initialize
+        let program = analyze_as(code, Standard::C23).unwrap();
+        let [HStatement::Initialize(_, x), HStatement::Return(_)] = &program.functions[0].body[..] else {
+                panic!("expected an initialization and a return")
+        };
+        assert!(matches!(x.kind, HExpressionKind::Constant(Constant::Int(0))));
+        assert_eq!(x.c_type, Type::Int);
+}
+
+#[test]
+fn typeof_in_a_typedef_is_worked_out_where_the_typedef_is() {
+        // the typedef sees the const file scope g, not the one main declares before using it
+        let code = "static const int g = 5; typedef __typeof__(g) T; \
+                    int main(void) { long g = 2; T t = 1; g = sizeof(T); ASSIGN return g + t; }";
+        assert!(analyze_code(&code.replace("ASSIGN", "")).is_ok());
+        assert!(matches!(
+                analyze_code(&code.replace("ASSIGN", "t = 2;")),
+                Err(semantic_analysis::Error::AssignToConst(_))
+        ));
+        // every declaration with the type shares the same typeof, so it's only worked out once
+        assert!(analyze_code("int f(void); typedef __typeof__(f()) R; R a = 1; R b = 2;").is_ok());
+}
+
+#[test]
+fn constexpr_objects_need_a_constant_they_can_hold_exactly_and_are_never_modified() {
+        let analyze_c23 = |code: &str| analyze_as(code, Standard::C23);
+        assert!(analyze_c23("constexpr long c = 3; int main(void) { constexpr int d = c + 1; return d; }").is_ok());
+        assert!(matches!(
+                analyze_c23("int f(void); constexpr int c = f();"),
+                Err(semantic_analysis::Error::InvalidConstantExpression(
+                        ConstantError::NotConstant(_)
+                ))
+        ));
+        assert!(matches!(
+                analyze_c23("constexpr char c = 300;"),
+                Err(semantic_analysis::Error::NotRepresentable(300, Type::Char))
+        ));
+        assert!(matches!(
+                analyze_c23("constexpr unsigned c = -1;"),
+                Err(semantic_analysis::Error::NotRepresentable(-1, Type::UInt))
+        ));
+        assert!(matches!(
+                analyze_c23("constexpr int c = 3; int main(void) { c = 4; return c; }"),
+                Err(semantic_analysis::Error::AssignToConst(_))
+        ));
+}
+
+#[test]
+fn nullptr_is_only_a_null_pointer_and_bool_only_holds_0_or_1() {
+        let analyze_c23 = |code: &str| analyze_as(code, Standard::C23);
+        assert!(analyze_c23("int main(void) { return nullptr == 0; }").is_ok());
+        assert!(matches!(
+                analyze_c23("int main(void) { return nullptr == 1; }"),
+                Err(semantic_analysis::Error::InvalidOperands(
+                        Binop::EqualTo,
+                        Type::NullPtr,
+                        Type::Int
+                ))
+        ));
+        assert!(matches!(
+                analyze_c23("int main(void) { int i = nullptr; return i; }"),
+                Err(semantic_analysis::Error::IncompatibleAssignment(
+                        Type::NullPtr,
+                        Type::Int
+                ))
+        ));
+        let program = analyze_c23("int main(void) { return sizeof(nullptr); }").unwrap();
+        let [HStatement::Return(Some(size))] = &program.functions[0].body[..] else {
+                panic!("expected a return")
+        };
+        let HExpressionKind::Convert(size) = &size.kind else {
+                panic!("expected the size to be converted to int")
+        };
+        assert!(matches!(size.kind, HExpressionKind::Constant(Constant::ULong(8))));
+
+        // anything that isn't 0 converts to 1, not to its low byte
+        let program = analyze_c23("bool b = 256;").unwrap();
+        let storage = program.variables.values().find_map(|f| f.storage.as_ref()).unwrap();
+        assert!(matches!(storage.init, StaticInit::Initial(Constant::UChar(1))));
+}
+
+#[test]
+fn typeof_unqual_drops_the_qualifiers_typeof_keeps() {
+        let code = "const int g = 1; int main(void) { TYPEOF(g) u = 1; u = 2; return u; }";
+        assert!(analyze_as(&code.replace("TYPEOF", "typeof_unqual"), Standard::C23).is_ok());
+        assert!(matches!(
+                analyze_as(&code.replace("TYPEOF", "typeof"), Standard::C23),
+                Err(semantic_analysis::Error::AssignToConst(_))
+        ));
+}
+
+#[test]
+fn nodiscard_and_deprecated_warn_where_they_are_used() {
+        let code = "[[nodiscard(\"why\")]] int f(void); [[deprecated]] int d(void); \
+                    int main(void) { f(); (void)f(); return d(); }";
+        let (_, analyzed, _) = analyze(parse_as(code, Standard::C23), code.as_bytes(), Standard::C23).unwrap();
+        // casting to void throws the result away on purpose
+        let [Warning::DiscardedResult(f, Some(_)), Warning::Deprecated(d, None)] = &analyzed.warnings[..] else {
+                panic!(
+                        "expected one discarded result and one deprecated use, not {:?}",
+                        analyzed.warnings
+                )
+        };
+        assert_eq!((&f[..], &d[..]), ("f", "d"));
+}
+
+#[test]
+fn fallthrough_has_to_lead_into_another_case() {
+        let code = |body: &str| format!("int main(void) {{ int x = 1; {body} return 0; }}");
+        for body in [
+                "switch (x) { case 1: x++; [[fallthrough]]; case 2: return x; }",
+                "switch (x) { case 1: { x++; [[fallthrough]]; } default: return x; }",
+        ] {
+                assert!(analyze_as(&code(body), Standard::C23).is_ok(), "{body}");
+        }
+        for body in [
+                "switch (x) { case 1: x++; [[fallthrough]]; x++; case 2: return x; }",
+                "switch (x) { case 1: { x++; [[fallthrough]]; } x++; case 2: return x; }",
+                // the end of the switch leads out of it
+                "switch (x) { case 1: x++; [[fallthrough]]; }",
+                "switch (x) { case 1: { x++; [[fallthrough]]; } }",
+        ] {
+                assert!(
+                        matches!(
+                                analyze_as(&code(body), Standard::C23),
+                                Err(semantic_analysis::Error::MisplacedFallthrough)
+                        ),
+                        "{body}"
+                );
+        }
+        assert!(matches!(
+                analyze_as(&code("[[fallthrough]];"), Standard::C23),
+                Err(semantic_analysis::Error::FallthroughOutsideSwitch)
+        ));
+}